@@ -16,6 +16,8 @@
 // You should have received a copy of the GNU General Public License
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
+use std::net::SocketAddr;
+
 use structopt::StructOpt;
 
 use sc_cli::{KeySubcommand, RunCmd, SignCmd, VanityCmd, VerifyCmd};
@@ -29,6 +31,39 @@ pub struct Cli {
     #[allow(missing_docs)]
     #[structopt(flatten)]
     pub run: RunCmd,
+
+    /// Listen address of the HTTP gateway serving the transaction data at
+    /// `/data/<chunk_root>`, e.g. `127.0.0.1:9977`.
+    ///
+    /// The gateway is disabled unless specified.
+    #[structopt(long = "gateway-addr", value_name = "ADDR")]
+    pub gateway_addr: Option<SocketAddr>,
+
+    /// Number of blocks after which the transaction data submitted along with an
+    /// extrinsic is removed if the extrinsic has not been included.
+    #[structopt(
+        long = "pending-data-expiry",
+        value_name = "BLOCKS",
+        default_value = "600"
+    )]
+    pub pending_data_expiry: u32,
+
+    /// Maximum total bytes of the transaction data submitted via RPC whose extrinsic
+    /// has not been included yet.
+    #[structopt(
+        long = "max-pending-data",
+        value_name = "BYTES",
+        default_value = "1073741824"
+    )]
+    pub max_pending_data: u64,
+
+    /// Maximum bytes of the transaction data each signer can submit via RPC per minute.
+    #[structopt(
+        long = "max-submit-rate",
+        value_name = "BYTES",
+        default_value = "104857600"
+    )]
+    pub max_submit_rate: u64,
 }
 
 /// Possible subcommands of the main binary.
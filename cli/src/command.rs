@@ -81,10 +81,21 @@ pub fn run() -> Result<()> {
     match &cli.subcommand {
         None => {
             let runner = cli.create_runner(&cli.run)?;
+            let gateway_addr = cli.gateway_addr;
+            let pending_data_expiry = cli.pending_data_expiry;
+            let admission_config = cc_rpc::permastore::AdmissionConfig {
+                max_pending_bytes: cli.max_pending_data,
+                max_origin_bytes: cli.max_submit_rate,
+            };
             runner.run_node_until_exit(|config| async move {
                 match config.role {
                     Role::Light => service::new_light(config),
-                    _ => service::new_full(config),
+                    _ => service::new_full(
+                        config,
+                        gateway_addr,
+                        pending_data_expiry,
+                        admission_config,
+                    ),
                 }
                 .map_err(sc_cli::Error::Service)
             })
@@ -121,7 +132,7 @@ pub fn run() -> Result<()> {
                     task_manager,
                     import_queue,
                     ..
-                } = new_partial(&config)?;
+                } = new_partial(&config, Default::default())?;
                 Ok((cmd.run(client, import_queue), task_manager))
             })
         }
@@ -132,7 +143,7 @@ pub fn run() -> Result<()> {
                     client,
                     task_manager,
                     ..
-                } = new_partial(&config)?;
+                } = new_partial(&config, Default::default())?;
                 Ok((cmd.run(client, config.database), task_manager))
             })
         }
@@ -143,7 +154,7 @@ pub fn run() -> Result<()> {
                     client,
                     task_manager,
                     ..
-                } = new_partial(&config)?;
+                } = new_partial(&config, Default::default())?;
                 Ok((cmd.run(client, config.chain_spec), task_manager))
             })
         }
@@ -155,7 +166,7 @@ pub fn run() -> Result<()> {
                     task_manager,
                     import_queue,
                     ..
-                } = new_partial(&config)?;
+                } = new_partial(&config, Default::default())?;
                 Ok((cmd.run(client, import_queue), task_manager))
             })
         }
@@ -171,7 +182,7 @@ pub fn run() -> Result<()> {
                     task_manager,
                     backend,
                     ..
-                } = new_partial(&config)?;
+                } = new_partial(&config, Default::default())?;
                 Ok((cmd.run(client, backend), task_manager))
             })
         }
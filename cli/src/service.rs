@@ -21,6 +21,7 @@
 
 //! Service implementation. Specialized wrapper over substrate service.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use futures::prelude::*;
@@ -34,7 +35,7 @@ use sc_telemetry::{Telemetry, TelemetryWorker};
 use sp_runtime::traits::Block as BlockT;
 
 use canyon_executor::ExecutorDispatch;
-use canyon_primitives::Block;
+use canyon_primitives::{AccountId, Balance, Block};
 use canyon_runtime::RuntimeApi;
 
 type FullClient =
@@ -46,16 +47,13 @@ type FullGrandpaBlockImport =
 type LightClient =
     sc_service::TLightClient<Block, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
 
-type FullPoaBlockImport = cc_consensus_poa::PurePoaBlockImport<
-    Block,
-    FullGrandpaBlockImport,
-    FullClient,
-    FullSelectChain,
->;
+type FullPoaBlockImport =
+    cc_consensus_poa::PurePoaBlockImport<Block, FullGrandpaBlockImport, FullClient>;
 
 #[allow(clippy::type_complexity)]
 pub fn new_partial(
     config: &Configuration,
+    admission_config: cc_rpc::permastore::AdmissionConfig,
 ) -> Result<
     sc_service::PartialComponents<
         FullClient,
@@ -75,6 +73,10 @@ pub fn new_partial(
             ),
             grandpa::SharedVoterState,
             Option<Telemetry>,
+            (
+                Option<cc_consensus_poa::PoaMetrics>,
+                Option<cc_datastore::DatastoreMetrics>,
+            ),
         ),
     >,
     ServiceError,
@@ -104,11 +106,36 @@ pub fn new_partial(
         )?;
     let client = Arc::new(client);
 
+    let offchain_storage = backend.offchain_storage().ok_or_else(|| {
+        ServiceError::Other("Offchain storage is required by the permastore".into())
+    })?;
+
+    // Enables the permastore host functions to access the local transaction data
+    // when validating the transactions.
+    client
+        .execution_extensions()
+        .set_extensions_factory(Box::new(cc_datastore::PermaStorageExtensionsFactory::new(
+            offchain_storage.clone(),
+        )));
+
     let telemetry = telemetry.map(|(worker, telemetry)| {
         task_manager.spawn_handle().spawn("telemetry", worker.run());
         telemetry
     });
 
+    let poa_metrics = config.prometheus_registry().and_then(|registry| {
+        cc_consensus_poa::PoaMetrics::register(registry)
+            .map_err(|e| log::error!(target: "poa", "Failed to register PoA metrics: {}", e))
+            .ok()
+    });
+    let datastore_metrics = config.prometheus_registry().and_then(|registry| {
+        cc_datastore::DatastoreMetrics::register(registry)
+            .map_err(
+                |e| log::error!(target: "datastore", "Failed to register datastore metrics: {}", e),
+            )
+            .ok()
+    });
+
     let select_chain = sc_consensus::LongestChain::new(backend.clone());
 
     let transaction_pool = sc_transaction_pool::BasicPool::new_full(
@@ -130,7 +157,8 @@ pub fn new_partial(
     let poa_block_import = cc_consensus_poa::PurePoaBlockImport::new(
         grandpa_block_import,
         client.clone(),
-        select_chain.clone(),
+        cc_datastore::WeaveIndex::new(offchain_storage.clone()),
+        poa_metrics.clone(),
     );
 
     let (block_import, babe_link) = sc_consensus_babe::block_import(
@@ -190,9 +218,10 @@ pub fn new_partial(
         let keystore = keystore_container.sync_keystore();
         let chain_spec = config.chain_spec.cloned_box();
 
-        let offchain_storage = backend
-            .offchain_storage()
-            .unwrap_or_else(|| panic!("offchain storage is some; qed"));
+        let datastore_metrics = datastore_metrics.clone();
+
+        // Shared by all the RPC servers.
+        let admission = cc_rpc::permastore::AdmissionControl::new(admission_config);
 
         let spawn_handle = task_manager.spawn_handle();
         let rpc_extensions_builder = move |deny_unsafe, subscription_executor| {
@@ -217,7 +246,11 @@ pub fn new_partial(
                 perma_storage: cc_datastore::PermanentStorage::new(
                     offchain_storage.clone(),
                     client.clone(),
-                ),
+                )
+                .with_metrics(datastore_metrics.clone()),
+                transaction_index: cc_indexer::TransactionIndex::new(offchain_storage.clone()),
+                pending_data: cc_datastore::PendingData::new(offchain_storage.clone()),
+                admission: admission.clone(),
             };
 
             use jsonrpc_pubsub::manager::SubscriptionManager;
@@ -246,7 +279,13 @@ pub fn new_partial(
         select_chain,
         import_queue,
         transaction_pool,
-        other: (rpc_extensions_builder, import_setup, rpc_setup, telemetry),
+        other: (
+            rpc_extensions_builder,
+            import_setup,
+            rpc_setup,
+            telemetry,
+            (poa_metrics, datastore_metrics),
+        ),
     })
 }
 
@@ -260,6 +299,9 @@ pub struct NewFullBase {
 /// Creates a full service from the configuration.
 pub fn new_full_base(
     mut config: Configuration,
+    gateway_addr: Option<SocketAddr>,
+    pending_data_expiry: u32,
+    admission_config: cc_rpc::permastore::AdmissionConfig,
     with_startup_data: impl FnOnce(
         &sc_consensus_babe::BabeBlockImport<Block, FullClient, FullPoaBlockImport>,
         &sc_consensus_babe::BabeLink<Block>,
@@ -273,8 +315,15 @@ pub fn new_full_base(
         keystore_container,
         select_chain,
         transaction_pool,
-        other: (rpc_extensions_builder, import_setup, rpc_setup, mut telemetry),
-    } = new_partial(&config)?;
+        other:
+            (
+                rpc_extensions_builder,
+                import_setup,
+                rpc_setup,
+                mut telemetry,
+                (poa_metrics, datastore_metrics),
+            ),
+    } = new_partial(&config, admission_config)?;
 
     let shared_voter_state = rpc_setup;
 
@@ -283,6 +332,46 @@ pub fn new_full_base(
         .extra_sets
         .push(grandpa::grandpa_peers_set_config());
 
+    let offchain_storage = backend.offchain_storage().ok_or_else(|| {
+        ServiceError::Other("Offchain storage is required by the permastore".into())
+    })?;
+
+    // The bodies of recall blocks are required by PoA once pruned from the database.
+    let keep_recall_bodies = !matches!(config.keep_blocks, sc_service::config::KeepBlocks::All);
+
+    task_manager.spawn_handle().spawn(
+        "permastore-weave-indexer",
+        cc_datastore::WeaveIndexer::new(client.clone(), offchain_storage.clone())
+            .keep_recall_bodies(keep_recall_bodies)
+            .run(),
+    );
+
+    task_manager.spawn_handle().spawn(
+        "permastore-transaction-indexer",
+        cc_indexer::TransactionIndexer::<_, _, AccountId, Balance>::new(
+            client.clone(),
+            offchain_storage.clone(),
+        )
+        .run(),
+    );
+
+    let new_perma_storage = {
+        let offchain_storage = offchain_storage.clone();
+        let client = client.clone();
+        move || {
+            cc_datastore::PermanentStorage::new(offchain_storage.clone(), client.clone())
+                .with_metrics(datastore_metrics.clone())
+        }
+    };
+
+    let protocol_id = config.protocol_id();
+    let (chunk_request_handler, chunk_request_protocol_config) =
+        cc_network::ChunkRequestHandler::new(&protocol_id, new_perma_storage());
+    config
+        .network
+        .request_response_protocols
+        .push(chunk_request_protocol_config);
+
     let warp_sync = Arc::new(grandpa::warp_proof::NetworkProvider::new(
         backend.clone(),
         import_setup.1.shared_authority_set().clone(),
@@ -300,6 +389,21 @@ pub fn new_full_base(
             warp_sync: Some(warp_sync),
         })?;
 
+    task_manager.spawn_handle().spawn(
+        "permastore-chunk-request-handler",
+        chunk_request_handler.run(),
+    );
+    task_manager.spawn_handle().spawn(
+        "permastore-data-sync",
+        cc_network::DataSync::new(
+            &protocol_id,
+            new_perma_storage(),
+            cc_datastore::WeaveIndex::new(offchain_storage.clone()),
+            network.clone(),
+        )
+        .run(),
+    );
+
     if config.offchain_worker.enabled {
         sc_service::build_offchain_workers(
             &config,
@@ -317,10 +421,6 @@ pub fn new_full_base(
     let enable_grandpa = !config.disable_grandpa;
     let prometheus_registry = config.prometheus_registry().cloned();
 
-    let offchain_storage = backend
-        .offchain_storage()
-        .unwrap_or_else(|| panic!("offchain storage is some; qed"));
-
     let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
         config,
         backend,
@@ -336,6 +436,29 @@ pub fn new_full_base(
         telemetry: telemetry.as_mut(),
     })?;
 
+    if let Some(gateway_addr) = gateway_addr {
+        let perma_storage = new_perma_storage();
+        task_manager
+            .spawn_handle()
+            .spawn("permastore-gateway", async move {
+                if let Err(e) = cc_gateway::run(gateway_addr, perma_storage).await {
+                    log::error!(target: "gateway", "Permastore gateway failed: {}", e);
+                }
+            });
+    }
+
+    task_manager.spawn_handle().spawn(
+        "permastore-pending-data-cleaner",
+        cc_datastore::PendingDataCleaner::new(
+            client.clone(),
+            transaction_pool.clone(),
+            new_perma_storage(),
+            offchain_storage.clone(),
+            pending_data_expiry.into(),
+        )
+        .run(),
+    );
+
     let (block_import, grandpa_link, babe_link) = import_setup;
 
     (with_startup_data)(&block_import, &babe_link);
@@ -365,8 +488,8 @@ pub fn new_full_base(
             create_inherent_data_providers: move |parent, ()| {
                 let client_clone = client_clone.clone();
                 let client_clone2 = client_clone.clone();
-                let client_clone3 = client_clone.clone();
-                let offchain_storage_clone = offchain_storage.clone();
+                let perma_storage = new_perma_storage();
+                let poa_metrics = poa_metrics.clone();
                 async move {
                     let uncles = sc_consensus_uncles::create_uncles_inherent_data_provider(
                         &*client_clone,
@@ -383,7 +506,8 @@ pub fn new_full_base(
                     let poa = cc_consensus_poa::PoaInherentDataProvider::create(
                         client_clone2,
                         parent,
-                        cc_datastore::PermanentStorage::new(offchain_storage_clone, client_clone3),
+                        perma_storage,
+                        poa_metrics.as_ref(),
                     )?;
 
                     Ok((timestamp, slot, uncles, poa))
@@ -484,8 +608,20 @@ pub fn new_full_base(
 }
 
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
-    new_full_base(config, |_, _| ()).map(|NewFullBase { task_manager, .. }| task_manager)
+pub fn new_full(
+    config: Configuration,
+    gateway_addr: Option<SocketAddr>,
+    pending_data_expiry: u32,
+    admission_config: cc_rpc::permastore::AdmissionConfig,
+) -> Result<TaskManager, ServiceError> {
+    new_full_base(
+        config,
+        gateway_addr,
+        pending_data_expiry,
+        admission_config,
+        |_, _| (),
+    )
+    .map(|NewFullBase { task_manager, .. }| task_manager)
 }
 
 pub fn new_light_base(
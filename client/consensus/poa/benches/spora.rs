@@ -0,0 +1,55 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::Rng;
+
+use cc_consensus_poa::{
+    spora::{
+        meets_difficulty, pack_chunk, packing_key, spora_challenge, spora_recall_byte,
+        spora_solution,
+    },
+    ChunkProof, ProofOfAccess, SporaVerifier,
+};
+use cp_permastore::CHUNK_SIZE;
+use sp_core::H256;
+
+fn random_data(data_size: usize) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    (0..data_size).map(|_| rng.gen::<u8>()).collect()
+}
+
+fn spora_benchmark(c: &mut Criterion) {
+    let seed = random_data(32);
+    let chunk = random_data(CHUNK_SIZE as usize);
+
+    c.bench_function("spora recall byte", |b| {
+        b.iter(|| {
+            spora_recall_byte(
+                &spora_challenge(black_box(&seed), black_box(20)),
+                black_box(1024 * 1024 * 1024),
+            )
+        })
+    });
+
+    let challenge = spora_challenge(&seed, 20);
+    c.bench_function("spora solution 256KiB", |b| {
+        b.iter(|| {
+            meets_difficulty(
+                &spora_solution(black_box(&challenge), black_box(&chunk)),
+                100,
+            )
+        })
+    });
+
+    let key = packing_key(&H256::repeat_byte(1), 0);
+    c.bench_function("spora packing 256KiB", |b| {
+        b.iter(|| pack_chunk(black_box(&key), black_box(&chunk)))
+    });
+
+    let verifier = SporaVerifier::new(seed, 1024 * 1024 * 1024, 100);
+    let poa = ProofOfAccess::new(20, vec![], ChunkProof::new(vec![], chunk, 0));
+    c.bench_function("spora verification 256KiB", |b| {
+        b.iter(|| verifier.verify(black_box(&poa), black_box(&key)))
+    });
+}
+
+criterion_group!(benches, spora_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Schema for the PoA data stored in the aux store.
+
+use codec::{Decode, Encode};
+
+use sc_client_api::backend::AuxStore;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+use crate::Error;
+
+/// Key of the number of the block whose state was imported by warp sync.
+const WARP_SYNC_TARGET_KEY: &[u8] = b"poa_warp_sync_target";
+
+/// Returns the aux data recording block `number` as the warp sync target,
+/// to be written along with the import of that block.
+pub(crate) fn warp_sync_target_aux<Number: Encode>(number: Number) -> (Vec<u8>, Option<Vec<u8>>) {
+    (WARP_SYNC_TARGET_KEY.to_vec(), Some(number.encode()))
+}
+
+/// Returns the number of the block whose state was imported by warp sync, if any.
+pub(crate) fn load_warp_sync_target<Block: BlockT, Backend: AuxStore>(
+    backend: &Backend,
+) -> Result<Option<NumberFor<Block>>, Error<Block>> {
+    match backend.get_aux(WARP_SYNC_TARGET_KEY)? {
+        Some(encoded) => Ok(Some(Decode::decode(&mut encoded.as_slice())?)),
+        None => Ok(None),
+    }
+}
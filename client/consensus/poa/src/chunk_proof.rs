@@ -18,13 +18,12 @@
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
 use sp_core::H256;
-use sp_io::hashing::blake2_256;
-use sp_trie::TrieMut;
+use sp_trie::MemoryDB;
 
-use cp_consensus_poa::{encode_index, ChunkProof};
-use cp_permastore::{Hasher, TrieLayout, VerifyError};
+use cp_consensus_poa::ChunkProof;
+use cp_permastore::{chunk_tree, Hasher, TrieLayout, VerifyError};
 
-use crate::trie::TrieError;
+use crate::trie::{prepare_trie_proof, TrieError};
 
 /// A verifier for chunk proof.
 #[derive(Debug, Clone)]
@@ -37,6 +36,9 @@ impl ChunkProofVerifier {
     }
 
     /// Returns `Ok(())` if the chunk proof matches given `chunk_root`.
+    ///
+    /// The chunk index of proof is taken as is, which has to be checked against
+    /// the recall byte by the caller.
     pub fn verify(&self, chunk_root: &H256) -> Result<(), VerifyError> {
         verify_chunk_proof(
             chunk_root,
@@ -54,34 +56,93 @@ pub fn verify_chunk_proof(
     chunk_index: u32,
     proof: &[Vec<u8>],
 ) -> Result<(), VerifyError> {
-    sp_trie::verify_trie_proof::<TrieLayout, _, _, _>(
-        chunk_root,
-        proof,
-        &[(encode_index(chunk_index), Some(blake2_256(&chunk)))],
-    )
+    chunk_tree::verify_chunk(chunk_root, chunk_index, &chunk, proof)
+}
+
+/// Trie of the chunks of a transaction data.
+///
+/// The proofs of all the chunks can be created from one trie, which is preferred
+/// over [`ChunkProofBuilder`] when more than one chunk is going to be proved.
+pub struct ChunkTrie {
+    db: MemoryDB<Hasher>,
+    chunk_root: H256,
+}
+
+impl ChunkTrie {
+    /// Builds the trie from the hashes of all chunks.
+    pub fn new(chunk_hashes: &[H256]) -> Self {
+        let leaves = chunk_hashes
+            .iter()
+            .map(|chunk_hash| chunk_hash.as_bytes().to_vec())
+            .collect();
+
+        let (db, chunk_root) = prepare_trie_proof(leaves);
+
+        Self { db, chunk_root }
+    }
+
+    /// Returns the chunk root of the trie.
+    pub fn chunk_root(&self) -> H256 {
+        self.chunk_root
+    }
+
+    /// Creates the [`ChunkProof`] of `chunk` at `chunk_index`.
+    pub fn prove(&self, chunk: Vec<u8>, chunk_index: u32) -> Result<ChunkProof, TrieError> {
+        let proof = sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(
+            &self.db,
+            self.chunk_root,
+            &[chunk_tree::encode_index(chunk_index)],
+        )
+        .map_err(|e| TrieError::Trie(Box::new(e)))?;
+
+        Ok(ChunkProof {
+            chunk,
+            chunk_index,
+            proof,
+        })
+    }
 }
 
-/// A builder for creating a [`ChunkProof`] from the entire raw transaction data.
+/// A builder for creating a [`ChunkProof`].
 #[derive(Debug, Clone)]
 pub struct ChunkProofBuilder {
-    /// Raw bytes of entire transaction data.
-    data: Vec<u8>,
-    /// Size of per data chunk in bytes.
-    chunk_size: u32,
+    /// Hashes of all the chunks of transaction data.
+    chunk_hashes: Vec<H256>,
+    /// Raw bytes of the recall chunk.
+    target_chunk: Vec<u8>,
     /// Index of the recall chunk.
     target_chunk_index: u32,
 }
 
 impl ChunkProofBuilder {
-    /// Constructs an instance of [`ChunkProofBuilder`].
-    pub fn new(data: Vec<u8>, chunk_size: u32, transaction_data_offset: u32) -> Self {
-        debug_assert!(chunk_size > 0);
+    /// Constructs an instance of [`ChunkProofBuilder`] from the entire raw transaction data.
+    pub fn new(data: Vec<u8>, transaction_data_offset: u32) -> Self {
+        let target_chunk_index = chunk_tree::chunk_index(transaction_data_offset);
+
+        let target_chunk = chunk_tree::chunks(&data)
+            .nth(target_chunk_index as usize)
+            .map(|chunk| chunk.to_vec())
+            .unwrap_or_default();
 
-        let target_chunk_index = transaction_data_offset / chunk_size;
+        let chunk_hashes = chunk_tree::chunk_hashes(&data);
 
         Self {
-            data,
-            chunk_size,
+            chunk_hashes,
+            target_chunk,
+            target_chunk_index,
+        }
+    }
+
+    /// Constructs an instance of [`ChunkProofBuilder`] from the hashes of all chunks
+    /// and the raw bytes of recall chunk only.
+    pub fn from_chunk(
+        chunk_hashes: Vec<H256>,
+        target_chunk: Vec<u8>,
+        target_chunk_index: u32,
+    ) -> Self {
+        Self {
+            chunk_hashes,
+            target_chunk,
             target_chunk_index,
         }
     }
@@ -92,69 +153,100 @@ impl ChunkProofBuilder {
     ///
     /// Panics if the building of chunks trie failed.
     pub fn build(&self) -> Result<ChunkProof, TrieError> {
-        let mut target_chunk = Vec::with_capacity(self.chunk_size as usize);
-
-        let mut db = sp_trie::MemoryDB::<Hasher>::default();
-        let mut chunk_root = sp_trie::empty_trie_root::<TrieLayout>();
-
-        {
-            let mut trie = sp_trie::TrieDBMut::<TrieLayout>::new(&mut db, &mut chunk_root);
-
-            let chunks = self
-                .data
-                .chunks(self.chunk_size as usize)
-                .map(|c| c.to_vec());
-
-            for (index, chunk) in chunks.enumerate() {
-                // Build the trie using chunk id.
-                trie.insert(&encode_index(index as u32), &blake2_256(&chunk))
-                    .unwrap_or_else(|e| {
-                        panic!(
-                            "Failed to insert the trie node: {:?}, chunk index: {}",
-                            e, index
-                        )
-                    });
-
-                if index == self.target_chunk_index as usize {
-                    target_chunk = chunk;
-                }
-            }
-
-            trie.commit();
-        }
-
-        let proof = sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(
-            &db,
-            chunk_root,
-            &[encode_index(self.target_chunk_index)],
-        )
-        .map_err(|e| TrieError::Trie(Box::new(e)))?;
-
-        Ok(ChunkProof {
-            chunk: target_chunk,
-            chunk_index: self.target_chunk_index,
-            proof,
-        })
+        ChunkTrie::new(&self.chunk_hashes).prove(self.target_chunk.clone(), self.target_chunk_index)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cp_permastore::CHUNK_SIZE;
+
+    const EDGE_SIZES: [u32; 5] = [
+        1,
+        CHUNK_SIZE - 1,
+        CHUNK_SIZE,
+        CHUNK_SIZE + 1,
+        CHUNK_SIZE * 3 + 7,
+    ];
+
+    fn random_data(data_size: u32) -> Vec<u8> {
+        use rand::RngCore;
+        let mut data = vec![0u8; data_size as usize];
+        rand::thread_rng().fill_bytes(&mut data);
+        data
+    }
 
     #[test]
     fn test_chunk_proof_verify() {
-        use std::str::FromStr;
+        let data = random_data(CHUNK_SIZE * 3 + 7);
+        let chunk_root = chunk_tree::chunk_root(&data);
 
-        let data = b"hello".to_vec();
-        let chunk_proof_builder = ChunkProofBuilder::new(data, 1, 3);
+        let chunk_proof_builder = ChunkProofBuilder::new(data.clone(), CHUNK_SIZE + 3);
         let chunk_proof = chunk_proof_builder.build().unwrap();
-        let chunk_root = sp_core::H256::from_str(
-            "0x26976dd39b2ea67e0b51f3511c394882523e91d7249a784c589da9654fbc51dc",
-        )
-        .unwrap();
+        let chunk = data[CHUNK_SIZE as usize..CHUNK_SIZE as usize * 2].to_vec();
 
-        assert!(verify_chunk_proof(&chunk_root, b"l".to_vec(), 3, &chunk_proof.proof).is_ok());
-        assert!(verify_chunk_proof(&chunk_root, b"l".to_vec(), 4, &chunk_proof.proof).is_err());
+        assert!(verify_chunk_proof(&chunk_root, chunk.clone(), 1, &chunk_proof.proof).is_ok());
+        assert!(verify_chunk_proof(&chunk_root, chunk, 2, &chunk_proof.proof).is_err());
+    }
+
+    #[test]
+    fn chunk_proof_from_chunk_should_match_entire_data() {
+        let data = random_data(CHUNK_SIZE * 3 + 7);
+        let chunk_hashes = chunk_tree::chunk_hashes(&data);
+        let chunk = data[CHUNK_SIZE as usize * 3..].to_vec();
+
+        let expected = ChunkProofBuilder::new(data, CHUNK_SIZE * 3)
+            .build()
+            .unwrap();
+        let chunk_proof = ChunkProofBuilder::from_chunk(chunk_hashes, chunk, 3)
+            .build()
+            .unwrap();
+
+        assert_eq!(chunk_proof, expected);
+    }
+
+    #[test]
+    fn chunk_trie_should_prove_every_chunk() {
+        let data = random_data(CHUNK_SIZE * 3 + 7);
+        let chunk_root = chunk_tree::chunk_root(&data);
+        let chunk_trie = ChunkTrie::new(&chunk_tree::chunk_hashes(&data));
+        assert_eq!(chunk_trie.chunk_root(), chunk_root);
+
+        for (chunk_index, chunk) in chunk_tree::chunks(&data).enumerate() {
+            let chunk_proof = chunk_trie
+                .prove(chunk.to_vec(), chunk_index as u32)
+                .unwrap();
+            assert_eq!(
+                chunk_proof,
+                ChunkProofBuilder::new(data.clone(), chunk_index as u32 * CHUNK_SIZE)
+                    .build()
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_proof_should_verify_against_shared_chunk_root_for_edge_sizes() {
+        for data_size in EDGE_SIZES.iter() {
+            let data = random_data(*data_size);
+            let chunk_root = chunk_tree::chunk_root(&data);
+
+            for offset in [0, data_size / 2, data_size - 1].iter() {
+                let chunk_proof = ChunkProofBuilder::new(data.clone(), *offset)
+                    .build()
+                    .unwrap();
+
+                assert_eq!(chunk_proof.chunk_index, chunk_tree::chunk_index(*offset));
+                assert!(
+                    ChunkProofVerifier::new(chunk_proof)
+                        .verify(&chunk_root)
+                        .is_ok(),
+                    "data size: {}, offset: {}",
+                    data_size,
+                    offset
+                );
+            }
+        }
     }
 }
\ No newline at end of file
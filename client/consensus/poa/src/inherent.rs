@@ -18,9 +18,10 @@
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
 use std::sync::Arc;
+use std::time::Instant;
 
 use sp_api::ProvideRuntimeApi;
-use sp_blockchain::HeaderBackend;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
 use sp_runtime::traits::{Block as BlockT, NumberFor};
 
 use sc_client_api::BlockBackend;
@@ -36,15 +37,19 @@ pub struct PoaInherentDataProvider {
 
 impl PoaInherentDataProvider {
     /// Creates a new instance of [`PoaInherentDataProvider`].
+    ///
+    /// The outcome is reported to `metrics` if any.
     pub fn create<Block, Client, TransactionDataBackend>(
         client: Arc<Client>,
         parent: Block::Hash,
         transaction_data_backend: TransactionDataBackend,
+        metrics: Option<&crate::PoaMetrics>,
     ) -> Result<Self, crate::Error<Block>>
     where
         Block: BlockT<Hash = canyon_primitives::Hash> + 'static,
         Client: BlockBackend<Block>
             + HeaderBackend<Block>
+            + HeaderMetadata<Block, Error = sp_blockchain::Error>
             + ProvideRuntimeApi<Block>
             + Send
             + Sync
@@ -53,8 +58,14 @@ impl PoaInherentDataProvider {
             + cp_poa::PoaApi<Block>,
         TransactionDataBackend: TransactionDataBackendT<Block>,
     {
+        let start = Instant::now();
         let poa_outcome = match crate::construct_poa(client, parent, transaction_data_backend) {
-            Ok(outcome) => outcome,
+            Ok(outcome) => {
+                if let Some(metrics) = metrics {
+                    metrics.report_outcome(&outcome, start.elapsed());
+                }
+                outcome
+            }
             Err(e) => {
                 log::error!(target: "poa", "Failed to construct poa: {:?}", e);
                 return Err(e);
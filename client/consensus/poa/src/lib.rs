@@ -35,9 +35,11 @@
 //!
 //! 1. Pick a random byte from the whole network storage space, aka BlockWeave.
 //!     - The block weave can be seen as an ever growing gigantic array.
-//!     - Currently, the randome byte is determined by hashing
-//!       the parent header hash for N times(see [`calculate_challenge_byte`]),
-//!       which will be replaced with another strategy in SPoRA.
+//!     - In [`PoaMode::Classic`], the randome byte is determined by hashing
+//!       the parent header hash for N times(see [`calculate_challenge_byte`]).
+//!     - In [`PoaMode::Spora`], the random byte is determined by the parent
+//!       header hash and a nonce, the recall chunk is further required to
+//!       meet the difficulty, see the [`spora`] module for details.
 //!
 //! 2. Locate the extrinsic in which the random byte is included.
 //!
@@ -60,7 +62,8 @@
 //! encourage the miners to store more data locally.
 //!
 //! This crate implements the core algorithem of Proof of Access in
-//! [`construct_poa`] and provides the inherent data provider via
+//! [`construct_poa`], which uses [`PoaBuilder`] or [`SporaBuilder`]
+//! according to the [`PoaMode`] configured on chain, and provides the inherent data provider via
 //! [`PoaInherentDataProvider`]. [`PurePoaBlockImport`] implements the
 //! `BlockImport` trait, thus can be wrapped in another block importer.
 //!
@@ -71,6 +74,15 @@
 //! pipeline. Refer to the [Substrate docs][1] for more information about
 //! creating a nested `BlockImport`.
 //!
+//! The recall block is looked up among the ancestors of the parent of the
+//! block being built or imported. To keep the PoA working on a node pruning
+//! the block bodies, the bodies of the blocks storing data have to be kept by
+//! the `WeaveIndexer` of `cc_datastore`. The history before the warp sync target
+//! is verified as far as it is available, see [`PurePoaBlockImport`] for the details.
+//!
+//! The construction and verification can be monitored via [`PoaMetrics`] registered
+//! in the Prometheus registry of node.
+//!
 //! [1]: https://substrate.dev/docs/en/knowledgebase/advanced/block-import
 //! [`pallet_poa::Call::deposit`]: ../pallet_poa/pallet/enum.Call.html#variant.deposit
 
@@ -78,17 +90,22 @@
 
 use std::collections::HashMap;
 use std::marker::PhantomData;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use codec::{Decode, Encode};
 use thiserror::Error;
 
 use sc_client_api::{backend::AuxStore, BlockBackend, BlockOf};
-use sc_consensus::{BlockCheckParams, BlockImport, BlockImportParams, ImportResult};
-use sp_api::ProvideRuntimeApi;
+use sc_consensus::{
+    BlockCheckParams, BlockImport, BlockImportParams, ImportResult, StateAction, StorageChanges,
+};
+use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
-use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderBackend, ProvideCache};
-use sp_consensus::{Error as ConsensusError, SelectChain};
+use sp_blockchain::{
+    well_known_cache_keys::Id as CacheKeyId, HeaderBackend, HeaderMetadata, ProvideCache,
+};
+use sp_consensus::Error as ConsensusError;
+use sp_core::H256;
 use sp_runtime::{
     generic::BlockId,
     traits::{Block as BlockT, Header as HeaderT, NumberFor},
@@ -96,22 +113,33 @@ use sp_runtime::{
 };
 
 use canyon_primitives::{DataIndex, Depth, ExtrinsicIndex};
-use cc_datastore::TransactionDataBackend as TransactionDataBackendT;
-use cp_permastore::{PermastoreApi, CHUNK_SIZE};
+use cc_datastore::{
+    RecallTransaction, RecallTransactionFor, TransactionDataBackend as TransactionDataBackendT,
+    WeaveIndex, WeaveIndexBackend,
+};
+use cp_permastore::{chunk_tree, PermaStorage, PermastoreApi, StoredTransaction, CHUNK_SIZE};
 use cp_poa::PoaApi;
 
+mod aux_schema;
 mod chunk_proof;
 mod inherent;
+mod metrics;
+pub mod spora;
+#[cfg(test)]
+mod tests;
 mod trie;
 mod tx_proof;
 
-pub use self::chunk_proof::{verify_chunk_proof, ChunkProofBuilder, ChunkProofVerifier};
+pub use self::chunk_proof::{verify_chunk_proof, ChunkProofBuilder, ChunkProofVerifier, ChunkTrie};
 pub use self::inherent::PoaInherentDataProvider;
+pub use self::metrics::PoaMetrics;
+pub use self::spora::{SporaBuilder, SporaVerifier};
 pub use self::tx_proof::{build_extrinsic_proof, verify_extrinsic_proof, TxProofVerifier};
 
 // Re-exports of the primitives of poa consensus.
 pub use cp_consensus_poa::{
-    ChunkProof, PoaConfiguration, PoaOutcome, PoaValidityError, ProofOfAccess, POA_ENGINE_ID,
+    ChunkProof, PoaConfiguration, PoaMode, PoaOutcome, PoaValidityError, ProofOfAccess,
+    POA_ENGINE_ID,
 };
 
 /// Minimum depth of PoA.
@@ -158,9 +186,29 @@ pub enum Error<Block: BlockT> {
     /// Recall extrinsic not found.
     #[error("Recall extrinsic index not found given the recall byte {0}")]
     RecallExtrinsicNotFound(DataIndex),
+    /// The recall block preceding the warp sync target is unavailable.
+    #[error("Recall block {0} preceding the warp sync target is unavailable")]
+    RecallBlockUnavailable(BlockId<Block>),
+    /// The recall extrinsic has been forgotten.
+    #[error("The data of recall extrinsic {0}#{1} has been forgotten")]
+    RecallDataForgotten(BlockId<Block>, ExtrinsicIndex),
     /// Maxinum depth reached.
     #[error("Reaching the maximum allowed depth {0}")]
     MaxDepthReached(Depth),
+    /// The poa mode does not match the builder.
+    #[error("Unexpected poa mode {0:?}")]
+    UnexpectedPoaMode(PoaMode),
+    /// The spora solution does not meet the difficulty.
+    #[error("Spora solution at nonce {0} does not meet the difficulty {1}")]
+    InvalidSporaSolution(Depth, u64),
+    /// The chunk proof is not for the chunk in which the recall byte is located.
+    #[error("Unexpected recall chunk index, expected: {expected}, got: {got}")]
+    UnexpectedChunkIndex {
+        /// Index of the chunk in which the recall byte is located.
+        expected: u32,
+        /// Index of the chunk in the proof.
+        got: u32,
+    },
 }
 
 impl<B: BlockT> From<Error<B>> for ConsensusError {
@@ -185,11 +233,11 @@ fn make_bytes(h: [u8; 32]) -> [u8; 8] {
     res
 }
 
-/// Returns the position of recall byte in the entire weave.
+/// Returns the position of recall byte in the entire weave in [`PoaMode::Classic`].
 ///
 /// Formula: `multihash(seed, depth) % weave_size`
 ///
-/// TODO: SPoRA
+/// See [`spora::spora_recall_byte`] for [`PoaMode::Spora`].
 pub fn calculate_challenge_byte(
     seed: Randomness,
     weave_size: DataIndex,
@@ -202,34 +250,49 @@ pub fn calculate_challenge_byte(
     DataIndex::from_le_bytes(make_bytes(multihash(seed, depth))) % weave_size
 }
 
-/// Returns a tuple of (extrinsic_index, absolute_data_index)
-/// of extrinsic in which `recall_byte` is located.
+/// Returns a tuple of (extrinsic_index, absolute_data_index) of extrinsic in
+/// which `recall_byte` is located, as well as the absolute data index at which
+/// the data of that extrinsic starts.
 fn find_recall_tx(
     recall_byte: DataIndex,
+    weave_base: DataIndex,
     sized_extrinsics: &[(ExtrinsicIndex, DataIndex)],
-) -> (ExtrinsicIndex, DataIndex) {
+) -> ((ExtrinsicIndex, DataIndex), DataIndex) {
     log::trace!(
         target: "poa",
         "Locating the position of recall tx, recall_byte: {}, sized_extrinsics: {:?}",
         recall_byte, sized_extrinsics
     );
-    match sized_extrinsics.binary_search_by_key(&recall_byte, |&(_, weave_size)| weave_size) {
-        Ok(i) => sized_extrinsics[i],
-        Err(i) => sized_extrinsics[i],
-    }
+    let i = match sized_extrinsics.binary_search_by_key(&recall_byte, |&(_, weave_size)| weave_size)
+    {
+        Ok(i) => i,
+        Err(i) => i,
+    };
+    let data_base = if i == 0 {
+        weave_base
+    } else {
+        sized_extrinsics[i - 1].1
+    };
+    (sized_extrinsics[i], data_base)
 }
 
 /// All information of recall block that is required to build a [`ProofOfAccess`].
 #[derive(Debug, Clone)]
 pub struct RecallInfo<B: BlockT> {
-    /// Weave size of last block.
-    weave_base: DataIndex,
+    /// Number of recall block.
+    recall_block_number: NumberFor<B>,
+    /// Absolute data index at which the data of recall extrinsic starts.
+    recall_tx_data_base: DataIndex,
     /// All extrinsics in recall block.
     extrinsics: Vec<B::Extrinsic>,
     /// Extrinsics root of recall block.
     extrinsics_root: B::Hash,
     /// Index of the extrinsic in which recall byte is located.
     recall_extrinsic_index: ExtrinsicIndex,
+    /// Chunk root of the recall extrinsic.
+    chunk_root: B::Hash,
+    /// Size of the data of recall extrinsic.
+    recall_tx_data_size: u32,
 }
 
 impl<B: BlockT<Hash = canyon_primitives::Hash>> RecallInfo<B> {
@@ -242,40 +305,275 @@ impl<B: BlockT<Hash = canyon_primitives::Hash>> RecallInfo<B> {
             self.recall_extrinsic_index,
         )
     }
+
+    /// Returns the index of the chunk in which `recall_byte` is located.
+    pub fn recall_chunk_index(&self, recall_byte: DataIndex) -> u32 {
+        recall_chunk_index(
+            recall_byte,
+            self.recall_tx_data_base,
+            self.recall_tx_data_size,
+        )
+    }
+}
+
+/// Returns the index of the chunk in which `recall_byte` is located given the
+/// recall tx of `data_size` bytes starting at `data_base`.
+///
+/// The recall byte is inclusive at the ceil of recall tx, which belongs to the last chunk.
+fn recall_chunk_index(recall_byte: DataIndex, data_base: DataIndex, data_size: u32) -> u32 {
+    let offset = recall_byte.saturating_sub(data_base);
+    ((offset / CHUNK_SIZE as u64) as u32).min(chunk_tree::chunk_count(data_size).saturating_sub(1))
+}
+
+/// Verifies `chunk_proof` is the proof of chunk `expected` of the data of `chunk_root`.
+fn verify_chunk_at<B: BlockT<Hash = canyon_primitives::Hash>>(
+    chunk_root: &B::Hash,
+    expected: u32,
+    chunk_proof: &ChunkProof,
+) -> Result<(), Error<B>> {
+    if chunk_proof.chunk_index != expected {
+        return Err(Error::UnexpectedChunkIndex {
+            expected,
+            got: chunk_proof.chunk_index,
+        });
+    }
+
+    chunk_proof::verify_chunk_proof(
+        chunk_root,
+        chunk_proof.chunk.clone(),
+        expected,
+        &chunk_proof.proof,
+    )
+    .map_err(Error::VerifyFailed)
+}
+
+/// The recall data against which a PoA is verified.
+enum Recall<B: BlockT> {
+    /// The recall block is available, both the tx proof and chunk proof are verified.
+    Block(RecallInfo<B>),
+    /// Only the header of recall block is available, the chunk proof is verified
+    /// against the chunk root in its [`cp_permastore::PERMASTORE_ENGINE_ID`] digest.
+    Header(RecallTransactionFor<B>),
+    /// The header of recall block preceding the warp sync target has not been
+    /// downloaded yet, or it carries no digest and the body is missing, the recall
+    /// proofs can not be verified.
+    Unavailable(NumberFor<B>),
 }
 
-/// Returns all the information about the recall block for the PoA consensus.
-fn find_recall_info<Block, Client>(
+/// Returns all the information about the recall block for the PoA consensus
+/// on top of block `parent_hash`.
+///
+/// The recall extrinsic is located via the local weave index if possible,
+/// otherwise it falls back to the runtime.
+fn find_recall_info<Block, Client, WeaveIndex>(
     recall_byte: DataIndex,
-    recall_block_number: NumberFor<Block>,
+    parent_hash: Block::Hash,
     client: &Arc<Client>,
+    weave_index: &WeaveIndex,
 ) -> Result<RecallInfo<Block>, Error<Block>>
 where
     Block: BlockT,
-    Client: BlockBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync,
+    Client: BlockBackend<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync,
     Client::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+    WeaveIndex: WeaveIndexBackend<Block>,
+{
+    match find_recall_info_locally(recall_byte, parent_hash, client, weave_index)? {
+        Some(recall_info) => Ok(recall_info),
+        None => find_recall_info_via_runtime(recall_byte, parent_hash, client, weave_index),
+    }
+}
+
+/// Returns the recall info using the local weave index.
+///
+/// Returns `None` if the recall byte has not been indexed yet, or the index
+/// can not be used as the recall block located in the index is not an ancestor
+/// of the parent. The weave before the recall block is shared by the parent
+/// otherwise, hence no runtime call on the historical state is needed.
+fn find_recall_info_locally<Block, Client, WeaveIndex>(
+    recall_byte: DataIndex,
+    parent_hash: Block::Hash,
+    client: &Arc<Client>,
+    weave_index: &WeaveIndex,
+) -> Result<Option<RecallInfo<Block>>, Error<Block>>
+where
+    Block: BlockT,
+    Client: BlockBackend<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+    WeaveIndex: WeaveIndexBackend<Block>,
 {
-    let recall_block_id = BlockId::number(recall_block_number);
+    let recall_tx = match weave_index.locate_recall_transaction(recall_byte) {
+        Some(recall_tx) => recall_tx,
+        None => return Ok(None),
+    };
+
+    let parent_number = client
+        .number(parent_hash)?
+        .ok_or(Error::BlockNotFound(BlockId::Hash(parent_hash)))?;
+
+    if recall_tx.block_number > parent_number
+        || ancestor_at(client, parent_hash, recall_tx.block_number)? != recall_tx.block_hash
+    {
+        log::debug!(
+            target: "poa",
+            "Skipping the weave index as recall block {:?} is not an ancestor of parent {:?}",
+            recall_tx.block_hash,
+            parent_hash,
+        );
+        return Ok(None);
+    }
 
-    let (header, extrinsics) = fetch_block(client, recall_block_id)?.deconstruct();
+    let (header, extrinsics) =
+        fetch_recall_block(client, weave_index, recall_tx.block_hash)?.deconstruct();
+
+    Ok(Some(RecallInfo {
+        recall_block_number: recall_tx.block_number,
+        recall_tx_data_base: recall_tx.data_base,
+        extrinsics,
+        extrinsics_root: *header.extrinsics_root(),
+        recall_extrinsic_index: recall_tx.extrinsic_index,
+        chunk_root: recall_tx.chunk_root,
+        recall_tx_data_size: recall_tx.data_size,
+    }))
+}
+
+/// Returns the recall info using the runtime API.
+///
+/// The recall block is resolved among the ancestors of `parent_hash`, which
+/// is not necessarily on the best chain.
+fn find_recall_info_via_runtime<Block, Client, WeaveIndex>(
+    recall_byte: DataIndex,
+    parent_hash: Block::Hash,
+    client: &Arc<Client>,
+    weave_index: &WeaveIndex,
+) -> Result<RecallInfo<Block>, Error<Block>>
+where
+    Block: BlockT,
+    Client: BlockBackend<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync,
+    Client::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+    WeaveIndex: WeaveIndexBackend<Block>,
+{
+    let parent_id = BlockId::Hash(parent_hash);
+
+    let recall_block_number = find_recall_block(parent_id, recall_byte, client)?;
+
+    log::debug!(
+        target: "poa", "Recall block number: {} was found given the recall byte: {}",
+        recall_block_number,
+        recall_byte,
+    );
+
+    let recall_block_hash = ancestor_at(client, parent_hash, recall_block_number)?;
+
+    let recall_block = fetch_recall_block(client, weave_index, recall_block_hash)?;
 
     let weave_base = client
         .runtime_api()
-        .weave_size(&BlockId::Hash(*header.parent_hash()))?;
+        .weave_size(&BlockId::Hash(*recall_block.header().parent_hash()))?;
+
+    let stored_transactions = recall_block_transactions(
+        parent_id,
+        recall_block.header(),
+        Some(recall_block.extrinsics().len() as u32),
+        client,
+    )?;
+
+    recall_info_in_block(
+        recall_byte,
+        recall_block_number,
+        weave_base,
+        recall_block,
+        &stored_transactions,
+    )
+}
+
+/// Returns the transaction data stored in the recall block of `header`.
+///
+/// The transaction data is read from the header digest, or from the legacy runtime
+/// state on top of block `at` if the recall block was created before the digest was
+/// introduced, which requires the `extrinsic_count` of recall block. An empty list
+/// is returned for such a block if its body is unavailable.
+fn recall_block_transactions<Block, RA>(
+    at: BlockId<Block>,
+    header: &Block::Header,
+    extrinsic_count: Option<u32>,
+    runtime_api: &Arc<RA>,
+) -> Result<Vec<StoredTransaction<Block::Hash>>, Error<Block>>
+where
+    Block: BlockT,
+    RA: ProvideRuntimeApi<Block> + Send + Sync,
+    RA::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+{
+    let transactions = cc_datastore::stored_transactions(header)?;
+
+    match extrinsic_count {
+        Some(extrinsic_count) if transactions.is_empty() => {
+            Ok(cc_datastore::legacy_stored_transactions(
+                &**runtime_api,
+                &at,
+                *header.number(),
+                extrinsic_count,
+            )?)
+        }
+        _ => Ok(transactions),
+    }
+}
+
+/// Returns the recall info given the recall block, the weave size before it and
+/// the transaction data stored in it.
+fn recall_info_in_block<Block: BlockT>(
+    recall_byte: DataIndex,
+    recall_block_number: NumberFor<Block>,
+    weave_base: DataIndex,
+    recall_block: Block,
+    stored_transactions: &[StoredTransaction<Block::Hash>],
+) -> Result<RecallInfo<Block>, Error<Block>> {
+    let (header, extrinsics) = recall_block.deconstruct();
+
+    let recall_tx = recall_tx_in_block::<Block>(
+        recall_byte,
+        recall_block_number,
+        header.hash(),
+        weave_base,
+        stored_transactions,
+    )?;
 
-    let mut sized_extrinsics = Vec::with_capacity(extrinsics.len());
+    Ok(RecallInfo {
+        recall_block_number,
+        recall_tx_data_base: recall_tx.data_base,
+        extrinsics,
+        extrinsics_root: *header.extrinsics_root(),
+        recall_extrinsic_index: recall_tx.extrinsic_index,
+        chunk_root: recall_tx.chunk_root,
+        recall_tx_data_size: recall_tx.data_size,
+    })
+}
+
+/// Returns the recall tx given the recall block, the weave size before it and
+/// the transaction data stored in it.
+fn recall_tx_in_block<Block: BlockT>(
+    recall_byte: DataIndex,
+    recall_block_number: NumberFor<Block>,
+    recall_block_hash: Block::Hash,
+    weave_base: DataIndex,
+    stored_transactions: &[StoredTransaction<Block::Hash>],
+) -> Result<RecallTransactionFor<Block>, Error<Block>> {
+    let mut sized_extrinsics = Vec::with_capacity(stored_transactions.len());
 
     let mut acc = 0u64;
-    for (index, _extrinsic) in extrinsics.iter().enumerate() {
-        let tx_size = client.runtime_api().data_size(
-            &recall_block_id,
-            recall_block_number,
-            index as ExtrinsicIndex,
-        )? as u64;
-        if tx_size > 0 {
-            sized_extrinsics.push((index as ExtrinsicIndex, weave_base + acc + tx_size));
-            acc += tx_size;
-        }
+    for tx in stored_transactions {
+        acc += tx.data_size as u64;
+        sized_extrinsics.push((tx.extrinsic_index, weave_base + acc));
     }
 
     log::trace!(
@@ -284,32 +582,88 @@ where
         sized_extrinsics,
     );
 
-    // No data store transactions in this block.
-    if sized_extrinsics.is_empty() {
+    // No data store transactions in this block, or the recall byte is beyond it.
+    if sized_extrinsics.is_empty() || recall_byte > weave_base + acc {
         return Err(Error::<Block>::RecallExtrinsicNotFound(recall_byte));
     }
 
-    let (recall_extrinsic_index, _recall_block_data_ceil) =
-        find_recall_tx(recall_byte, &sized_extrinsics);
+    let ((recall_extrinsic_index, _recall_block_data_ceil), recall_tx_data_base) =
+        find_recall_tx(recall_byte, weave_base, &sized_extrinsics);
 
-    Ok(RecallInfo {
-        weave_base,
-        extrinsics,
-        extrinsics_root: *header.extrinsics_root(),
-        recall_extrinsic_index,
+    let recall_tx = stored_transactions
+        .iter()
+        .find(|tx| tx.extrinsic_index == recall_extrinsic_index)
+        .ok_or(Error::<Block>::ChunkRootNotFound(
+            BlockId::Number(recall_block_number),
+            recall_extrinsic_index,
+        ))?;
+
+    Ok(RecallTransaction {
+        block_number: recall_block_number,
+        block_hash: recall_block_hash,
+        extrinsic_index: recall_extrinsic_index,
+        chunk_root: recall_tx.chunk_root,
+        data_size: recall_tx.data_size,
+        data_base: recall_tx_data_base,
     })
 }
 
-/// Returns the header and body of block `id`.
-fn fetch_block<Block, Client>(
+/// Returns the header and body of recall block `hash`.
+///
+/// The body kept in the weave index is used if it has been pruned from the database.
+fn fetch_recall_block<Block, Client, WeaveIndex>(
     client: &Arc<Client>,
-    id: BlockId<Block>,
+    weave_index: &WeaveIndex,
+    hash: Block::Hash,
 ) -> Result<Block, Error<Block>>
 where
     Block: BlockT,
-    Client: BlockBackend<Block>,
+    Client: BlockBackend<Block> + HeaderBackend<Block>,
+    WeaveIndex: WeaveIndexBackend<Block>,
+{
+    let id = BlockId::Hash(hash);
+
+    if let Some(signed_block) = client.block(&id)? {
+        return Ok(signed_block.block);
+    }
+
+    match (client.header(id)?, weave_index.recall_block_body(hash)) {
+        (Some(header), Some(extrinsics)) => Ok(Block::new(header, extrinsics)),
+        _ => Err(Error::BlockNotFound(id)),
+    }
+}
+
+/// Returns the hash of the ancestor of block `hash` at height `number`.
+///
+/// The ancestry of `hash` is walked back until it joins the best chain, so only
+/// the blocks of a side fork are visited one by one.
+fn ancestor_at<Block, Client>(
+    client: &Arc<Client>,
+    hash: Block::Hash,
+    number: NumberFor<Block>,
+) -> Result<Block::Hash, Error<Block>>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
 {
-    Ok(client.block(&id)?.ok_or(Error::BlockNotFound(id))?.block)
+    let mut current = client.header_metadata(hash)?;
+
+    while current.number > number {
+        if client.hash(current.number)? == Some(current.hash) {
+            break;
+        }
+        current = client.header_metadata(current.parent)?;
+    }
+
+    if current.number == number {
+        Ok(current.hash)
+    } else if current.number > number {
+        client
+            .hash(number)?
+            .ok_or(Error::BlockNotFound(BlockId::Number(number)))
+    } else {
+        Err(Error::BlockNotFound(BlockId::Number(number)))
+    }
 }
 
 /// Returns the block number of recall block.
@@ -328,7 +682,247 @@ where
         .ok_or(Error::RecallBlockNotFound(recall_byte))
 }
 
-/// A builder for creating [`PoaOutcome`].
+/// Returns `true` if the recall tx `block_number#extrinsic_index` has been forgotten
+/// on top of block `at`.
+///
+/// `is_forgotten` is only provided since version 2 of [`PermastoreApi`], the data
+/// can not be forgotten in the older runtimes.
+fn is_forgotten<Block: BlockT, RA>(
+    at: BlockId<Block>,
+    block_number: NumberFor<Block>,
+    extrinsic_index: ExtrinsicIndex,
+    runtime_api: &Arc<RA>,
+) -> Result<bool, Error<Block>>
+where
+    RA: ProvideRuntimeApi<Block> + Send + Sync,
+    RA::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+{
+    let api = runtime_api.runtime_api();
+
+    if !api.has_api_with::<dyn PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>, _>(
+        &at,
+        |version| version >= 2,
+    )? {
+        return Ok(false);
+    }
+
+    Ok(api.is_forgotten(&at, block_number, extrinsic_index)?)
+}
+
+/// Returns the [`PoaConfiguration`] on top of block `at`.
+///
+/// The configuration has no mode before version 2 of [`PoaApi`], which is
+/// always [`PoaMode::Classic`] then.
+pub(crate) fn fetch_poa_config<Block: BlockT, RA>(
+    at: BlockId<Block>,
+    runtime_api: &Arc<RA>,
+) -> Result<PoaConfiguration, Error<Block>>
+where
+    RA: ProvideRuntimeApi<Block> + Send + Sync,
+    RA::Api: PoaApi<Block>,
+{
+    let api = runtime_api.runtime_api();
+
+    if api.has_api_with::<dyn PoaApi<Block>, _>(&at, |version| version >= 2)? {
+        Ok(api.poa_config(&at)?)
+    } else {
+        #[allow(deprecated)]
+        Ok(api.poa_config_before_version_2(&at)?.into())
+    }
+}
+
+/// Returns the weave size before recall block `recall_block_number` in which
+/// `recall_byte` is located on top of block `at`.
+///
+/// The state of the blocks before the warp sync target is unavailable, the weave
+/// size is instead searched for as the last byte located in the previous recall
+/// block, i.e., the first byte located in the recall block minus one.
+fn find_weave_base<Block: BlockT, RA>(
+    at: BlockId<Block>,
+    recall_byte: DataIndex,
+    recall_block_number: NumberFor<Block>,
+    runtime_api: &Arc<RA>,
+) -> Result<DataIndex, Error<Block>>
+where
+    RA: ProvideRuntimeApi<Block> + Send + Sync,
+    RA::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+{
+    let api = runtime_api.runtime_api();
+
+    let (mut low, mut high) = (0, recall_byte);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if api.find_recall_block(&at, mid)? == Some(recall_block_number) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Ok(low.saturating_sub(1))
+}
+
+/// The recall chunk read from the local storage.
+pub(crate) struct RecallChunk<B: BlockT> {
+    /// Information of the recall block.
+    pub(crate) recall_info: RecallInfo<B>,
+    /// Hashes of all the chunks of recall transaction data.
+    pub(crate) chunk_hashes: Vec<H256>,
+    /// Index of the recall chunk.
+    pub(crate) chunk_index: u32,
+    /// Raw bytes of the recall chunk.
+    pub(crate) chunk: Vec<u8>,
+}
+
+/// Returns the chunk in which `recall_byte` is located on top of block `parent`.
+///
+/// Returns `None` if the recall extrinsic can not be located, or the recall data
+/// has been forgotten or is not found locally.
+pub(crate) fn fetch_recall_chunk<Block, Client, TransactionDataBackend>(
+    client: &Arc<Client>,
+    transaction_data_backend: &TransactionDataBackend,
+    parent: Block::Hash,
+    recall_byte: DataIndex,
+) -> Result<Option<RecallChunk<Block>>, Error<Block>>
+where
+    Block: BlockT<Hash = canyon_primitives::Hash> + 'static,
+    Client: BlockBackend<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync,
+    Client::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+    TransactionDataBackend: TransactionDataBackendT<Block>,
+{
+    let recall_info = match find_recall_info(recall_byte, parent, client, transaction_data_backend)
+    {
+        Ok(recall_info) => recall_info,
+        Err(Error::RecallExtrinsicNotFound(_)) => {
+            log::debug!(
+                target: "poa",
+                "Recall extrinsic not found given the recall byte {}",
+                recall_byte,
+            );
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let RecallInfo {
+        recall_block_number,
+        recall_extrinsic_index,
+        chunk_root,
+        ..
+    } = recall_info;
+
+    // The forgot txs can not participate in the consensus.
+    if is_forgotten(
+        BlockId::Hash(parent),
+        recall_block_number,
+        recall_extrinsic_index,
+        client,
+    )? {
+        log::debug!(
+            target: "poa",
+            "Recall extrinsic {}#{} has been forgotten",
+            recall_block_number,
+            recall_extrinsic_index,
+        );
+        return Ok(None);
+    }
+
+    let transaction_data_key = chunk_root.encode();
+
+    let data_meta = match transaction_data_backend.data_meta(&transaction_data_key) {
+        Some(data_meta) => data_meta,
+        None => {
+            log::warn!(
+                target: "poa",
+                "Transaction data not found given block {} and extrinsic index {}",
+                recall_block_number,
+                recall_extrinsic_index,
+            );
+            return Ok(None);
+        }
+    };
+
+    let chunk_index = recall_info.recall_chunk_index(recall_byte);
+
+    match transaction_data_backend.retrieve_chunk(&transaction_data_key, chunk_index) {
+        Some(chunk) => Ok(Some(RecallChunk {
+            recall_info,
+            chunk_hashes: data_meta.chunk_hashes,
+            chunk_index,
+            chunk,
+        })),
+        None => {
+            log::warn!(
+                target: "poa",
+                "Chunk {} not found given block {} and extrinsic index {}",
+                chunk_index,
+                recall_block_number,
+                recall_extrinsic_index,
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Returns a [`ProofOfAccess`] of `depth` for the recall chunk.
+///
+/// Returns `None` if the proof can not be built or is too large given `poa_config`.
+pub(crate) fn prove_recall_chunk<Block>(
+    recall_chunk: RecallChunk<Block>,
+    depth: Depth,
+    poa_config: &PoaConfiguration,
+) -> Option<ProofOfAccess>
+where
+    Block: BlockT<Hash = canyon_primitives::Hash>,
+{
+    let RecallChunk {
+        recall_info,
+        chunk_hashes,
+        chunk_index,
+        chunk,
+    } = recall_chunk;
+
+    let chunk_proof = ChunkProofBuilder::from_chunk(chunk_hashes, chunk, chunk_index)
+        .build()
+        .ok()?;
+
+    if chunk_proof.size() > poa_config.max_chunk_path as usize {
+        log::debug!(
+            target: "poa",
+            "Dropping the chunk proof as it's too large ({} > {})",
+            chunk_proof.size(),
+            poa_config.max_chunk_path,
+        );
+        return None;
+    }
+
+    let tx_proof = build_extrinsic_proof::<Block>(
+        recall_info.recall_extrinsic_index,
+        recall_info.extrinsics_root,
+        recall_info.extrinsics,
+    )
+    .ok()?;
+
+    let tx_path_size: usize = tx_proof.iter().map(|t| t.len()).sum();
+    if tx_path_size > poa_config.max_tx_path as usize {
+        log::debug!(
+            target: "poa",
+            "Dropping the tx proof as it's too large ({} > {})",
+            tx_path_size,
+            poa_config.max_tx_path,
+        );
+        return None;
+    }
+
+    Some(ProofOfAccess::new(depth, tx_proof, chunk_proof))
+}
+
+/// A builder for creating [`PoaOutcome`] in [`PoaMode::Classic`].
 pub struct PoaBuilder<Block, Client, TransactionDataBackend> {
     client: Arc<Client>,
     transaction_data_backend: TransactionDataBackend,
@@ -340,6 +934,7 @@ where
     Block: BlockT<Hash = canyon_primitives::Hash> + 'static,
     Client: BlockBackend<Block>
         + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
         + ProvideRuntimeApi<Block>
         + Send
         + Sync
@@ -356,18 +951,6 @@ where
         }
     }
 
-    /// Returns the number of recall block.
-    fn find_recall_block(
-        &self,
-        at: BlockId<Block>,
-        recall_byte: DataIndex,
-    ) -> Result<NumberFor<Block>, Error<Block>> {
-        self.client
-            .runtime_api()
-            .find_recall_block(&at, recall_byte)?
-            .ok_or(Error::RecallBlockNotFound(recall_byte))
-    }
-
     /// Creates the inherent data [`PoaOutcome`].
     pub fn build(&self, parent: Block::Hash) -> Result<PoaOutcome, Error<Block>> {
         log::debug!(target: "poa", "Start building poa on top of {:?}", parent);
@@ -380,11 +963,8 @@ where
             return Ok(PoaOutcome::Skipped);
         }
 
-        let PoaConfiguration {
-            max_depth,
-            max_tx_path,
-            max_chunk_path,
-        } = self.client.runtime_api().poa_config(&parent_id)?;
+        let poa_config = fetch_poa_config(parent_id, &self.client)?;
+        let max_depth = poa_config.max_depth;
 
         for depth in MIN_DEPTH..=max_depth {
             let recall_byte = calculate_challenge_byte(parent.encode(), weave_size, depth);
@@ -393,95 +973,23 @@ where
                 "Attempting to generate poa at depth: {}, recall byte found: {}",
                 depth, recall_byte,
             );
-            let recall_block_number = self.find_recall_block(parent_id, recall_byte)?;
 
-            log::debug!(
-                target: "poa", "Recall block number: {} was found given the recall byte: {}",
-                recall_block_number,
+            let recall_chunk = match fetch_recall_chunk(
+                &self.client,
+                &self.transaction_data_backend,
+                parent,
                 recall_byte,
-            );
-
-            let RecallInfo {
-                weave_base,
-                extrinsics,
-                extrinsics_root,
-                recall_extrinsic_index,
-            } = find_recall_info(recall_byte, recall_block_number, &self.client)?;
-
-            // Continue if the recall tx has been forgotten as the forgot
-            // txs can not participate in the consensus.
-            //
-            // FIXME: handle the data oblivion
-            // if todo!("recall_tx has been forgotten via runtime api") {
-            // continue;
-            // }
-
-            let recall_data = self.transaction_data_backend.transaction_data(
-                BlockId::Number(recall_block_number),
-                recall_extrinsic_index as u32,
-            );
-
-            match recall_data {
-                Ok(Some(tx_data)) => {
-                    let transaction_data_offset = match recall_byte.checked_sub(weave_base) {
-                        Some(offset) => offset,
-                        None => panic!(
-                            "Underflow happened! recall_byte: {}, recall_block_weave_base: {}",
-                            recall_byte, weave_base
-                        ),
-                    };
-
-                    if let Ok(chunk_proof) =
-                        ChunkProofBuilder::new(tx_data, CHUNK_SIZE, transaction_data_offset as u32)
-                            .build()
-                    {
-                        if chunk_proof.size() > max_chunk_path as usize {
-                            log::debug!(
-                                target: "poa",
-                                "Dropping the chunk proof as it's too large ({} > {})",
-                                chunk_proof.size(),
-                                max_chunk_path,
-                            );
-                            continue;
-                        }
-
-                        if let Ok(tx_proof) = build_extrinsic_proof::<Block>(
-                            recall_extrinsic_index,
-                            extrinsics_root,
-                            extrinsics,
-                        ) {
-                            let tx_path_size: usize = tx_proof.iter().map(|t| t.len()).sum();
-                            if tx_path_size > max_tx_path as usize {
-                                log::debug!(
-                                    target: "poa",
-                                    "Dropping the tx proof as it's too large ({} > {})",
-                                    tx_path_size,
-                                    max_tx_path,
-                                );
-                                continue;
-                            }
-                            let poa = ProofOfAccess::new(depth, tx_proof, chunk_proof);
-                            log::trace!(target: "poa", "Generate the poa proof successfully: {:?}", poa);
-                            return Ok(PoaOutcome::Justification(poa));
-                        }
-                    }
-                }
-                Ok(None) => {
-                    log::warn!(
-                        target: "poa",
-                        "Transaction data not found given block {} and extrinsic index {}, continuing next depth: {}",
-                        recall_block_number,
-                        recall_extrinsic_index,
-                        depth + 1
-                    );
-                }
-                Err(e) => {
-                    log::error!(
-                        target: "poa",
-                        "Error occurred when retrieving the transaction data: {:?}",
-                        e,
-                    );
+            )? {
+                Some(recall_chunk) => recall_chunk,
+                None => {
+                    log::debug!(target: "poa", "Continuing next depth: {}", depth + 1);
+                    continue;
                 }
+            };
+
+            if let Some(poa) = prove_recall_chunk(recall_chunk, depth, &poa_config) {
+                log::trace!(target: "poa", "Generate the poa proof successfully: {:?}", poa);
+                return Ok(PoaOutcome::Justification(poa));
             }
         }
 
@@ -501,6 +1009,7 @@ where
     Block: BlockT<Hash = canyon_primitives::Hash> + 'static,
     Client: BlockBackend<Block>
         + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
         + ProvideRuntimeApi<Block>
         + Send
         + Sync
@@ -508,7 +1017,10 @@ where
     Client::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash> + PoaApi<Block>,
     TransactionDataBackend: TransactionDataBackendT<Block>,
 {
-    PoaBuilder::new(client, transaction_data_backend).build(parent)
+    match fetch_poa_config(BlockId::Hash(parent), &client)?.mode {
+        PoaMode::Classic => PoaBuilder::new(client, transaction_data_backend).build(parent),
+        PoaMode::Spora { .. } => SporaBuilder::new(client, transaction_data_backend).build(parent),
+    }
 }
 
 /// Extracts PoA digest from a header that should contain one.
@@ -539,25 +1051,57 @@ fn fetch_poa<B: BlockT>(header: B::Header, hash: B::Hash) -> Result<ProofOfAcces
 /// This importer has to be used with other mature block importer
 /// together, e.g., grandpa block import, for it only verifies the
 /// validity of PoA sealed digest item in the header and nothing else.
-pub struct PurePoaBlockImport<B, I, C, S> {
+///
+/// The verification only relies on the state of the parent of imported block,
+/// thus the blocks of side forks are verified in the same way as the ones
+/// extending the best chain.
+///
+/// During warp sync, the block whose state is imported is not verified, as its
+/// ancestry is covered by the finality proofs. The blocks imported without state,
+/// i.e., [`StateAction::Skip`], are the history before the warp sync target which
+/// is downloaded afterwards. They are finalized by the same proofs, and the state
+/// of their parents required by the verification never exists locally, hence are
+/// not verified either.
+///
+/// After warp sync, the recall block at or before the warp sync target has no state
+/// and may have no body locally. The recall chunk and SPoRA solution are verified
+/// against the chunk root in the header of recall block, and the tx proof only if
+/// the body is available. The recall proofs can not be verified at all if the header
+/// of recall block has not been downloaded yet, or it has neither the permastore digest
+/// nor the body. Such a block is only imported if it's already finalized, i.e.,
+/// `BlockImportParams::finalized`, otherwise it's rejected and will be imported again
+/// by the sync once the recall block is available.
+pub struct PurePoaBlockImport<B: BlockT, I, C> {
     inner: I,
-    select_chain: S,
     client: Arc<C>,
+    weave_index: WeaveIndex<B>,
+    /// Weave size before the recall block preceding the warp sync target, which
+    /// otherwise has to be searched for with a number of runtime calls.
+    ///
+    /// The blocks preceding the warp sync target are finalized, the recall block
+    /// is identified by its number.
+    weave_bases: Arc<Mutex<HashMap<NumberFor<B>, DataIndex>>>,
+    metrics: Option<PoaMetrics>,
     phatom: PhantomData<B>,
 }
 
-impl<B: Clone, I: Clone, C, S: Clone> Clone for PurePoaBlockImport<B, I, C, S> {
+/// Maximum number of the cached weave bases in [`PurePoaBlockImport`].
+const MAX_CACHED_WEAVE_BASES: usize = 4096;
+
+impl<B: BlockT, I: Clone, C> Clone for PurePoaBlockImport<B, I, C> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
-            select_chain: self.select_chain.clone(),
             client: self.client.clone(),
+            weave_index: self.weave_index.clone(),
+            weave_bases: self.weave_bases.clone(),
+            metrics: self.metrics.clone(),
             phatom: self.phatom,
         }
     }
 }
 
-impl<B, I, C, S> PurePoaBlockImport<B, I, C, S>
+impl<B, I, C> PurePoaBlockImport<B, I, C>
 where
     B: BlockT,
     I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync,
@@ -566,28 +1110,293 @@ where
     C::Api: BlockBuilderApi<B>,
 {
     /// Creates a new block import suitable to be used in PoA.
-    pub fn new(inner: I, client: Arc<C>, select_chain: S) -> Self {
+    pub fn new(
+        inner: I,
+        client: Arc<C>,
+        weave_index: WeaveIndex<B>,
+        metrics: Option<PoaMetrics>,
+    ) -> Self {
         Self {
             inner,
             client,
-            select_chain,
+            weave_index,
+            weave_bases: Default::default(),
+            metrics,
             phatom: PhantomData::<B>,
         }
     }
 }
 
+impl<B, I, C> PurePoaBlockImport<B, I, C>
+where
+    B: BlockT<Hash = canyon_primitives::Hash>,
+    I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync,
+    I::Error: Into<ConsensusError>,
+    C: ProvideRuntimeApi<B>
+        + Send
+        + Sync
+        + BlockBackend<B>
+        + HeaderBackend<B>
+        + HeaderMetadata<B, Error = sp_blockchain::Error>
+        + AuxStore
+        + ProvideCache<B>
+        + BlockOf,
+    C::Api: BlockBuilderApi<B> + PermastoreApi<B, NumberFor<B>, u32, B::Hash> + PoaApi<B>,
+{
+    /// Verifies the PoA of the block `post_hash` on top of block `parent_hash`.
+    ///
+    /// The recall block at or before the warp sync target may be partially available,
+    /// see [`Self::find_recall_before_warp_sync_target`]. If it's unavailable, the
+    /// block is only accepted given it has been `finalized`.
+    fn verify_poa(
+        &self,
+        parent_hash: B::Hash,
+        post_header: B::Header,
+        post_hash: B::Hash,
+        finalized: bool,
+    ) -> Result<(), Error<B>> {
+        if !self
+            .client
+            .runtime_api()
+            .require_proof_of_access(&BlockId::Hash(parent_hash))?
+        {
+            return Ok(());
+        }
+
+        // Observed once dropped, including the failed verification.
+        let _timer = self
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.verification_duration.start_timer());
+
+        let poa = fetch_poa::<B>(post_header, post_hash)?;
+
+        let poa_config = fetch_poa_config(BlockId::Hash(parent_hash), &self.client)?;
+
+        poa.check_validity(&poa_config)
+            .map_err(Error::<B>::InvalidPoa)?;
+
+        let weave_size = self
+            .client
+            .runtime_api()
+            .weave_size(&BlockId::Hash(parent_hash))?;
+
+        let spora_verifier = match poa_config.mode {
+            PoaMode::Classic => None,
+            PoaMode::Spora { difficulty } => Some(SporaVerifier::new(
+                parent_hash.encode(),
+                weave_size,
+                difficulty,
+            )),
+        };
+
+        let recall_byte = match spora_verifier {
+            Some(ref verifier) => verifier.recall_byte(poa.depth),
+            None => calculate_challenge_byte(parent_hash.encode(), weave_size, poa.depth),
+        };
+
+        let recall = match self.find_recall_before_warp_sync_target(parent_hash, recall_byte)? {
+            Some(recall) => recall,
+            None => Recall::Block(find_recall_info(
+                recall_byte,
+                parent_hash,
+                &self.client,
+                &self.weave_index,
+            )?),
+        };
+
+        let (recall_block_number, recall_extrinsic_index, chunk_root, chunk_index) = match recall {
+            Recall::Block(recall_info) => {
+                recall_info
+                    .as_tx_proof_verifier()
+                    .verify(&poa.tx_path)
+                    .map_err(Error::<B>::VerifyFailed)?;
+                (
+                    recall_info.recall_block_number,
+                    recall_info.recall_extrinsic_index,
+                    recall_info.chunk_root,
+                    recall_info.recall_chunk_index(recall_byte),
+                )
+            }
+            Recall::Header(recall_tx) => (
+                recall_tx.block_number,
+                recall_tx.extrinsic_index,
+                recall_tx.chunk_root,
+                recall_chunk_index(recall_byte, recall_tx.data_base, recall_tx.data_size),
+            ),
+            Recall::Unavailable(_) if finalized => return Ok(()),
+            Recall::Unavailable(recall_block_number) => {
+                return Err(Error::<B>::RecallBlockUnavailable(BlockId::Number(
+                    recall_block_number,
+                )))
+            }
+        };
+
+        if is_forgotten(
+            BlockId::Hash(parent_hash),
+            recall_block_number,
+            recall_extrinsic_index,
+            &self.client,
+        )? {
+            return Err(Error::<B>::RecallDataForgotten(
+                BlockId::Number(recall_block_number),
+                recall_extrinsic_index,
+            ));
+        }
+
+        verify_chunk_at(&chunk_root, chunk_index, &poa.chunk_proof)?;
+
+        // The solution is checked against the packed chunk at the verified position.
+        if let Some(verifier) = spora_verifier {
+            let packing_key = spora::packing_key(&chunk_root, chunk_index);
+            if !verifier.verify(&poa, &packing_key) {
+                return Err(Error::<B>::InvalidSporaSolution(
+                    poa.depth,
+                    verifier.difficulty(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the recall data if the recall block is at or before the warp sync target.
+    ///
+    /// The state of the recall block is never available after warp sync, the weave size
+    /// before it is searched for in the parent state and the recall tx is read from its
+    /// header. The body is used for the tx proof if it has been downloaded.
+    ///
+    /// Returns `None` if the recall block is after the warp sync target, or no warp
+    /// sync has happened.
+    fn find_recall_before_warp_sync_target(
+        &self,
+        parent_hash: B::Hash,
+        recall_byte: DataIndex,
+    ) -> Result<Option<Recall<B>>, Error<B>> {
+        let warp_sync_target = match aux_schema::load_warp_sync_target::<B, _>(&*self.client)? {
+            Some(warp_sync_target) => warp_sync_target,
+            None => return Ok(None),
+        };
+
+        let parent_id = BlockId::Hash(parent_hash);
+
+        let recall_block_number = find_recall_block(parent_id, recall_byte, &self.client)?;
+        if recall_block_number > warp_sync_target {
+            return Ok(None);
+        }
+
+        // The blocks at or before the warp sync target are finalized.
+        let header = match self.client.hash(recall_block_number)? {
+            Some(hash) => self.client.header(BlockId::Hash(hash))?,
+            None => None,
+        };
+        let header = match header {
+            Some(header) => header,
+            None => {
+                log::debug!(
+                    target: "poa",
+                    "Recall block #{} preceding warp sync target #{} is unavailable",
+                    recall_block_number,
+                    warp_sync_target,
+                );
+                return Ok(Some(Recall::Unavailable(recall_block_number)));
+            }
+        };
+
+        let weave_base = self.weave_base_of(parent_id, recall_byte, recall_block_number)?;
+
+        match fetch_recall_block(&self.client, &self.weave_index, header.hash()) {
+            Ok(recall_block) => {
+                let stored_transactions = recall_block_transactions(
+                    parent_id,
+                    &header,
+                    Some(recall_block.extrinsics().len() as u32),
+                    &self.client,
+                )?;
+                recall_info_in_block(
+                    recall_byte,
+                    recall_block_number,
+                    weave_base,
+                    recall_block,
+                    &stored_transactions,
+                )
+                .map(|recall_info| Some(Recall::Block(recall_info)))
+            }
+            Err(Error::BlockNotFound(_)) => {
+                let stored_transactions =
+                    recall_block_transactions(parent_id, &header, None, &self.client)?;
+                // The legacy transaction data can not be located without the body.
+                if stored_transactions.is_empty() {
+                    log::debug!(
+                        target: "poa",
+                        "Recall block #{} created before the permastore digest has no body",
+                        recall_block_number,
+                    );
+                    return Ok(Some(Recall::Unavailable(recall_block_number)));
+                }
+                log::debug!(
+                    target: "poa",
+                    "Skipping the tx proof as the body of recall block #{} is unavailable",
+                    recall_block_number,
+                );
+                recall_tx_in_block::<B>(
+                    recall_byte,
+                    recall_block_number,
+                    header.hash(),
+                    weave_base,
+                    &stored_transactions,
+                )
+                .map(|recall_tx| Some(Recall::Header(recall_tx)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the weave size before recall block `recall_block_number` preceding the
+    /// warp sync target, which is searched for only once per recall block.
+    fn weave_base_of(
+        &self,
+        at: BlockId<B>,
+        recall_byte: DataIndex,
+        recall_block_number: NumberFor<B>,
+    ) -> Result<DataIndex, Error<B>> {
+        let cached = self
+            .weave_bases
+            .lock()
+            .expect("The weave base cache is never locked across a panic; qed")
+            .get(&recall_block_number)
+            .copied();
+        if let Some(weave_base) = cached {
+            return Ok(weave_base);
+        }
+
+        let weave_base = find_weave_base(at, recall_byte, recall_block_number, &self.client)?;
+
+        let mut weave_bases = self
+            .weave_bases
+            .lock()
+            .expect("The weave base cache is never locked across a panic; qed");
+        if weave_bases.len() >= MAX_CACHED_WEAVE_BASES {
+            weave_bases.clear();
+        }
+        weave_bases.insert(recall_block_number, weave_base);
+
+        Ok(weave_base)
+    }
+}
+
 #[async_trait::async_trait]
-impl<B, I, C, S> BlockImport<B> for PurePoaBlockImport<B, I, C, S>
+impl<B, I, C> BlockImport<B> for PurePoaBlockImport<B, I, C>
 where
     B: BlockT<Hash = canyon_primitives::Hash>,
     I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync,
     I::Error: Into<ConsensusError>,
-    S: SelectChain<B>,
     C: ProvideRuntimeApi<B>
         + Send
         + Sync
         + BlockBackend<B>
         + HeaderBackend<B>
+        + HeaderMetadata<B, Error = sp_blockchain::Error>
         + AuxStore
         + ProvideCache<B>
         + BlockOf,
@@ -605,76 +1414,40 @@ where
 
     async fn import_block(
         &mut self,
-        block: BlockImportParams<B, Self::Transaction>,
+        mut block: BlockImportParams<B, Self::Transaction>,
         new_cache: HashMap<CacheKeyId, Vec<u8>>,
     ) -> Result<ImportResult, Self::Error> {
-        let best_header = self
-            .select_chain
-            .best_chain()
-            .await
-            .map_err(|e| format!("Fetch best chain failed via select chain: {:?}", e))?;
-
-        let best_hash = best_header.hash();
-
-        if self
-            .client
-            .runtime_api()
-            .require_proof_of_access(&BlockId::Hash(best_hash))
-            .map_err(Error::<B>::ApiError)?
-        {
-            let header = block.post_header();
-            let poa = fetch_poa::<B>(header, best_hash)?;
-
-            let parent_hash = *block.header.parent_hash();
-            let poa_config = self
-                .client
-                .runtime_api()
-                .poa_config(&BlockId::Hash(parent_hash))
-                .map_err(Error::<B>::ApiError)?;
-
-            poa.check_validity(&poa_config)
-                .map_err(Error::<B>::InvalidPoa)?;
-
-            let weave_size = self
-                .client
-                .runtime_api()
-                .weave_size(&BlockId::Hash(parent_hash))
-                .map_err(Error::<B>::ApiError)?;
-
-            let ProofOfAccess {
-                depth,
-                tx_path,
-                chunk_proof,
-            } = poa;
-
-            let recall_byte = calculate_challenge_byte(parent_hash.encode(), weave_size, depth);
-            let recall_block_number =
-                find_recall_block(BlockId::Hash(parent_hash), recall_byte, &self.client)?;
-
-            let recall_info = find_recall_info(recall_byte, recall_block_number, &self.client)?;
-
-            recall_info
-                .as_tx_proof_verifier()
-                .verify(&tx_path)
-                .map_err(Error::<B>::VerifyFailed)?;
-
-            let chunk_root = self
-                .client
-                .runtime_api()
-                .chunk_root(
-                    &BlockId::Hash(parent_hash),
-                    recall_block_number,
-                    recall_info.recall_extrinsic_index,
-                )
-                .map_err(Error::<B>::ApiError)?
-                .ok_or(Error::<B>::ChunkRootNotFound(
-                    BlockId::Number(recall_block_number),
-                    recall_info.recall_extrinsic_index,
-                ))?;
-
-            chunk_proof::ChunkProofVerifier::new(chunk_proof)
-                .verify(&chunk_root)
-                .map_err(Error::<B>::VerifyFailed)?;
+        let parent_hash = *block.header.parent_hash();
+        let number = *block.header.number();
+
+        match &block.state_action {
+            // The state of warp sync target is imported from the network, whose
+            // ancestry has been proven by the finality proofs.
+            StateAction::ApplyChanges(StorageChanges::Import(_)) => {
+                log::debug!(
+                    target: "poa",
+                    "Skipping the PoA verification of warp sync target #{}",
+                    number,
+                );
+                block
+                    .auxiliary
+                    .push(aux_schema::warp_sync_target_aux(number));
+            }
+            // The block is imported without the state, e.g., the history downloaded
+            // after warp sync, thus the parent state required by PoA is unavailable.
+            StateAction::Skip => {
+                log::trace!(
+                    target: "poa",
+                    "Skipping the PoA verification of block #{} imported without state",
+                    number,
+                );
+            }
+            _ => self.verify_poa(
+                parent_hash,
+                block.post_header(),
+                block.post_hash(),
+                block.finalized,
+            )?,
         }
 
         self.inner
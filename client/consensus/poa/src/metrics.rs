@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the PoA construction and verification.
+
+use std::time::Duration;
+
+use codec::Encode;
+use prometheus_endpoint::{
+    exponential_buckets, register, CounterVec, Histogram, HistogramOpts, Opts, PrometheusError,
+    Registry, U64,
+};
+
+use cp_consensus_poa::PoaOutcome;
+
+/// Metrics of PoA.
+#[derive(Clone)]
+pub struct PoaMetrics {
+    depth: Histogram,
+    build_duration: Histogram,
+    outcomes: CounterVec<U64>,
+    proof_size: Histogram,
+    pub(crate) verification_duration: Histogram,
+}
+
+impl PoaMetrics {
+    /// Registers the PoA metrics in `registry`.
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            depth: register(
+                Histogram::with_opts(
+                    HistogramOpts::new("poa_depth", "Depth of the proof of access built per block")
+                        .buckets(exponential_buckets(1.0, 2.0, 11)?),
+                )?,
+                registry,
+            )?,
+            build_duration: register(
+                Histogram::with_opts(
+                    HistogramOpts::new(
+                        "poa_build_duration_seconds",
+                        "Time taken to build the proof of access",
+                    )
+                    .buckets(exponential_buckets(0.001, 2.0, 14)?),
+                )?,
+                registry,
+            )?,
+            outcomes: register(
+                CounterVec::new(
+                    Opts::new(
+                        "poa_outcomes_total",
+                        "Number of the proof of access outcomes by kind",
+                    ),
+                    &["outcome"],
+                )?,
+                registry,
+            )?,
+            proof_size: register(
+                Histogram::with_opts(
+                    HistogramOpts::new(
+                        "poa_proof_size_bytes",
+                        "Encoded size of the proof of access built",
+                    )
+                    .buckets(exponential_buckets(256.0, 2.0, 12)?),
+                )?,
+                registry,
+            )?,
+            verification_duration: register(
+                Histogram::with_opts(
+                    HistogramOpts::new(
+                        "poa_verification_duration_seconds",
+                        "Time taken to verify the proof of access on block import",
+                    )
+                    .buckets(exponential_buckets(0.001, 2.0, 14)?),
+                )?,
+                registry,
+            )?,
+        })
+    }
+
+    /// Reports the `outcome` built in `build_duration`.
+    pub(crate) fn report_outcome(&self, outcome: &PoaOutcome, build_duration: Duration) {
+        self.build_duration.observe(build_duration.as_secs_f64());
+
+        let label = match outcome {
+            PoaOutcome::Skipped => "skipped",
+            PoaOutcome::MaxDepthReached(_) => "max_depth_reached",
+            PoaOutcome::Justification(poa) => {
+                self.depth.observe(poa.depth as f64);
+                self.proof_size.observe(poa.encoded_size() as f64);
+                "justification"
+            }
+        };
+        self.outcomes.with_label_values(&[label]).inc();
+    }
+}
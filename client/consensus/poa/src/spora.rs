@@ -0,0 +1,347 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Succinct Proofs of Random Access (SPoRA).
+//!
+//! Instead of deriving the recall byte from the parent hash only, SPoRA lets the
+//! miner try with a sequence of nonces. For each nonce:
+//!
+//! 1. `challenge = blake2_256(parent_hash, nonce)`.
+//! 2. `recall_byte = challenge % weave_size`.
+//! 3. `solution = blake2_256(challenge ++ pack(recall_chunk, packing_key))`.
+//!
+//! The attempt succeeds only if the solution meets the difficulty. As the solution
+//! can not be computed without the recall chunk, the miner that is able to read the
+//! random chunks from the local storage faster has a greater chance of winning,
+//! which discourages fetching the data from the network on demand.
+//!
+//! The recall chunk is packed with the key derived from its position in the recall
+//! transaction data, i.e., `packing_key = blake2_256(chunk_root, chunk_index)`, which
+//! binds the solution to the chunk proven by the [`ProofOfAccess`]. The packing is a
+//! cheap keystream and the same data stored by multiple transactions shares the key,
+//! thus it does not prevent the miner from packing the chunk on the fly.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use codec::Encode;
+
+use sc_client_api::BlockBackend;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_runtime::{
+    generic::BlockId,
+    traits::{Block as BlockT, NumberFor},
+};
+
+use sp_core::H256;
+
+use canyon_primitives::{DataIndex, Depth};
+use cc_datastore::TransactionDataBackend as TransactionDataBackendT;
+use cp_consensus_poa::{PoaMode, PoaOutcome, ProofOfAccess};
+use cp_permastore::PermastoreApi;
+use cp_poa::PoaApi;
+
+use crate::{fetch_poa_config, fetch_recall_chunk, prove_recall_chunk, Error, MIN_DEPTH};
+
+/// Returns the challenge of SPoRA given the `seed` and `nonce`.
+pub fn spora_challenge(seed: &[u8], nonce: Depth) -> [u8; 32] {
+    sp_io::hashing::blake2_256(&(seed, nonce).encode())
+}
+
+/// Returns the position of recall byte in the entire weave given the `challenge`.
+pub fn spora_recall_byte(challenge: &[u8; 32], weave_size: DataIndex) -> DataIndex {
+    assert!(
+        weave_size > 0,
+        "weave size can not be 0 when calculating the recall byte"
+    );
+    DataIndex::from_le_bytes(crate::make_bytes(*challenge)) % weave_size
+}
+
+/// Returns the solution of SPoRA given the `challenge` and the recall `chunk`.
+pub fn spora_solution(challenge: &[u8; 32], chunk: &[u8]) -> [u8; 32] {
+    sp_io::hashing::blake2_256(&[&challenge[..], chunk].concat())
+}
+
+/// Returns the key for packing the chunk `chunk_index` of transaction data `chunk_root`.
+pub fn packing_key(chunk_root: &H256, chunk_index: u32) -> [u8; 32] {
+    sp_io::hashing::blake2_256(&(b"spora_packing", chunk_root, chunk_index).encode())
+}
+
+/// Returns the packed `chunk` with `packing_key`.
+///
+/// The chunk is XORed with a keystream of chained hashes starting from the packing
+/// key, which is the same operation for unpacking.
+pub fn pack_chunk(packing_key: &[u8; 32], chunk: &[u8]) -> Vec<u8> {
+    let mut key = *packing_key;
+    let mut packed = Vec::with_capacity(chunk.len());
+    for piece in chunk.chunks(32) {
+        key = sp_io::hashing::blake2_256(&key);
+        packed.extend(piece.iter().zip(key.iter()).map(|(byte, k)| byte ^ k));
+    }
+    packed
+}
+
+/// Returns `true` if the `solution` meets the `difficulty`.
+///
+/// The chance of a random solution meeting the difficulty is `1 / difficulty`.
+pub fn meets_difficulty(solution: &[u8; 32], difficulty: u64) -> bool {
+    difficulty > 0 && u64::from_le_bytes(crate::make_bytes(*solution)) <= u64::MAX / difficulty
+}
+
+/// A verifier of the [`ProofOfAccess`] created in [`PoaMode::Spora`].
+#[derive(Debug, Clone)]
+pub struct SporaVerifier {
+    seed: Vec<u8>,
+    weave_size: DataIndex,
+    difficulty: u64,
+}
+
+impl SporaVerifier {
+    /// Creates a new instance of [`SporaVerifier`].
+    ///
+    /// `seed` is the encoded parent hash, `weave_size` is the weave size of parent block.
+    pub fn new(seed: Vec<u8>, weave_size: DataIndex, difficulty: u64) -> Self {
+        Self {
+            seed,
+            weave_size,
+            difficulty,
+        }
+    }
+
+    /// Returns the recall byte of the attempt `nonce`.
+    pub fn recall_byte(&self, nonce: Depth) -> DataIndex {
+        spora_recall_byte(&spora_challenge(&self.seed, nonce), self.weave_size)
+    }
+
+    /// Returns the difficulty that the solution has to meet.
+    pub fn difficulty(&self) -> u64 {
+        self.difficulty
+    }
+
+    /// Returns `true` if the recall chunk of `poa` packed with `packing_key` makes
+    /// a solution meeting the difficulty.
+    ///
+    /// The recall chunk itself has to be verified against the recall byte separately,
+    /// from which the `packing_key` is derived.
+    pub fn verify(&self, poa: &ProofOfAccess, packing_key: &[u8; 32]) -> bool {
+        let challenge = spora_challenge(&self.seed, poa.depth);
+        meets_difficulty(
+            &spora_solution(&challenge, &pack_chunk(packing_key, &poa.chunk_proof.chunk)),
+            self.difficulty,
+        )
+    }
+}
+
+/// A builder for creating [`PoaOutcome`] in [`PoaMode::Spora`].
+pub struct SporaBuilder<Block, Client, TransactionDataBackend> {
+    client: Arc<Client>,
+    transaction_data_backend: TransactionDataBackend,
+    phatom: PhantomData<Block>,
+}
+
+impl<Block, Client, TransactionDataBackend> SporaBuilder<Block, Client, TransactionDataBackend>
+where
+    Block: BlockT<Hash = canyon_primitives::Hash> + 'static,
+    Client: BlockBackend<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync
+        + 'static,
+    Client::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash> + PoaApi<Block>,
+    TransactionDataBackend: TransactionDataBackendT<Block>,
+{
+    /// Creates a new instance of [`SporaBuilder`].
+    pub fn new(client: Arc<Client>, transaction_data_backend: TransactionDataBackend) -> Self {
+        Self {
+            client,
+            transaction_data_backend,
+            phatom: PhantomData::<Block>,
+        }
+    }
+
+    /// Creates the inherent data [`PoaOutcome`].
+    ///
+    /// The nonces from `1` to `max_depth` are attempted in order, the first one whose
+    /// solution meets the difficulty is used as the `depth` of [`ProofOfAccess`].
+    pub fn build(&self, parent: Block::Hash) -> Result<PoaOutcome, Error<Block>> {
+        log::debug!(target: "poa", "Start building spora on top of {:?}", parent);
+        let parent_id = BlockId::Hash(parent);
+
+        let weave_size = self.client.runtime_api().weave_size(&parent_id)?;
+
+        if weave_size == 0 {
+            log::debug!(target: "poa", "Skipping the spora construction as the weave size is 0");
+            return Ok(PoaOutcome::Skipped);
+        }
+
+        let poa_config = fetch_poa_config(parent_id, &self.client)?;
+        let difficulty = match poa_config.mode {
+            PoaMode::Spora { difficulty } => difficulty,
+            mode => return Err(Error::UnexpectedPoaMode(mode)),
+        };
+        let max_depth = poa_config.max_depth;
+
+        let seed = parent.encode();
+
+        for nonce in MIN_DEPTH..=max_depth {
+            let challenge = spora_challenge(&seed, nonce);
+            let recall_byte = spora_recall_byte(&challenge, weave_size);
+
+            let recall_chunk = match fetch_recall_chunk(
+                &self.client,
+                &self.transaction_data_backend,
+                parent,
+                recall_byte,
+            )? {
+                Some(recall_chunk) => recall_chunk,
+                None => continue,
+            };
+
+            let packing_key = packing_key(
+                &recall_chunk.recall_info.chunk_root,
+                recall_chunk.chunk_index,
+            );
+            let packed_chunk = pack_chunk(&packing_key, &recall_chunk.chunk);
+
+            if !meets_difficulty(&spora_solution(&challenge, &packed_chunk), difficulty) {
+                continue;
+            }
+
+            log::debug!(
+                target: "poa",
+                "Found a spora solution at nonce: {}, recall byte: {}",
+                nonce, recall_byte,
+            );
+
+            if let Some(poa) = prove_recall_chunk(recall_chunk, nonce, &poa_config) {
+                log::trace!(target: "poa", "Generate the spora proof successfully: {:?}", poa);
+                return Ok(PoaOutcome::Justification(poa));
+            }
+        }
+
+        log::warn!(target: "poa", "Failed to create a spora as the max nonce: {} has been reached", max_depth);
+
+        Ok(PoaOutcome::MaxDepthReached(max_depth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cp_consensus_poa::ChunkProof;
+
+    #[test]
+    fn meets_difficulty_should_work() {
+        let mut solution = [0u8; 32];
+        assert!(meets_difficulty(&solution, 1));
+        assert!(meets_difficulty(&solution, u64::MAX));
+        assert!(!meets_difficulty(&solution, 0));
+
+        solution[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(meets_difficulty(&solution, 1));
+        assert!(!meets_difficulty(&solution, 2));
+
+        solution[..8].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        assert!(meets_difficulty(&solution, 2));
+        assert!(!meets_difficulty(&solution, 3));
+    }
+
+    #[test]
+    fn spora_recall_byte_should_depend_on_nonce() {
+        let seed = b"parent".to_vec();
+        let verifier = SporaVerifier::new(seed.clone(), 1_000_000, 1);
+
+        for nonce in 1..10 {
+            let recall_byte = verifier.recall_byte(nonce);
+            assert!(recall_byte < 1_000_000);
+            assert_eq!(
+                recall_byte,
+                spora_recall_byte(&spora_challenge(&seed, nonce), 1_000_000)
+            );
+        }
+
+        assert_ne!(verifier.recall_byte(1), verifier.recall_byte(2));
+    }
+
+    #[test]
+    fn pack_chunk_should_be_bound_to_the_position() {
+        let chunk = vec![7u8; 100];
+        let key = packing_key(&H256::repeat_byte(1), 0);
+
+        let packed = pack_chunk(&key, &chunk);
+        assert_eq!(packed.len(), chunk.len());
+        assert_ne!(packed, chunk);
+        assert_eq!(pack_chunk(&key, &packed), chunk);
+
+        assert_ne!(
+            packed,
+            pack_chunk(&packing_key(&H256::repeat_byte(1), 1), &chunk)
+        );
+        assert_ne!(
+            packed,
+            pack_chunk(&packing_key(&H256::repeat_byte(2), 0), &chunk)
+        );
+    }
+
+    #[test]
+    fn spora_verifier_should_check_the_solution() {
+        let seed = b"parent".to_vec();
+        let difficulty = 4;
+        let chunk = b"recall chunk".to_vec();
+        let key = packing_key(&H256::repeat_byte(1), 0);
+
+        let poa_at =
+            |nonce| ProofOfAccess::new(nonce, vec![], ChunkProof::new(vec![], chunk.clone(), 0));
+        let solution_at = |nonce, key: &[u8; 32], chunk: &[u8]| {
+            spora_solution(&spora_challenge(&seed, nonce), &pack_chunk(key, chunk))
+        };
+
+        let verifier = SporaVerifier::new(seed.clone(), 100, difficulty);
+
+        let valid_nonce = (1..)
+            .find(|&nonce| meets_difficulty(&solution_at(nonce, &key, &chunk), difficulty))
+            .unwrap();
+        assert!(verifier.verify(&poa_at(valid_nonce), &key));
+
+        let invalid_nonce = (1..)
+            .find(|&nonce| !meets_difficulty(&solution_at(nonce, &key, &chunk), difficulty))
+            .unwrap();
+        assert!(!verifier.verify(&poa_at(invalid_nonce), &key));
+
+        // The solution is bound to the recall chunk.
+        let mut tampered = poa_at(valid_nonce);
+        tampered.chunk_proof.chunk = b"another chunk".to_vec();
+        assert_eq!(
+            verifier.verify(&tampered, &key),
+            meets_difficulty(
+                &solution_at(valid_nonce, &key, b"another chunk"),
+                difficulty
+            )
+        );
+
+        // The solution is bound to the position of recall chunk.
+        let another_key = packing_key(&H256::repeat_byte(1), 1);
+        assert_eq!(
+            verifier.verify(&poa_at(valid_nonce), &another_key),
+            meets_difficulty(&solution_at(valid_nonce, &another_key, &chunk), difficulty)
+        );
+    }
+}
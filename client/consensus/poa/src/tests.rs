@@ -0,0 +1,897 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use codec::Encode;
+use futures::executor::block_on;
+use sc_block_builder::BlockBuilderProvider;
+use sc_client_api::{backend::AuxStore, BlockBackend, BlockOf};
+use sc_consensus::{BlockCheckParams, BlockImport, BlockImportParams, ImportResult, StateAction};
+use sp_api::{ApiRef, ProvideRuntimeApi};
+use sp_blockchain::{
+    well_known_cache_keys::Id as CacheKeyId, BlockStatus, CachedHeaderMetadata, HeaderBackend,
+    HeaderMetadata, Info, ProvideCache,
+};
+use sp_consensus::{BlockOrigin, Error as ConsensusError};
+use sp_core::NativeOrEncoded;
+use sp_inherents::{CheckInherentsResult, InherentData};
+use sp_runtime::{
+    generic::{BlockId, Digest, SignedBlock},
+    traits::{Block as BlockT, Header as HeaderT},
+    ApplyExtrinsicResult, DigestItem, Justifications,
+};
+use substrate_test_runtime_client::{
+    runtime::{Extrinsic, Header},
+    ClientBlockImportExt, DefaultTestClientBuilderExt, TestClientBuilderExt,
+};
+
+use cc_datastore::{WeaveBlock, WeaveIndex};
+use cp_permastore::{chunk_tree, StoredTransaction, CHUNK_SIZE, PERMASTORE_ENGINE_ID};
+
+use crate::{
+    ancestor_at, aux_schema, build_extrinsic_proof, calculate_challenge_byte,
+    find_recall_info_locally, recall_chunk_index, recall_info_in_block, recall_tx_in_block,
+    verify_chunk_at, ChunkProof, ChunkProofBuilder, Error, PoaConfiguration, ProofOfAccess,
+    PurePoaBlockImport, RecallInfo, POA_ENGINE_ID,
+};
+
+type Block = substrate_test_runtime_client::runtime::Block;
+type Hash = substrate_test_runtime_client::runtime::Hash;
+type TestClient = substrate_test_runtime_client::TestClient;
+
+fn stored_transactions(data_sizes: &[u32], chunk_root_seed: u8) -> Vec<StoredTransaction<Hash>> {
+    data_sizes
+        .iter()
+        .enumerate()
+        .map(|(index, &data_size)| StoredTransaction {
+            extrinsic_index: index as u32,
+            chunk_root: Hash::repeat_byte(chunk_root_seed + index as u8),
+            data_size,
+        })
+        .collect()
+}
+
+/// Returns the transaction data in the digest of `block`.
+fn digest_transactions(block: &Block) -> Vec<StoredTransaction<Hash>> {
+    cc_datastore::stored_transactions(&block.header).unwrap()
+}
+
+/// Imports a block on top of `parent` storing `transactions`.
+fn import_block(
+    client: &mut Arc<TestClient>,
+    parent: Hash,
+    transactions: Vec<StoredTransaction<Hash>>,
+) -> Block {
+    import_block_with_extrinsics(client, parent, transactions, Vec::new())
+}
+
+/// Imports a block on top of `parent` storing `data` by its only extrinsic.
+fn import_block_storing(client: &mut Arc<TestClient>, parent: Hash, data: &[u8]) -> Block {
+    let transactions = vec![StoredTransaction {
+        extrinsic_index: 0,
+        chunk_root: chunk_tree::chunk_root(data),
+        data_size: data.len() as u32,
+    }];
+    import_block_with_extrinsics(
+        client,
+        parent,
+        transactions,
+        vec![Extrinsic::IncludeData(data.to_vec())],
+    )
+}
+
+/// Imports a block on top of `parent` including `extrinsics` and storing `transactions`.
+fn import_block_with_extrinsics(
+    client: &mut Arc<TestClient>,
+    parent: Hash,
+    transactions: Vec<StoredTransaction<Hash>>,
+    extrinsics: Vec<Extrinsic>,
+) -> Block {
+    let mut digest = Digest::default();
+    if !transactions.is_empty() {
+        digest.push(DigestItem::Consensus(
+            PERMASTORE_ENGINE_ID,
+            transactions.encode(),
+        ));
+    }
+
+    let mut block_builder = client
+        .new_block_at(&BlockId::Hash(parent), digest, false)
+        .unwrap();
+    for extrinsic in extrinsics {
+        block_builder.push(extrinsic).unwrap();
+    }
+    let block = block_builder.build().unwrap().block;
+
+    block_on(client.import(BlockOrigin::Own, block.clone())).unwrap();
+
+    block
+}
+
+/// Builds the competing forks:
+///
+/// ```text
+///          /- a1 [10] - a2 - a3   (best chain, weave size 10)
+/// genesis -
+///          \- b1 [30, 5] - b2     (side fork, weave size 35)
+/// ```
+fn forked_client() -> (Arc<TestClient>, Vec<Block>, Vec<Block>) {
+    let mut client = Arc::new(substrate_test_runtime_client::new());
+    let genesis = client.chain_info().genesis_hash;
+
+    let a1 = import_block(&mut client, genesis, stored_transactions(&[10], 1));
+    let a2 = import_block(&mut client, a1.hash(), Vec::new());
+    let a3 = import_block(&mut client, a2.hash(), Vec::new());
+
+    let b1 = import_block(&mut client, genesis, stored_transactions(&[30, 5], 100));
+    let b2 = import_block(&mut client, b1.hash(), Vec::new());
+
+    assert_eq!(client.chain_info().best_hash, a3.hash());
+
+    (client, vec![a1, a2, a3], vec![b1, b2])
+}
+
+#[test]
+fn ancestor_at_should_follow_the_fork() {
+    let (client, a, b) = forked_client();
+    let genesis = client.chain_info().genesis_hash;
+
+    assert_eq!(ancestor_at(&client, a[2].hash(), 1).unwrap(), a[0].hash());
+    assert_eq!(ancestor_at(&client, a[2].hash(), 3).unwrap(), a[2].hash());
+    assert_eq!(ancestor_at(&client, b[1].hash(), 1).unwrap(), b[0].hash());
+    assert_eq!(ancestor_at(&client, b[1].hash(), 0).unwrap(), genesis);
+
+    // No descendants.
+    assert!(ancestor_at(&client, b[1].hash(), 3).is_err());
+}
+
+#[test]
+fn recall_info_should_be_relative_to_the_parent() {
+    let (client, a, b) = forked_client();
+
+    // The recall byte 20 is out of the weave on the best chain, but is
+    // located in the first transaction of b1 on the side fork.
+    let recall_block_hash = ancestor_at(&client, b[1].hash(), 1).unwrap();
+    let recall_block = client
+        .block(&BlockId::Hash(recall_block_hash))
+        .unwrap()
+        .unwrap()
+        .block;
+    let stored_transactions = digest_transactions(&recall_block);
+    let recall_info = recall_info_in_block(20, 1, 0, recall_block, &stored_transactions).unwrap();
+    assert_eq!(recall_info.chunk_root, Hash::repeat_byte(100));
+    assert_eq!(recall_info.recall_extrinsic_index, 0);
+    assert_eq!(recall_info.recall_tx_data_base, 0);
+    assert_eq!(recall_info.extrinsics_root, b[0].header.extrinsics_root);
+
+    let recall_info =
+        recall_info_in_block(32, 1, 0, b[0].clone(), &digest_transactions(&b[0])).unwrap();
+    assert_eq!(recall_info.chunk_root, Hash::repeat_byte(101));
+    assert_eq!(recall_info.recall_tx_data_base, 30);
+
+    let recall_info =
+        recall_info_in_block(5, 1, 0, a[0].clone(), &digest_transactions(&a[0])).unwrap();
+    assert_eq!(recall_info.chunk_root, Hash::repeat_byte(1));
+    assert_eq!(recall_info.extrinsics_root, a[0].header.extrinsics_root);
+
+    // a2 stores nothing.
+    assert!(recall_info_in_block(5, 2, 10, a[1].clone(), &digest_transactions(&a[1])).is_err());
+}
+
+#[test]
+fn weave_index_should_not_be_used_for_side_fork() {
+    let (client, a, b) = forked_client();
+
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+    weave_index.push_for_test(
+        WeaveBlock {
+            block_number: 1,
+            block_hash: a[0].hash(),
+            weave_base: 0,
+            transactions: stored_transactions(&[10], 1),
+        },
+        &[],
+    );
+
+    let recall_info = find_recall_info_locally(5, a[2].hash(), &client, &weave_index)
+        .unwrap()
+        .unwrap();
+    assert_eq!(recall_info.recall_block_number, 1);
+    assert_eq!(recall_info.chunk_root, Hash::repeat_byte(1));
+
+    // The weave index follows the best chain, which has a different weave
+    // from the side fork.
+    assert!(
+        find_recall_info_locally(5, b[1].hash(), &client, &weave_index)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn weave_index_should_be_used_below_fork_point() {
+    let (mut client, a, _) = forked_client();
+
+    // a1 [10] - c2 [3], which forks from the best chain after the recall block a1.
+    let c2 = import_block(&mut client, a[0].hash(), stored_transactions(&[3], 50));
+
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+    weave_index.push_for_test(
+        WeaveBlock {
+            block_number: 1,
+            block_hash: a[0].hash(),
+            weave_base: 0,
+            transactions: stored_transactions(&[10], 1),
+        },
+        &[],
+    );
+
+    let recall_info = find_recall_info_locally(5, c2.hash(), &client, &weave_index)
+        .unwrap()
+        .unwrap();
+    assert_eq!(recall_info.recall_block_number, 1);
+    assert_eq!(recall_info.chunk_root, Hash::repeat_byte(1));
+    assert_eq!(recall_info.extrinsics_root, a[0].header.extrinsics_root);
+
+    // The data of c2 is not in the weave of best chain.
+    assert!(
+        find_recall_info_locally(12, c2.hash(), &client, &weave_index)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn valid_proof_of_wrong_chunk_should_be_rejected() {
+    let data = (0..CHUNK_SIZE * 3 + 7)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let recall_info = RecallInfo::<Block> {
+        recall_block_number: 1,
+        recall_tx_data_base: 100,
+        extrinsics: Vec::new(),
+        extrinsics_root: Default::default(),
+        recall_extrinsic_index: 0,
+        chunk_root: chunk_tree::chunk_root(&data),
+        recall_tx_data_size: data.len() as u32,
+    };
+
+    let proof_of = |offset| {
+        ChunkProofBuilder::new(data.clone(), offset)
+            .build()
+            .unwrap()
+    };
+    let verify_recall_chunk =
+        |recall_info: &RecallInfo<Block>, recall_byte: u64, chunk_proof: &ChunkProof| {
+            verify_chunk_at::<Block>(
+                &recall_info.chunk_root,
+                recall_info.recall_chunk_index(recall_byte),
+                chunk_proof,
+            )
+        };
+
+    // The recall byte is located in the third chunk.
+    let recall_byte = 100 + CHUNK_SIZE as u64 * 2 + 1;
+    assert!(verify_recall_chunk(&recall_info, recall_byte, &proof_of(CHUNK_SIZE * 2)).is_ok());
+    assert!(matches!(
+        verify_recall_chunk(&recall_info, recall_byte, &proof_of(0)),
+        Err(Error::UnexpectedChunkIndex {
+            expected: 2,
+            got: 0
+        })
+    ));
+
+    // The recall byte at the ceil of recall tx belongs to the last chunk.
+    let recall_byte = 100 + data.len() as u64;
+    assert_eq!(recall_info.recall_chunk_index(recall_byte), 3);
+    assert!(verify_recall_chunk(&recall_info, recall_byte, &proof_of(CHUNK_SIZE * 3)).is_ok());
+}
+
+#[test]
+fn recall_tx_should_be_located_in_block() {
+    let (_client, _a, b) = forked_client();
+    let recall_tx_in = |recall_byte, block_number, weave_base, block: &Block| {
+        recall_tx_in_block::<Block>(
+            recall_byte,
+            block_number,
+            block.hash(),
+            weave_base,
+            &digest_transactions(block),
+        )
+    };
+
+    let recall_tx = recall_tx_in(32, 1, 0, &b[0]).unwrap();
+    assert_eq!(recall_tx.block_hash, b[0].hash());
+    assert_eq!(recall_tx.extrinsic_index, 1);
+    assert_eq!(recall_tx.chunk_root, Hash::repeat_byte(101));
+    assert_eq!(recall_tx.data_size, 5);
+    assert_eq!(recall_tx.data_base, 30);
+
+    // Beyond the data stored in b1.
+    assert!(recall_tx_in(36, 1, 0, &b[0]).is_err());
+
+    // b2 stores nothing.
+    assert!(recall_tx_in(5, 2, 35, &b[1]).is_err());
+}
+
+/// Runtime API answering the permastore queries with the recall index at the block queried.
+#[derive(Clone, Default)]
+struct MockRuntimeApi {
+    /// `(weave_size, block_number)` of the blocks storing data, by the block at which
+    /// the recall index is queried.
+    recall_indices: Arc<HashMap<Hash, Vec<(u64, u64)>>>,
+    /// `(chunk_root, data_size)` of the data stored before the permastore digest,
+    /// by `(block_number, extrinsic_index)`.
+    legacy_transactions: Arc<HashMap<(u64, u32), (Hash, u32)>>,
+}
+
+impl MockRuntimeApi {
+    fn recall_index(&self, at: &BlockId<Block>) -> &[(u64, u64)] {
+        match at {
+            BlockId::Hash(hash) => self
+                .recall_indices
+                .get(hash)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+            BlockId::Number(_) => unimplemented!("PoA queries the runtime at block hash"),
+        }
+    }
+}
+
+sp_api::mock_impl_runtime_apis! {
+    impl cp_permastore::PermastoreApi<Block, u64, u32, Hash> for MockRuntimeApi {
+        #[advanced]
+        fn find_recall_block(
+            &self,
+            at: &BlockId<Block>,
+            recall_byte: u64,
+        ) -> Result<NativeOrEncoded<Option<u64>>, sp_api::ApiError> {
+            Ok(self
+                .recall_index(at)
+                .iter()
+                .find(|(weave_size, _)| *weave_size >= recall_byte)
+                .map(|(_, block_number)| *block_number)
+                .into())
+        }
+
+        fn require_proof_of_access() -> bool {
+            true
+        }
+
+        fn block_size() -> u64 {
+            0
+        }
+
+        #[advanced]
+        fn weave_size(&self, at: &BlockId<Block>) -> Result<NativeOrEncoded<u64>, sp_api::ApiError> {
+            Ok(self
+                .recall_index(at)
+                .last()
+                .map(|(weave_size, _)| *weave_size)
+                .unwrap_or_default()
+                .into())
+        }
+
+        fn is_forgotten(_block_number: u64, _extrinsic_index: u32) -> bool {
+            false
+        }
+
+        fn chunk_root(&self, block_number: u64, extrinsic_index: u32) -> Option<Hash> {
+            self.legacy_transactions
+                .get(&(block_number, extrinsic_index))
+                .map(|(chunk_root, _)| *chunk_root)
+        }
+
+        fn data_size(&self, block_number: u64, extrinsic_index: u32) -> u32 {
+            self.legacy_transactions
+                .get(&(block_number, extrinsic_index))
+                .map(|(_, data_size)| *data_size)
+                .unwrap_or_default()
+        }
+    }
+
+    impl cp_poa::PoaApi<Block> for MockRuntimeApi {
+        fn poa_config() -> PoaConfiguration {
+            PoaConfiguration::default()
+        }
+    }
+
+    impl sp_block_builder::BlockBuilder<Block> for MockRuntimeApi {
+        fn apply_extrinsic(_extrinsic: Extrinsic) -> ApplyExtrinsicResult {
+            unimplemented!()
+        }
+
+        fn finalize_block() -> Header {
+            unimplemented!()
+        }
+
+        fn inherent_extrinsics(_data: InherentData) -> Vec<Extrinsic> {
+            unimplemented!()
+        }
+
+        fn check_inherents(_block: Block, _data: InherentData) -> CheckInherentsResult {
+            unimplemented!()
+        }
+    }
+}
+
+/// Test client with the runtime api mocked.
+struct MockClient {
+    client: Arc<TestClient>,
+    runtime_api: MockRuntimeApi,
+    /// Blocks whose bodies are not available locally.
+    missing_bodies: HashSet<Hash>,
+}
+
+impl MockClient {
+    fn has_body(&self, id: &BlockId<Block>) -> bool {
+        !matches!(id, BlockId::Hash(hash) if self.missing_bodies.contains(hash))
+    }
+}
+
+impl HeaderBackend<Block> for MockClient {
+    fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+        HeaderBackend::header(&*self.client, id)
+    }
+
+    fn info(&self) -> Info<Block> {
+        self.client.info()
+    }
+
+    fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+        self.client.status(id)
+    }
+
+    fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<u64>> {
+        self.client.number(hash)
+    }
+
+    fn hash(&self, number: u64) -> sp_blockchain::Result<Option<Hash>> {
+        self.client.hash(number)
+    }
+}
+
+impl HeaderMetadata<Block> for MockClient {
+    type Error = sp_blockchain::Error;
+
+    fn header_metadata(&self, hash: Hash) -> Result<CachedHeaderMetadata<Block>, Self::Error> {
+        self.client.header_metadata(hash)
+    }
+
+    fn insert_header_metadata(&self, hash: Hash, metadata: CachedHeaderMetadata<Block>) {
+        self.client.insert_header_metadata(hash, metadata)
+    }
+
+    fn remove_header_metadata(&self, hash: Hash) {
+        self.client.remove_header_metadata(hash)
+    }
+}
+
+impl BlockBackend<Block> for MockClient {
+    fn block_body(&self, id: &BlockId<Block>) -> sp_blockchain::Result<Option<Vec<Extrinsic>>> {
+        if self.has_body(id) {
+            self.client.block_body(id)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn block_indexed_body(
+        &self,
+        id: &BlockId<Block>,
+    ) -> sp_blockchain::Result<Option<Vec<Vec<u8>>>> {
+        self.client.block_indexed_body(id)
+    }
+
+    fn block(&self, id: &BlockId<Block>) -> sp_blockchain::Result<Option<SignedBlock<Block>>> {
+        if self.has_body(id) {
+            self.client.block(id)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn block_status(
+        &self,
+        id: &BlockId<Block>,
+    ) -> sp_blockchain::Result<sp_consensus::BlockStatus> {
+        self.client.block_status(id)
+    }
+
+    fn justifications(&self, id: &BlockId<Block>) -> sp_blockchain::Result<Option<Justifications>> {
+        self.client.justifications(id)
+    }
+
+    fn block_hash(&self, number: u64) -> sp_blockchain::Result<Option<Hash>> {
+        self.client.block_hash(number)
+    }
+
+    fn indexed_transaction(&self, hash: &Hash) -> sp_blockchain::Result<Option<Vec<u8>>> {
+        self.client.indexed_transaction(hash)
+    }
+}
+
+impl AuxStore for MockClient {
+    fn insert_aux<
+        'a,
+        'b: 'a,
+        'c: 'a,
+        I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+        D: IntoIterator<Item = &'a &'b [u8]>,
+    >(
+        &self,
+        insert: I,
+        delete: D,
+    ) -> sp_blockchain::Result<()> {
+        self.client.insert_aux(insert, delete)
+    }
+
+    fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+        self.client.get_aux(key)
+    }
+}
+
+impl ProvideCache<Block> for MockClient {
+    fn cache(&self) -> Option<Arc<dyn sp_blockchain::Cache<Block>>> {
+        None
+    }
+}
+
+impl BlockOf for MockClient {
+    type Type = Block;
+}
+
+impl ProvideRuntimeApi<Block> for MockClient {
+    type Api = MockRuntimeApi;
+
+    fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+        self.runtime_api.clone().into()
+    }
+}
+
+type TransactionFor = sp_api::TransactionFor<MockClient, Block>;
+
+/// Inner block import accepting every block.
+struct MockBlockImport;
+
+#[async_trait::async_trait]
+impl BlockImport<Block> for MockBlockImport {
+    type Error = ConsensusError;
+    type Transaction = TransactionFor;
+
+    async fn check_block(
+        &mut self,
+        _block: BlockCheckParams<Block>,
+    ) -> Result<ImportResult, Self::Error> {
+        Ok(ImportResult::imported(false))
+    }
+
+    async fn import_block(
+        &mut self,
+        _block: BlockImportParams<Block, Self::Transaction>,
+        _new_cache: HashMap<CacheKeyId, Vec<u8>>,
+    ) -> Result<ImportResult, Self::Error> {
+        Ok(ImportResult::imported(false))
+    }
+}
+
+type TestPoaBlockImport = PurePoaBlockImport<Block, MockBlockImport, MockClient>;
+
+/// Returns the PoA block import on top of `client`, whose runtime has `recall_indices`.
+fn poa_block_import(
+    client: Arc<TestClient>,
+    recall_indices: Vec<(Hash, Vec<(u64, u64)>)>,
+    missing_bodies: Vec<Hash>,
+    weave_index: WeaveIndex<Block>,
+) -> TestPoaBlockImport {
+    let client = MockClient {
+        client,
+        runtime_api: MockRuntimeApi {
+            recall_indices: Arc::new(recall_indices.into_iter().collect()),
+            ..Default::default()
+        },
+        missing_bodies: missing_bodies.into_iter().collect(),
+    };
+    PurePoaBlockImport::new(MockBlockImport, Arc::new(client), weave_index, None)
+}
+
+/// Imports a block on top of `parent` sealed with `poa` if any.
+fn import_on(
+    poa_block_import: &mut TestPoaBlockImport,
+    parent: &Block,
+    poa: Option<ProofOfAccess>,
+    state_action: StateAction<Block, TransactionFor>,
+) -> Result<ImportResult, ConsensusError> {
+    let block = block_import_params(parent, poa, state_action);
+    block_on(poa_block_import.import_block(block, HashMap::new()))
+}
+
+/// Returns the params of importing a block on top of `parent` sealed with `poa` if any.
+fn block_import_params(
+    parent: &Block,
+    poa: Option<ProofOfAccess>,
+    state_action: StateAction<Block, TransactionFor>,
+) -> BlockImportParams<Block, TransactionFor> {
+    let mut digest = Digest::default();
+    if let Some(poa) = poa {
+        digest.push(DigestItem::Seal(POA_ENGINE_ID, poa.encode()));
+    }
+    let header = Header::new(
+        parent.header.number + 1,
+        Default::default(),
+        Default::default(),
+        parent.hash(),
+        digest,
+    );
+
+    let mut block = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+    block.state_action = state_action;
+    block
+}
+
+/// Returns the data spanning 3 chunks.
+fn recall_data() -> Vec<u8> {
+    (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect()
+}
+
+/// Returns the index of the recall chunk of depth 1 on top of `parent`, given
+/// `data` is the only data in the weave.
+fn recall_chunk_on(parent: Hash, data: &[u8]) -> u32 {
+    let recall_byte = calculate_challenge_byte(parent.encode(), data.len() as u64, 1);
+    recall_chunk_index(recall_byte, 0, data.len() as u32)
+}
+
+/// Returns the PoA of depth 1 proving the chunk `chunk_index` of `data`.
+fn proof_of_access(data: &[u8], chunk_index: u32, tx_path: Vec<Vec<u8>>) -> ProofOfAccess {
+    let chunk_proof = ChunkProofBuilder::new(data.to_vec(), chunk_index * CHUNK_SIZE)
+        .build()
+        .unwrap();
+    ProofOfAccess::new(1, tx_path, chunk_proof)
+}
+
+#[test]
+fn block_imported_without_state_should_not_be_verified() {
+    let (client, a, _) = forked_client();
+    let mut block_import = poa_block_import(
+        client,
+        Vec::new(),
+        Vec::new(),
+        WeaveIndex::new(sc_client_db::offchain::LocalStorage::new_test()),
+    );
+
+    // The history before warp sync target is imported without state, whose parent
+    // state required by the verification does not exist.
+    assert!(import_on(&mut block_import, &a[2], None, StateAction::Skip).is_ok());
+
+    assert!(import_on(&mut block_import, &a[2], None, StateAction::Execute).is_err());
+}
+
+#[test]
+fn recall_before_warp_sync_target_should_be_verified_against_header() {
+    let mut client = Arc::new(substrate_test_runtime_client::new());
+    let genesis = client.chain_info().genesis_hash;
+
+    let data = recall_data();
+    let c1 = import_block_storing(&mut client, genesis, &data);
+    let c2 = import_block(&mut client, c1.hash(), Vec::new());
+
+    // The state of c2 has been imported by warp sync.
+    let (key, value) = aux_schema::warp_sync_target_aux(2u64);
+    client
+        .insert_aux(&[(key.as_slice(), value.unwrap().as_slice())], &[])
+        .unwrap();
+
+    let recall_indices = vec![(c2.hash(), vec![(data.len() as u64, 1)])];
+    let chunk_index = recall_chunk_on(c2.hash(), &data);
+    let other_data = data.iter().map(|b| b.wrapping_add(1)).collect::<Vec<_>>();
+
+    // The body of c1 has not been downloaded yet, only the chunk proof is verified.
+    let mut block_import = poa_block_import(
+        client.clone(),
+        recall_indices.clone(),
+        vec![c1.hash()],
+        WeaveIndex::new(sc_client_db::offchain::LocalStorage::new_test()),
+    );
+    let mut import_on_c2 = |poa| import_on(&mut block_import, &c2, Some(poa), StateAction::Execute);
+
+    assert!(import_on_c2(proof_of_access(&data, chunk_index, Vec::new())).is_ok());
+    assert!(import_on_c2(proof_of_access(&data, (chunk_index + 1) % 3, Vec::new())).is_err());
+    assert!(import_on_c2(proof_of_access(&other_data, chunk_index, Vec::new())).is_err());
+
+    // The weave base before c1 is searched for only once.
+    assert_eq!(block_import.weave_bases.lock().unwrap().get(&1), Some(&0));
+
+    // The tx proof is verified too once the body of c1 is available.
+    let mut block_import = poa_block_import(
+        client,
+        recall_indices,
+        Vec::new(),
+        WeaveIndex::new(sc_client_db::offchain::LocalStorage::new_test()),
+    );
+    let mut import_on_c2 = |poa| import_on(&mut block_import, &c2, Some(poa), StateAction::Execute);
+
+    let tx_path =
+        build_extrinsic_proof::<Block>(0, c1.header.extrinsics_root, c1.extrinsics.clone())
+            .unwrap();
+    assert!(import_on_c2(proof_of_access(&data, chunk_index, tx_path)).is_ok());
+    assert!(import_on_c2(proof_of_access(&data, chunk_index, Vec::new())).is_err());
+}
+
+#[test]
+fn block_on_side_fork_should_be_verified_against_fork() {
+    let mut client = Arc::new(substrate_test_runtime_client::new());
+    let genesis = client.chain_info().genesis_hash;
+
+    //          /- a1 [10] - a2 - a3   (best chain)
+    // genesis -
+    //          \- b1 [data] - b2      (side fork)
+    let a1 = import_block(&mut client, genesis, stored_transactions(&[10], 1));
+    let a2 = import_block(&mut client, a1.hash(), Vec::new());
+    let a3 = import_block(&mut client, a2.hash(), Vec::new());
+
+    let data = recall_data();
+    let b1 = import_block_storing(&mut client, genesis, &data);
+    let b2 = import_block(&mut client, b1.hash(), Vec::new());
+
+    assert_eq!(client.chain_info().best_hash, a3.hash());
+
+    // The weave index follows the best chain.
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+    weave_index.push_for_test(
+        WeaveBlock {
+            block_number: 1,
+            block_hash: a1.hash(),
+            weave_base: 0,
+            transactions: stored_transactions(&[10], 1),
+        },
+        &[],
+    );
+
+    let mut block_import = poa_block_import(
+        client,
+        vec![(b2.hash(), vec![(data.len() as u64, 1)])],
+        Vec::new(),
+        weave_index,
+    );
+
+    // The recall tx of the block on top of b2 exists only on the side fork.
+    let chunk_index = recall_chunk_on(b2.hash(), &data);
+    let tx_path =
+        build_extrinsic_proof::<Block>(0, b1.header.extrinsics_root, b1.extrinsics.clone())
+            .unwrap();
+
+    let poa = proof_of_access(&data, chunk_index, tx_path.clone());
+    assert!(import_on(&mut block_import, &b2, Some(poa), StateAction::Execute).is_ok());
+
+    let poa = proof_of_access(&data, (chunk_index + 1) % 3, tx_path);
+    assert!(import_on(&mut block_import, &b2, Some(poa), StateAction::Execute).is_err());
+
+    let poa = proof_of_access(&data, chunk_index, Vec::new());
+    assert!(import_on(&mut block_import, &b2, Some(poa), StateAction::Execute).is_err());
+}
+
+#[test]
+fn recall_block_before_digest_should_be_verified_against_legacy_state() {
+    let mut client = Arc::new(substrate_test_runtime_client::new());
+    let genesis = client.chain_info().genesis_hash;
+
+    // c1 stores the data without the permastore digest.
+    let data = recall_data();
+    let c1 = import_block_with_extrinsics(
+        &mut client,
+        genesis,
+        Vec::new(),
+        vec![Extrinsic::IncludeData(data.clone())],
+    );
+    let c2 = import_block(&mut client, c1.hash(), Vec::new());
+
+    let block_import_with = |legacy_transactions: Vec<((u64, u32), (Hash, u32))>| {
+        let client = MockClient {
+            client: client.clone(),
+            runtime_api: MockRuntimeApi {
+                recall_indices: Arc::new(
+                    vec![(c2.hash(), vec![(data.len() as u64, 1)])]
+                        .into_iter()
+                        .collect(),
+                ),
+                legacy_transactions: Arc::new(legacy_transactions.into_iter().collect()),
+            },
+            missing_bodies: HashSet::new(),
+        };
+        PurePoaBlockImport::new(
+            MockBlockImport,
+            Arc::new(client),
+            WeaveIndex::new(sc_client_db::offchain::LocalStorage::new_test()),
+            None,
+        )
+    };
+
+    let chunk_index = recall_chunk_on(c2.hash(), &data);
+    let tx_path =
+        build_extrinsic_proof::<Block>(0, c1.header.extrinsics_root, c1.extrinsics.clone())
+            .unwrap();
+    let poa = proof_of_access(&data, chunk_index, tx_path);
+
+    let mut block_import = block_import_with(vec![(
+        (1, 0),
+        (chunk_tree::chunk_root(&data), data.len() as u32),
+    )]);
+    assert!(import_on(
+        &mut block_import,
+        &c2,
+        Some(poa.clone()),
+        StateAction::Execute
+    )
+    .is_ok());
+
+    let poa_of_wrong_chunk = proof_of_access(&data, (chunk_index + 1) % 3, poa.tx_path.clone());
+    assert!(import_on(
+        &mut block_import,
+        &c2,
+        Some(poa_of_wrong_chunk),
+        StateAction::Execute
+    )
+    .is_err());
+
+    // The recall tx can not be located without the legacy state.
+    let mut block_import = block_import_with(Vec::new());
+    assert!(import_on(&mut block_import, &c2, Some(poa), StateAction::Execute).is_err());
+}
+
+#[test]
+fn block_with_unavailable_recall_block_should_only_be_imported_once_finalized() {
+    let mut client = Arc::new(substrate_test_runtime_client::new());
+    let genesis = client.chain_info().genesis_hash;
+
+    // c1 stores the data without the permastore digest.
+    let data = recall_data();
+    let c1 = import_block_with_extrinsics(
+        &mut client,
+        genesis,
+        Vec::new(),
+        vec![Extrinsic::IncludeData(data.clone())],
+    );
+    let c2 = import_block(&mut client, c1.hash(), Vec::new());
+
+    let (key, value) = aux_schema::warp_sync_target_aux(2u64);
+    client
+        .insert_aux(&[(key.as_slice(), value.unwrap().as_slice())], &[])
+        .unwrap();
+
+    // The recall tx can not be located as the body of c1 has not been downloaded.
+    let mut block_import = poa_block_import(
+        client,
+        vec![(c2.hash(), vec![(data.len() as u64, 1)])],
+        vec![c1.hash()],
+        WeaveIndex::new(sc_client_db::offchain::LocalStorage::new_test()),
+    );
+    let forged_data = data.iter().map(|b| b.wrapping_add(1)).collect::<Vec<_>>();
+    let poa = proof_of_access(&forged_data, recall_chunk_on(c2.hash(), &data), Vec::new());
+
+    let block = block_import_params(&c2, Some(poa.clone()), StateAction::Execute);
+    assert!(block_on(block_import.import_block(block, HashMap::new())).is_err());
+
+    let mut block = block_import_params(&c2, Some(poa), StateAction::Execute);
+    block.finalized = true;
+    assert!(block_on(block_import.import_block(block, HashMap::new())).is_ok());
+}
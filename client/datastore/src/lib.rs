@@ -23,30 +23,99 @@
 //!
 //! Currently, it is implemented on the top of offchain storage, which is a persistent
 //! local storage of each node.
+//!
+//! The transaction data is not persisted as a whole, instead it's split into chunks of
+//! [`CHUNK_SIZE`] and each chunk is stored individually under `(chunk_root, chunk_index)`,
+//! together with a [`DataMeta`] record per transaction, which makes it possible to read
+//! only the chunk needed, e.g., when building the proof of access. The chunks can also
+//! be uploaded one by one, the progress is tracked by an [`UploadSession`] persisted
+//! in the offchain storage, so that the upload can be resumed after a restart.
+//!
+//! A [`WeaveIndex`] of all the transaction data on the best chain is maintained by
+//! [`WeaveIndexer`] as well, which enables locating the recall transaction locally.
+//! The chunk root and size of each transaction data are also indexed from the block
+//! digest, which are no longer kept in the runtime state, see [`ChunkRootBackend`].
+//! The data stored before the digest was introduced is backfilled from the legacy
+//! runtime state.
+//!
+//! The data submitted along with an extrinsic is tracked in [`PendingData`] until the
+//! extrinsic lands on chain, [`PendingDataCleaner`] removes the data whose extrinsic
+//! is rejected, dropped or never included.
+//!
+//! The size of stored data is tracked in [`DataStats`], which can be reported along
+//! with the read latency via [`DatastoreMetrics`].
 
+mod metrics;
+mod pending_data;
 #[cfg(test)]
 mod tests;
+mod weave_index;
+
+pub use self::metrics::DatastoreMetrics;
+pub use self::pending_data::{PendingData, PendingDataCleaner, PendingEntry, PendingEntryFor};
+
+pub use self::weave_index::{
+    legacy_stored_transactions, stored_transactions, RecallTransaction, RecallTransactionFor,
+    WeaveBlock, WeaveBlockFor, WeaveIndex, WeaveIndexBackend, WeaveIndexer,
+};
 
 use std::sync::Arc;
+use std::time::Instant;
 
-use codec::Encode;
+use codec::{Decode, Encode};
 
+use sc_client_api::execution_extensions::ExtensionsFactory;
 use sc_client_db::offchain::LocalStorage;
-use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
+use sp_core::offchain::{Capabilities, Capability};
+use sp_externalities::Extensions;
 use sp_runtime::{
     generic::BlockId,
     offchain::OffchainStorage,
     traits::{Block as BlockT, NumberFor},
 };
 
-use cp_permastore::{PermaStorage, PermastoreApi};
+use cp_permastore::{
+    chunk_tree, DataMeta, PermaStorage, PermaStorageExt, UploadSession, CHUNK_SIZE,
+};
+
+/// Prefix of the offchain storage for the metadata of transaction data.
+const META_PREFIX: &[u8] = b"permastore_meta";
+
+/// Prefix of the offchain storage for the chunks of transaction data.
+const CHUNK_PREFIX: &[u8] = b"permastore_chunk";
+
+/// Prefix of the offchain storage for the ongoing chunked uploads of transaction data.
+const UPLOAD_PREFIX: &[u8] = b"permastore_upload";
+
+/// Prefix of the offchain storage for the [`DataStats`].
+const STATS_PREFIX: &[u8] = b"permastore_stats";
+
+const STATS_KEY: &[u8] = b"stats";
+
+/// Returns the storage key of chunk given the data `key` and `chunk_index`.
+fn chunk_key(key: &[u8], chunk_index: u32) -> Vec<u8> {
+    (key, chunk_index).encode()
+}
+
+/// Statistics of the transaction data stored locally.
+///
+/// Only the data whose [`DataMeta`] has been written is counted, i.e., the chunks
+/// of an unfinished upload are excluded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct DataStats {
+    /// Total size of the stored data.
+    pub stored_bytes: u64,
+    /// Number of the stored chunks.
+    pub stored_chunks: u64,
+}
 
 /// Permanent storage backed by offchain storage.
 #[derive(Clone)]
 pub struct PermanentStorage<C> {
     offchain_storage: LocalStorage,
     client: Arc<C>,
+    metrics: Option<DatastoreMetrics>,
 }
 
 impl<C> PermanentStorage<C> {
@@ -56,6 +125,7 @@ impl<C> PermanentStorage<C> {
         Self {
             offchain_storage: LocalStorage::new_test(),
             client,
+            metrics: None,
         }
     }
 
@@ -64,6 +134,67 @@ impl<C> PermanentStorage<C> {
         Self {
             offchain_storage,
             client,
+            metrics: None,
+        }
+    }
+
+    /// Reports the stats and reads of this storage to `metrics`.
+    pub fn with_metrics(mut self, metrics: Option<DatastoreMetrics>) -> Self {
+        if let Some(metrics) = &metrics {
+            metrics.report_stats(&self.stats());
+        }
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the [`DataStats`] of this storage.
+    pub fn stats(&self) -> DataStats {
+        self.offchain_storage
+            .get(STATS_PREFIX, STATS_KEY)
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the [`DataMeta`] `removed` with `added` in the [`DataStats`].
+    fn update_stats(&mut self, removed: Option<&DataMeta>, added: Option<&DataMeta>) {
+        let stats = loop {
+            let encoded = self.offchain_storage.get(STATS_PREFIX, STATS_KEY);
+            let mut stats: DataStats = encoded
+                .as_ref()
+                .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+                .unwrap_or_default();
+
+            if let Some(removed) = removed {
+                stats.stored_bytes = stats
+                    .stored_bytes
+                    .saturating_sub(u64::from(removed.data_size));
+                stats.stored_chunks = stats
+                    .stored_chunks
+                    .saturating_sub(u64::from(removed.chunk_count()));
+            }
+            if let Some(added) = added {
+                stats.stored_bytes += u64::from(added.data_size);
+                stats.stored_chunks += u64::from(added.chunk_count());
+            }
+
+            if self.offchain_storage.compare_and_set(
+                STATS_PREFIX,
+                STATS_KEY,
+                encoded.as_deref(),
+                &stats.encode(),
+            ) {
+                break stats;
+            }
+        };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.report_stats(&stats);
+        }
+    }
+
+    fn report_read(&self, kind: &str, start: Instant) {
+        if let Some(metrics) = &self.metrics {
+            metrics.report_read(kind, start.elapsed());
         }
     }
 }
@@ -74,6 +205,9 @@ where
 {
     /// Sets the value of transaction data given `key`.
     ///
+    /// The value is split into chunks of [`CHUNK_SIZE`] which are stored
+    /// individually, the [`DataMeta`] is stored last.
+    ///
     /// # Arguments
     ///
     /// * `key`: encoded chunk root of transaction data.
@@ -82,27 +216,165 @@ where
     /// NOTE: the maximum size of served value is 10MiB,
     /// this limit should be enforced by the higher level API.
     fn submit(&mut self, key: &[u8], value: &[u8]) {
+        let mut chunk_hashes = Vec::with_capacity(value.len() / CHUNK_SIZE as usize + 1);
+
+        for (index, chunk) in chunk_tree::chunks(value).enumerate() {
+            self.submit_chunk(key, index as u32, chunk);
+            chunk_hashes.push(chunk_tree::chunk_hash(chunk));
+        }
+
+        let data_meta = DataMeta {
+            data_size: value.len() as u32,
+            chunk_hashes,
+        };
+
+        let old_data_meta = self.data_meta(key);
         self.offchain_storage
-            .set(sp_offchain::STORAGE_PREFIX, key, value)
+            .set(META_PREFIX, key, &data_meta.encode());
+        self.update_stats(old_data_meta.as_ref(), Some(&data_meta));
     }
 
     /// Returns the entire transaction data given `key`.
     ///
+    /// Returns `None` if any chunk of the data is missing.
+    ///
     /// # Arguments
     ///
     /// * `key`: chunk_root of the transaction data.
     fn retrieve(&self, key: &[u8]) -> Option<Vec<u8>> {
-        self.offchain_storage.get(sp_offchain::STORAGE_PREFIX, key)
+        let start = Instant::now();
+
+        let data_meta = self.data_meta(key)?;
+
+        let mut value = Vec::with_capacity(data_meta.data_size as usize);
+        for chunk_index in 0..data_meta.chunk_count() {
+            value.extend(self.retrieve_chunk(key, chunk_index)?);
+        }
+
+        self.report_read("data", start);
+
+        Some(value)
     }
 
     /// Removes the storage value under given key.
     ///
+    /// The chunks of an unfinished upload under `key` are removed as well.
+    ///
     /// # Arguments
     ///
     /// * `key`: encoded chunk root of transaction data.
     fn remove(&mut self, key: &[u8]) {
+        let data_meta = self.data_meta(key);
+        let chunk_count = data_meta
+            .as_ref()
+            .map(|data_meta| data_meta.chunk_count())
+            .or_else(|| {
+                self.upload_session(key)
+                    .map(|upload_session| upload_session.chunk_count())
+            });
+        if let Some(chunk_count) = chunk_count {
+            for chunk_index in 0..chunk_count {
+                self.offchain_storage
+                    .remove(CHUNK_PREFIX, &chunk_key(key, chunk_index));
+            }
+        }
+        self.offchain_storage.remove(UPLOAD_PREFIX, key);
+        self.offchain_storage.remove(META_PREFIX, key);
+
+        if data_meta.is_some() {
+            self.update_stats(data_meta.as_ref(), None);
+        }
+    }
+
+    fn data_meta(&self, key: &[u8]) -> Option<DataMeta> {
+        self.offchain_storage
+            .get(META_PREFIX, key)
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    fn submit_chunk(&mut self, key: &[u8], chunk_index: u32, chunk: &[u8]) {
         self.offchain_storage
-            .remove(sp_offchain::STORAGE_PREFIX, key)
+            .set(CHUNK_PREFIX, &chunk_key(key, chunk_index), chunk)
+    }
+
+    fn retrieve_chunk(&self, key: &[u8], chunk_index: u32) -> Option<Vec<u8>> {
+        let start = Instant::now();
+        let chunk = self
+            .offchain_storage
+            .get(CHUNK_PREFIX, &chunk_key(key, chunk_index));
+        self.report_read("chunk", start);
+        chunk
+    }
+
+    fn upload_session(&self, key: &[u8]) -> Option<UploadSession> {
+        self.offchain_storage
+            .get(UPLOAD_PREFIX, key)
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    fn set_upload_session(&mut self, key: &[u8], upload_session: &UploadSession) {
+        self.offchain_storage
+            .set(UPLOAD_PREFIX, key, &upload_session.encode())
+    }
+
+    /// Writes the [`DataMeta`] of the uploaded chunks under `key`.
+    ///
+    /// The chunk hashes are computed from the stored chunks instead of being tracked
+    /// in the [`UploadSession`], which keeps the session small to update per chunk.
+    fn finish_upload(&mut self, key: &[u8]) -> Option<DataMeta> {
+        let upload_session = self.upload_session(key)?;
+
+        if !upload_session.is_complete() {
+            return None;
+        }
+
+        let chunk_hashes = (0..upload_session.chunk_count())
+            .map(|chunk_index| {
+                self.retrieve_chunk(key, chunk_index)
+                    .map(|chunk| chunk_tree::chunk_hash(&chunk))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let data_meta = DataMeta {
+            data_size: upload_session.data_size,
+            chunk_hashes,
+        };
+
+        let old_data_meta = self.data_meta(key);
+        self.offchain_storage
+            .set(META_PREFIX, key, &data_meta.encode());
+        self.offchain_storage.remove(UPLOAD_PREFIX, key);
+        self.update_stats(old_data_meta.as_ref(), Some(&data_meta));
+
+        Some(data_meta)
+    }
+}
+
+/// Extensions factory for providing the [`PermaStorageExt`] to the runtime.
+///
+/// The extension is only registered for the calls having the capability of reading
+/// the local offchain database, e.g., the transaction validation in the pool, since
+/// whether the data has been stored locally must not affect the block import.
+pub struct PermaStorageExtensionsFactory {
+    offchain_storage: LocalStorage,
+}
+
+impl PermaStorageExtensionsFactory {
+    /// Creates a new instance of [`PermaStorageExtensionsFactory`].
+    pub fn new(offchain_storage: LocalStorage) -> Self {
+        Self { offchain_storage }
+    }
+}
+
+impl ExtensionsFactory for PermaStorageExtensionsFactory {
+    fn extensions_for(&self, capabilities: Capabilities) -> Extensions {
+        let mut extensions = Extensions::new();
+        if capabilities.has(Capability::OffchainDbRead) {
+            // The client is not required for checking the existence of data.
+            let perma_storage = PermanentStorage::new(self.offchain_storage.clone(), Arc::new(()));
+            extensions.register(PermaStorageExt::new(perma_storage));
+        }
+        extensions
     }
 }
 
@@ -112,9 +384,15 @@ pub enum Error<Block: BlockT> {
     /// Block number not found.
     #[error("Block number not found given block id `{0}`")]
     BlockNumberNotFound(BlockId<Block>),
+    /// Block header not found.
+    #[error("Header not found given block id `{0}`")]
+    HeaderNotFound(BlockId<Block>),
     /// Chunk root does not exist.
     #[error("Chunk root is None at block: {0}, extrinsic index: {1}")]
     ChunkRootIsNone(BlockId<Block>, u32),
+    /// Codec error.
+    #[error(transparent)]
+    Codec(#[from] codec::Error),
     /// Blockchain error.
     #[error(transparent)]
     Blockchain(#[from] Box<sp_blockchain::Error>),
@@ -124,41 +402,64 @@ pub enum Error<Block: BlockT> {
 }
 
 /// Backend for storing a map of (block_number, extrinsic_index) to chunk_root.
+///
+/// The map is indexed locally from the [`cp_permastore::PERMASTORE_ENGINE_ID`] digest
+/// of the blocks on the best chain.
 pub trait ChunkRootBackend<Block: BlockT> {
     /// Returns chunk root given `block_number` and `extrinsic_index`.
-    ///
-    /// It's fetched from the runtime now.
     fn chunk_root(
         &self,
-        at: Option<BlockId<Block>>,
         block_number: NumberFor<Block>,
         extrinsic_index: u32,
-    ) -> Result<Option<Block::Hash>, Error<Block>>;
+    ) -> Option<Block::Hash>;
+
+    /// Returns the size of transaction data given `block_number` and `extrinsic_index`.
+    fn data_size(&self, block_number: NumberFor<Block>, extrinsic_index: u32) -> Option<u32>;
+
+    /// Returns `(block_number, extrinsic_index)` of the extrinsic storing data given its hash.
+    fn extrinsic_position(&self, extrinsic_hash: Block::Hash) -> Option<(NumberFor<Block>, u32)>;
 }
 
 /// Permanent transaction data backend.
 ///
 /// High level API for accessing the transaction data.
-pub trait TransactionDataBackend<Block: BlockT>: PermaStorage + ChunkRootBackend<Block> {
+pub trait TransactionDataBackend<Block: BlockT>:
+    PermaStorage + ChunkRootBackend<Block> + WeaveIndexBackend<Block>
+{
     /// Get transaction data. Returns `None` if data is not found.
     fn transaction_data(
         &self,
         id: BlockId<Block>,
         extrinsic_index: u32,
     ) -> Result<Option<Vec<u8>>, Error<Block>>;
+
+    /// Get the metadata of transaction data. Returns `None` if data is not found.
+    fn transaction_data_meta(
+        &self,
+        id: BlockId<Block>,
+        extrinsic_index: u32,
+    ) -> Result<Option<DataMeta>, Error<Block>>;
+
+    /// Get a single chunk of transaction data. Returns `None` if the chunk is not found.
+    fn transaction_data_chunk(
+        &self,
+        id: BlockId<Block>,
+        extrinsic_index: u32,
+        chunk_index: u32,
+    ) -> Result<Option<Vec<u8>>, Error<Block>>;
 }
 
-impl<Block, C> TransactionDataBackend<Block> for PermanentStorage<C>
-where
-    Block: BlockT,
-    C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync,
-    C::Api: cp_permastore::PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
-{
-    fn transaction_data(
+impl<C> PermanentStorage<C> {
+    /// Returns the database key of transaction data given `block_id` and `extrinsic_index`.
+    fn transaction_data_key<Block>(
         &self,
         block_id: BlockId<Block>,
         extrinsic_index: u32,
-    ) -> Result<Option<Vec<u8>>, Error<Block>> {
+    ) -> Result<Vec<u8>, Error<Block>>
+    where
+        Block: BlockT,
+        C: HeaderBackend<Block> + Send + Sync,
+    {
         log::debug!(
             target: "datastore",
             "Fetching chunk root at block_id: {}, extrinsic_index: {}",
@@ -172,7 +473,7 @@ where
             .ok_or(Error::BlockNumberNotFound(block_id))?;
 
         let chunk_root = self
-            .chunk_root(None, block_number, extrinsic_index)?
+            .chunk_root(block_number, extrinsic_index)
             .ok_or(Error::ChunkRootIsNone(block_id, extrinsic_index))?;
 
         let key = chunk_root.encode();
@@ -182,26 +483,76 @@ where
             chunk_root, key,
         );
 
+        Ok(key)
+    }
+}
+
+impl<Block, C> TransactionDataBackend<Block> for PermanentStorage<C>
+where
+    Block: BlockT,
+    C: HeaderBackend<Block> + Send + Sync,
+{
+    fn transaction_data(
+        &self,
+        block_id: BlockId<Block>,
+        extrinsic_index: u32,
+    ) -> Result<Option<Vec<u8>>, Error<Block>> {
+        let key = self.transaction_data_key(block_id, extrinsic_index)?;
         Ok(self.retrieve(&key))
     }
+
+    fn transaction_data_meta(
+        &self,
+        block_id: BlockId<Block>,
+        extrinsic_index: u32,
+    ) -> Result<Option<DataMeta>, Error<Block>> {
+        let key = self.transaction_data_key(block_id, extrinsic_index)?;
+        Ok(self.data_meta(&key))
+    }
+
+    fn transaction_data_chunk(
+        &self,
+        block_id: BlockId<Block>,
+        extrinsic_index: u32,
+        chunk_index: u32,
+    ) -> Result<Option<Vec<u8>>, Error<Block>> {
+        let key = self.transaction_data_key(block_id, extrinsic_index)?;
+        Ok(self.retrieve_chunk(&key, chunk_index))
+    }
 }
 
 impl<Block, C> ChunkRootBackend<Block> for PermanentStorage<C>
 where
     Block: BlockT,
-    C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync,
-    C::Api: cp_permastore::PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
 {
     fn chunk_root(
         &self,
-        at: Option<BlockId<Block>>,
         block_number: NumberFor<Block>,
         extrinsic_index: u32,
-    ) -> Result<Option<Block::Hash>, Error<Block>> {
-        let at = at.unwrap_or_else(|| BlockId::hash(self.client.info().best_hash));
-        self.client
-            .runtime_api()
-            .chunk_root(&at, block_number, extrinsic_index)
-            .map_err(Error::ApiError)
+    ) -> Option<Block::Hash> {
+        WeaveIndex::<Block>::new(self.offchain_storage.clone())
+            .chunk_root(block_number, extrinsic_index)
+    }
+
+    fn data_size(&self, block_number: NumberFor<Block>, extrinsic_index: u32) -> Option<u32> {
+        WeaveIndex::<Block>::new(self.offchain_storage.clone())
+            .data_size(block_number, extrinsic_index)
+    }
+
+    fn extrinsic_position(&self, extrinsic_hash: Block::Hash) -> Option<(NumberFor<Block>, u32)> {
+        WeaveIndex::<Block>::new(self.offchain_storage.clone()).extrinsic_position(extrinsic_hash)
+    }
+}
+
+impl<Block, C> WeaveIndexBackend<Block> for PermanentStorage<C>
+where
+    Block: BlockT,
+{
+    fn locate_recall_transaction(&self, recall_byte: u64) -> Option<RecallTransactionFor<Block>> {
+        WeaveIndex::<Block>::new(self.offchain_storage.clone()).locate(recall_byte)
+    }
+
+    fn recall_block_body(&self, block_hash: Block::Hash) -> Option<Vec<Block::Extrinsic>> {
+        WeaveIndex::<Block>::new(self.offchain_storage.clone()).recall_body(block_hash)
     }
 }
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the datastore.
+
+use std::time::Duration;
+
+use prometheus_endpoint::{
+    exponential_buckets, register, Gauge, HistogramOpts, HistogramVec, PrometheusError, Registry,
+    U64,
+};
+
+use crate::DataStats;
+
+/// Metrics of the datastore.
+#[derive(Clone)]
+pub struct DatastoreMetrics {
+    stored_bytes: Gauge<U64>,
+    stored_chunks: Gauge<U64>,
+    read_duration: HistogramVec,
+}
+
+impl DatastoreMetrics {
+    /// Registers the datastore metrics in `registry`.
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            stored_bytes: register(
+                Gauge::new(
+                    "datastore_stored_bytes",
+                    "Total size of the transaction data stored locally",
+                )?,
+                registry,
+            )?,
+            stored_chunks: register(
+                Gauge::new(
+                    "datastore_stored_chunks",
+                    "Number of the chunks of transaction data stored locally",
+                )?,
+                registry,
+            )?,
+            read_duration: register(
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "datastore_read_duration_seconds",
+                        "Time taken to read the transaction data by kind",
+                    )
+                    .buckets(exponential_buckets(0.00001, 4.0, 10)?),
+                    &["kind"],
+                )?,
+                registry,
+            )?,
+        })
+    }
+
+    /// Reports the current `stats` of datastore.
+    pub fn report_stats(&self, stats: &DataStats) {
+        self.stored_bytes.set(stats.stored_bytes);
+        self.stored_chunks.set(stats.stored_chunks);
+    }
+
+    /// Reports a read of `kind` taken `duration`.
+    pub(crate) fn report_read(&self, kind: &str, duration: Duration) {
+        self.read_duration
+            .with_label_values(&[kind])
+            .observe(duration.as_secs_f64());
+    }
+}
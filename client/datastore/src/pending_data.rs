@@ -0,0 +1,497 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracker of the transaction data submitted along with an extrinsic that has not
+//! landed on chain yet.
+//!
+//! The data of `permastore_submitExtrinsic` is written to the datastore before the
+//! extrinsic is even validated by the transaction pool. If the extrinsic is rejected,
+//! dropped or never included, nothing would ever remove the data again, hence each
+//! piece of data that did not exist locally before the submission is recorded in
+//! [`PendingData`] until the extrinsic storing it is found on the indexed chain. The
+//! data of a chunked upload is recorded as well from the beginning of upload session,
+//! whose extrinsic is submitted separately, until the data is found stored on the
+//! indexed chain by any extrinsic, so that the abandoned sessions are cleaned up.
+//!
+//! [`PendingDataCleaner`] follows the transaction pool and the block import, the data
+//! is removed once the extrinsic has left the pool without being included or the data
+//! has stayed unconfirmed for a configurable number of blocks.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::StreamExt;
+
+use sc_client_api::BlockchainEvents;
+use sc_client_db::offchain::LocalStorage;
+use sc_transaction_pool_api::TransactionPool;
+use sp_runtime::{
+    offchain::OffchainStorage,
+    traits::{Block as BlockT, NumberFor, Saturating},
+};
+
+use cp_permastore::PermaStorage;
+
+use crate::WeaveIndex;
+
+/// Prefix of the offchain storage for the pending data.
+const PENDING_DATA_PREFIX: &[u8] = b"permastore_pending_data";
+
+/// Key of the chunk roots of all the pending data.
+const CHUNK_ROOTS_KEY: &[u8] = b"chunk_roots";
+
+/// Returns the storage key of the pending entries of data `chunk_root`.
+fn entries_key<Hash: Encode>(chunk_root: &Hash) -> Vec<u8> {
+    (b"entries", chunk_root).encode()
+}
+
+/// Returns the storage key of the chunk root stored by extrinsic `extrinsic_hash`.
+fn extrinsic_key<Hash: Encode>(extrinsic_hash: &Hash) -> Vec<u8> {
+    (b"extrinsic", extrinsic_hash).encode()
+}
+
+/// Number of blocks the extrinsic is allowed to be absent from the pool before
+/// its data is removed, which gives the weave index time to catch up with the
+/// block including it.
+const LEFT_POOL_GRACE_PERIOD: u32 = 5;
+
+/// Transaction data waiting for the extrinsic storing it to be included.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct PendingEntry<Number, Hash> {
+    /// Chunk root of the transaction data.
+    pub chunk_root: Hash,
+    /// Hash of the extrinsic storing the transaction data.
+    ///
+    /// `None` if the data is uploaded on its own, whose extrinsic is submitted separately.
+    pub extrinsic_hash: Option<Hash>,
+    /// Size of the transaction data.
+    pub data_size: u32,
+    /// Indexed block number when the entry was first pruned.
+    pub first_seen: Option<Number>,
+    /// Whether the extrinsic has ever entered the ready queue of transaction pool.
+    pub ready: bool,
+    /// Indexed block number when the ready extrinsic was found absent from the pool.
+    pub left_pool_at: Option<Number>,
+}
+
+/// [`PendingEntry`] of Block.
+pub type PendingEntryFor<Block> = PendingEntry<NumberFor<Block>, <Block as BlockT>::Hash>;
+
+/// Pending transaction data persisted in the offchain storage.
+///
+/// The entries are keyed by the chunk root of data, so that the updates of
+/// different data do not contend with each other.
+#[derive(Clone)]
+pub struct PendingData<Block> {
+    offchain_storage: LocalStorage,
+    phantom: PhantomData<Block>,
+}
+
+impl<Block> std::fmt::Debug for PendingData<Block> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingData").finish()
+    }
+}
+
+impl<Block: BlockT> PendingData<Block> {
+    /// Creates a new instance of [`PendingData`].
+    pub fn new(offchain_storage: LocalStorage) -> Self {
+        Self {
+            offchain_storage,
+            phantom: PhantomData::<Block>,
+        }
+    }
+
+    fn get<T: Decode>(&self, key: &[u8]) -> Option<T> {
+        self.offchain_storage
+            .get(PENDING_DATA_PREFIX, key)
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    /// Returns the chunk roots of all the pending data.
+    pub fn chunk_roots(&self) -> Vec<Block::Hash> {
+        self.get(CHUNK_ROOTS_KEY).unwrap_or_default()
+    }
+
+    /// Returns the pending entries of data `chunk_root`.
+    pub fn entries_of(&self, chunk_root: &Block::Hash) -> Vec<PendingEntryFor<Block>> {
+        self.get(&entries_key(chunk_root)).unwrap_or_default()
+    }
+
+    /// Returns all the pending entries.
+    pub fn entries(&self) -> Vec<PendingEntryFor<Block>> {
+        self.chunk_roots()
+            .iter()
+            .flat_map(|chunk_root| self.entries_of(chunk_root))
+            .collect()
+    }
+
+    /// Returns the total size of the pending data.
+    ///
+    /// The data shared by multiple entries is only counted once.
+    pub fn pending_bytes(&self) -> u64 {
+        self.chunk_roots()
+            .iter()
+            .filter_map(|chunk_root| self.entries_of(chunk_root).first().map(|e| e.data_size))
+            .map(u64::from)
+            .sum()
+    }
+
+    /// Applies `f` to the value under `key` atomically and returns its output.
+    ///
+    /// `f` may be invoked more than once on the concurrent updates, the value is only
+    /// written when `f` returns `true` as the first item.
+    fn mutate<T, R>(&self, key: &[u8], f: impl Fn(&mut Vec<T>) -> (bool, R)) -> R
+    where
+        T: Encode + Decode,
+    {
+        let mut offchain_storage = self.offchain_storage.clone();
+        loop {
+            let encoded = offchain_storage.get(PENDING_DATA_PREFIX, key);
+            let mut values: Vec<T> = encoded
+                .as_ref()
+                .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+                .unwrap_or_default();
+            let (changed, output) = f(&mut values);
+            if !changed
+                || offchain_storage.compare_and_set(
+                    PENDING_DATA_PREFIX,
+                    key,
+                    encoded.as_deref(),
+                    &values.encode(),
+                )
+            {
+                return output;
+            }
+        }
+    }
+
+    /// Applies `f` to the pending entries of data `chunk_root` and returns its output.
+    ///
+    /// The chunk root is listed in [`Self::chunk_roots`] as long as it has any entry.
+    fn mutate_entries<R>(
+        &self,
+        chunk_root: Block::Hash,
+        f: impl Fn(&mut Vec<PendingEntryFor<Block>>) -> (bool, R),
+    ) -> R {
+        let output = self.mutate(&entries_key(&chunk_root), f);
+
+        if self.entries_of(&chunk_root).is_empty() {
+            self.mutate(CHUNK_ROOTS_KEY, |chunk_roots: &mut Vec<Block::Hash>| {
+                let len = chunk_roots.len();
+                chunk_roots.retain(|c| *c != chunk_root);
+                (chunk_roots.len() != len, ())
+            });
+            // The entries may be added again before the chunk root is unlisted.
+            if self.entries_of(&chunk_root).is_empty() {
+                return output;
+            }
+        }
+
+        self.mutate(CHUNK_ROOTS_KEY, |chunk_roots: &mut Vec<Block::Hash>| {
+            if chunk_roots.contains(&chunk_root) {
+                return (false, ());
+            }
+            chunk_roots.push(chunk_root);
+            (true, ())
+        });
+
+        output
+    }
+
+    fn insert(&self, entry: PendingEntryFor<Block>) {
+        let chunk_root = entry.chunk_root;
+        self.mutate_entries(chunk_root, |entries| {
+            if entries
+                .iter()
+                .any(|e| e.extrinsic_hash == entry.extrinsic_hash)
+            {
+                return (false, ());
+            }
+            entries.push(entry.clone());
+            (true, ())
+        });
+    }
+
+    /// Starts tracking the data `chunk_root` of `data_size` bytes stored by extrinsic
+    /// `extrinsic_hash`.
+    pub fn track(&self, chunk_root: Block::Hash, extrinsic_hash: Block::Hash, data_size: u32) {
+        self.offchain_storage.clone().set(
+            PENDING_DATA_PREFIX,
+            &extrinsic_key(&extrinsic_hash),
+            &chunk_root.encode(),
+        );
+        self.insert(PendingEntry {
+            chunk_root,
+            extrinsic_hash: Some(extrinsic_hash),
+            data_size,
+            first_seen: None,
+            ready: false,
+            left_pool_at: None,
+        });
+    }
+
+    /// Starts tracking the data `chunk_root` of `data_size` bytes uploaded on its own,
+    /// including the unfinished upload session.
+    ///
+    /// The entry is confirmed once the data is stored on the indexed chain by any
+    /// extrinsic. The expiry of a tracked upload restarts from the next pruning.
+    pub fn track_upload(&self, chunk_root: Block::Hash, data_size: u32) {
+        self.mutate_entries(chunk_root, |entries| {
+            match entries.iter_mut().find(|e| e.extrinsic_hash.is_none()) {
+                Some(entry) => (entry.first_seen.take().is_some(), ()),
+                None => {
+                    entries.push(PendingEntry {
+                        chunk_root,
+                        extrinsic_hash: None,
+                        data_size,
+                        first_seen: None,
+                        ready: false,
+                        left_pool_at: None,
+                    });
+                    (true, ())
+                }
+            }
+        })
+    }
+
+    /// Stops tracking the data `chunk_root` uploaded on its own.
+    pub fn untrack_upload(&self, chunk_root: Block::Hash) {
+        self.mutate_entries(chunk_root, |entries| {
+            let len = entries.len();
+            entries.retain(|e| e.extrinsic_hash.is_some());
+            (entries.len() != len, ())
+        })
+    }
+
+    fn chunk_root_of(&self, extrinsic_hash: &Block::Hash) -> Option<Block::Hash> {
+        self.get(&extrinsic_key(extrinsic_hash))
+    }
+
+    /// Stops tracking the data of `extrinsic_hashes`, returns the chunk roots of
+    /// the data no longer tracked.
+    pub fn untrack(&self, extrinsic_hashes: &[Block::Hash]) -> Vec<Block::Hash> {
+        let mut untracked = Vec::new();
+        for extrinsic_hash in extrinsic_hashes {
+            let chunk_root = match self.chunk_root_of(extrinsic_hash) {
+                Some(chunk_root) => chunk_root,
+                None => continue,
+            };
+            let is_untracked = self.mutate_entries(chunk_root, |entries| {
+                let len = entries.len();
+                entries.retain(|e| e.extrinsic_hash != Some(*extrinsic_hash));
+                (entries.len() != len, entries.is_empty())
+            });
+            self.offchain_storage
+                .clone()
+                .remove(PENDING_DATA_PREFIX, &extrinsic_key(extrinsic_hash));
+            if is_untracked && !untracked.contains(&chunk_root) {
+                untracked.push(chunk_root);
+            }
+        }
+        untracked
+    }
+
+    /// Marks the extrinsic `extrinsic_hash` as ready in the transaction pool.
+    pub fn mark_ready(&self, extrinsic_hash: Block::Hash) {
+        let chunk_root = match self.chunk_root_of(&extrinsic_hash) {
+            Some(chunk_root) => chunk_root,
+            None => return,
+        };
+        self.mutate_entries(chunk_root, |entries| {
+            let mut changed = false;
+            for entry in entries
+                .iter_mut()
+                .filter(|e| e.extrinsic_hash == Some(extrinsic_hash))
+            {
+                changed |= !entry.ready || entry.left_pool_at.is_some();
+                entry.ready = true;
+                entry.left_pool_at = None;
+            }
+            (changed, ())
+        })
+    }
+
+    /// Prunes the pending entries at indexed block `now`.
+    ///
+    /// The entries whose extrinsic `is_included`, or whose data `is_stored` on chain,
+    /// are confirmed and dropped, the data is kept. The entries that have been pending
+    /// for `expiry` blocks or whose extrinsic has left the pool for longer than the
+    /// grace period are dropped as well, the chunk roots of which are returned for
+    /// removing the data unless the data is still tracked or has been confirmed.
+    pub fn prune(
+        &self,
+        now: NumberFor<Block>,
+        expiry: NumberFor<Block>,
+        is_included: impl Fn(&Block::Hash) -> bool,
+        is_in_pool: impl Fn(&Block::Hash) -> bool,
+        is_stored: impl Fn(&Block::Hash) -> bool,
+    ) -> Vec<Block::Hash> {
+        let mut orphaned = Vec::new();
+
+        for chunk_root in self.chunk_roots() {
+            let (is_orphaned, dropped) = self.mutate_entries(chunk_root, |entries| {
+                if entries.is_empty() {
+                    return (false, (false, Vec::new()));
+                }
+
+                if is_stored(&chunk_root) {
+                    let dropped = std::mem::take(entries);
+                    return (true, (false, dropped));
+                }
+
+                let mut confirmed = false;
+                let mut dropped = Vec::new();
+                let mut pending = Vec::with_capacity(entries.len());
+                for mut entry in std::mem::take(entries) {
+                    if entry.extrinsic_hash.as_ref().map_or(false, &is_included) {
+                        confirmed = true;
+                        dropped.push(entry);
+                        continue;
+                    }
+
+                    let first_seen = *entry.first_seen.get_or_insert(now);
+
+                    if entry.ready {
+                        if entry.extrinsic_hash.as_ref().map_or(false, &is_in_pool) {
+                            entry.left_pool_at = None;
+                        } else if entry.left_pool_at.is_none() {
+                            entry.left_pool_at = Some(now);
+                        }
+                    }
+
+                    let left_pool = entry.left_pool_at.map_or(false, |left_pool_at| {
+                        now >= left_pool_at.saturating_add(LEFT_POOL_GRACE_PERIOD.into())
+                    });
+                    let expired = now >= first_seen.saturating_add(expiry);
+
+                    if left_pool || expired {
+                        dropped.push(entry);
+                    } else {
+                        pending.push(entry);
+                    }
+                }
+                *entries = pending;
+
+                (true, (!confirmed && entries.is_empty(), dropped))
+            });
+
+            for extrinsic_hash in dropped.iter().filter_map(|e| e.extrinsic_hash.as_ref()) {
+                self.offchain_storage
+                    .clone()
+                    .remove(PENDING_DATA_PREFIX, &extrinsic_key(extrinsic_hash));
+            }
+
+            if is_orphaned {
+                orphaned.push(chunk_root);
+            }
+        }
+
+        orphaned
+    }
+}
+
+/// Worker removing the orphaned transaction data tracked in [`PendingData`].
+pub struct PendingDataCleaner<Block: BlockT, C, P, T> {
+    client: Arc<C>,
+    pool: Arc<P>,
+    storage: T,
+    pending_data: PendingData<Block>,
+    weave_index: WeaveIndex<Block>,
+    expiry: NumberFor<Block>,
+}
+
+impl<Block, C, P, T> PendingDataCleaner<Block, C, P, T>
+where
+    Block: BlockT,
+    C: BlockchainEvents<Block>,
+    P: TransactionPool<Block = Block, Hash = Block::Hash>,
+    T: PermaStorage,
+{
+    /// Creates a new instance of [`PendingDataCleaner`].
+    ///
+    /// The unconfirmed data is removed after `expiry` blocks.
+    pub fn new(
+        client: Arc<C>,
+        pool: Arc<P>,
+        storage: T,
+        offchain_storage: LocalStorage,
+        expiry: NumberFor<Block>,
+    ) -> Self {
+        Self {
+            client,
+            pool,
+            storage,
+            pending_data: PendingData::new(offchain_storage.clone()),
+            weave_index: WeaveIndex::new(offchain_storage),
+            expiry,
+        }
+    }
+
+    /// Removes the orphaned data relative to the tip of weave index.
+    fn clean(&mut self) {
+        let now = match self.weave_index.tip() {
+            Some((number, _)) => number,
+            None => return,
+        };
+
+        let weave_index = &self.weave_index;
+        let pool = &self.pool;
+        let orphaned = self.pending_data.prune(
+            now,
+            self.expiry,
+            |extrinsic_hash| weave_index.extrinsic_position(*extrinsic_hash).is_some(),
+            |extrinsic_hash| pool.ready_transaction(extrinsic_hash).is_some(),
+            |chunk_root| weave_index.chunk_root_position(*chunk_root).is_some(),
+        );
+
+        for chunk_root in orphaned {
+            log::debug!(
+                target: "datastore",
+                "Removing orphaned transaction data {:?} at #{}",
+                chunk_root,
+                now,
+            );
+            self.storage.remove(&chunk_root.encode());
+        }
+    }
+
+    /// Runs the worker, which cleans the pending data on every new best block.
+    pub async fn run(mut self) {
+        let mut ready_transactions = self.pool.import_notification_stream().fuse();
+        let mut import_notification_stream = self
+            .client
+            .import_notification_stream()
+            .filter(|notification| futures::future::ready(notification.is_new_best))
+            .fuse();
+
+        loop {
+            futures::select! {
+                extrinsic_hash = ready_transactions.next() => match extrinsic_hash {
+                    Some(extrinsic_hash) => self.pending_data.mark_ready(extrinsic_hash),
+                    None => return,
+                },
+                notification = import_notification_stream.next() => match notification {
+                    Some(_) => self.clean(),
+                    None => return,
+                },
+            }
+        }
+    }
+}
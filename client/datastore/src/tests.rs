@@ -22,9 +22,21 @@ use sp_keystore::testing::KeyStore;
 use substrate_test_runtime_client::DefaultTestClientBuilderExt;
 use substrate_test_runtime_client::TestClientBuilderExt;
 
-use cp_permastore::PermaStorage;
+use codec::Encode;
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT, DigestItem};
 
-use crate::PermanentStorage;
+use cp_permastore::{
+    chunk_tree, PermaStorage, StoredTransaction, UploadSession, CHUNK_SIZE, PERMASTORE_ENGINE_ID,
+};
+
+use crate::{
+    legacy_stored_transactions, stored_transactions, ChunkRootBackend, DataStats, PendingData,
+    PendingEntry, PermanentStorage, RecallTransaction, WeaveBlock, WeaveIndex,
+};
+
+type Block = substrate_test_runtime_client::runtime::Block;
+type Hash = substrate_test_runtime_client::runtime::Hash;
+type Header = substrate_test_runtime_client::runtime::Header;
 
 #[test]
 fn basic_operations_should_work() {
@@ -43,3 +55,627 @@ fn basic_operations_should_work() {
     assert!(!perma_storage.exists(b"key"));
     assert_eq!(perma_storage.retrieve(b"key"), None);
 }
+
+#[test]
+fn data_stats_should_follow_stored_data() {
+    let keystore = Arc::new(KeyStore::new());
+    let client_builder = substrate_test_runtime_client::TestClientBuilder::new();
+    let client = Arc::new(client_builder.set_keystore(keystore.clone()).build());
+
+    let mut perma_storage = PermanentStorage::new_test(client.clone());
+
+    let chunk_size = CHUNK_SIZE as usize;
+    let value = vec![1u8; chunk_size + 1];
+
+    perma_storage.submit(b"key1", &value);
+    perma_storage.submit(b"key2", b"value");
+    assert_eq!(
+        perma_storage.stats(),
+        DataStats {
+            stored_bytes: value.len() as u64 + 5,
+            stored_chunks: 3,
+        }
+    );
+
+    // Resubmitting the same data must not be counted twice.
+    perma_storage.submit(b"key2", b"value");
+    assert_eq!(perma_storage.stats().stored_chunks, 3);
+
+    perma_storage.remove(b"key1");
+    assert_eq!(
+        perma_storage.stats(),
+        DataStats {
+            stored_bytes: 5,
+            stored_chunks: 1,
+        }
+    );
+
+    perma_storage.remove(b"key2");
+    perma_storage.remove(b"key2");
+    assert_eq!(perma_storage.stats(), DataStats::default());
+}
+
+#[test]
+fn chunk_level_operations_should_work() {
+    let keystore = Arc::new(KeyStore::new());
+    let client_builder = substrate_test_runtime_client::TestClientBuilder::new();
+    let client = Arc::new(client_builder.set_keystore(keystore.clone()).build());
+
+    let mut perma_storage = PermanentStorage::new_test(client.clone());
+
+    let chunk_size = CHUNK_SIZE as usize;
+    let value = (0..2 * chunk_size + 7)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+
+    perma_storage.submit(b"key", &value);
+
+    let data_meta = perma_storage.data_meta(b"key").unwrap();
+    assert_eq!(data_meta.data_size, value.len() as u32);
+    assert_eq!(data_meta.chunk_count(), 3);
+    assert_eq!(
+        data_meta.chunk_hashes[2],
+        sp_core::hashing::blake2_256(&value[2 * chunk_size..]).into()
+    );
+
+    assert_eq!(
+        perma_storage.retrieve_chunk(b"key", 1),
+        Some(value[chunk_size..2 * chunk_size].to_vec())
+    );
+    assert_eq!(
+        perma_storage.retrieve_chunk(b"key", 2),
+        Some(value[2 * chunk_size..].to_vec())
+    );
+    assert_eq!(perma_storage.retrieve_chunk(b"key", 3), None);
+    assert_eq!(perma_storage.retrieve(b"key"), Some(value));
+
+    perma_storage.remove(b"key");
+    assert!(perma_storage.data_meta(b"key").is_none());
+    assert_eq!(perma_storage.retrieve_chunk(b"key", 0), None);
+}
+
+#[test]
+fn stored_chunk_hashes_should_match_shared_chunk_root_for_edge_sizes() {
+    let client = Arc::new(substrate_test_runtime_client::new());
+    let mut perma_storage = PermanentStorage::new_test(client);
+
+    for data_size in [
+        0,
+        1,
+        CHUNK_SIZE - 1,
+        CHUNK_SIZE,
+        CHUNK_SIZE + 1,
+        CHUNK_SIZE * 3 + 7,
+    ]
+    .iter()
+    {
+        let value = (0..*data_size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let chunk_root = chunk_tree::chunk_root(&value);
+        let key = chunk_root.encode();
+
+        perma_storage.submit(&key, &value);
+
+        let data_meta = perma_storage.data_meta(&key).unwrap();
+        assert_eq!(data_meta.chunk_hashes, chunk_tree::chunk_hashes(&value));
+        assert_eq!(
+            chunk_tree::chunk_root_from_hashes(&data_meta.chunk_hashes),
+            chunk_root,
+            "data size: {}",
+            data_size
+        );
+        assert_eq!(perma_storage.retrieve(&key), Some(value));
+    }
+}
+
+#[test]
+fn upload_session_should_survive_restart() {
+    let client = Arc::new(substrate_test_runtime_client::new());
+    let offchain_storage = sc_client_db::offchain::LocalStorage::new_test();
+
+    let value = (0..CHUNK_SIZE * 2 + 7)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let key = chunk_tree::chunk_root(&value).encode();
+    let chunks = chunk_tree::chunks(&value).collect::<Vec<_>>();
+
+    let mut perma_storage = PermanentStorage::new(offchain_storage.clone(), client.clone());
+    let mut upload_session = UploadSession::new(value.len() as u32);
+    perma_storage.set_upload_session(&key, &upload_session);
+    perma_storage.submit_chunk(&key, 0, chunks[0]);
+    upload_session.uploaded[0] = true;
+    perma_storage.set_upload_session(&key, &upload_session);
+
+    assert!(perma_storage.finish_upload(&key).is_none());
+    assert!(!perma_storage.exists(&key));
+
+    // Restart with the same offchain storage.
+    let mut perma_storage = PermanentStorage::new(offchain_storage, client);
+    let mut upload_session = perma_storage.upload_session(&key).unwrap();
+    assert_eq!(upload_session.missing_chunks(), vec![1, 2]);
+
+    for chunk_index in upload_session.missing_chunks() {
+        perma_storage.submit_chunk(&key, chunk_index, chunks[chunk_index as usize]);
+        upload_session.uploaded[chunk_index as usize] = true;
+    }
+    perma_storage.set_upload_session(&key, &upload_session);
+
+    let data_meta = perma_storage.finish_upload(&key).unwrap();
+    assert_eq!(data_meta.chunk_hashes, chunk_tree::chunk_hashes(&value));
+    assert!(perma_storage.upload_session(&key).is_none());
+    assert_eq!(perma_storage.retrieve(&key), Some(value));
+}
+
+#[test]
+fn remove_should_clean_up_unfinished_upload() {
+    let client = Arc::new(substrate_test_runtime_client::new());
+    let mut perma_storage = PermanentStorage::new_test(client);
+
+    let mut upload_session = UploadSession::new(CHUNK_SIZE + 1);
+    perma_storage.submit_chunk(b"key", 0, &vec![0u8; CHUNK_SIZE as usize]);
+    upload_session.uploaded[0] = true;
+    perma_storage.set_upload_session(b"key", &upload_session);
+
+    perma_storage.remove(b"key");
+    assert!(perma_storage.upload_session(b"key").is_none());
+    assert_eq!(perma_storage.retrieve_chunk(b"key", 0), None);
+}
+
+fn weave_block(
+    block_number: u64,
+    weave_base: u64,
+    data_sizes: &[u32],
+) -> Option<WeaveBlock<u64, Hash>> {
+    Some(WeaveBlock {
+        block_number,
+        block_hash: Hash::repeat_byte(block_number as u8),
+        weave_base,
+        transactions: data_sizes
+            .iter()
+            .enumerate()
+            .map(|(index, &data_size)| StoredTransaction {
+                extrinsic_index: index as u32 + 1,
+                chunk_root: Hash::repeat_byte(index as u8 + 100),
+                data_size,
+            })
+            .collect(),
+    })
+}
+
+#[test]
+fn weave_index_locate_should_work() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    assert_eq!(weave_index.locate(0), None);
+
+    // [5, 12, 22]
+    // [1, 4, 10]
+    weave_index.push(1, Hash::repeat_byte(1), weave_block(1, 0, &[5]));
+    weave_index.push(2, Hash::repeat_byte(2), None);
+    weave_index.push(4, Hash::repeat_byte(4), weave_block(4, 5, &[3, 4]));
+    weave_index.push(10, Hash::repeat_byte(10), weave_block(10, 12, &[10]));
+
+    assert_eq!(weave_index.len(), 3);
+    assert_eq!(weave_index.weave_size(), 22);
+    assert_eq!(weave_index.tip(), Some((10, Hash::repeat_byte(10))));
+
+    let block_number_of = |recall_byte| {
+        weave_index
+            .locate(recall_byte)
+            .map(|recall_tx| recall_tx.block_number)
+    };
+
+    // Consistent with `pallet_permastore::find_recall_block`.
+    assert_eq!(block_number_of(3), Some(1));
+    assert_eq!(block_number_of(12), Some(4));
+    assert_eq!(block_number_of(13), Some(10));
+    assert_eq!(block_number_of(15), Some(10));
+    assert_eq!(block_number_of(23), None);
+
+    assert_eq!(
+        weave_index.locate(9),
+        Some(RecallTransaction {
+            block_number: 4,
+            block_hash: Hash::repeat_byte(4),
+            extrinsic_index: 2,
+            chunk_root: Hash::repeat_byte(101),
+            data_size: 4,
+            data_base: 8,
+        })
+    );
+}
+
+#[test]
+fn weave_index_revert_should_work() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    weave_index.push(1, Hash::repeat_byte(1), weave_block(1, 0, &[5]));
+    weave_index.push(2, Hash::repeat_byte(2), weave_block(2, 5, &[7]));
+    weave_index.push(3, Hash::repeat_byte(3), weave_block(3, 12, &[10]));
+
+    // Reorg happens at block 1.
+    weave_index.revert_to(1, Hash::repeat_byte(1));
+    assert_eq!(weave_index.len(), 1);
+    assert_eq!(weave_index.weave_size(), 5);
+    assert_eq!(weave_index.tip(), Some((1, Hash::repeat_byte(1))));
+    assert_eq!(weave_index.weave_block(1), None);
+    assert_eq!(weave_index.locate(6), None);
+    assert_eq!(weave_index.chunk_root(3, 1), None);
+
+    weave_index.push(2, Hash::repeat_byte(22), weave_block(2, 5, &[1]));
+    assert_eq!(weave_index.weave_size(), 6);
+    assert_eq!(weave_index.data_size(2, 1), Some(1));
+    assert_eq!(
+        weave_index
+            .locate(6)
+            .map(|recall_tx| recall_tx.block_number),
+        Some(2)
+    );
+}
+
+#[test]
+fn interrupted_indexing_should_be_rolled_back() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    weave_index.push(1, Hash::repeat_byte(1), weave_block(1, 0, &[5]));
+
+    // Interrupted before the head is written.
+    weave_index.stage(&weave_block(2, 5, &[7]).unwrap());
+    weave_index.index_extrinsic_hashes(2, &[(1, Hash::repeat_byte(21))]);
+    weave_index.keep_recall_body(Hash::repeat_byte(2), &[]);
+    assert_eq!(weave_index.len(), 1);
+    assert_eq!(weave_index.tip(), Some((1, Hash::repeat_byte(1))));
+
+    weave_index.recover();
+    assert_eq!(weave_index.weave_block(1), None);
+    assert_eq!(weave_index.chunk_root(2, 1), None);
+    assert_eq!(weave_index.extrinsic_position(Hash::repeat_byte(21)), None);
+    assert_eq!(weave_index.recall_body(Hash::repeat_byte(2)), None);
+
+    // Interrupted after the head is written.
+    weave_index.stage(&weave_block(2, 5, &[7]).unwrap());
+    weave_index.set_head(2, 2, Hash::repeat_byte(2));
+
+    weave_index.recover();
+    assert_eq!(weave_index.len(), 2);
+    assert_eq!(weave_index.weave_size(), 12);
+    assert_eq!(weave_index.chunk_root(2, 1), Some(Hash::repeat_byte(100)));
+
+    // Indexing the next block is unaffected by the journal.
+    weave_index.push(3, Hash::repeat_byte(3), None);
+    assert_eq!(weave_index.len(), 2);
+    assert_eq!(weave_index.tip(), Some((3, Hash::repeat_byte(3))));
+}
+
+#[test]
+fn chunk_root_backend_should_work() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    weave_index.push(4, Hash::repeat_byte(4), weave_block(4, 0, &[3, 4]));
+
+    assert_eq!(weave_index.chunk_root(4, 1), Some(Hash::repeat_byte(100)));
+    assert_eq!(weave_index.chunk_root(4, 2), Some(Hash::repeat_byte(101)));
+    assert_eq!(weave_index.data_size(4, 2), Some(4));
+    assert_eq!(weave_index.chunk_root(4, 3), None);
+    assert_eq!(weave_index.chunk_root(3, 1), None);
+}
+
+#[test]
+fn recall_bodies_should_be_kept_and_reverted() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    let body = |data: &[u8]| {
+        vec![substrate_test_runtime_client::runtime::Extrinsic::IncludeData(data.to_vec())]
+    };
+
+    weave_index.push(1, Hash::repeat_byte(1), weave_block(1, 0, &[5]));
+    weave_index.keep_recall_body(Hash::repeat_byte(1), &body(b"1"));
+    weave_index.push(2, Hash::repeat_byte(2), weave_block(2, 5, &[7]));
+    weave_index.keep_recall_body(Hash::repeat_byte(2), &body(b"2"));
+
+    assert_eq!(
+        weave_index.recall_body(Hash::repeat_byte(1)),
+        Some(body(b"1"))
+    );
+    assert_eq!(
+        weave_index.recall_body(Hash::repeat_byte(2)),
+        Some(body(b"2"))
+    );
+
+    weave_index.revert_to(1, Hash::repeat_byte(1));
+    assert_eq!(
+        weave_index.recall_body(Hash::repeat_byte(1)),
+        Some(body(b"1"))
+    );
+    assert_eq!(weave_index.recall_body(Hash::repeat_byte(2)), None);
+}
+
+#[test]
+fn extrinsic_hash_index_should_work() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    weave_index.push(1, Hash::repeat_byte(1), weave_block(1, 0, &[5]));
+    weave_index.index_extrinsic_hashes(1, &[(1, Hash::repeat_byte(11))]);
+    weave_index.push(2, Hash::repeat_byte(2), weave_block(2, 5, &[3, 4]));
+    weave_index
+        .index_extrinsic_hashes(2, &[(1, Hash::repeat_byte(21)), (2, Hash::repeat_byte(22))]);
+
+    assert_eq!(
+        weave_index.extrinsic_position(Hash::repeat_byte(11)),
+        Some((1, 1))
+    );
+    assert_eq!(
+        weave_index.extrinsic_position(Hash::repeat_byte(22)),
+        Some((2, 2))
+    );
+    assert_eq!(weave_index.extrinsic_position(Hash::repeat_byte(99)), None);
+
+    // The hashes of the reverted blocks are dropped.
+    weave_index.revert_to(1, Hash::repeat_byte(1));
+    assert_eq!(weave_index.extrinsic_position(Hash::repeat_byte(21)), None);
+    assert_eq!(weave_index.extrinsic_position(Hash::repeat_byte(22)), None);
+    assert_eq!(
+        weave_index.extrinsic_position(Hash::repeat_byte(11)),
+        Some((1, 1))
+    );
+}
+
+#[test]
+fn stored_transactions_should_be_decoded_from_digest() {
+    let mut header = Header::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+
+    assert_eq!(stored_transactions(&header).unwrap(), vec![]);
+
+    let transactions = vec![
+        StoredTransaction {
+            extrinsic_index: 1,
+            chunk_root: Hash::repeat_byte(1),
+            data_size: 10,
+        },
+        StoredTransaction {
+            extrinsic_index: 3,
+            chunk_root: Hash::repeat_byte(3),
+            data_size: 30,
+        },
+    ];
+    header.digest_mut().push(DigestItem::Consensus(
+        PERMASTORE_ENGINE_ID,
+        transactions.encode(),
+    ));
+
+    assert_eq!(stored_transactions(&header).unwrap(), transactions);
+}
+
+/// Runtime API providing the legacy transaction data of block #1.
+#[derive(Clone, Default)]
+struct LegacyRuntimeApi;
+
+sp_api::mock_impl_runtime_apis! {
+    impl cp_permastore::PermastoreApi<Block, u64, u32, Hash> for LegacyRuntimeApi {
+        fn find_recall_block(_recall_byte: u64) -> Option<u64> {
+            None
+        }
+
+        fn require_proof_of_access() -> bool {
+            false
+        }
+
+        fn block_size() -> u64 {
+            0
+        }
+
+        fn weave_size() -> u64 {
+            0
+        }
+
+        fn is_forgotten(_block_number: u64, _extrinsic_index: u32) -> bool {
+            false
+        }
+
+        fn chunk_root(block_number: u64, extrinsic_index: u32) -> Option<Hash> {
+            (block_number == 1 && extrinsic_index % 2 == 1)
+                .then(|| Hash::repeat_byte(extrinsic_index as u8))
+        }
+
+        fn data_size(block_number: u64, extrinsic_index: u32) -> u32 {
+            if block_number == 1 && extrinsic_index % 2 == 1 {
+                extrinsic_index * 10
+            } else {
+                0
+            }
+        }
+    }
+}
+
+#[test]
+fn legacy_stored_transactions_should_be_read_from_runtime() {
+    let at = BlockId::Number(5);
+
+    assert_eq!(
+        legacy_stored_transactions(&LegacyRuntimeApi, &at, 1, 4).unwrap(),
+        vec![
+            StoredTransaction {
+                extrinsic_index: 1,
+                chunk_root: Hash::repeat_byte(1),
+                data_size: 10,
+            },
+            StoredTransaction {
+                extrinsic_index: 3,
+                chunk_root: Hash::repeat_byte(3),
+                data_size: 30,
+            },
+        ]
+    );
+
+    // Block #2 stores nothing.
+    assert_eq!(
+        legacy_stored_transactions(&LegacyRuntimeApi, &at, 2, 4).unwrap(),
+        vec![]
+    );
+}
+
+#[test]
+fn pending_data_should_be_tracked_and_untracked() {
+    let pending_data = PendingData::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    pending_data.track(Hash::repeat_byte(1), Hash::repeat_byte(11), 10);
+    pending_data.track(Hash::repeat_byte(2), Hash::repeat_byte(22), 20);
+    // Tracking the same data twice is a no-op.
+    pending_data.track(Hash::repeat_byte(1), Hash::repeat_byte(11), 10);
+    assert_eq!(pending_data.entries().len(), 2);
+    assert_eq!(pending_data.pending_bytes(), 30);
+
+    assert_eq!(
+        pending_data.untrack(&[Hash::repeat_byte(11), Hash::repeat_byte(33)]),
+        vec![Hash::repeat_byte(1)]
+    );
+    assert_eq!(pending_data.pending_bytes(), 20);
+    assert_eq!(
+        pending_data.entries(),
+        vec![PendingEntry {
+            chunk_root: Hash::repeat_byte(2),
+            extrinsic_hash: Some(Hash::repeat_byte(22)),
+            data_size: 20,
+            first_seen: None,
+            ready: false,
+            left_pool_at: None,
+        }]
+    );
+}
+
+#[test]
+fn pending_data_should_be_pruned() {
+    let pending_data = PendingData::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    // Included.
+    pending_data.track(Hash::repeat_byte(1), Hash::repeat_byte(11), 10);
+    // Never ready.
+    pending_data.track(Hash::repeat_byte(2), Hash::repeat_byte(22), 20);
+    // Dropped from the pool.
+    pending_data.track(Hash::repeat_byte(3), Hash::repeat_byte(33), 30);
+    // Stays in the pool.
+    pending_data.track(Hash::repeat_byte(4), Hash::repeat_byte(44), 40);
+
+    pending_data.mark_ready(Hash::repeat_byte(33));
+    pending_data.mark_ready(Hash::repeat_byte(44));
+
+    let is_included = |extrinsic_hash: &Hash| *extrinsic_hash == Hash::repeat_byte(11);
+    let is_in_pool = |extrinsic_hash: &Hash| *extrinsic_hash == Hash::repeat_byte(44);
+    let is_stored = |_: &Hash| false;
+
+    assert!(pending_data
+        .prune(10, 20, is_included, is_in_pool, is_stored)
+        .is_empty());
+    assert_eq!(pending_data.entries().len(), 3);
+    assert!(pending_data
+        .entries()
+        .iter()
+        .all(|entry| entry.first_seen == Some(10)));
+
+    // The grace period of the extrinsic absent from the pool is over.
+    assert_eq!(
+        pending_data.prune(15, 20, is_included, is_in_pool, is_stored),
+        vec![Hash::repeat_byte(3)]
+    );
+
+    // Expired.
+    assert!(pending_data
+        .prune(29, 20, is_included, is_in_pool, is_stored)
+        .is_empty());
+    assert_eq!(
+        pending_data.prune(30, 20, is_included, is_in_pool, is_stored),
+        vec![Hash::repeat_byte(2), Hash::repeat_byte(4)]
+    );
+    assert!(pending_data.entries().is_empty());
+}
+
+#[test]
+fn pending_data_back_to_pool_should_not_be_pruned() {
+    let pending_data = PendingData::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    pending_data.track(Hash::repeat_byte(1), Hash::repeat_byte(11), 10);
+    pending_data.mark_ready(Hash::repeat_byte(11));
+
+    assert!(pending_data
+        .prune(10, 100, |_| false, |_| false, |_| false)
+        .is_empty());
+    assert_eq!(pending_data.entries()[0].left_pool_at, Some(10));
+
+    // E.g., resubmitted on reorg.
+    pending_data.mark_ready(Hash::repeat_byte(11));
+    assert_eq!(pending_data.entries()[0].left_pool_at, None);
+
+    assert!(pending_data
+        .prune(20, 100, |_| false, |_| true, |_| false)
+        .is_empty());
+    assert_eq!(pending_data.entries().len(), 1);
+}
+
+#[test]
+fn pending_data_should_be_keyed_by_chunk_root() {
+    let pending_data = PendingData::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    // The same data submitted along with two extrinsics.
+    pending_data.track(Hash::repeat_byte(1), Hash::repeat_byte(11), 10);
+    pending_data.track(Hash::repeat_byte(1), Hash::repeat_byte(12), 10);
+    pending_data.track_upload(Hash::repeat_byte(2), 20);
+    assert_eq!(
+        pending_data.chunk_roots(),
+        vec![Hash::repeat_byte(1), Hash::repeat_byte(2)]
+    );
+    assert_eq!(pending_data.entries_of(&Hash::repeat_byte(1)).len(), 2);
+    assert_eq!(pending_data.pending_bytes(), 30);
+
+    // The data is still tracked by the other extrinsic.
+    assert!(pending_data.untrack(&[Hash::repeat_byte(11)]).is_empty());
+    assert_eq!(
+        pending_data.untrack(&[Hash::repeat_byte(12)]),
+        vec![Hash::repeat_byte(1)]
+    );
+    assert_eq!(pending_data.chunk_roots(), vec![Hash::repeat_byte(2)]);
+
+    // The uploaded data is confirmed once stored on chain by any extrinsic.
+    let is_stored = |chunk_root: &Hash| *chunk_root == Hash::repeat_byte(2);
+    assert!(pending_data
+        .prune(10, 20, |_| false, |_| false, is_stored)
+        .is_empty());
+    assert!(pending_data.chunk_roots().is_empty());
+    assert_eq!(pending_data.pending_bytes(), 0);
+}
+
+#[test]
+fn chunk_root_index_should_be_reverted() {
+    let mut weave_index =
+        WeaveIndex::<Block>::new(sc_client_db::offchain::LocalStorage::new_test());
+
+    weave_index.push(1, Hash::repeat_byte(1), weave_block(1, 0, &[5]));
+    // The same data is stored again in block 2.
+    weave_index.push(2, Hash::repeat_byte(2), weave_block(2, 5, &[5]));
+    assert_eq!(
+        weave_index.chunk_root_position(Hash::repeat_byte(100)),
+        Some((1, 1))
+    );
+
+    weave_index.revert_to(1, Hash::repeat_byte(1));
+    assert_eq!(
+        weave_index.chunk_root_position(Hash::repeat_byte(100)),
+        Some((1, 1))
+    );
+
+    weave_index.revert_to(0, Hash::repeat_byte(0));
+    assert_eq!(
+        weave_index.chunk_root_position(Hash::repeat_byte(100)),
+        None
+    );
+}
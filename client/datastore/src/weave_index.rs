@@ -0,0 +1,821 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Local index of the weave for resolving the recall byte without runtime calls.
+//!
+//! The weave index records the transaction data stored in each block of the best
+//! chain in the ascending order of weave size. It's maintained by [`WeaveIndexer`]
+//! following the block import notifications, and the entries of retracted blocks
+//! are reverted on reorg.
+//!
+//! The transaction data of a block is read from the [`PERMASTORE_ENGINE_ID`] digest
+//! of its header, or from the legacy runtime state for the blocks created before the
+//! digest was introduced, see [`legacy_stored_transactions`]. It's also indexed by `(block_number, extrinsic_index)` in a
+//! separate column for serving the [`ChunkRootBackend`]. The hashes of the
+//! extrinsics storing data and the chunk roots are indexed as well, so that the
+//! transaction data can be located by the extrinsic hash or the chunk root.
+//!
+//! The bodies of the blocks storing data can optionally be kept in the index as
+//! well, so that the PoA can still be built and verified when the block bodies
+//! are pruned from the database, see [`WeaveIndexer::keep_recall_bodies`].
+//!
+//! The number of indexed blocks and the latest indexed block are written as one
+//! record, which is the last write of indexing a block. The block being indexed is
+//! journaled before any of its entries is written, so that the entries left by an
+//! interrupted indexing can be rolled back and the block indexed again on restart.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::StreamExt;
+
+use sc_client_api::{BlockBackend, BlockchainEvents};
+use sc_client_db::offchain::LocalStorage;
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_runtime::{
+    generic::BlockId,
+    offchain::OffchainStorage,
+    traits::{Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor, Zero},
+    DigestItem,
+};
+
+use cp_permastore::{PermastoreApi, StoredTransaction, PERMASTORE_ENGINE_ID};
+
+use crate::{ChunkRootBackend, Error};
+
+/// Prefix of the offchain storage for the weave index.
+const WEAVE_INDEX_PREFIX: &[u8] = b"permastore_weave_index";
+
+/// Prefix of the offchain storage for the transaction index,
+/// `(block_number, extrinsic_index) => StoredTransaction`.
+const TRANSACTION_INDEX_PREFIX: &[u8] = b"permastore_transaction_index";
+
+/// Prefix of the offchain storage for the chunk root index,
+/// `chunk_root => (block_number, extrinsic_index)`.
+const CHUNK_ROOT_INDEX_PREFIX: &[u8] = b"permastore_chunk_root_index";
+
+/// Prefix of the offchain storage for the extrinsic hash index,
+/// `extrinsic_hash => (block_number, extrinsic_index)` and the reverse.
+const EXTRINSIC_HASH_INDEX_PREFIX: &[u8] = b"permastore_extrinsic_hash_index";
+
+/// Prefix of the offchain storage for the bodies of the blocks storing data,
+/// `block_hash => Vec<Extrinsic>`.
+const RECALL_BODY_PREFIX: &[u8] = b"permastore_recall_body";
+
+/// Key of the number of indexed blocks along with the latest block that has been indexed,
+/// `(len, tip_number, tip_hash)`.
+const HEAD_KEY: &[u8] = b"head";
+
+/// Key of the block storing data which is being indexed.
+const PENDING_KEY: &[u8] = b"pending";
+
+/// Returns the storage key of the `index`-th indexed block.
+fn weave_block_key(index: u32) -> Vec<u8> {
+    (b"block", index).encode()
+}
+
+/// Returns the transaction data stored in the block of `header`.
+///
+/// The transactions are decoded from the [`PERMASTORE_ENGINE_ID`] digest,
+/// an empty list is returned if the block stores no data.
+pub fn stored_transactions<Header: HeaderT>(
+    header: &Header,
+) -> Result<Vec<StoredTransaction<Header::Hash>>, codec::Error> {
+    header
+        .digest()
+        .logs()
+        .iter()
+        .find_map(|digest_item| match digest_item {
+            DigestItem::Consensus(id, data) if id == &PERMASTORE_ENGINE_ID => Some(data),
+            _ => None,
+        })
+        .map(|data| Decode::decode(&mut data.as_slice()))
+        .unwrap_or_else(|| Ok(Vec::new()))
+}
+
+/// Returns the transaction data stored in block `block_number` of `extrinsic_count`
+/// extrinsics before the [`PERMASTORE_ENGINE_ID`] digest was introduced.
+///
+/// These are only kept in the legacy runtime state, which is read on top of block
+/// `at`. An empty list is returned if the runtime at `at` does not provide them,
+/// i.e., version 2 of [`PermastoreApi`].
+pub fn legacy_stored_transactions<Block, Client>(
+    client: &Client,
+    at: &BlockId<Block>,
+    block_number: NumberFor<Block>,
+    extrinsic_count: u32,
+) -> Result<Vec<StoredTransaction<Block::Hash>>, sp_api::ApiError>
+where
+    Block: BlockT,
+    Client: ProvideRuntimeApi<Block>,
+    Client::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+{
+    let api = client.runtime_api();
+
+    if !api.has_api_with::<dyn PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>, _>(
+        at,
+        |version| version != 2,
+    )? {
+        return Ok(Vec::new());
+    }
+
+    let mut transactions = Vec::new();
+    for extrinsic_index in 0..extrinsic_count {
+        if let Some(chunk_root) = api.chunk_root(at, block_number, extrinsic_index)? {
+            transactions.push(StoredTransaction {
+                extrinsic_index,
+                chunk_root,
+                data_size: api.data_size(at, block_number, extrinsic_index)?,
+            });
+        }
+    }
+
+    Ok(transactions)
+}
+
+/// Returns the storage key of the position of extrinsic `extrinsic_hash`.
+fn extrinsic_position_key<Hash: Encode>(extrinsic_hash: Hash) -> Vec<u8> {
+    (b"position", extrinsic_hash).encode()
+}
+
+/// Returns the storage key of the hash of extrinsic `(block_number, extrinsic_index)`.
+fn extrinsic_hash_key<Number: Encode>(block_number: Number, extrinsic_index: u32) -> Vec<u8> {
+    (b"hash", block_number, extrinsic_index).encode()
+}
+
+/// All the transaction data stored in a block.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct WeaveBlock<Number, Hash> {
+    /// Number of the block.
+    pub block_number: Number,
+    /// Hash of the block.
+    pub block_hash: Hash,
+    /// Weave size before this block.
+    pub weave_base: u64,
+    /// Transactions storing data in this block, in the order of extrinsic index.
+    pub transactions: Vec<StoredTransaction<Hash>>,
+}
+
+impl<Number, Hash> WeaveBlock<Number, Hash> {
+    /// Returns the weave size at the end of this block.
+    pub fn weave_size(&self) -> u64 {
+        self.weave_base
+            + self
+                .transactions
+                .iter()
+                .map(|tx| tx.data_size as u64)
+                .sum::<u64>()
+    }
+}
+
+/// The transaction in which the recall byte is located.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecallTransaction<Number, Hash> {
+    /// Number of the block including the transaction.
+    pub block_number: Number,
+    /// Hash of the block including the transaction.
+    pub block_hash: Hash,
+    /// Index of the extrinsic in block.
+    pub extrinsic_index: u32,
+    /// Chunk root of the transaction data.
+    pub chunk_root: Hash,
+    /// Size of the transaction data.
+    pub data_size: u32,
+    /// Absolute position in the weave at which the transaction data starts.
+    pub data_base: u64,
+}
+
+/// Type alias of [`WeaveBlock`] for a specific `Block`.
+pub type WeaveBlockFor<Block> = WeaveBlock<NumberFor<Block>, <Block as BlockT>::Hash>;
+
+/// Type alias of [`RecallTransaction`] for a specific `Block`.
+pub type RecallTransactionFor<Block> = RecallTransaction<NumberFor<Block>, <Block as BlockT>::Hash>;
+
+/// Backend for locating the recall transaction locally.
+pub trait WeaveIndexBackend<Block: BlockT> {
+    /// Returns the transaction in which `recall_byte` is located on the best chain.
+    ///
+    /// Returns `None` if the recall byte has not been indexed yet.
+    fn locate_recall_transaction(&self, recall_byte: u64) -> Option<RecallTransactionFor<Block>>;
+
+    /// Returns the body of block `block_hash` storing data if it has been kept.
+    fn recall_block_body(&self, block_hash: Block::Hash) -> Option<Vec<Block::Extrinsic>>;
+}
+
+/// Weave index persisted in the offchain storage.
+#[derive(Clone)]
+pub struct WeaveIndex<Block> {
+    offchain_storage: LocalStorage,
+    phantom: PhantomData<Block>,
+}
+
+impl<Block: BlockT> WeaveIndex<Block> {
+    /// Creates a new instance of [`WeaveIndex`].
+    pub fn new(offchain_storage: LocalStorage) -> Self {
+        Self {
+            offchain_storage,
+            phantom: PhantomData::<Block>,
+        }
+    }
+
+    fn get<T: Decode>(&self, key: &[u8]) -> Option<T> {
+        self.offchain_storage
+            .get(WEAVE_INDEX_PREFIX, key)
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    fn head(&self) -> Option<(u32, NumberFor<Block>, Block::Hash)> {
+        self.get(HEAD_KEY)
+    }
+
+    /// Returns the number of indexed blocks storing data.
+    pub fn len(&self) -> u32 {
+        self.head().map(|(len, _, _)| len).unwrap_or_default()
+    }
+
+    /// Returns `true` if no data has been indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `index`-th indexed block.
+    pub fn weave_block(&self, index: u32) -> Option<WeaveBlockFor<Block>> {
+        self.get(&weave_block_key(index))
+    }
+
+    /// Returns the transaction data given `block_number` and `extrinsic_index`
+    /// on the indexed chain.
+    pub fn stored_transaction(
+        &self,
+        block_number: NumberFor<Block>,
+        extrinsic_index: u32,
+    ) -> Option<StoredTransaction<Block::Hash>> {
+        self.offchain_storage
+            .get(
+                TRANSACTION_INDEX_PREFIX,
+                &(block_number, extrinsic_index).encode(),
+            )
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    /// Returns the `(block_number, extrinsic_index)` of a transaction storing data
+    /// `chunk_root` on the indexed chain.
+    pub fn chunk_root_position(&self, chunk_root: Block::Hash) -> Option<(NumberFor<Block>, u32)> {
+        self.offchain_storage
+            .get(CHUNK_ROOT_INDEX_PREFIX, &chunk_root.encode())
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    /// Returns the `(block_number, extrinsic_index)` of the extrinsic `extrinsic_hash`
+    /// storing data on the indexed chain.
+    pub fn extrinsic_position(
+        &self,
+        extrinsic_hash: Block::Hash,
+    ) -> Option<(NumberFor<Block>, u32)> {
+        self.offchain_storage
+            .get(
+                EXTRINSIC_HASH_INDEX_PREFIX,
+                &extrinsic_position_key(extrinsic_hash),
+            )
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    /// Returns the kept body of block `block_hash` storing data.
+    pub fn recall_body(&self, block_hash: Block::Hash) -> Option<Vec<Block::Extrinsic>> {
+        self.offchain_storage
+            .get(RECALL_BODY_PREFIX, &block_hash.encode())
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    /// Keeps the body of block `block_hash` storing data.
+    pub(crate) fn keep_recall_body(
+        &mut self,
+        block_hash: Block::Hash,
+        extrinsics: &[Block::Extrinsic],
+    ) {
+        self.offchain_storage.set(
+            RECALL_BODY_PREFIX,
+            &block_hash.encode(),
+            &extrinsics.encode(),
+        );
+    }
+
+    /// Returns the number and hash of the latest indexed block.
+    pub fn tip(&self) -> Option<(NumberFor<Block>, Block::Hash)> {
+        self.head().map(|(_, number, hash)| (number, hash))
+    }
+
+    /// Returns the indexed weave size.
+    pub fn weave_size(&self) -> u64 {
+        self.len()
+            .checked_sub(1)
+            .and_then(|last| self.weave_block(last))
+            .map(|weave_block| weave_block.weave_size())
+            .unwrap_or_default()
+    }
+
+    /// Returns the transaction in which `recall_byte` is located.
+    ///
+    /// Keep in line with the runtime, the recall byte belongs to the first block
+    /// and transaction whose weave size is not less than `recall_byte`.
+    pub fn locate(&self, recall_byte: u64) -> Option<RecallTransactionFor<Block>> {
+        let (mut low, mut high) = (0u32, self.len());
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.weave_block(mid)?.weave_size() < recall_byte {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let WeaveBlock {
+            block_number,
+            block_hash,
+            weave_base,
+            transactions,
+        } = self.weave_block(low)?;
+
+        let mut data_base = weave_base;
+        for tx in transactions {
+            if data_base + tx.data_size as u64 >= recall_byte {
+                return Some(RecallTransaction {
+                    block_number,
+                    block_hash,
+                    extrinsic_index: tx.extrinsic_index,
+                    chunk_root: tx.chunk_root,
+                    data_size: tx.data_size,
+                    data_base,
+                });
+            }
+            data_base += tx.data_size as u64;
+        }
+
+        None
+    }
+
+    /// Marks the block `(number, hash)` as indexed and appends `weave_block` if any.
+    pub(crate) fn push(
+        &mut self,
+        number: NumberFor<Block>,
+        hash: Block::Hash,
+        weave_block: Option<WeaveBlockFor<Block>>,
+    ) {
+        if let Some(weave_block) = weave_block {
+            self.stage(&weave_block);
+        }
+        self.commit(number, hash);
+    }
+
+    /// Writes the entries of `weave_block` without marking it as indexed.
+    ///
+    /// The block is journaled first, the entries written until [`Self::commit`],
+    /// including the extrinsic hashes and body of the block, are rolled back
+    /// by [`Self::recover`] if the indexing is interrupted.
+    pub(crate) fn stage(&mut self, weave_block: &WeaveBlockFor<Block>) {
+        self.offchain_storage
+            .set(WEAVE_INDEX_PREFIX, PENDING_KEY, &weave_block.encode());
+        for tx in &weave_block.transactions {
+            self.offchain_storage.set(
+                TRANSACTION_INDEX_PREFIX,
+                &(weave_block.block_number, tx.extrinsic_index).encode(),
+                &tx.encode(),
+            );
+            // The same data may be stored by multiple transactions, the first one is kept.
+            if self.chunk_root_position(tx.chunk_root).is_none() {
+                self.offchain_storage.set(
+                    CHUNK_ROOT_INDEX_PREFIX,
+                    &tx.chunk_root.encode(),
+                    &(weave_block.block_number, tx.extrinsic_index).encode(),
+                );
+            }
+        }
+        self.offchain_storage.set(
+            WEAVE_INDEX_PREFIX,
+            &weave_block_key(self.len()),
+            &weave_block.encode(),
+        );
+    }
+
+    /// Marks the block `(number, hash)` as indexed, along with the staged block if any.
+    pub(crate) fn commit(&mut self, number: NumberFor<Block>, hash: Block::Hash) {
+        let staged = self.get::<WeaveBlockFor<Block>>(PENDING_KEY).is_some();
+        self.set_head(self.len() + staged as u32, number, hash);
+        self.offchain_storage
+            .remove(WEAVE_INDEX_PREFIX, PENDING_KEY);
+    }
+
+    /// Rolls back the entries of the block whose indexing was interrupted, if any.
+    pub(crate) fn recover(&mut self) {
+        let pending = match self.get::<WeaveBlockFor<Block>>(PENDING_KEY) {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        // Nothing to roll back if the head has been written.
+        if self.tip().map(|(_, hash)| hash) != Some(pending.block_hash) {
+            log::debug!(
+                target: "datastore",
+                "Rolling back the interrupted indexing of block #{}",
+                pending.block_number,
+            );
+            self.remove_entries(&pending);
+            self.offchain_storage
+                .remove(WEAVE_INDEX_PREFIX, &weave_block_key(self.len()));
+        }
+
+        self.offchain_storage
+            .remove(WEAVE_INDEX_PREFIX, PENDING_KEY);
+    }
+
+    /// Indexes the hashes of the extrinsics storing data in block `block_number`.
+    ///
+    /// `extrinsic_hashes` is a list of `(extrinsic_index, extrinsic_hash)`.
+    pub(crate) fn index_extrinsic_hashes(
+        &mut self,
+        block_number: NumberFor<Block>,
+        extrinsic_hashes: &[(u32, Block::Hash)],
+    ) {
+        for (extrinsic_index, extrinsic_hash) in extrinsic_hashes {
+            self.offchain_storage.set(
+                EXTRINSIC_HASH_INDEX_PREFIX,
+                &extrinsic_position_key(extrinsic_hash),
+                &(block_number, extrinsic_index).encode(),
+            );
+            self.offchain_storage.set(
+                EXTRINSIC_HASH_INDEX_PREFIX,
+                &extrinsic_hash_key(block_number, *extrinsic_index),
+                &extrinsic_hash.encode(),
+            );
+        }
+    }
+
+    /// Reverts all the indexed blocks after the block `(number, hash)`.
+    ///
+    /// The reverted blocks stay readable until the head is written, so that an
+    /// interrupted revert is simply done again on restart.
+    pub(crate) fn revert_to(&mut self, number: NumberFor<Block>, hash: Block::Hash) {
+        let old_len = self.len();
+        let mut len = old_len;
+        while let Some(last) = len.checked_sub(1) {
+            match self.weave_block(last) {
+                Some(weave_block) if weave_block.block_number > number => {
+                    self.remove_entries(&weave_block);
+                    len = last;
+                }
+                _ => break,
+            }
+        }
+        self.set_head(len, number, hash);
+        for index in len..old_len {
+            self.offchain_storage
+                .remove(WEAVE_INDEX_PREFIX, &weave_block_key(index));
+        }
+    }
+
+    /// Removes the entries of `weave_block` other than the block itself.
+    fn remove_entries(&mut self, weave_block: &WeaveBlockFor<Block>) {
+        for tx in &weave_block.transactions {
+            self.offchain_storage.remove(
+                TRANSACTION_INDEX_PREFIX,
+                &(weave_block.block_number, tx.extrinsic_index).encode(),
+            );
+            if self.chunk_root_position(tx.chunk_root)
+                == Some((weave_block.block_number, tx.extrinsic_index))
+            {
+                self.offchain_storage
+                    .remove(CHUNK_ROOT_INDEX_PREFIX, &tx.chunk_root.encode());
+            }
+            self.remove_extrinsic_hash(weave_block.block_number, tx.extrinsic_index);
+        }
+        self.offchain_storage
+            .remove(RECALL_BODY_PREFIX, &weave_block.block_hash.encode());
+    }
+
+    fn remove_extrinsic_hash(&mut self, block_number: NumberFor<Block>, extrinsic_index: u32) {
+        let hash_key = extrinsic_hash_key(block_number, extrinsic_index);
+        if let Some(extrinsic_hash) = self
+            .offchain_storage
+            .get(EXTRINSIC_HASH_INDEX_PREFIX, &hash_key)
+            .and_then(|encoded| Block::Hash::decode(&mut encoded.as_slice()).ok())
+        {
+            self.offchain_storage.remove(
+                EXTRINSIC_HASH_INDEX_PREFIX,
+                &extrinsic_position_key(extrinsic_hash),
+            );
+            self.offchain_storage
+                .remove(EXTRINSIC_HASH_INDEX_PREFIX, &hash_key);
+        }
+    }
+
+    pub(crate) fn set_head(&mut self, len: u32, number: NumberFor<Block>, hash: Block::Hash) {
+        self.offchain_storage
+            .set(WEAVE_INDEX_PREFIX, HEAD_KEY, &(len, number, hash).encode());
+    }
+}
+
+#[cfg(any(feature = "test-helpers", test))]
+impl<Block: BlockT> WeaveIndex<Block> {
+    /// Indexes `weave_block` along with the `(extrinsic_index, extrinsic_hash)` of
+    /// its transactions, for testing only.
+    pub fn push_for_test(
+        &mut self,
+        weave_block: WeaveBlockFor<Block>,
+        extrinsic_hashes: &[(u32, Block::Hash)],
+    ) {
+        let (number, hash) = (weave_block.block_number, weave_block.block_hash);
+        self.push(number, hash, Some(weave_block));
+        self.index_extrinsic_hashes(number, extrinsic_hashes);
+    }
+}
+
+impl<Block: BlockT> WeaveIndexBackend<Block> for WeaveIndex<Block> {
+    fn locate_recall_transaction(&self, recall_byte: u64) -> Option<RecallTransactionFor<Block>> {
+        self.locate(recall_byte)
+    }
+
+    fn recall_block_body(&self, block_hash: Block::Hash) -> Option<Vec<Block::Extrinsic>> {
+        self.recall_body(block_hash)
+    }
+}
+
+impl<Block: BlockT> ChunkRootBackend<Block> for WeaveIndex<Block> {
+    fn chunk_root(
+        &self,
+        block_number: NumberFor<Block>,
+        extrinsic_index: u32,
+    ) -> Option<Block::Hash> {
+        self.stored_transaction(block_number, extrinsic_index)
+            .map(|tx| tx.chunk_root)
+    }
+
+    fn data_size(&self, block_number: NumberFor<Block>, extrinsic_index: u32) -> Option<u32> {
+        self.stored_transaction(block_number, extrinsic_index)
+            .map(|tx| tx.data_size)
+    }
+
+    fn extrinsic_position(&self, extrinsic_hash: Block::Hash) -> Option<(NumberFor<Block>, u32)> {
+        WeaveIndex::extrinsic_position(self, extrinsic_hash)
+    }
+}
+
+/// Returns the `(extrinsic_index, extrinsic_hash)` of the transactions in `weave_block`.
+fn extrinsic_hashes<Block: BlockT>(
+    weave_block: &WeaveBlockFor<Block>,
+    extrinsics: &[Block::Extrinsic],
+) -> Vec<(u32, Block::Hash)> {
+    weave_block
+        .transactions
+        .iter()
+        .filter_map(|tx| {
+            extrinsics
+                .get(tx.extrinsic_index as usize)
+                .map(|extrinsic| {
+                    (
+                        tx.extrinsic_index,
+                        <<Block::Header as HeaderT>::Hashing as HashT>::hash_of(extrinsic),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Worker for keeping the [`WeaveIndex`] in sync with the best chain.
+pub struct WeaveIndexer<Block, C> {
+    client: Arc<C>,
+    weave_index: WeaveIndex<Block>,
+    keep_recall_bodies: bool,
+    /// Whether a block with the [`PERMASTORE_ENGINE_ID`] digest has been indexed,
+    /// after which no block can be created before the digest was introduced.
+    digest_seen: bool,
+}
+
+impl<Block, C> WeaveIndexer<Block, C>
+where
+    Block: BlockT,
+    C: BlockchainEvents<Block>
+        + BlockBackend<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync,
+    C::Api: PermastoreApi<Block, NumberFor<Block>, u32, Block::Hash>,
+{
+    /// Creates a new instance of [`WeaveIndexer`].
+    pub fn new(client: Arc<C>, offchain_storage: LocalStorage) -> Self {
+        Self {
+            client,
+            weave_index: WeaveIndex::new(offchain_storage),
+            keep_recall_bodies: false,
+            digest_seen: false,
+        }
+    }
+
+    /// Keeps the bodies of the blocks storing data in the weave index if `keep` is `true`.
+    ///
+    /// This is required for the PoA when the block bodies are pruned, as the
+    /// recall block can be any block storing data in the history.
+    pub fn keep_recall_bodies(mut self, keep: bool) -> Self {
+        self.keep_recall_bodies = keep;
+        self
+    }
+
+    /// Updates the weave index to the new best block `best_hash`.
+    ///
+    /// The indexed blocks which are not on the new best chain are reverted first.
+    pub fn update_to(&mut self, best_hash: Block::Hash) -> Result<(), Error<Block>> {
+        self.weave_index.recover();
+
+        let (tip_number, tip_hash) = match self.weave_index.tip() {
+            Some(tip) => tip,
+            None => {
+                let genesis_hash = self
+                    .client
+                    .hash(Zero::zero())
+                    .map_err(Box::new)?
+                    .ok_or_else(|| Error::BlockNumberNotFound(BlockId::Number(Zero::zero())))?;
+                (Zero::zero(), genesis_hash)
+            }
+        };
+
+        if tip_hash == best_hash {
+            return Ok(());
+        }
+
+        let tree_route =
+            sp_blockchain::tree_route(&*self.client, tip_hash, best_hash).map_err(Box::new)?;
+
+        let common_block = tree_route.common_block();
+        if common_block.hash != tip_hash {
+            log::debug!(
+                target: "datastore",
+                "Reverting the weave index from #{} to #{} due to reorg",
+                tip_number,
+                common_block.number,
+            );
+            self.weave_index
+                .revert_to(common_block.number, common_block.hash);
+        }
+
+        for block in tree_route.enacted() {
+            let weave_base = self.weave_index.weave_size();
+            if let Some(weave_block) =
+                self.fetch_weave_block(block.number, block.hash, weave_base)?
+            {
+                self.weave_index.stage(&weave_block);
+                if let Some(extrinsics) = self.fetch_block_body(&weave_block)? {
+                    if self.keep_recall_bodies {
+                        self.weave_index.keep_recall_body(block.hash, &extrinsics);
+                    }
+                    self.weave_index.index_extrinsic_hashes(
+                        block.number,
+                        &extrinsic_hashes::<Block>(&weave_block, &extrinsics),
+                    );
+                }
+            }
+            self.weave_index.commit(block.number, block.hash);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the transaction data stored in block `(number, hash)`.
+    ///
+    /// `weave_base` is the weave size at the end of the previous indexed block,
+    /// the state of block is not required as it may have been pruned. The legacy
+    /// runtime state is read on top of the best block instead for the blocks without
+    /// digest until the first block with digest has been indexed, as it's never pruned.
+    fn fetch_weave_block(
+        &mut self,
+        number: NumberFor<Block>,
+        hash: Block::Hash,
+        weave_base: u64,
+    ) -> Result<Option<WeaveBlockFor<Block>>, Error<Block>> {
+        let at = BlockId::Hash(hash);
+
+        let header = self
+            .client
+            .header(at)
+            .map_err(Box::new)?
+            .ok_or(Error::HeaderNotFound(at))?;
+
+        let mut transactions = stored_transactions(&header)?;
+
+        if !transactions.is_empty() {
+            self.digest_seen = true;
+        } else if !self.digest_seen {
+            transactions = self.fetch_legacy_transactions(number, hash)?;
+        }
+
+        if transactions.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(WeaveBlock {
+            block_number: number,
+            block_hash: hash,
+            weave_base,
+            transactions,
+        }))
+    }
+
+    /// Returns the transaction data stored in block `(number, hash)` from the legacy
+    /// runtime state.
+    ///
+    /// An empty list is returned if the block body has been pruned, as the extrinsics
+    /// storing data are unknown.
+    fn fetch_legacy_transactions(
+        &self,
+        number: NumberFor<Block>,
+        hash: Block::Hash,
+    ) -> Result<Vec<StoredTransaction<Block::Hash>>, Error<Block>> {
+        let extrinsic_count = match self
+            .client
+            .block_body(&BlockId::Hash(hash))
+            .map_err(Box::new)?
+        {
+            Some(extrinsics) => extrinsics.len() as u32,
+            None => {
+                log::debug!(
+                    target: "datastore",
+                    "Body of block #{} is unavailable, skipping the legacy transaction data",
+                    number,
+                );
+                return Ok(Vec::new());
+            }
+        };
+
+        Ok(legacy_stored_transactions(
+            &*self.client,
+            &BlockId::Hash(self.client.info().best_hash),
+            number,
+            extrinsic_count,
+        )?)
+    }
+
+    /// Returns the body of the block of `weave_block`.
+    ///
+    /// `None` is returned if the block body has been pruned.
+    fn fetch_block_body(
+        &self,
+        weave_block: &WeaveBlockFor<Block>,
+    ) -> Result<Option<Vec<Block::Extrinsic>>, Error<Block>> {
+        let at = BlockId::Hash(weave_block.block_hash);
+
+        let extrinsics = self.client.block_body(&at).map_err(Box::new)?;
+
+        if extrinsics.is_none() {
+            log::debug!(
+                target: "datastore",
+                "Body of block #{} is unavailable, skipping the extrinsic hash index",
+                weave_block.block_number,
+            );
+        }
+
+        Ok(extrinsics)
+    }
+
+    fn update(&mut self, best_hash: Block::Hash) {
+        if let Err(e) = self.update_to(best_hash) {
+            log::error!(
+                target: "datastore",
+                "Failed to update the weave index to {:?}: {:?}",
+                best_hash,
+                e,
+            );
+        }
+    }
+
+    /// Runs the worker, which indexes every new best block.
+    pub async fn run(mut self) {
+        let mut import_notification_stream = self.client.import_notification_stream();
+
+        // Catch up with the current best chain.
+        self.update(self.client.info().best_hash);
+
+        while let Some(notification) = import_notification_stream.next().await {
+            if notification.is_new_best {
+                self.update(notification.hash);
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+#![deny(missing_docs, unused_extern_crates)]
+
+//! HTTP gateway serving the permanent transaction data by chunk root.
+//!
+//! The transaction data of chunk root `0x..` is served at `GET /data/0x..` as raw
+//! bytes. The data is content addressed, hence the chunk root is used as the entity
+//! tag and the response can be cached indefinitely. A single byte range can be
+//! requested via the `Range` header, in which case only the chunks covering the
+//! range are read from the [`PermaStorage`].
+
+#[cfg(test)]
+mod tests;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::str::FromStr;
+
+use codec::Encode;
+use futures::{stream, Stream};
+use hyper::{
+    header::{self, HeaderValue},
+    server::conn::AddrIncoming,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use tokio::net::TcpListener;
+
+use sp_core::H256;
+
+use cp_permastore::{chunk_tree, PermaStorage, CHUNK_SIZE};
+
+/// Path prefix of the transaction data.
+const DATA_PATH_PREFIX: &str = "/data/";
+
+/// The transaction data never changes once stored.
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Gateway error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Hyper internal error.
+    #[error(transparent)]
+    Hyper(#[from] hyper::Error),
+    /// The listen address is unavailable.
+    #[error("Gateway port {0} already in use.")]
+    PortInUse(SocketAddr),
+}
+
+/// Byte range requested by the `Range` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ByteRange {
+    /// The entire data, if the header is absent or not a single byte range.
+    Full,
+    /// The satisfiable part of the requested range.
+    Partial(Range<u32>),
+    /// None of the requested bytes is in the data.
+    Unsatisfiable,
+}
+
+/// Parses the value of `Range` header against the data of `data_size` bytes.
+///
+/// Only a single byte range is supported, other forms of the header are ignored
+/// as permitted by RFC 7233.
+fn parse_range(value: &str, data_size: u32) -> ByteRange {
+    let spec = match value.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec,
+        _ => return ByteRange::Full,
+    };
+
+    let (first, last) = match spec.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => return ByteRange::Full,
+    };
+
+    let data_size = data_size as u64;
+
+    let range = match (first.parse::<u64>(), last.parse::<u64>()) {
+        // `bytes=-suffix_length`
+        (Err(_), Ok(suffix_length)) if first.is_empty() => {
+            data_size.saturating_sub(suffix_length)..data_size
+        }
+        // `bytes=first-`
+        (Ok(first), Err(_)) if last.is_empty() => first..data_size,
+        // `bytes=first-last`
+        (Ok(first), Ok(last)) if first <= last => first..(last + 1).min(data_size),
+        _ => return ByteRange::Full,
+    };
+
+    if range.start >= range.end {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(range.start as u32..range.end as u32)
+    }
+}
+
+/// Streams the bytes in `range` of the data under `key` chunk by chunk.
+fn data_stream<T: PermaStorage>(
+    storage: T,
+    key: Vec<u8>,
+    range: Range<u32>,
+) -> impl Stream<Item = std::io::Result<Vec<u8>>> {
+    let chunk_indices = if range.is_empty() {
+        0..0
+    } else {
+        chunk_tree::chunk_index(range.start)..chunk_tree::chunk_index(range.end - 1) + 1
+    };
+
+    stream::iter(chunk_indices.map(move |chunk_index| {
+        let chunk = storage.retrieve_chunk(&key, chunk_index).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("chunk {} is missing", chunk_index),
+            )
+        })?;
+
+        let chunk_start = chunk_index * CHUNK_SIZE;
+        let from = (range.start.saturating_sub(chunk_start) as usize).min(chunk.len());
+        let to = ((range.end - chunk_start) as usize).min(chunk.len());
+
+        Ok(chunk[from..to].to_vec())
+    }))
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Returns the response to `req`.
+fn handle_request<T>(storage: &T, req: &Request<Body>) -> Response<Body>
+where
+    T: PermaStorage + Clone + 'static,
+{
+    let chunk_root = match req.uri().path().strip_prefix(DATA_PATH_PREFIX) {
+        Some(chunk_root) => chunk_root,
+        None => return empty_response(StatusCode::NOT_FOUND),
+    };
+
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        let mut response = empty_response(StatusCode::METHOD_NOT_ALLOWED);
+        response
+            .headers_mut()
+            .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+        return response;
+    }
+
+    let chunk_root = match H256::from_str(chunk_root) {
+        Ok(chunk_root) => chunk_root,
+        Err(_) => return empty_response(StatusCode::BAD_REQUEST),
+    };
+
+    let key = chunk_root.encode();
+
+    let data_size = match storage.data_meta(&key) {
+        Some(data_meta) => data_meta.data_size,
+        None => return empty_response(StatusCode::NOT_FOUND),
+    };
+
+    let etag = format!("\"{:?}\"", chunk_root);
+
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        })
+        .unwrap_or(false);
+
+    let builder = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, CACHE_CONTROL)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    if not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .expect("Response with valid headers; qed");
+    }
+
+    let range = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| parse_range(value, data_size))
+        .unwrap_or(ByteRange::Full);
+
+    let (builder, range) = match range {
+        ByteRange::Full => (builder.status(StatusCode::OK), 0..data_size),
+        ByteRange::Partial(range) => (
+            builder.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end - 1, data_size),
+            ),
+            range,
+        ),
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", data_size))
+                .body(Body::empty())
+                .expect("Response with valid headers; qed");
+        }
+    };
+
+    let body = if req.method() == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::wrap_stream(data_stream(storage.clone(), key, range.clone()))
+    };
+
+    builder
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, range.end - range.start)
+        .body(body)
+        .expect("Response with valid headers; qed")
+}
+
+/// Serves the transaction data in `storage` over HTTP at `addr`.
+pub async fn run<T>(addr: SocketAddr, storage: T) -> Result<(), Error>
+where
+    T: PermaStorage + Clone + 'static,
+{
+    let listener = TcpListener::bind(&addr)
+        .await
+        .map_err(|_| Error::PortInUse(addr))?;
+
+    run_with_listener(listener, storage).await
+}
+
+/// Serves the transaction data in `storage` over HTTP with the given `listener`.
+pub async fn run_with_listener<T>(listener: TcpListener, storage: T) -> Result<(), Error>
+where
+    T: PermaStorage + Clone + 'static,
+{
+    let incoming = AddrIncoming::from_listener(listener)?;
+
+    log::info!(
+        target: "gateway",
+        "Permastore gateway listening on http://{}",
+        incoming.local_addr(),
+    );
+
+    let service = make_service_fn(move |_| {
+        let storage = storage.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let response = handle_request(&storage, &req);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    Server::builder(incoming).serve(service).await?;
+
+    Ok(())
+}
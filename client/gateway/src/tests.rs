@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+
+use std::sync::Arc;
+
+use hyper::{body::to_bytes, Client};
+
+use cc_datastore::PermanentStorage;
+use substrate_test_runtime_client::Backend;
+
+type TestStorage = PermanentStorage<substrate_test_runtime_client::Client<Backend>>;
+
+/// Test data spanning 3 chunks.
+fn test_data() -> Vec<u8> {
+    (0..CHUNK_SIZE * 2 + 5).map(|i| (i % 251) as u8).collect()
+}
+
+fn storage_with(data: &[u8]) -> (TestStorage, H256) {
+    let client = Arc::new(substrate_test_runtime_client::new());
+    let mut storage = PermanentStorage::new_test(client);
+
+    let chunk_root = chunk_tree::chunk_root(data);
+    storage.submit(&chunk_root.encode(), data);
+
+    (storage, chunk_root)
+}
+
+fn data_uri(chunk_root: H256) -> String {
+    format!("{}{:?}", DATA_PATH_PREFIX, chunk_root)
+}
+
+async fn respond(
+    storage: &TestStorage,
+    req: hyper::http::request::Builder,
+) -> (Response<()>, Vec<u8>) {
+    let response = handle_request::<TestStorage>(storage, &req.body(Body::empty()).unwrap());
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body).await.unwrap().to_vec();
+    (Response::from_parts(parts, ()), body)
+}
+
+fn header_of<'a>(response: &'a Response<()>, name: header::HeaderName) -> &'a str {
+    response.headers().get(name).unwrap().to_str().unwrap()
+}
+
+#[test]
+fn parse_range_should_work() {
+    assert_eq!(parse_range("bytes=0-9", 100), ByteRange::Partial(0..10));
+    assert_eq!(parse_range("bytes=90-", 100), ByteRange::Partial(90..100));
+    assert_eq!(parse_range("bytes=-10", 100), ByteRange::Partial(90..100));
+    // Truncated at the end of data.
+    assert_eq!(
+        parse_range("bytes=90-200", 100),
+        ByteRange::Partial(90..100)
+    );
+    assert_eq!(parse_range("bytes=-200", 100), ByteRange::Partial(0..100));
+
+    assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+    assert_eq!(parse_range("bytes=-0", 100), ByteRange::Unsatisfiable);
+    assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+
+    // Ignored.
+    assert_eq!(parse_range("bytes=9-0", 100), ByteRange::Full);
+    assert_eq!(parse_range("bytes=0-1,5-6", 100), ByteRange::Full);
+    assert_eq!(parse_range("items=0-1", 100), ByteRange::Full);
+    assert_eq!(parse_range("bytes=-", 100), ByteRange::Full);
+}
+
+#[tokio::test]
+async fn get_data_should_work() {
+    let data = test_data();
+    let (storage, chunk_root) = storage_with(&data);
+
+    let (response, body) = respond(&storage, Request::get(&data_uri(chunk_root))).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        header_of(&response, header::CONTENT_LENGTH),
+        data.len().to_string()
+    );
+    assert_eq!(
+        header_of(&response, header::ETAG),
+        format!("\"{:?}\"", chunk_root)
+    );
+    assert_eq!(header_of(&response, header::ACCEPT_RANGES), "bytes");
+    assert_eq!(body, data);
+}
+
+#[tokio::test]
+async fn get_range_should_work() {
+    let data = test_data();
+    let data_size = data.len() as u32;
+    let (storage, chunk_root) = storage_with(&data);
+
+    // Across the chunk boundary.
+    let (first, last) = (CHUNK_SIZE - 2, CHUNK_SIZE + 1);
+    let (response, body) = respond(
+        &storage,
+        Request::get(&data_uri(chunk_root))
+            .header(header::RANGE, format!("bytes={}-{}", first, last)),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        header_of(&response, header::CONTENT_RANGE),
+        format!("bytes {}-{}/{}", first, last, data_size)
+    );
+    assert_eq!(header_of(&response, header::CONTENT_LENGTH), "4");
+    assert_eq!(body, &data[first as usize..=last as usize]);
+
+    // Suffix within the last chunk.
+    let (response, body) = respond(
+        &storage,
+        Request::get(&data_uri(chunk_root)).header(header::RANGE, "bytes=-7"),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        header_of(&response, header::CONTENT_RANGE),
+        format!("bytes {}-{}/{}", data_size - 7, data_size - 1, data_size)
+    );
+    assert_eq!(body, &data[data.len() - 7..]);
+}
+
+#[tokio::test]
+async fn unsatisfiable_range_should_be_rejected() {
+    let data = test_data();
+    let (storage, chunk_root) = storage_with(&data);
+
+    let (response, body) = respond(
+        &storage,
+        Request::get(&data_uri(chunk_root)).header(header::RANGE, format!("bytes={}-", data.len())),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(
+        header_of(&response, header::CONTENT_RANGE),
+        format!("bytes */{}", data.len())
+    );
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn matching_etag_should_not_be_modified() {
+    let data = test_data();
+    let (storage, chunk_root) = storage_with(&data);
+
+    let (response, body) = respond(
+        &storage,
+        Request::get(&data_uri(chunk_root))
+            .header(header::IF_NONE_MATCH, format!("\"{:?}\"", chunk_root)),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn head_should_not_return_body() {
+    let data = test_data();
+    let (storage, chunk_root) = storage_with(&data);
+
+    let (response, body) = respond(&storage, Request::head(data_uri(chunk_root))).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        header_of(&response, header::CONTENT_LENGTH),
+        data.len().to_string()
+    );
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn invalid_requests_should_be_rejected() {
+    let (storage, chunk_root) = storage_with(&test_data());
+
+    let status_of = |req| {
+        let storage = storage.clone();
+        async move { respond(&storage, req).await.0.status() }
+    };
+
+    assert_eq!(
+        status_of(Request::get(&data_uri(H256::repeat_byte(1)))).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status_of(Request::get("/data/0x1234")).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        status_of(Request::get(&format!("/chunks/{:?}", chunk_root))).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status_of(Request::post(data_uri(chunk_root))).await,
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+#[tokio::test]
+async fn serve_over_http_should_work() {
+    let data = test_data();
+    let (storage, chunk_root) = storage_with(&data);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(run_with_listener(listener, storage));
+
+    let client = Client::new();
+    let uri: hyper::Uri = format!("http://{}{}", addr, data_uri(chunk_root))
+        .parse()
+        .unwrap();
+
+    let response = client.get(uri.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(to_bytes(response.into_body()).await.unwrap().to_vec(), data);
+
+    let response = client
+        .request(
+            Request::get(uri)
+                .header(header::RANGE, "bytes=10-19")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        to_bytes(response.into_body()).await.unwrap().to_vec(),
+        &data[10..20]
+    );
+}
@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+#![deny(missing_docs, unused_extern_crates)]
+
+//! This crate provides a local index of the transaction data stored on the best chain,
+//! which can be queried by owner, block range and tag.
+//!
+//! The [`TransactionIndex`] is persisted in the offchain storage and maintained by
+//! [`TransactionIndexer`] following the block import notifications, in the same way
+//! as the weave index of `cc_datastore`. The transactions of each block are read from
+//! the [`PERMASTORE_ENGINE_ID`](cp_permastore::PERMASTORE_ENGINE_ID) digest, their
+//! owners and tags are read from the state of the block via the runtime APIs.
+//!
+//! Each indexed transaction is assigned a sequential id in the order of inclusion,
+//! the ids of the transactions of an owner or with a tag are kept in a list of their
+//! own, so that a query only walks through the transactions it may match. All the
+//! lists are append-only on the best chain and truncated on reorg.
+
+#[cfg(test)]
+mod tests;
+mod transaction_index;
+
+pub use self::transaction_index::{
+    IndexedTransaction, IndexedTransactionFor, Page, TransactionIndex, TransactionIndexer,
+    TransactionQuery, MAX_SCANNED_TRANSACTIONS,
+};
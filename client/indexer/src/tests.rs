@@ -0,0 +1,358 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+use sc_client_db::offchain::LocalStorage;
+
+use cp_permastore::Tag;
+
+use crate::{IndexedTransaction, Page, TransactionIndex, TransactionQuery};
+
+type Block = substrate_test_runtime_client::runtime::Block;
+type Hash = substrate_test_runtime_client::runtime::Hash;
+type AccountId = u64;
+
+fn indexed_transaction(
+    block_number: u64,
+    extrinsic_index: u32,
+    owner: Option<AccountId>,
+    tags: Vec<Tag>,
+) -> IndexedTransaction<u64, Hash, AccountId> {
+    IndexedTransaction {
+        block_number,
+        block_hash: Hash::repeat_byte(block_number as u8),
+        extrinsic_index,
+        chunk_root: Hash::repeat_byte(100 + block_number as u8 + extrinsic_index as u8),
+        data_size: 10,
+        owner,
+        tags,
+    }
+}
+
+/// Returns the `(block_number, extrinsic_index)` of the items in `page`.
+fn positions(page: &Page<IndexedTransaction<u64, Hash, AccountId>>) -> Vec<(u64, u32)> {
+    page.items
+        .iter()
+        .map(|tx| (tx.block_number, tx.extrinsic_index))
+        .collect()
+}
+
+/// Indexes the transactions below:
+///
+/// | block | extrinsic | owner | tags      |
+/// |-------|-----------|-------|-----------|
+/// | 1     | 1         | 1     | html, app |
+/// | 1     | 2         | 2     | html      |
+/// | 3     | 1         | 1     |           |
+/// | 4     | 1         | 2     | app       |
+/// | 4     | 2         | 1     | html      |
+fn new_transaction_index(html: &Tag, app: &Tag) -> TransactionIndex<Block, AccountId> {
+    let mut transaction_index = TransactionIndex::new(LocalStorage::new_test());
+
+    transaction_index.push(
+        1,
+        Hash::repeat_byte(1),
+        vec![
+            indexed_transaction(1, 1, Some(1), vec![html.clone(), app.clone()]),
+            indexed_transaction(1, 2, Some(2), vec![html.clone()]),
+        ],
+    );
+    transaction_index.push(2, Hash::repeat_byte(2), vec![]);
+    transaction_index.push(
+        3,
+        Hash::repeat_byte(3),
+        vec![indexed_transaction(3, 1, Some(1), vec![])],
+    );
+    transaction_index.push(
+        4,
+        Hash::repeat_byte(4),
+        vec![
+            indexed_transaction(4, 1, Some(2), vec![app.clone()]),
+            indexed_transaction(4, 2, Some(1), vec![html.clone()]),
+        ],
+    );
+
+    transaction_index
+}
+
+#[test]
+fn query_should_work() {
+    let html = Tag::new("Content-Type", "text/html");
+    let app = Tag::new("App-Name", "canyon");
+    let transaction_index = new_transaction_index(&html, &app);
+
+    assert_eq!(transaction_index.len(), 5);
+    assert_eq!(transaction_index.tip(), Some((4, Hash::repeat_byte(4))));
+
+    let query = |query: TransactionQuery<u64, AccountId>| {
+        positions(&transaction_index.query(&query, None, 10))
+    };
+
+    assert_eq!(
+        query(Default::default()),
+        vec![(1, 1), (1, 2), (3, 1), (4, 1), (4, 2)]
+    );
+    assert_eq!(
+        query(TransactionQuery {
+            owner: Some(1),
+            ..Default::default()
+        }),
+        vec![(1, 1), (3, 1), (4, 2)]
+    );
+    assert_eq!(
+        query(TransactionQuery {
+            tag: Some(html.clone()),
+            ..Default::default()
+        }),
+        vec![(1, 1), (1, 2), (4, 2)]
+    );
+    assert_eq!(
+        query(TransactionQuery {
+            from_block: Some(2),
+            to_block: Some(3),
+            ..Default::default()
+        }),
+        vec![(3, 1)]
+    );
+    assert_eq!(
+        query(TransactionQuery {
+            owner: Some(2),
+            tag: Some(html.clone()),
+            ..Default::default()
+        }),
+        vec![(1, 2)]
+    );
+    assert_eq!(
+        query(TransactionQuery {
+            owner: Some(1),
+            tag: Some(html.clone()),
+            from_block: Some(2),
+            to_block: None,
+        }),
+        vec![(4, 2)]
+    );
+    assert!(query(TransactionQuery {
+        owner: Some(3),
+        ..Default::default()
+    })
+    .is_empty());
+    assert!(query(TransactionQuery {
+        tag: Some(Tag::new("Content-Type", "text/plain")),
+        ..Default::default()
+    })
+    .is_empty());
+}
+
+#[test]
+fn query_should_be_paginated() {
+    let html = Tag::new("Content-Type", "text/html");
+    let app = Tag::new("App-Name", "canyon");
+    let transaction_index = new_transaction_index(&html, &app);
+
+    let query = TransactionQuery {
+        owner: Some(1),
+        ..Default::default()
+    };
+
+    let page = transaction_index.query(&query, None, 2);
+    assert_eq!(positions(&page), vec![(1, 1), (3, 1)]);
+    assert_eq!(page.next_cursor, Some(2));
+
+    let page = transaction_index.query(&query, page.next_cursor, 2);
+    assert_eq!(positions(&page), vec![(4, 2)]);
+    assert_eq!(page.next_cursor, None);
+
+    // The cursor is not ahead of the block range.
+    let query = TransactionQuery {
+        from_block: Some(3),
+        ..Default::default()
+    };
+    let page = transaction_index.query(&query, Some(1), 1);
+    assert_eq!(positions(&page), vec![(3, 1)]);
+    assert_eq!(page.next_cursor, Some(3));
+
+    let query = TransactionQuery {
+        to_block: Some(3),
+        ..Default::default()
+    };
+    let page = transaction_index.query(&query, Some(2), 1);
+    assert_eq!(positions(&page), vec![(3, 1)]);
+    assert_eq!(page.next_cursor, Some(3));
+
+    // No more transactions within the block range.
+    let page = transaction_index.query(&query, page.next_cursor, 1);
+    assert!(page.items.is_empty());
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn duplicate_tags_should_be_indexed_once() {
+    let html = Tag::new("Content-Type", "text/html");
+    let mut transaction_index = TransactionIndex::<Block, AccountId>::new(LocalStorage::new_test());
+
+    transaction_index.push(
+        1,
+        Hash::repeat_byte(1),
+        vec![indexed_transaction(
+            1,
+            1,
+            None,
+            vec![html.clone(), html.clone()],
+        )],
+    );
+
+    let query = TransactionQuery {
+        tag: Some(html.clone()),
+        ..Default::default()
+    };
+    assert_eq!(
+        positions(&transaction_index.query(&query, None, 10)),
+        vec![(1, 1)]
+    );
+
+    transaction_index.revert_to(0, Hash::repeat_byte(0));
+    assert!(transaction_index.query(&query, None, 10).items.is_empty());
+}
+
+#[test]
+fn chunk_roots_by_tag_should_work() {
+    let html = Tag::new("Content-Type", "text/html");
+    let app = Tag::new("App-Name", "canyon");
+    let mut transaction_index = new_transaction_index(&html, &app);
+
+    assert_eq!(
+        transaction_index.chunk_roots_by_tag(&html),
+        vec![
+            Hash::repeat_byte(102),
+            Hash::repeat_byte(103),
+            Hash::repeat_byte(106)
+        ]
+    );
+    assert!(transaction_index
+        .chunk_roots_by_tag(&Tag::new("Content-Type", "text/plain"))
+        .is_empty());
+
+    // The tags of the reverted transactions are dropped.
+    transaction_index.revert_to(3, Hash::repeat_byte(3));
+    assert_eq!(
+        transaction_index.chunk_roots_by_tag(&app),
+        vec![Hash::repeat_byte(102)]
+    );
+}
+
+#[test]
+fn revert_should_work() {
+    let html = Tag::new("Content-Type", "text/html");
+    let app = Tag::new("App-Name", "canyon");
+    let mut transaction_index = new_transaction_index(&html, &app);
+
+    transaction_index.revert_to(3, Hash::repeat_byte(3));
+    assert_eq!(transaction_index.len(), 3);
+    assert_eq!(transaction_index.tip(), Some((3, Hash::repeat_byte(3))));
+    assert_eq!(transaction_index.transaction(3), None);
+
+    let owner_query = TransactionQuery {
+        owner: Some(2),
+        ..Default::default()
+    };
+    let app_query = TransactionQuery {
+        tag: Some(app.clone()),
+        ..Default::default()
+    };
+    assert_eq!(
+        positions(&transaction_index.query(&owner_query, None, 10)),
+        vec![(1, 2)]
+    );
+    assert_eq!(
+        positions(&transaction_index.query(&app_query, None, 10)),
+        vec![(1, 1)]
+    );
+
+    // The reverted positions are reused by the new best chain.
+    let mut tx = indexed_transaction(4, 3, Some(2), vec![app.clone()]);
+    tx.block_hash = Hash::repeat_byte(44);
+    transaction_index.push(4, Hash::repeat_byte(44), vec![tx.clone()]);
+    assert_eq!(transaction_index.transaction(3), Some(tx));
+    assert_eq!(
+        positions(&transaction_index.query(&owner_query, None, 10)),
+        vec![(1, 2), (4, 3)]
+    );
+    assert_eq!(
+        positions(&transaction_index.query(&app_query, None, 10)),
+        vec![(1, 1), (4, 3)]
+    );
+
+    transaction_index.revert_to(0, Hash::repeat_byte(0));
+    assert!(transaction_index.is_empty());
+    assert!(transaction_index
+        .query(&Default::default(), None, 10)
+        .items
+        .is_empty());
+    assert!(transaction_index
+        .query(&app_query, None, 10)
+        .items
+        .is_empty());
+}
+
+#[test]
+fn interrupted_indexing_should_be_rolled_back() {
+    let html = Tag::new("Content-Type", "text/html");
+    let app = Tag::new("App-Name", "canyon");
+    let mut transaction_index = new_transaction_index(&html, &app);
+
+    let owner_query = TransactionQuery {
+        owner: Some(1),
+        ..Default::default()
+    };
+    let html_query = TransactionQuery {
+        tag: Some(html.clone()),
+        ..Default::default()
+    };
+    let tx = indexed_transaction(5, 1, Some(1), vec![html.clone()]);
+
+    // Interrupted before the head is written.
+    transaction_index.stage(Hash::repeat_byte(5), &[tx.clone()]);
+    assert_eq!(transaction_index.len(), 5);
+    assert_eq!(transaction_index.tip(), Some((4, Hash::repeat_byte(4))));
+
+    transaction_index.recover();
+    assert_eq!(transaction_index.transaction(5), None);
+    assert_eq!(
+        positions(&transaction_index.query(&owner_query, None, 10)),
+        vec![(1, 1), (3, 1), (4, 2)]
+    );
+    assert_eq!(
+        positions(&transaction_index.query(&html_query, None, 10)),
+        vec![(1, 1), (1, 2), (4, 2)]
+    );
+
+    // Interrupted after the head is written.
+    transaction_index.stage(Hash::repeat_byte(5), &[tx.clone()]);
+    transaction_index.set_head(6, 5, Hash::repeat_byte(5));
+
+    transaction_index.recover();
+    assert_eq!(transaction_index.transaction(5), Some(tx));
+    assert_eq!(
+        positions(&transaction_index.query(&html_query, None, 10)),
+        vec![(1, 1), (1, 2), (4, 2), (5, 1)]
+    );
+
+    // Indexing the next block is unaffected by the journal.
+    transaction_index.push(6, Hash::repeat_byte(6), vec![]);
+    assert_eq!(transaction_index.len(), 6);
+    assert_eq!(transaction_index.tip(), Some((6, Hash::repeat_byte(6))));
+}
@@ -0,0 +1,620 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Index of the transactions by owner, block range and tag.
+//!
+//! The number of indexed transactions and the latest indexed block are written as
+//! one record, which is the last write of indexing a block. The transactions of the
+//! block being indexed are journaled before any of them is written, so that the
+//! entries left by an interrupted indexing can be rolled back on restart.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use codec::{Codec, Decode, Encode};
+use futures::StreamExt;
+
+use sc_client_api::BlockchainEvents;
+use sc_client_db::offchain::LocalStorage;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_runtime::{
+    generic::BlockId,
+    offchain::OffchainStorage,
+    traits::{Block as BlockT, NumberFor, Zero},
+};
+
+use cc_datastore::{stored_transactions, Error};
+use cp_permastore::{OrderApi, Tag, TagApi};
+
+/// Prefix of the offchain storage for the transaction index.
+const TRANSACTION_INDEX_PREFIX: &[u8] = b"permastore_indexer";
+
+/// Key of the number of indexed transactions along with the latest block that has
+/// been indexed, `(len, tip_number, tip_hash)`.
+const HEAD_KEY: &[u8] = b"head";
+
+/// Key of the transactions of the block which is being indexed.
+const PENDING_KEY: &[u8] = b"pending";
+
+/// Maximum number of transactions walked through by a single query.
+///
+/// A query combining the owner and tag may match few of the transactions it walks
+/// through, in which case a page with fewer items than requested is returned along
+/// with the cursor for resuming the query.
+pub const MAX_SCANNED_TRANSACTIONS: usize = 1024;
+
+/// Returns the storage key of the transaction with `id`.
+fn transaction_key(id: u64) -> Vec<u8> {
+    (b"tx", id).encode()
+}
+
+/// Returns the key of the list of transactions owned by `owner`.
+fn owner_list<AccountId: Encode>(owner: &AccountId) -> Vec<u8> {
+    (b"owner", owner).encode()
+}
+
+/// Returns the key of the list of transactions tagged with `tag`.
+fn tag_list(tag: &Tag) -> Vec<u8> {
+    (b"tag", tag).encode()
+}
+
+/// Returns the storage key of the length of `list`.
+fn list_len_key(list: &[u8]) -> Vec<u8> {
+    (b"list_len", list).encode()
+}
+
+/// Returns the storage key of the transaction id at `position` of `list`.
+fn list_entry_key(list: &[u8], position: u64) -> Vec<u8> {
+    (b"list_entry", list, position).encode()
+}
+
+/// A transaction storing data on the indexed chain.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct IndexedTransaction<Number, Hash, AccountId> {
+    /// Number of the block including the transaction.
+    pub block_number: Number,
+    /// Hash of the block including the transaction.
+    pub block_hash: Hash,
+    /// Index of the extrinsic in block.
+    pub extrinsic_index: u32,
+    /// Chunk root of the transaction data.
+    pub chunk_root: Hash,
+    /// Size of the transaction data.
+    pub data_size: u32,
+    /// Owner of the transaction data, `None` if it's unavailable on indexing.
+    pub owner: Option<AccountId>,
+    /// Tags of the transaction data.
+    pub tags: Vec<Tag>,
+}
+
+/// Type alias of [`IndexedTransaction`] for a specific `Block`.
+pub type IndexedTransactionFor<Block, AccountId> =
+    IndexedTransaction<NumberFor<Block>, <Block as BlockT>::Hash, AccountId>;
+
+/// Filter of the indexed transactions, all the criteria specified must be met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionQuery<Number, AccountId> {
+    /// Owner of the transaction data.
+    pub owner: Option<AccountId>,
+    /// Tag of the transaction data.
+    pub tag: Option<Tag>,
+    /// The first block of the range, inclusive.
+    pub from_block: Option<Number>,
+    /// The last block of the range, inclusive.
+    pub to_block: Option<Number>,
+}
+
+impl<Number, AccountId> Default for TransactionQuery<Number, AccountId> {
+    fn default() -> Self {
+        Self {
+            owner: None,
+            tag: None,
+            from_block: None,
+            to_block: None,
+        }
+    }
+}
+
+/// A page of the query result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    /// Items of this page in the order of inclusion.
+    pub items: Vec<T>,
+    /// Cursor of the next page, `None` if there are no more items.
+    pub next_cursor: Option<u64>,
+}
+
+/// Transaction index persisted in the offchain storage.
+#[derive(Clone)]
+pub struct TransactionIndex<Block, AccountId> {
+    offchain_storage: LocalStorage,
+    phantom: PhantomData<(Block, AccountId)>,
+}
+
+impl<Block, AccountId> TransactionIndex<Block, AccountId>
+where
+    Block: BlockT,
+    AccountId: Codec + PartialEq,
+{
+    /// Creates a new instance of [`TransactionIndex`].
+    pub fn new(offchain_storage: LocalStorage) -> Self {
+        Self {
+            offchain_storage,
+            phantom: PhantomData,
+        }
+    }
+
+    fn get<T: Decode>(&self, key: &[u8]) -> Option<T> {
+        self.offchain_storage
+            .get(TRANSACTION_INDEX_PREFIX, key)
+            .and_then(|encoded| Decode::decode(&mut encoded.as_slice()).ok())
+    }
+
+    fn set(&mut self, key: &[u8], value: &impl Encode) {
+        self.offchain_storage
+            .set(TRANSACTION_INDEX_PREFIX, key, &value.encode());
+    }
+
+    fn head(&self) -> Option<(u64, NumberFor<Block>, Block::Hash)> {
+        self.get(HEAD_KEY)
+    }
+
+    pub(crate) fn set_head(&mut self, len: u64, number: NumberFor<Block>, hash: Block::Hash) {
+        self.set(HEAD_KEY, &(len, number, hash));
+    }
+
+    /// Returns the number of indexed transactions.
+    pub fn len(&self) -> u64 {
+        self.head().map(|(len, _, _)| len).unwrap_or_default()
+    }
+
+    /// Returns `true` if no transaction has been indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number and hash of the latest indexed block.
+    pub fn tip(&self) -> Option<(NumberFor<Block>, Block::Hash)> {
+        self.head().map(|(_, number, hash)| (number, hash))
+    }
+
+    /// Returns the indexed transaction with `id`.
+    pub fn transaction(&self, id: u64) -> Option<IndexedTransactionFor<Block, AccountId>> {
+        self.get(&transaction_key(id))
+    }
+
+    fn list_len(&self, list: &[u8]) -> u64 {
+        self.get(&list_len_key(list)).unwrap_or_default()
+    }
+
+    /// Returns the id of the transaction at `position` of `list`, or the position
+    /// itself if no list is given, i.e., the list of all the transactions.
+    fn list_entry(&self, list: Option<&[u8]>, position: u64) -> Option<u64> {
+        match list {
+            Some(list) => self.get(&list_entry_key(list, position)),
+            None => Some(position),
+        }
+    }
+
+    fn push_to_list(&mut self, list: &[u8], id: u64) {
+        let len = self.list_len(list);
+        self.set(&list_entry_key(list, len), &id);
+        self.set(&list_len_key(list), &(len + 1));
+    }
+
+    /// Removes the last entry of `list` if it's `id`.
+    fn pop_from_list(&mut self, list: &[u8], id: u64) {
+        let len = self.list_len(list);
+        if let Some(last) = len.checked_sub(1) {
+            if self.list_entry(Some(list), last) == Some(id) {
+                self.offchain_storage
+                    .remove(TRANSACTION_INDEX_PREFIX, &list_entry_key(list, last));
+                if last == 0 {
+                    self.offchain_storage
+                        .remove(TRANSACTION_INDEX_PREFIX, &list_len_key(list));
+                } else {
+                    self.set(&list_len_key(list), &last);
+                }
+            }
+        }
+    }
+
+    /// Returns the chunk roots of the transactions tagged with `tag`, in the order
+    /// of inclusion.
+    pub fn chunk_roots_by_tag(&self, tag: &Tag) -> Vec<Block::Hash> {
+        let list = tag_list(tag);
+        (0..self.list_len(&list))
+            .filter_map(|position| self.list_entry(Some(&list), position))
+            .filter_map(|id| self.transaction(id))
+            .map(|tx| tx.chunk_root)
+            .collect()
+    }
+
+    /// Returns the page of transactions matching `query`, starting from `cursor`.
+    ///
+    /// At most `limit` transactions are returned, and no more than
+    /// [`MAX_SCANNED_TRANSACTIONS`] transactions are walked through.
+    pub fn query(
+        &self,
+        query: &TransactionQuery<NumberFor<Block>, AccountId>,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Page<IndexedTransactionFor<Block, AccountId>> {
+        // Walk through the shortest list implied by the query.
+        let list = match (&query.tag, &query.owner) {
+            (Some(tag), _) => Some(tag_list(tag)),
+            (None, Some(owner)) => Some(owner_list(owner)),
+            (None, None) => None,
+        };
+        let list = list.as_deref();
+
+        let len = match list {
+            Some(list) => self.list_len(list),
+            None => self.len(),
+        };
+
+        let transaction_at = |position| {
+            self.list_entry(list, position)
+                .and_then(|id| self.transaction(id))
+        };
+
+        // The transactions of a list are in the ascending order of block number.
+        let mut position = match query.from_block {
+            Some(from_block) => {
+                let (mut low, mut high) = (0u64, len);
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    match transaction_at(mid) {
+                        Some(tx) if tx.block_number < from_block => low = mid + 1,
+                        _ => high = mid,
+                    }
+                }
+                low
+            }
+            None => 0,
+        };
+        position = position.max(cursor.unwrap_or_default());
+
+        let mut items = Vec::new();
+        let mut scanned = 0;
+        while position < len && items.len() < limit && scanned < MAX_SCANNED_TRANSACTIONS {
+            let tx = match transaction_at(position) {
+                Some(tx) => tx,
+                None => break,
+            };
+
+            if matches!(query.to_block, Some(to_block) if tx.block_number > to_block) {
+                position = len;
+                break;
+            }
+
+            let owner_matched = query.tag.is_none()
+                || query
+                    .owner
+                    .as_ref()
+                    .map_or(true, |owner| tx.owner.as_ref() == Some(owner));
+            if owner_matched {
+                items.push(tx);
+            }
+
+            position += 1;
+            scanned += 1;
+        }
+
+        Page {
+            items,
+            next_cursor: if position < len { Some(position) } else { None },
+        }
+    }
+
+    /// Marks the block `(number, hash)` as indexed and appends its `transactions`.
+    pub(crate) fn push(
+        &mut self,
+        number: NumberFor<Block>,
+        hash: Block::Hash,
+        transactions: Vec<IndexedTransactionFor<Block, AccountId>>,
+    ) {
+        if !transactions.is_empty() {
+            self.stage(hash, &transactions);
+        }
+        self.commit(number, hash);
+    }
+
+    /// Writes the entries of `transactions` in block `hash` without marking them as indexed.
+    ///
+    /// The transactions are journaled first, the entries written until [`Self::commit`]
+    /// are rolled back by [`Self::recover`] if the indexing is interrupted.
+    pub(crate) fn stage(
+        &mut self,
+        hash: Block::Hash,
+        transactions: &[IndexedTransactionFor<Block, AccountId>],
+    ) {
+        self.set(PENDING_KEY, &(hash, transactions));
+        for (id, tx) in (self.len()..).zip(transactions) {
+            if let Some(ref owner) = tx.owner {
+                self.push_to_list(&owner_list(owner), id);
+            }
+            for (i, tag) in tx.tags.iter().enumerate() {
+                // The same tag may be attached to a transaction more than once.
+                if !tx.tags[..i].contains(tag) {
+                    self.push_to_list(&tag_list(tag), id);
+                }
+            }
+            self.set(&transaction_key(id), tx);
+        }
+    }
+
+    /// Marks the block `(number, hash)` as indexed, along with the staged transactions if any.
+    pub(crate) fn commit(&mut self, number: NumberFor<Block>, hash: Block::Hash) {
+        let staged = self
+            .pending()
+            .map(|(_, transactions)| transactions.len() as u64)
+            .unwrap_or_default();
+        self.set_head(self.len() + staged, number, hash);
+        self.offchain_storage
+            .remove(TRANSACTION_INDEX_PREFIX, PENDING_KEY);
+    }
+
+    fn pending(&self) -> Option<(Block::Hash, Vec<IndexedTransactionFor<Block, AccountId>>)> {
+        self.get(PENDING_KEY)
+    }
+
+    /// Rolls back the entries of the block whose indexing was interrupted, if any.
+    pub(crate) fn recover(&mut self) {
+        let (hash, transactions) = match self.pending() {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        // Nothing to roll back if the head has been written.
+        if self.tip().map(|(_, tip_hash)| tip_hash) != Some(hash) {
+            log::debug!(
+                target: "indexer",
+                "Rolling back the interrupted indexing of block {:?}",
+                hash,
+            );
+            let len = self.len();
+            for (offset, tx) in transactions.iter().enumerate().rev() {
+                self.remove_transaction(len + offset as u64, tx);
+            }
+        }
+
+        self.offchain_storage
+            .remove(TRANSACTION_INDEX_PREFIX, PENDING_KEY);
+    }
+
+    /// Reverts all the indexed transactions after the block `(number, hash)`.
+    ///
+    /// The reverted transactions stay readable until the head is written, so that an
+    /// interrupted revert is simply done again on restart.
+    pub(crate) fn revert_to(&mut self, number: NumberFor<Block>, hash: Block::Hash) {
+        let old_len = self.len();
+        let mut len = old_len;
+        while let Some(last) = len.checked_sub(1) {
+            match self.transaction(last) {
+                Some(tx) if tx.block_number > number => {
+                    self.pop_from_lists(last, &tx);
+                    len = last;
+                }
+                _ => break,
+            }
+        }
+        self.set_head(len, number, hash);
+        for id in len..old_len {
+            self.offchain_storage
+                .remove(TRANSACTION_INDEX_PREFIX, &transaction_key(id));
+        }
+    }
+
+    /// Removes the transaction `tx` with `id` from the lists it belongs to.
+    fn pop_from_lists(&mut self, id: u64, tx: &IndexedTransactionFor<Block, AccountId>) {
+        if let Some(ref owner) = tx.owner {
+            self.pop_from_list(&owner_list(owner), id);
+        }
+        for tag in &tx.tags {
+            self.pop_from_list(&tag_list(tag), id);
+        }
+    }
+
+    /// Removes the transaction `tx` with `id` along with its list entries.
+    fn remove_transaction(&mut self, id: u64, tx: &IndexedTransactionFor<Block, AccountId>) {
+        self.pop_from_lists(id, tx);
+        self.offchain_storage
+            .remove(TRANSACTION_INDEX_PREFIX, &transaction_key(id));
+    }
+}
+
+#[cfg(any(feature = "test-helpers", test))]
+impl<Block, AccountId> TransactionIndex<Block, AccountId>
+where
+    Block: BlockT,
+    AccountId: Codec + PartialEq,
+{
+    /// Indexes the `transactions` of block `(number, hash)`, for testing only.
+    pub fn push_for_test(
+        &mut self,
+        number: NumberFor<Block>,
+        hash: Block::Hash,
+        transactions: Vec<IndexedTransactionFor<Block, AccountId>>,
+    ) {
+        self.push(number, hash, transactions);
+    }
+}
+
+/// Worker for keeping the [`TransactionIndex`] in sync with the best chain.
+pub struct TransactionIndexer<Block, C, AccountId, Balance> {
+    client: Arc<C>,
+    transaction_index: TransactionIndex<Block, AccountId>,
+    phantom: PhantomData<Balance>,
+}
+
+impl<Block, C, AccountId, Balance> TransactionIndexer<Block, C, AccountId, Balance>
+where
+    Block: BlockT,
+    C: BlockchainEvents<Block>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync,
+    C::Api: OrderApi<Block, AccountId, NumberFor<Block>, u32, Balance> + TagApi<Block, u32>,
+    AccountId: Codec + PartialEq,
+    Balance: Codec,
+{
+    /// Creates a new instance of [`TransactionIndexer`].
+    pub fn new(client: Arc<C>, offchain_storage: LocalStorage) -> Self {
+        Self {
+            client,
+            transaction_index: TransactionIndex::new(offchain_storage),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Updates the transaction index to the new best block `best_hash`.
+    ///
+    /// The indexed blocks which are not on the new best chain are reverted first.
+    pub fn update_to(&mut self, best_hash: Block::Hash) -> Result<(), Error<Block>> {
+        self.transaction_index.recover();
+
+        let (tip_number, tip_hash) = match self.transaction_index.tip() {
+            Some(tip) => tip,
+            None => {
+                let genesis_hash = self
+                    .client
+                    .hash(Zero::zero())
+                    .map_err(Box::new)?
+                    .ok_or_else(|| Error::BlockNumberNotFound(BlockId::Number(Zero::zero())))?;
+                (Zero::zero(), genesis_hash)
+            }
+        };
+
+        if tip_hash == best_hash {
+            return Ok(());
+        }
+
+        let tree_route =
+            sp_blockchain::tree_route(&*self.client, tip_hash, best_hash).map_err(Box::new)?;
+
+        let common_block = tree_route.common_block();
+        if common_block.hash != tip_hash {
+            log::debug!(
+                target: "indexer",
+                "Reverting the transaction index from #{} to #{} due to reorg",
+                tip_number,
+                common_block.number,
+            );
+            self.transaction_index
+                .revert_to(common_block.number, common_block.hash);
+        }
+
+        for block in tree_route.enacted() {
+            let transactions = self.fetch_transactions(block.number, block.hash)?;
+            self.transaction_index
+                .push(block.number, block.hash, transactions);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the transactions storing data in block `(number, hash)`.
+    ///
+    /// The owners and tags are left empty if they are unavailable, e.g., the state
+    /// of block has been pruned.
+    fn fetch_transactions(
+        &self,
+        number: NumberFor<Block>,
+        hash: Block::Hash,
+    ) -> Result<Vec<IndexedTransactionFor<Block, AccountId>>, Error<Block>> {
+        let at = BlockId::Hash(hash);
+
+        let header = self
+            .client
+            .header(at)
+            .map_err(Box::new)?
+            .ok_or(Error::HeaderNotFound(at))?;
+
+        let stored = stored_transactions(&header)?;
+
+        if stored.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let api = self.client.runtime_api();
+
+        let mut block_tags = api.block_tags(&at).unwrap_or_else(|e| {
+            log::debug!(
+                target: "indexer",
+                "Tags of block #{} are unavailable: {:?}",
+                number,
+                e,
+            );
+            Vec::new()
+        });
+
+        Ok(stored
+            .into_iter()
+            .map(|tx| {
+                let owner = api
+                    .order(&at, number, tx.extrinsic_index)
+                    .ok()
+                    .flatten()
+                    .map(|(owner, _fee)| owner);
+                let tags = block_tags
+                    .iter()
+                    .position(|(extrinsic_index, _)| *extrinsic_index == tx.extrinsic_index)
+                    .map(|i| block_tags.swap_remove(i).1)
+                    .unwrap_or_default();
+                IndexedTransaction {
+                    block_number: number,
+                    block_hash: hash,
+                    extrinsic_index: tx.extrinsic_index,
+                    chunk_root: tx.chunk_root,
+                    data_size: tx.data_size,
+                    owner,
+                    tags,
+                }
+            })
+            .collect())
+    }
+
+    fn update(&mut self, best_hash: Block::Hash) {
+        if let Err(e) = self.update_to(best_hash) {
+            log::error!(
+                target: "indexer",
+                "Failed to update the transaction index to {:?}: {:?}",
+                best_hash,
+                e,
+            );
+        }
+    }
+
+    /// Runs the worker, which indexes every new best block.
+    pub async fn run(mut self) {
+        let mut import_notification_stream = self.client.import_notification_stream();
+
+        // Catch up with the current best chain.
+        self.update(self.client.info().best_hash);
+
+        while let Some(notification) = import_notification_stream.next().await {
+            if notification.is_new_best {
+                self.update(notification.hash);
+            }
+        }
+    }
+}
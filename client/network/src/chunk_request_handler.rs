@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Handler of the incoming chunk requests.
+
+use std::collections::VecDeque;
+
+use codec::{Decode, Encode};
+use futures::{channel::mpsc, StreamExt};
+
+use sc_network::{
+    config::{IncomingRequest, OutgoingResponse, ProtocolId, RequestResponseConfig},
+    ReputationChange,
+};
+use sp_core::H256;
+
+use cc_consensus_poa::ChunkTrie;
+use cp_permastore::PermaStorage;
+
+use crate::{generate_protocol_config, ChunkRequest, ChunkResponse};
+
+/// Maximum number of the pending chunk requests.
+const MAX_CHUNK_REQUEST_QUEUE: usize = 20;
+
+/// Maximum number of the chunk tries kept in [`ChunkTrieCache`].
+const MAX_CACHED_CHUNK_TRIES: usize = 16;
+
+/// Reputation change of a peer sending a malformed request.
+const BAD_REQUEST: ReputationChange = ReputationChange::new(-(1 << 12), "Bad chunk request");
+
+/// Chunk tries of the most recently requested transaction data.
+///
+/// A peer syncing the transaction data requests its chunks one after another, the
+/// trie is kept so that it's not rebuilt from all the chunk hashes on every request.
+#[derive(Default)]
+pub(crate) struct ChunkTrieCache {
+    /// Chunk tries in the order of recent use, the most recent one first.
+    tries: VecDeque<ChunkTrie>,
+}
+
+impl ChunkTrieCache {
+    /// Returns the trie of `chunk_root`, which is built from `chunk_hashes` if absent.
+    fn get_or_build(
+        &mut self,
+        chunk_root: H256,
+        chunk_hashes: impl FnOnce() -> Vec<H256>,
+    ) -> &ChunkTrie {
+        match self
+            .tries
+            .iter()
+            .position(|trie| trie.chunk_root() == chunk_root)
+        {
+            Some(position) => {
+                let trie = self
+                    .tries
+                    .remove(position)
+                    .expect("Position is within the cache; qed");
+                self.tries.push_front(trie);
+            }
+            None => {
+                if self.tries.len() >= MAX_CACHED_CHUNK_TRIES {
+                    self.tries.pop_back();
+                }
+                self.tries.push_front(ChunkTrie::new(&chunk_hashes()));
+            }
+        }
+        &self.tries[0]
+    }
+
+    /// Returns the number of the cached tries.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.tries.len()
+    }
+}
+
+/// Returns the encoded response to the encoded chunk `request`.
+///
+/// The chunk trie of requested data is taken from `cache`, or built and cached.
+pub(crate) fn handle_request<T: PermaStorage>(
+    storage: &T,
+    cache: &mut ChunkTrieCache,
+    mut request: &[u8],
+) -> Result<Vec<u8>, codec::Error> {
+    let ChunkRequest {
+        chunk_root,
+        chunk_index,
+    } = ChunkRequest::decode(&mut request)?;
+
+    let key = chunk_root.encode();
+
+    let response = storage
+        .data_meta(&key)
+        .filter(|data_meta| chunk_index < data_meta.chunk_count())
+        .and_then(|data_meta| {
+            let chunk = storage.retrieve_chunk(&key, chunk_index)?;
+            let chunk_trie = cache.get_or_build(chunk_root, || data_meta.chunk_hashes);
+            match chunk_trie.prove(chunk, chunk_index) {
+                Ok(chunk_proof) => Some(ChunkResponse {
+                    chunk: chunk_proof.chunk,
+                    proof: chunk_proof.proof,
+                }),
+                Err(e) => {
+                    log::error!(
+                        target: "sync::permastore",
+                        "Failed to build the proof of chunk #{} of {:?}: {:?}",
+                        chunk_index,
+                        chunk_root,
+                        e,
+                    );
+                    None
+                }
+            }
+        });
+
+    Ok(response.encode())
+}
+
+/// Handler for the incoming chunk requests from peers.
+pub struct ChunkRequestHandler<T> {
+    storage: T,
+    chunk_tries: ChunkTrieCache,
+    request_receiver: mpsc::Receiver<IncomingRequest>,
+}
+
+impl<T: PermaStorage> ChunkRequestHandler<T> {
+    /// Creates a new instance of [`ChunkRequestHandler`] serving the chunks in `storage`,
+    /// along with the protocol config to be registered in the network.
+    pub fn new(protocol_id: &ProtocolId, storage: T) -> (Self, RequestResponseConfig) {
+        let (tx, request_receiver) = mpsc::channel(MAX_CHUNK_REQUEST_QUEUE);
+
+        let mut protocol_config = generate_protocol_config(protocol_id);
+        protocol_config.inbound_queue = Some(tx);
+
+        (
+            Self {
+                storage,
+                chunk_tries: ChunkTrieCache::default(),
+                request_receiver,
+            },
+            protocol_config,
+        )
+    }
+
+    /// Runs the handler, which serves the incoming requests one by one.
+    pub async fn run(mut self) {
+        while let Some(request) = self.request_receiver.next().await {
+            let IncomingRequest {
+                peer,
+                payload,
+                pending_response,
+            } = request;
+
+            let (result, reputation_changes) =
+                match handle_request(&self.storage, &mut self.chunk_tries, &payload) {
+                    Ok(response) => (Ok(response), Vec::new()),
+                    Err(e) => {
+                        log::debug!(
+                            target: "sync::permastore",
+                            "Invalid chunk request from {}: {:?}",
+                            peer,
+                            e,
+                        );
+                        (Err(()), vec![BAD_REQUEST])
+                    }
+                };
+
+            let response = OutgoingResponse {
+                result,
+                reputation_changes,
+                sent_feedback: None,
+            };
+
+            if pending_response.send(response).is_err() {
+                log::debug!(
+                    target: "sync::permastore",
+                    "Failed to respond the chunk request of {}, the request was dropped",
+                    peer,
+                );
+            }
+        }
+    }
+}
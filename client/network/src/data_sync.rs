@@ -0,0 +1,348 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Background task filling the gaps of transaction data in the local storage.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use codec::{Decode, Encode};
+use futures::{future::BoxFuture, FutureExt, Stream, StreamExt};
+use futures_timer::Delay;
+
+use sc_network::{
+    config::ProtocolId, Event, ExHashT, IfDisconnected, NetworkService, PeerId, ReputationChange,
+    RequestFailure,
+};
+use sp_core::H256;
+use sp_runtime::traits::Block as BlockT;
+
+use cc_datastore::WeaveIndex;
+use cp_permastore::{chunk_tree, PermaStorage, UploadSession};
+
+use crate::{protocol_name, ChunkRequest, ChunkResponse};
+
+/// Interval between the sync rounds.
+const SYNC_INTERVAL: Duration = Duration::from_secs(6);
+
+/// Maximum number of transaction data synced in a round.
+const MAX_TRANSACTIONS_PER_ROUND: usize = 4;
+
+/// Reputation change of a peer responding an invalid chunk.
+const INVALID_CHUNK: ReputationChange = ReputationChange::new(-(1 << 12), "Invalid chunk");
+
+/// Network for requesting the chunks from peers.
+pub trait Network: Send + Sync {
+    /// Returns a stream of the network events.
+    fn event_stream(&self) -> Pin<Box<dyn Stream<Item = Event> + Send>>;
+
+    /// Sends `request` to `peer` over `protocol`.
+    fn request(
+        &self,
+        peer: PeerId,
+        protocol: Cow<'static, str>,
+        request: Vec<u8>,
+    ) -> BoxFuture<'static, Result<Vec<u8>, RequestFailure>>;
+
+    /// Adjusts the reputation of `peer`.
+    fn report_peer(&self, peer: PeerId, cost: ReputationChange);
+}
+
+impl<B: BlockT, H: ExHashT> Network for Arc<NetworkService<B, H>> {
+    fn event_stream(&self) -> Pin<Box<dyn Stream<Item = Event> + Send>> {
+        Box::pin(NetworkService::event_stream(self, "permastore-data-sync"))
+    }
+
+    fn request(
+        &self,
+        peer: PeerId,
+        protocol: Cow<'static, str>,
+        request: Vec<u8>,
+    ) -> BoxFuture<'static, Result<Vec<u8>, RequestFailure>> {
+        let service = self.clone();
+        async move {
+            NetworkService::request(
+                &service,
+                peer,
+                protocol,
+                request,
+                IfDisconnected::ImmediateError,
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn report_peer(&self, peer: PeerId, cost: ReputationChange) {
+        NetworkService::report_peer(self, peer, cost);
+    }
+}
+
+/// Worker for fetching the transaction data on the best chain missing locally
+/// from the connected peers.
+pub struct DataSync<B: BlockT, T, N> {
+    storage: T,
+    weave_index: WeaveIndex<B>,
+    network: N,
+    protocol_name: Cow<'static, str>,
+    /// Connected peers.
+    peers: Vec<PeerId>,
+    /// Index of the peer to send the next request to.
+    next_peer: usize,
+    /// Index and hash of the last weave block that has been scanned.
+    last_scanned: Option<(u32, B::Hash)>,
+    /// Transaction data missing locally, `(chunk_root, data_size)`.
+    missing: VecDeque<(H256, u32)>,
+}
+
+impl<B, T, N> DataSync<B, T, N>
+where
+    B: BlockT<Hash = H256>,
+    T: PermaStorage,
+    N: Network,
+{
+    /// Creates a new instance of [`DataSync`].
+    pub fn new(
+        protocol_id: &ProtocolId,
+        storage: T,
+        weave_index: WeaveIndex<B>,
+        network: N,
+    ) -> Self {
+        Self {
+            storage,
+            weave_index,
+            network,
+            protocol_name: protocol_name(protocol_id).into(),
+            peers: Vec::new(),
+            next_peer: 0,
+            last_scanned: None,
+            missing: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of transaction data known to be missing locally.
+    pub fn missing(&self) -> usize {
+        self.missing.len()
+    }
+
+    pub(crate) fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::SyncConnected { remote } => {
+                if !self.peers.contains(&remote) {
+                    self.peers.push(remote);
+                }
+            }
+            Event::SyncDisconnected { remote } => {
+                self.peers.retain(|peer| *peer != remote);
+            }
+            _ => {}
+        }
+    }
+
+    /// Enqueues the missing transaction data of the weave blocks indexed since
+    /// the last scan.
+    fn scan(&mut self) {
+        let len = self.weave_index.len();
+
+        // Rescan the entire weave if the last scanned block has been reverted.
+        let start = match self.last_scanned {
+            Some((index, hash))
+                if self
+                    .weave_index
+                    .weave_block(index)
+                    .map_or(false, |weave_block| weave_block.block_hash == hash) =>
+            {
+                index + 1
+            }
+            Some(_) => {
+                self.missing.clear();
+                0
+            }
+            None => 0,
+        };
+
+        for index in start..len {
+            let weave_block = match self.weave_index.weave_block(index) {
+                Some(weave_block) => weave_block,
+                None => break,
+            };
+            for tx in &weave_block.transactions {
+                if !self.storage.exists(&tx.chunk_root.encode()) {
+                    self.missing.push_back((tx.chunk_root, tx.data_size));
+                }
+            }
+            self.last_scanned = Some((index, weave_block.block_hash));
+        }
+    }
+
+    /// Requests the chunk at `chunk_index` of `(chunk_root, data_size)` from the peers
+    /// in turn until a valid one is received.
+    async fn request_chunk(
+        &mut self,
+        chunk_root: H256,
+        data_size: u32,
+        chunk_index: u32,
+    ) -> Option<Vec<u8>> {
+        let expected_size = chunk_tree::chunk_size(data_size, chunk_index)?;
+
+        let request = ChunkRequest {
+            chunk_root,
+            chunk_index,
+        }
+        .encode();
+
+        for _ in 0..self.peers.len() {
+            let peer = self.peers[self.next_peer % self.peers.len()];
+            self.next_peer = self.next_peer.wrapping_add(1);
+
+            let response = match self
+                .network
+                .request(peer, self.protocol_name.clone(), request.clone())
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    log::debug!(
+                        target: "sync::permastore",
+                        "Chunk request to {} failed: {:?}",
+                        peer,
+                        e,
+                    );
+                    continue;
+                }
+            };
+
+            match Option::<ChunkResponse>::decode(&mut response.as_slice()) {
+                Ok(Some(ChunkResponse { chunk, proof })) => {
+                    if chunk.len() as u32 == expected_size
+                        && chunk_tree::verify_chunk(&chunk_root, chunk_index, &chunk, &proof)
+                            .is_ok()
+                    {
+                        return Some(chunk);
+                    }
+                    log::debug!(
+                        target: "sync::permastore",
+                        "Invalid chunk #{} of {:?} from {}",
+                        chunk_index,
+                        chunk_root,
+                        peer,
+                    );
+                    self.network.report_peer(peer, INVALID_CHUNK);
+                }
+                Ok(None) => {}
+                Err(_) => self.network.report_peer(peer, INVALID_CHUNK),
+            }
+        }
+
+        None
+    }
+
+    /// Fetches the missing chunks of `(chunk_root, data_size)`.
+    ///
+    /// Returns `true` if the transaction data exists locally afterwards.
+    async fn fetch(&mut self, chunk_root: H256, data_size: u32) -> bool {
+        let key = chunk_root.encode();
+
+        if self.storage.exists(&key) {
+            return true;
+        }
+
+        let mut upload_session = self
+            .storage
+            .upload_session(&key)
+            .filter(|upload_session| upload_session.data_size == data_size)
+            .unwrap_or_else(|| UploadSession::new(data_size));
+
+        for chunk_index in upload_session.missing_chunks() {
+            match self.request_chunk(chunk_root, data_size, chunk_index).await {
+                Some(chunk) => {
+                    self.storage.submit_chunk(&key, chunk_index, &chunk);
+                    upload_session.uploaded[chunk_index as usize] = true;
+                }
+                None => break,
+            }
+        }
+
+        self.storage.set_upload_session(&key, &upload_session);
+
+        if !upload_session.is_complete() {
+            return false;
+        }
+
+        match self.storage.finish_upload(&key) {
+            Some(data_meta)
+                if chunk_tree::chunk_root_from_hashes(&data_meta.chunk_hashes) == chunk_root =>
+            {
+                log::debug!(
+                    target: "sync::permastore",
+                    "Synced the transaction data of {:?}, data_size: {}",
+                    chunk_root,
+                    data_size,
+                );
+                true
+            }
+            Some(_) => {
+                self.storage.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Runs a round of sync.
+    pub(crate) async fn sync_once(&mut self) {
+        self.scan();
+
+        if self.peers.is_empty() {
+            return;
+        }
+
+        for _ in 0..MAX_TRANSACTIONS_PER_ROUND.min(self.missing.len()) {
+            let (chunk_root, data_size) = match self.missing.pop_front() {
+                Some(missing) => missing,
+                None => break,
+            };
+            if !self.fetch(chunk_root, data_size).await {
+                // Retry later, the data may become available on the peers.
+                self.missing.push_back((chunk_root, data_size));
+            }
+        }
+    }
+
+    /// Runs the worker, which syncs the missing transaction data periodically.
+    pub async fn run(mut self) {
+        let mut event_stream = self.network.event_stream().fuse();
+        let mut delay = Delay::new(SYNC_INTERVAL).fuse();
+
+        loop {
+            futures::select! {
+                event = event_stream.next() => match event {
+                    Some(event) => self.handle_event(event),
+                    None => return,
+                },
+                _ = delay => {
+                    self.sync_once().await;
+                    delay = Delay::new(SYNC_INTERVAL).fuse();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+#![deny(missing_docs, unused_extern_crates)]
+
+//! This crate provides the replication of transaction data between nodes.
+//!
+//! The transaction data only exists on the node it was submitted to initially, the
+//! other nodes obtain it chunk by chunk over a dedicated request-response protocol:
+//!
+//! - [`ChunkRequestHandler`] serves the [`ChunkRequest`]s of peers from the local
+//!   storage, each chunk is responded along with its chunk proof.
+//! - [`DataSync`] looks for the transaction data on the best chain missing in the
+//!   local storage and requests the chunks from the connected peers. Each chunk is
+//!   verified against the chunk root recorded on chain before being persisted, the
+//!   progress is tracked by the same [`UploadSession`](cp_permastore::UploadSession)
+//!   as the chunked upload via RPC.
+
+mod chunk_request_handler;
+mod data_sync;
+#[cfg(test)]
+mod tests;
+
+pub use self::chunk_request_handler::ChunkRequestHandler;
+pub use self::data_sync::{DataSync, Network};
+
+use std::time::Duration;
+
+use codec::{Decode, Encode};
+
+use sc_network::config::{ProtocolId, RequestResponseConfig};
+use sp_core::H256;
+
+use cp_permastore::CHUNK_SIZE;
+
+/// Maximum byte size of a chunk request.
+const MAX_REQUEST_SIZE: u64 = 1024;
+
+/// Maximum byte size of a chunk response, i.e., a chunk along with its proof.
+const MAX_RESPONSE_SIZE: u64 = CHUNK_SIZE as u64 + 1024 * 1024;
+
+/// Timeout of a chunk request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Request for a chunk of the transaction data.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct ChunkRequest {
+    /// Chunk root of the transaction data.
+    pub chunk_root: H256,
+    /// Index of the requested chunk.
+    pub chunk_index: u32,
+}
+
+/// A chunk along with its proof, the response is `Option<ChunkResponse>`,
+/// `None` if the chunk is unavailable.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct ChunkResponse {
+    /// Raw bytes of the chunk.
+    pub chunk: Vec<u8>,
+    /// Merkle proof of the chunk against the chunk root.
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Returns the name of the chunk request protocol given `protocol_id`.
+pub fn protocol_name(protocol_id: &ProtocolId) -> String {
+    format!("/{}/permastore/chunk/1", protocol_id.as_ref())
+}
+
+/// Generates a [`RequestResponseConfig`] for the chunk request protocol,
+/// refusing incoming requests.
+pub fn generate_protocol_config(protocol_id: &ProtocolId) -> RequestResponseConfig {
+    RequestResponseConfig {
+        name: protocol_name(protocol_id).into(),
+        max_request_size: MAX_REQUEST_SIZE,
+        max_response_size: MAX_RESPONSE_SIZE,
+        request_timeout: REQUEST_TIMEOUT,
+        inbound_queue: None,
+    }
+}
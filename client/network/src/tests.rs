@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use codec::{Decode, Encode};
+use futures::{executor::block_on, future::BoxFuture, FutureExt, Stream};
+
+use sc_client_db::offchain::LocalStorage;
+use sc_network::{config::ProtocolId, Event, PeerId, ReputationChange, RequestFailure};
+use sp_core::H256;
+use substrate_test_runtime_client::{runtime::Block, Backend, Client};
+
+use cc_datastore::{PermanentStorage, WeaveBlock, WeaveIndex};
+use cp_permastore::{chunk_tree, PermaStorage, StoredTransaction, CHUNK_SIZE};
+
+use crate::{
+    chunk_request_handler::{handle_request, ChunkTrieCache},
+    ChunkRequest, ChunkResponse, DataSync, Network,
+};
+
+type TestStorage = PermanentStorage<Client<Backend>>;
+
+/// Test data spanning 3 chunks.
+fn test_data() -> Vec<u8> {
+    (0..CHUNK_SIZE * 2 + 5).map(|i| (i % 251) as u8).collect()
+}
+
+fn new_storage() -> TestStorage {
+    PermanentStorage::new_test(Arc::new(substrate_test_runtime_client::new()))
+}
+
+fn request(chunk_root: H256, chunk_index: u32) -> Vec<u8> {
+    ChunkRequest {
+        chunk_root,
+        chunk_index,
+    }
+    .encode()
+}
+
+enum Peer {
+    /// Serves the chunks in its storage.
+    Honest(TestStorage),
+    /// Responds an invalid chunk to every request.
+    Malicious,
+}
+
+struct TestNetwork {
+    peers: Vec<(PeerId, Peer)>,
+    reported: Arc<Mutex<Vec<PeerId>>>,
+}
+
+impl Network for TestNetwork {
+    fn event_stream(&self) -> Pin<Box<dyn Stream<Item = Event> + Send>> {
+        Box::pin(futures::stream::pending())
+    }
+
+    fn request(
+        &self,
+        peer: PeerId,
+        _protocol: Cow<'static, str>,
+        request: Vec<u8>,
+    ) -> BoxFuture<'static, Result<Vec<u8>, RequestFailure>> {
+        let response = match self.peers.iter().find(|(id, _)| *id == peer) {
+            Some((_, Peer::Honest(storage))) => {
+                handle_request(storage, &mut ChunkTrieCache::default(), &request)
+                    .map_err(|_| RequestFailure::Refused)
+            }
+            Some((_, Peer::Malicious)) => Ok(Some(ChunkResponse {
+                chunk: vec![0u8; CHUNK_SIZE as usize],
+                proof: Vec::new(),
+            })
+            .encode()),
+            None => Err(RequestFailure::NotConnected),
+        };
+        async move { response }.boxed()
+    }
+
+    fn report_peer(&self, peer: PeerId, _cost: ReputationChange) {
+        self.reported.lock().unwrap().push(peer);
+    }
+}
+
+/// Creates a [`DataSync`] connected to `peers`, with the weave index of `data` only.
+///
+/// Returns the local storage and the peers reported along with the [`DataSync`].
+fn data_sync_with(
+    data: &[u8],
+    peers: Vec<(PeerId, Peer)>,
+) -> (
+    DataSync<Block, TestStorage, TestNetwork>,
+    TestStorage,
+    Arc<Mutex<Vec<PeerId>>>,
+) {
+    let mut weave_index = WeaveIndex::<Block>::new(LocalStorage::new_test());
+    weave_index.push_for_test(
+        WeaveBlock {
+            block_number: 1,
+            block_hash: H256::repeat_byte(1),
+            weave_base: 0,
+            transactions: vec![StoredTransaction {
+                extrinsic_index: 1,
+                chunk_root: chunk_tree::chunk_root(data),
+                data_size: data.len() as u32,
+            }],
+        },
+        &[],
+    );
+
+    let peer_ids = peers.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+    let storage = new_storage();
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let mut data_sync = DataSync::new(
+        &ProtocolId::from("test"),
+        storage.clone(),
+        weave_index,
+        TestNetwork {
+            peers,
+            reported: reported.clone(),
+        },
+    );
+    for remote in peer_ids {
+        data_sync.handle_event(Event::SyncConnected { remote });
+    }
+
+    (data_sync, storage, reported)
+}
+
+#[test]
+fn handle_request_should_respond_chunk_with_proof() {
+    let data = test_data();
+    let chunk_root = chunk_tree::chunk_root(&data);
+    let mut storage = new_storage();
+    storage.submit(&chunk_root.encode(), &data);
+    let mut cache = ChunkTrieCache::default();
+
+    for (chunk_index, expected) in chunk_tree::chunks(&data).enumerate() {
+        let response = handle_request(
+            &storage,
+            &mut cache,
+            &request(chunk_root, chunk_index as u32),
+        )
+        .unwrap();
+        let ChunkResponse { chunk, proof } =
+            Option::<ChunkResponse>::decode(&mut response.as_slice())
+                .unwrap()
+                .unwrap();
+        assert_eq!(chunk, expected);
+        assert!(chunk_tree::verify_chunk(&chunk_root, chunk_index as u32, &chunk, &proof).is_ok());
+    }
+
+    // The trie is built once for all the chunks.
+    assert_eq!(cache.len(), 1);
+
+    let mut unavailable = |request: Vec<u8>| {
+        let response = handle_request(&storage, &mut cache, &request).unwrap();
+        Option::<ChunkResponse>::decode(&mut response.as_slice())
+            .unwrap()
+            .is_none()
+    };
+    assert!(unavailable(request(chunk_root, 3)));
+    assert!(unavailable(request(H256::zero(), 0)));
+    assert_eq!(cache.len(), 1);
+
+    assert!(handle_request(&storage, &mut cache, &[1, 2, 3]).is_err());
+}
+
+#[test]
+fn chunk_trie_cache_should_be_bounded() {
+    let mut storage = new_storage();
+    let mut cache = ChunkTrieCache::default();
+
+    for i in 0..20u8 {
+        let data = vec![i; 10];
+        let chunk_root = chunk_tree::chunk_root(&data);
+        storage.submit(&chunk_root.encode(), &data);
+        handle_request(&storage, &mut cache, &request(chunk_root, 0)).unwrap();
+    }
+
+    assert_eq!(cache.len(), 16);
+}
+
+#[test]
+fn data_sync_should_fetch_missing_data_from_peers() {
+    let data = test_data();
+    let chunk_root = chunk_tree::chunk_root(&data);
+    let mut peer_storage = new_storage();
+    peer_storage.submit(&chunk_root.encode(), &data);
+
+    let malicious = PeerId::random();
+    let (mut data_sync, storage, reported) = data_sync_with(
+        &data,
+        vec![
+            (malicious, Peer::Malicious),
+            (PeerId::random(), Peer::Honest(peer_storage)),
+        ],
+    );
+    assert!(!storage.exists(&chunk_root.encode()));
+
+    block_on(data_sync.sync_once());
+
+    assert_eq!(data_sync.missing(), 0);
+    assert_eq!(storage.retrieve(&chunk_root.encode()), Some(data));
+    assert!(storage.upload_session(&chunk_root.encode()).is_none());
+
+    // The invalid chunks are rejected and the malicious peer is reported.
+    let reported = reported.lock().unwrap().clone();
+    assert!(!reported.is_empty());
+    assert!(reported.iter().all(|peer| *peer == malicious));
+}
+
+#[test]
+fn data_sync_should_resume_once_data_is_available() {
+    let data = test_data();
+    let chunk_root = chunk_tree::chunk_root(&data);
+    let key = chunk_root.encode();
+
+    // The peer only has the first chunk.
+    let mut peer_storage = new_storage();
+    let first_chunk = chunk_tree::chunks(&data).next().unwrap();
+    let mut upload_session = cp_permastore::UploadSession::new(data.len() as u32);
+    peer_storage.submit_chunk(&key, 0, first_chunk);
+    upload_session.uploaded[0] = true;
+    peer_storage.set_upload_session(&key, &upload_session);
+
+    let (mut data_sync, storage, _) = data_sync_with(
+        &data,
+        vec![(PeerId::random(), Peer::Honest(peer_storage.clone()))],
+    );
+
+    // Chunks are served only once the peer has the entire data.
+    block_on(data_sync.sync_once());
+    assert_eq!(data_sync.missing(), 1);
+    assert!(!storage.exists(&key));
+
+    peer_storage.submit(&key, &data);
+
+    block_on(data_sync.sync_once());
+    assert_eq!(data_sync.missing(), 0);
+    assert_eq!(storage.retrieve(&key), Some(data));
+}
+
+#[test]
+fn data_sync_without_peers_should_keep_missing_data() {
+    let data = test_data();
+    let (mut data_sync, storage, _) = data_sync_with(&data, vec![]);
+
+    block_on(data_sync.sync_once());
+    block_on(data_sync.sync_once());
+
+    // The weave is scanned only once.
+    assert_eq!(data_sync.missing(), 1);
+    assert!(!storage.exists(&chunk_tree::chunk_root(&data).encode()));
+}
@@ -58,6 +58,32 @@ pub enum Error {
     DataSizeTooLarge,
     #[error("invalid proof: ")]
     InvalidProof,
+    #[error("extrinsic is not a permastore store call")]
+    NotStoreCall,
+    #[error("upload not found")]
+    UploadNotFound,
+    #[error("upload is incomplete, {0} chunks missing")]
+    UploadIncomplete(u32),
+    #[error("upload has begun with data size {0}")]
+    UploadDataSizeMismatch(u32),
+    #[error("range is out of bounds, data size: {0}")]
+    RangeOutOfBounds(u32),
+    #[error("failed to build chunk proof: {0}")]
+    ChunkProof(String),
+    #[error("client error: {0}")]
+    Client(Box<dyn std::error::Error + Send>),
+    #[error("page is too large. {}", _0)]
+    PageTooLarge(InvalidCount),
+    #[error("invalid bundle: {0}")]
+    InvalidBundle(String),
+    #[error("store call is not signed")]
+    UnsignedStoreCall,
+    #[error("store call is invalid: {0}")]
+    InvalidStoreCall(String),
+    #[error("pending data quota exceeded, max: {0} bytes")]
+    PendingDataQuotaExceeded(u64),
+    #[error("submission rate limit exceeded, max: {0} bytes per {1} seconds")]
+    RateLimitExceeded(u64, u64),
     #[error("authoring api: {0}")]
     AuthoringApiError(#[from] sc_rpc_api::author::error::Error),
     /// Call to an unsafe RPC was denied.
@@ -110,6 +136,74 @@ impl From<Error> for rpc::Error {
                 message: e.to_string(),
                 data: None,
             },
+            Error::NotStoreCall => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 8),
+                message: "extrinsic is not a permastore store call".into(),
+                data: None,
+            },
+            Error::UploadNotFound => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 9),
+                message: "upload not found".into(),
+                data: Some("the upload has to be begun first via permastore_beginUpload.".into()),
+            },
+            Error::UploadIncomplete(missing) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 10),
+                message: format!("upload is incomplete, {} chunks missing", missing),
+                data: None,
+            },
+            Error::UploadDataSizeMismatch(data_size) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 11),
+                message: format!("upload has begun with data size {}", data_size),
+                data: None,
+            },
+            Error::RangeOutOfBounds(data_size) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 12),
+                message: format!("range is out of bounds, data size: {}", data_size),
+                data: None,
+            },
+            Error::ChunkProof(e) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 13),
+                message: format!("failed to build chunk proof: {}", e),
+                data: None,
+            },
+            Error::Client(e) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 14),
+                message: format!("client error: {}", e),
+                data: None,
+            },
+            Error::PageTooLarge(invalid_count) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 15),
+                message: format!("page is too large. {}", invalid_count),
+                data: None,
+            },
+            Error::InvalidBundle(e) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 16),
+                message: format!("invalid bundle: {}", e),
+                data: None,
+            },
+            Error::UnsignedStoreCall => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 17),
+                message: "store call is not signed".into(),
+                data: Some("the transaction data must be accompanied by a signed store extrinsic.".into()),
+            },
+            Error::InvalidStoreCall(e) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 18),
+                message: format!("store call is invalid: {}", e),
+                data: Some("the signer has to be able to pay the transaction fee and the storage fee.".into()),
+            },
+            Error::PendingDataQuotaExceeded(max) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 19),
+                message: format!("pending data quota exceeded, max: {} bytes", max),
+                data: Some("retry once the pending data has been included on chain.".into()),
+            },
+            Error::RateLimitExceeded(max, period) => rpc::Error {
+                code: rpc::ErrorCode::ServerError(BASE_ERROR + 20),
+                message: format!(
+                    "submission rate limit exceeded, max: {} bytes per {} seconds",
+                    max, period
+                ),
+                data: None,
+            },
             Error::UnsafeRpcCalled(e) => e.into(),
         }
     }
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC API for querying the locally indexed transactions by owner, block range and tag.
+
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+
+use sp_core::Bytes;
+
+use super::error::Result;
+
+pub use self::gen_client::Client as IndexerClient;
+
+/// A name-value pair describing the transaction data.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    /// Name of the tag.
+    pub name: Bytes,
+    /// Value of the tag.
+    pub value: Bytes,
+}
+
+/// Filter of the transactions, all the criteria specified must be met.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TransactionQuery<BlockNumber, AccountId> {
+    /// Owner of the transaction data.
+    pub owner: Option<AccountId>,
+    /// Tag of the transaction data.
+    pub tag: Option<Tag>,
+    /// The first block of the range, inclusive.
+    pub from_block: Option<BlockNumber>,
+    /// The last block of the range, inclusive.
+    pub to_block: Option<BlockNumber>,
+}
+
+/// A transaction storing data on the best chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionInfo<BlockNumber, BlockHash, AccountId> {
+    /// Number of the block including the transaction.
+    pub block_number: BlockNumber,
+    /// Hash of the block including the transaction.
+    pub block_hash: BlockHash,
+    /// Index of the extrinsic in block.
+    pub extrinsic_index: u32,
+    /// Chunk root of the transaction data.
+    pub chunk_root: BlockHash,
+    /// Byte size of the transaction data.
+    pub data_size: u32,
+    /// Owner of the transaction data, `None` if it was unavailable on indexing.
+    pub owner: Option<AccountId>,
+    /// Tags of the transaction data.
+    pub tags: Vec<Tag>,
+}
+
+/// A page of the transactions matching the query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionPage<BlockNumber, BlockHash, AccountId> {
+    /// Transactions of this page in the order of inclusion.
+    pub transactions: Vec<TransactionInfo<BlockNumber, BlockHash, AccountId>>,
+    /// Cursor for fetching the next page, `None` if there are no more transactions.
+    ///
+    /// A page may contain fewer transactions than the limit even if it's not the last.
+    pub next_cursor: Option<u64>,
+}
+
+/// Canyon transaction indexer RPC API.
+#[rpc]
+pub trait IndexerApi<BlockNumber, BlockHash, AccountId> {
+    /// Returns a page of the transactions on the best chain matching `query`,
+    /// in the order of inclusion.
+    ///
+    /// The query starts from `cursor` of the previous page if any, at most `limit`
+    /// transactions are returned, which is 50 by default and no more than 100.
+    #[rpc(name = "permastore_queryTransactions")]
+    fn query_transactions(
+        &self,
+        query: TransactionQuery<BlockNumber, AccountId>,
+        cursor: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<TransactionPage<BlockNumber, BlockHash, AccountId>>;
+
+    /// Returns the chunk roots of the transaction data on the best chain tagged with
+    /// `name: value`, in the order of inclusion.
+    #[rpc(name = "permastore_chunkRootsByTag")]
+    fn chunk_roots_by_tag(&self, name: Bytes, value: Bytes) -> Result<Vec<BlockHash>>;
+}
@@ -17,8 +17,11 @@
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
 pub mod error;
+pub mod indexer;
+pub mod transaction_data;
 
 use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
 
 use sc_rpc_api::author::{error::FutureResult, hash::ExtrinsicOrHash};
 
@@ -28,10 +31,74 @@ use self::error::Result;
 
 pub use self::gen_client::Client as OffchainClient;
 
+/// A chunk of the transaction data along with its merkle proof.
+///
+/// The proof can be verified against the chunk root of the transaction data.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkWithProof {
+    /// Index of the chunk.
+    pub chunk_index: u32,
+    /// Raw bytes of the chunk.
+    pub chunk: Bytes,
+    /// Merkle proof of the chunk.
+    pub proof: Vec<Bytes>,
+}
+
+/// A byte range of the transaction data.
+///
+/// The range is served as the entire chunks covering it, so that each chunk can be
+/// verified individually, the requested bytes start at `offset % CHUNK_SIZE` of
+/// the first chunk.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataRange {
+    /// Byte size of the entire transaction data.
+    pub data_size: u32,
+    /// Offset of the range.
+    pub offset: u32,
+    /// Byte size of the range, which may be truncated at the end of data.
+    pub len: u32,
+    /// Chunks covering the range in order.
+    pub chunks: Vec<ChunkWithProof>,
+}
+
+/// A bundle of items submitted to the node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmittedBundle {
+    /// Root of the bundle, i.e., the chunk root to be declared in the `store` call.
+    pub bundle_root: H256,
+    /// Byte size of the bundle, i.e., the data size to be declared in the `store` call.
+    pub data_size: u32,
+    /// Ids of the items in order.
+    pub item_ids: Vec<H256>,
+}
+
+/// An item of the bundle along with its proof of inclusion.
+///
+/// The item is proven by the chunks covering the item count and its entry in the
+/// bundle header, see `cp_permastore::bundle::verify_item`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleItem {
+    /// Raw bytes of the item.
+    pub item: Bytes,
+    /// Index of the item in the bundle.
+    pub index: u32,
+    /// Chunks proving the entry of the item.
+    pub chunks: Vec<ChunkWithProof>,
+}
+
 /// Canyon perma storage RPC API.
 #[rpc]
 pub trait PermastoreApi<Hash, BlockHash> {
     /// Sepecialized `submit_extrinsic` for submitting the store extrinsic and transaction data.
+    ///
+    /// The data is rejected unless it matches the `data_size` and `chunk_root`
+    /// declared in the `store` call of `ext`, which has to be signed by an account
+    /// able to pay the fees. The data not stored yet is subject to the quota of
+    /// pending data and the submission rate limit of the signer.
     #[rpc(name = "permastore_submitExtrinsic")]
     fn submit_extrinsic(&self, ext: Bytes, data: Bytes) -> FutureResult<Hash>;
 
@@ -44,10 +111,62 @@ pub trait PermastoreApi<Hash, BlockHash> {
     fn remove_data(&self, chunk_root: BlockHash) -> Result<bool>;
 
     /// Submit the whole data of a transaction.
+    ///
+    /// Returns the chunk root of the submitted data.
+    ///
+    /// This is an unsafe RPC, the public should submit the data along with
+    /// a signed `store` extrinsic via `permastore_submitExtrinsic`.
     #[rpc(name = "permastore_submit")]
     fn submit(&self, value: Bytes) -> Result<H256>;
 
     /// Fetch storage under given key.
     #[rpc(name = "permastore_retrieve")]
     fn retrieve(&self, key: Bytes) -> Result<Option<Bytes>>;
+
+    /// Begin uploading the data of `chunk_root` chunk by chunk.
+    ///
+    /// Returns the indices of chunks not yet uploaded, calling it again with the same
+    /// arguments resumes an unfinished upload, even after the node is restarted.
+    ///
+    /// This is an unsafe RPC, as are the other upload calls. The upload session is
+    /// removed if no chunk has been uploaded for the pending data expiry, and so is
+    /// the finished data if it's not stored on chain in time.
+    #[rpc(name = "permastore_beginUpload")]
+    fn begin_upload(&self, chunk_root: H256, data_size: u32) -> Result<Vec<u32>>;
+
+    /// Upload a single chunk of the data of `chunk_root` with its merkle proof.
+    ///
+    /// Returns the number of chunks not yet uploaded.
+    #[rpc(name = "permastore_uploadChunk")]
+    fn upload_chunk(
+        &self,
+        chunk_root: H256,
+        chunk_index: u32,
+        chunk: Bytes,
+        proof: Vec<Bytes>,
+    ) -> Result<u32>;
+
+    /// Finish the upload of `chunk_root` once all the chunks have been uploaded.
+    #[rpc(name = "permastore_finishUpload")]
+    fn finish_upload(&self, chunk_root: H256) -> Result<bool>;
+
+    /// Fetch a single chunk of the data of `chunk_root` along with its proof.
+    #[rpc(name = "permastore_getChunk")]
+    fn get_chunk(&self, chunk_root: H256, chunk_index: u32) -> Result<Option<ChunkWithProof>>;
+
+    /// Fetch the byte range `[offset, offset + len)` of the data of `chunk_root`
+    /// along with the proofs of the chunks covering it.
+    #[rpc(name = "permastore_getRange")]
+    fn get_range(&self, chunk_root: H256, offset: u32, len: u32) -> Result<Option<DataRange>>;
+
+    /// Submit `items` as a bundle, which can be stored by a single `store` call.
+    ///
+    /// This is an unsafe RPC, the public should encode the bundle locally and submit
+    /// it along with a signed `store` extrinsic via `permastore_submitExtrinsic`.
+    #[rpc(name = "permastore_submitBundle")]
+    fn submit_bundle(&self, items: Vec<Bytes>) -> Result<SubmittedBundle>;
+
+    /// Fetch the item `item_id` of the bundle `bundle_root` along with its proof.
+    #[rpc(name = "permastore_getBundleItem")]
+    fn get_bundle_item(&self, bundle_root: H256, item_id: H256) -> Result<Option<BundleItem>>;
 }
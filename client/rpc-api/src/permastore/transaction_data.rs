@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC API for retrieving the transaction data by the extrinsic storing it.
+
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+
+use sp_core::Bytes;
+
+use super::error::Result;
+
+pub use self::gen_client::Client as TransactionDataClient;
+
+/// Metadata of the storage order placed by a `store` extrinsic.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderMetadata<AccountId, Balance, Hash> {
+    /// Owner of the order, `None` if the order has been forgotten.
+    pub owner: Option<AccountId>,
+    /// Byte size of the transaction data.
+    pub data_size: u32,
+    /// Chunk root of the transaction data.
+    pub chunk_root: Hash,
+    /// Fee paid for the order, `None` if the order has been forgotten.
+    pub fee: Option<Balance>,
+    /// Whether the order has been forgotten by its owner.
+    pub forgotten: bool,
+}
+
+/// Canyon transaction data RPC API.
+#[rpc]
+pub trait TransactionDataApi<BlockHash, AccountId, Balance> {
+    /// Returns the transaction data stored by the extrinsic at `extrinsic_index`
+    /// of block `block_hash`.
+    ///
+    /// Returns `None` if the block is not on the best chain or the data is unavailable.
+    #[rpc(name = "permastore_retrieveByIndex")]
+    fn retrieve_by_index(
+        &self,
+        block_hash: BlockHash,
+        extrinsic_index: u32,
+    ) -> Result<Option<Bytes>>;
+
+    /// Returns the transaction data stored by the extrinsic `extrinsic_hash`.
+    ///
+    /// Only the extrinsics included in the best chain whose block body was
+    /// available on import can be located.
+    #[rpc(name = "permastore_retrieveByHash")]
+    fn retrieve_by_hash(&self, extrinsic_hash: BlockHash) -> Result<Option<Bytes>>;
+
+    /// Returns the metadata of the order placed by the extrinsic at `extrinsic_index`
+    /// of block `block_hash`.
+    #[rpc(name = "permastore_order")]
+    fn order(
+        &self,
+        block_hash: BlockHash,
+        extrinsic_index: u32,
+    ) -> Result<Option<OrderMetadata<AccountId, Balance, BlockHash>>>;
+}
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Admission control of the transaction data submitted via RPC.
+//!
+//! The data of `permastore_submitExtrinsic` is written to the local storage before
+//! the extrinsic is validated by the transaction pool. The total size of such pending
+//! data is bounded, and each origin, i.e., the signer of the `store` extrinsic, can only
+//! submit a limited amount of data within [`RATE_LIMIT_PERIOD`].
+//!
+//! A chunked upload is admitted against its declared data size once the session
+//! begins, and the open sessions count towards the pending data until expired. The
+//! uploads are unsigned and only allowed via the unsafe RPCs, hence are bounded by
+//! the pending data only, see [`AdmissionControl::admit_unsafe`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use cc_rpc_api::permastore::error::{Error, Result};
+
+/// Period of the per-origin submission rate limit.
+pub const RATE_LIMIT_PERIOD: Duration = Duration::from_secs(60);
+
+/// Number of origins beyond which the expired rate limit windows are pruned.
+const MAX_TRACKED_ORIGINS: usize = 1024;
+
+/// Limits of the transaction data submitted via RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionConfig {
+    /// Maximum total size in bytes of the pending data, i.e., the data whose extrinsic
+    /// has not been included yet.
+    pub max_pending_bytes: u64,
+    /// Maximum size in bytes of the data each origin can submit within
+    /// [`RATE_LIMIT_PERIOD`].
+    pub max_origin_bytes: u64,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_pending_bytes: 1024 * 1024 * 1024,
+            max_origin_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+/// Bytes submitted by an origin in the current period.
+#[derive(Debug)]
+struct RateLimitWindow {
+    start: Instant,
+    bytes: u64,
+}
+
+/// Admission control shared by all the RPC connections.
+#[derive(Debug, Clone)]
+pub struct AdmissionControl {
+    config: AdmissionConfig,
+    windows: Arc<Mutex<HashMap<Vec<u8>, RateLimitWindow>>>,
+}
+
+impl AdmissionControl {
+    /// Creates a new instance of [`AdmissionControl`].
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            config,
+            windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Admits `data_size` bytes submitted by `origin` given the size of current
+    /// pending data.
+    pub fn admit(&self, origin: &[u8], data_size: u32, pending_bytes: u64) -> Result<()> {
+        self.admit_at(origin, data_size, pending_bytes, Instant::now())
+    }
+
+    /// Admits `data_size` bytes submitted via the unsafe RPCs given the size of current
+    /// pending data.
+    ///
+    /// The caller of the unsafe RPCs is trusted, no rate limit is applied.
+    pub fn admit_unsafe(&self, data_size: u32, pending_bytes: u64) -> Result<()> {
+        self.check_pending(u64::from(data_size), pending_bytes)
+    }
+
+    fn check_pending(&self, data_size: u64, pending_bytes: u64) -> Result<()> {
+        if pending_bytes.saturating_add(data_size) > self.config.max_pending_bytes {
+            return Err(Error::PendingDataQuotaExceeded(
+                self.config.max_pending_bytes,
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn admit_at(
+        &self,
+        origin: &[u8],
+        data_size: u32,
+        pending_bytes: u64,
+        now: Instant,
+    ) -> Result<()> {
+        let data_size = u64::from(data_size);
+
+        self.check_pending(data_size, pending_bytes)?;
+
+        let is_expired =
+            |window: &RateLimitWindow| now.duration_since(window.start) >= RATE_LIMIT_PERIOD;
+
+        let mut windows = self.windows.lock();
+        if windows.len() >= MAX_TRACKED_ORIGINS {
+            windows.retain(|_, window| !is_expired(window));
+        }
+
+        let window = windows.entry(origin.to_vec()).or_insert(RateLimitWindow {
+            start: now,
+            bytes: 0,
+        });
+        if is_expired(window) {
+            *window = RateLimitWindow {
+                start: now,
+                bytes: 0,
+            };
+        }
+
+        if window.bytes.saturating_add(data_size) > self.config.max_origin_bytes {
+            return Err(Error::RateLimitExceeded(
+                self.config.max_origin_bytes,
+                RATE_LIMIT_PERIOD.as_secs(),
+            ));
+        }
+        window.bytes += data_size;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Querying the locally indexed transactions by owner, block range and tag.
+
+use codec::Codec;
+use serde::{de::DeserializeOwned, Serialize};
+
+use sp_core::Bytes;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+use cc_indexer::{IndexedTransactionFor, TransactionIndex, TransactionQuery};
+use cc_rpc_api::permastore::{
+    error::{Error, InvalidCount, Result},
+    indexer::{self as api, IndexerApi, TransactionInfo, TransactionPage},
+};
+use cp_permastore::Tag;
+
+/// Default number of transactions per page.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Maximum number of transactions per page.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Indexer API for the full node.
+pub struct Indexer<B, AccountId> {
+    /// Local index of the transactions.
+    transaction_index: TransactionIndex<B, AccountId>,
+}
+
+impl<B, AccountId> Indexer<B, AccountId> {
+    /// Creates a new instance of [`Indexer`].
+    pub fn new(transaction_index: TransactionIndex<B, AccountId>) -> Self {
+        Self { transaction_index }
+    }
+}
+
+fn into_info<B: BlockT, AccountId>(
+    tx: IndexedTransactionFor<B, AccountId>,
+) -> TransactionInfo<NumberFor<B>, B::Hash, AccountId> {
+    TransactionInfo {
+        block_number: tx.block_number,
+        block_hash: tx.block_hash,
+        extrinsic_index: tx.extrinsic_index,
+        chunk_root: tx.chunk_root,
+        data_size: tx.data_size,
+        owner: tx.owner,
+        tags: tx
+            .tags
+            .into_iter()
+            .map(|tag| api::Tag {
+                name: tag.name.into(),
+                value: tag.value.into(),
+            })
+            .collect(),
+    }
+}
+
+impl<B, AccountId> IndexerApi<NumberFor<B>, B::Hash, AccountId> for Indexer<B, AccountId>
+where
+    B: BlockT,
+    AccountId: Codec + PartialEq + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn query_transactions(
+        &self,
+        query: api::TransactionQuery<NumberFor<B>, AccountId>,
+        cursor: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<TransactionPage<NumberFor<B>, B::Hash, AccountId>> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit > MAX_PAGE_SIZE {
+            return Err(Error::PageTooLarge(InvalidCount::new(limit, MAX_PAGE_SIZE)));
+        }
+
+        let query = TransactionQuery {
+            owner: query.owner,
+            tag: query.tag.map(|tag| Tag::new(tag.name.0, tag.value.0)),
+            from_block: query.from_block,
+            to_block: query.to_block,
+        };
+
+        let page = self.transaction_index.query(&query, cursor, limit as usize);
+
+        Ok(TransactionPage {
+            transactions: page.items.into_iter().map(into_info::<B, _>).collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    fn chunk_roots_by_tag(&self, name: Bytes, value: Bytes) -> Result<Vec<B::Hash>> {
+        Ok(self
+            .transaction_index
+            .chunk_roots_by_tag(&Tag::new(name.0, value.0)))
+    }
+}
@@ -17,8 +17,11 @@
 // You should have received a copy of the GNU General Public License
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
+mod admission;
+pub mod indexer;
 #[cfg(test)]
 mod tests;
+pub mod transaction_data;
 
 use std::marker::PhantomData;
 use std::ops::Deref;
@@ -34,22 +37,62 @@ use sc_rpc_api::{
 use sc_transaction_pool_api::{TransactionPool, TxHash};
 
 use sp_core::{Bytes, Encode, H256};
-use sp_runtime::traits::{BlakeTwo256, Block as BlockT, Hash};
+use sp_runtime::traits::Block as BlockT;
 
+use cc_consensus_poa::ChunkProofBuilder;
+use cc_datastore::PendingData;
 use cc_rpc_api::permastore::{
     error::{Error, InvalidCount, Result},
-    PermastoreApi,
+    BundleItem, ChunkWithProof, DataRange, PermastoreApi, SubmittedBundle,
 };
-use cp_permastore::{PermaStorage, CHUNK_SIZE};
+use cp_permastore::{
+    bundle::{self, BundleError},
+    chunk_tree::{self, chunk_root},
+    PermaStorage, UploadSession, CHUNK_SIZE,
+};
+
+pub use self::admission::{AdmissionConfig, AdmissionControl, RATE_LIMIT_PERIOD};
+
+/// `Permastore::store` call of an encoded extrinsic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreCall {
+    /// Encoded signer of the extrinsic, `None` if the extrinsic is unsigned.
+    pub signer: Option<Vec<u8>>,
+    /// Declared size of the transaction data.
+    pub data_size: u32,
+    /// Declared chunk root of the transaction data.
+    pub chunk_root: H256,
+}
+
+/// Extracts the [`StoreCall`] from an encoded extrinsic.
+///
+/// This is runtime-specific, the node has to provide an implementation that knows
+/// how to decode and validate the extrinsic of its runtime.
+pub trait StoreCallExtractor: Send + Sync {
+    /// Returns `Some(store_call)` if `ext` is a `store` call.
+    fn extract_store_call(&self, ext: &[u8]) -> Option<StoreCall>;
+
+    /// Checks `ext` as if its data has been stored, i.e., it's properly signed and
+    /// the signer is able to pay the transaction fee as well as the storage fee.
+    ///
+    /// Returns the reason if `ext` would be rejected by the transaction pool.
+    fn check_store_call(&self, ext: &[u8]) -> std::result::Result<(), String>;
+}
 
 #[derive(Debug)]
-pub struct Permastore<T, P, A, B> {
+pub struct Permastore<T, P, A, E, B> {
     /// Permanent data storage.
     storage: Arc<RwLock<T>>,
     /// Transaction pool.
     pool: Arc<P>,
     /// Authoring api.
     author: A,
+    /// Extractor of the `store` call in the submitted extrinsic.
+    store_call_extractor: E,
+    /// Data submitted along with the extrinsics not included yet.
+    pending_data: PendingData<B>,
+    /// Admission control of the submitted data.
+    admission: AdmissionControl,
     /// Whether to deny unsafe calls
     ///
     /// TODO: since this is a pretty dangerous operation we might
@@ -59,45 +102,229 @@ pub struct Permastore<T, P, A, B> {
     phatom: PhantomData<B>,
 }
 
-impl<T, P, A, B> Permastore<T, P, A, B> {
-    pub fn new(storage: T, pool: Arc<P>, author: A, deny_unsafe: DenyUnsafe) -> Self {
+impl<T, P, A, E, B> Permastore<T, P, A, E, B> {
+    pub fn new(
+        storage: T,
+        pool: Arc<P>,
+        author: A,
+        store_call_extractor: E,
+        pending_data: PendingData<B>,
+        admission: AdmissionControl,
+        deny_unsafe: DenyUnsafe,
+    ) -> Self {
         Self {
             storage: Arc::new(RwLock::new(storage)),
             pool,
             author,
+            store_call_extractor,
+            pending_data,
+            admission,
             deny_unsafe,
             phatom: PhantomData::<B>,
         }
     }
 }
 
+impl<T, P, A, E, B> Permastore<T, P, A, E, B>
+where
+    E: StoreCallExtractor,
+{
+    /// Ensures `data` matches the `data_size` and `chunk_root` declared in the
+    /// signed `store` call of `ext`, returns the chunk root of `data` and the signer.
+    fn verify_extrinsic_data(&self, ext: &[u8], data: &[u8]) -> Result<(H256, Vec<u8>)> {
+        let StoreCall {
+            signer,
+            data_size,
+            chunk_root: expected_chunk_root,
+        } = self
+            .store_call_extractor
+            .extract_store_call(ext)
+            .ok_or(Error::NotStoreCall)?;
+
+        let signer = signer.ok_or(Error::UnsignedStoreCall)?;
+
+        let provided_size = data.len() as u32;
+        if provided_size > data_size {
+            return Err(Error::DataTooLarge(InvalidCount::new(
+                provided_size,
+                data_size,
+            )));
+        }
+        if provided_size < data_size {
+            return Err(Error::DataSizeTooLarge);
+        }
+
+        if chunk_root(data) != expected_chunk_root {
+            return Err(Error::InvalidProof);
+        }
+
+        self.store_call_extractor
+            .check_store_call(ext)
+            .map_err(Error::InvalidStoreCall)?;
+
+        Ok((expected_chunk_root, signer))
+    }
+}
+
+impl<T, P, A, E, B> Permastore<T, P, A, E, B>
+where
+    T: PermaStorage,
+{
+    /// Writes `value` to the local storage, returns its chunk root.
+    fn store_data(&self, value: Bytes) -> Result<H256> {
+        let data_size = value.deref().len() as u32;
+        if data_size > MAX_UPLOAD_DATA_SIZE {
+            return Err(Error::DataTooLarge(InvalidCount::new(
+                data_size,
+                MAX_UPLOAD_DATA_SIZE,
+            )));
+        }
+
+        let chunk_root = chunk_root(&value);
+
+        let key = chunk_root.encode();
+
+        log::debug!(
+            target: "rpc::permastore",
+            "Submitted chunk_root: {:?}, stored key: {:?}",
+            chunk_root, key,
+        );
+
+        self.storage.write().submit(key.as_slice(), &*value);
+
+        Ok(chunk_root)
+    }
+}
+
+/// Returns the chunk at `chunk_index` of the data under `key` along with its proof.
+///
+/// Returns `None` if the chunk is not found.
+fn chunk_with_proof<T: PermaStorage>(
+    storage: &T,
+    key: &[u8],
+    chunk_hashes: &[H256],
+    chunk_index: u32,
+) -> Result<Option<ChunkWithProof>> {
+    let chunk = match storage.retrieve_chunk(key, chunk_index) {
+        Some(chunk) => chunk,
+        None => return Ok(None),
+    };
+
+    let chunk_proof = ChunkProofBuilder::from_chunk(chunk_hashes.to_vec(), chunk, chunk_index)
+        .build()
+        .map_err(|e| Error::ChunkProof(e.to_string()))?;
+
+    Ok(Some(ChunkWithProof {
+        chunk_index,
+        chunk: chunk_proof.chunk.into(),
+        proof: chunk_proof.proof.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Reads `len` bytes at `offset` of the data under `key` from the chunks covering them.
+///
+/// Returns `None` if any of the chunks is not found.
+fn read_range<T: PermaStorage>(storage: &T, key: &[u8], offset: u32, len: u32) -> Option<Vec<u8>> {
+    if len == 0 {
+        return Some(Vec::new());
+    }
+
+    let end = offset.checked_add(len)?;
+
+    let mut bytes = Vec::with_capacity(len as usize);
+    for chunk_index in chunk_tree::chunk_index(offset)..=chunk_tree::chunk_index(end - 1) {
+        let chunk = storage.retrieve_chunk(key, chunk_index)?;
+        let chunk_base = chunk_index * CHUNK_SIZE;
+        let start = offset.saturating_sub(chunk_base) as usize;
+        let stop = (end - chunk_base).min(chunk.len() as u32) as usize;
+        bytes.extend_from_slice(chunk.get(start..stop)?);
+    }
+
+    if bytes.len() == len as usize {
+        Some(bytes)
+    } else {
+        None
+    }
+}
+
+fn invalid_bundle(e: BundleError) -> Error {
+    Error::InvalidBundle(format!("{:?}", e))
+}
+
 /// Maximum byte size of uploading transaction data directly. 10MiB
 const MAX_UPLOAD_DATA_SIZE: u32 = 10 * 1024 * 1024;
 
 /// Maximum byte size of downloading transaction data directly. 12MiB
 const MAX_DOWNLOAD_DATA_SIZE: u32 = 12 * 1024 * 1024;
 
-impl<T, P, A, B> PermastoreApi<TxHash<P>, <B as BlockT>::Hash> for Permastore<T, P, A, B>
+/// Maximum byte size of uploading transaction data chunk by chunk, which is
+/// the same as the `MaxDataSize` of runtime. 1GiB
+const MAX_CHUNKED_UPLOAD_DATA_SIZE: u32 = 1024 * 1024 * 1024;
+
+/// Maximum byte size of the merkle proof of an uploaded chunk. 256KiB
+const MAX_UPLOAD_CHUNK_PATH: usize = 256 * 1024;
+
+impl<T, P, A, E, B> PermastoreApi<TxHash<P>, <B as BlockT>::Hash> for Permastore<T, P, A, E, B>
 where
     T: PermaStorage + 'static,
-    P: TransactionPool + Send + Sync + 'static,
-    B: BlockT,
+    P: TransactionPool<Hash = H256> + Send + Sync + 'static,
+    B: BlockT<Hash = H256>,
     A: AuthorApi<TxHash<P>, <B as BlockT>::Hash>,
+    E: StoreCallExtractor + 'static,
 {
     fn submit_extrinsic(&self, ext: Bytes, data: Bytes) -> FutureResult<TxHash<P>> {
-        if let Err(e) = self.submit(data) {
-            return async move { Err(sc_rpc_api::author::error::Error::Client(Box::new(e))) }
-                .boxed();
-        }
-        self.author.submit_extrinsic(ext)
+        let data_size = data.len() as u32;
+        let submitted = self
+            .verify_extrinsic_data(&ext, &data)
+            .and_then(|(chunk_root, signer)| {
+                // The data existing before may belong to the other extrinsics,
+                // only the newly submitted data is tracked.
+                let is_new = !self.storage.read().exists(chunk_root.encode().as_slice());
+                if is_new {
+                    self.admission
+                        .admit(&signer, data_size, self.pending_data.pending_bytes())?;
+                }
+                self.store_data(data).map(|_| (chunk_root, is_new))
+            });
+
+        let chunk_root = match submitted {
+            Ok((chunk_root, true)) => chunk_root,
+            Ok((_, false)) => return self.author.submit_extrinsic(ext),
+            Err(e) => {
+                return async move { Err(sc_rpc_api::author::error::Error::Client(Box::new(e))) }
+                    .boxed()
+            }
+        };
+
+        let storage = self.storage.clone();
+        let pending_data = self.pending_data.clone();
+        self.author
+            .submit_extrinsic(ext)
+            .map(move |result| {
+                match &result {
+                    Ok(extrinsic_hash) => {
+                        pending_data.track(chunk_root, *extrinsic_hash, data_size)
+                    }
+                    // The extrinsic is rejected by the pool, nothing else will store the data.
+                    Err(_) => storage.write().remove(chunk_root.encode().as_slice()),
+                }
+                result
+            })
+            .boxed()
     }
 
     fn remove_extrinsic(
         &self,
         bytes_or_hash: Vec<ExtrinsicOrHash<TxHash<P>>>,
     ) -> Result<Vec<TxHash<P>>> {
-        // FIXME: remove the transaction data directly or later?
-        Ok(self.author.remove_extrinsic(bytes_or_hash)?)
+        let removed = self.author.remove_extrinsic(bytes_or_hash)?;
+
+        let mut storage = self.storage.write();
+        for chunk_root in self.pending_data.untrack(&removed) {
+            storage.remove(chunk_root.encode().as_slice());
+        }
+
+        Ok(removed)
     }
 
     fn remove_data(&self, chunk_root: <B as BlockT>::Hash) -> Result<bool> {
@@ -108,51 +335,328 @@ where
         Ok(true)
     }
 
-    // Can this be an attack as anyone can submit arbitrary data to the node?
-    // TODO: add tests for submit and retrieve?
     fn submit(&self, value: Bytes) -> Result<H256> {
-        let data_size = value.deref().len() as u32;
-        if data_size > MAX_UPLOAD_DATA_SIZE {
+        // Anyone could fill the disk with arbitrary data otherwise.
+        self.deny_unsafe.check_if_safe()?;
+
+        self.store_data(value)
+    }
+
+    fn retrieve(&self, key: Bytes) -> Result<Option<Bytes>> {
+        let storage = self.storage.read();
+
+        // Check the data size via the metadata first to avoid loading
+        // the chunks of a value that is too large to be served.
+        match storage.data_meta(&*key) {
+            Some(data_meta) => {
+                if data_meta.data_size > MAX_DOWNLOAD_DATA_SIZE {
+                    return Err(Error::DataTooLarge(InvalidCount::new(
+                        data_meta.data_size,
+                        MAX_DOWNLOAD_DATA_SIZE,
+                    )));
+                }
+                Ok(storage.retrieve(&*key).map(Into::into))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn begin_upload(&self, chunk_root: H256, data_size: u32) -> Result<Vec<u32>> {
+        self.deny_unsafe.check_if_safe()?;
+
+        if data_size > MAX_CHUNKED_UPLOAD_DATA_SIZE {
             return Err(Error::DataTooLarge(InvalidCount::new(
                 data_size,
-                MAX_UPLOAD_DATA_SIZE,
+                MAX_CHUNKED_UPLOAD_DATA_SIZE,
             )));
         }
 
-        let chunks = value
-            .0
-            .chunks(CHUNK_SIZE as usize)
-            .map(|c| BlakeTwo256::hash(c).encode())
-            .collect();
+        let key = chunk_root.encode();
+
+        let mut storage = self.storage.write();
 
-        let chunk_root = BlakeTwo256::ordered_trie_root(chunks);
+        if storage.exists(&key) {
+            return Err(Error::DataExists);
+        }
+
+        let upload_session = match storage.upload_session(&key) {
+            Some(upload_session) => {
+                if upload_session.data_size != data_size {
+                    return Err(Error::UploadDataSizeMismatch(upload_session.data_size));
+                }
+                log::debug!(
+                    target: "rpc::permastore",
+                    "Resumed upload of chunk_root: {:?}",
+                    chunk_root,
+                );
+                upload_session
+            }
+            None => {
+                self.admission
+                    .admit_unsafe(data_size, self.pending_data.pending_bytes())?;
+                let upload_session = UploadSession::new(data_size);
+                storage.set_upload_session(&key, &upload_session);
+                log::debug!(
+                    target: "rpc::permastore",
+                    "Began upload of chunk_root: {:?}, data_size: {}",
+                    chunk_root, data_size,
+                );
+                upload_session
+            }
+        };
+
+        // The session is removed once expired, unless the upload proceeds.
+        self.pending_data.track_upload(chunk_root, data_size);
+
+        Ok(upload_session.missing_chunks())
+    }
+
+    fn upload_chunk(
+        &self,
+        chunk_root: H256,
+        chunk_index: u32,
+        chunk: Bytes,
+        proof: Vec<Bytes>,
+    ) -> Result<u32> {
+        self.deny_unsafe.check_if_safe()?;
+
+        if chunk.len() > CHUNK_SIZE as usize {
+            return Err(Error::ChunkTooLarge);
+        }
+
+        let proof = proof.into_iter().map(|p| p.0).collect::<Vec<_>>();
+        if proof.iter().map(|p| p.len()).sum::<usize>() > MAX_UPLOAD_CHUNK_PATH {
+            return Err(Error::DataPathTooLarge);
+        }
+
+        let key = chunk_root.encode();
+
+        let mut storage = self.storage.write();
+
+        let mut upload_session = storage.upload_session(&key).ok_or(Error::UploadNotFound)?;
+
+        // The chunk must be in range and of the exact size given the declared data size.
+        let expected_chunk_size = chunk_tree::chunk_size(upload_session.data_size, chunk_index)
+            .ok_or(Error::InvalidProof)?;
+        if chunk.len() as u32 > expected_chunk_size {
+            return Err(Error::ChunkTooLarge);
+        }
+        if chunk.len() as u32 != expected_chunk_size {
+            return Err(Error::InvalidProof);
+        }
+
+        if upload_session.uploaded[chunk_index as usize] {
+            return Err(Error::ChunkExists);
+        }
+
+        chunk_tree::verify_chunk(&chunk_root, chunk_index, &chunk, &proof)
+            .map_err(|_| Error::InvalidProof)?;
+
+        storage.submit_chunk(&key, chunk_index, &chunk);
+        upload_session.uploaded[chunk_index as usize] = true;
+        storage.set_upload_session(&key, &upload_session);
+
+        self.pending_data
+            .track_upload(chunk_root, upload_session.data_size);
+
+        Ok(upload_session.missing_chunks().len() as u32)
+    }
+
+    fn finish_upload(&self, chunk_root: H256) -> Result<bool> {
+        self.deny_unsafe.check_if_safe()?;
 
         let key = chunk_root.encode();
 
+        let mut storage = self.storage.write();
+
+        let upload_session = storage.upload_session(&key).ok_or(Error::UploadNotFound)?;
+
+        let missing_chunks = upload_session.missing_chunks().len() as u32;
+        if missing_chunks > 0 {
+            return Err(Error::UploadIncomplete(missing_chunks));
+        }
+
+        let data_meta = storage
+            .finish_upload(&key)
+            .ok_or(Error::UploadIncomplete(0))?;
+
+        // Every chunk has been verified against the chunk root, but the root of the
+        // assembled data still mismatches if the declared data size is too small.
+        if chunk_tree::chunk_root_from_hashes(&data_meta.chunk_hashes) != chunk_root {
+            storage.remove(&key);
+            self.pending_data.untrack_upload(chunk_root);
+            return Err(Error::InvalidProof);
+        }
+
+        // Removed unless the data is stored on chain before expiry, like the data
+        // submitted along with an extrinsic.
+        self.pending_data
+            .track_upload(chunk_root, data_meta.data_size);
+
         log::debug!(
             target: "rpc::permastore",
-            "Submitted chunk_root: {:?}, stored key: {:?}",
-            chunk_root, key,
+            "Finished upload of chunk_root: {:?}, data_size: {}",
+            chunk_root, data_meta.data_size,
         );
 
-        // TODO: verify chunk_root matches the submitted data.
-        self.storage.write().submit(key.as_slice(), &*value);
+        Ok(true)
+    }
 
-        Ok(chunk_root)
+    fn get_chunk(&self, chunk_root: H256, chunk_index: u32) -> Result<Option<ChunkWithProof>> {
+        let key = chunk_root.encode();
+
+        let storage = self.storage.read();
+
+        match storage.data_meta(&key) {
+            Some(data_meta) if chunk_index < data_meta.chunk_count() => {
+                chunk_with_proof(&*storage, &key, &data_meta.chunk_hashes, chunk_index)
+            }
+            _ => Ok(None),
+        }
     }
 
-    fn retrieve(&self, key: Bytes) -> Result<Option<Bytes>> {
-        if let Some(value) = self.storage.read().retrieve(&*key) {
-            let data_size = value.len() as u32;
-            if data_size > MAX_DOWNLOAD_DATA_SIZE {
-                return Err(Error::DataTooLarge(InvalidCount::new(
-                    data_size,
-                    MAX_DOWNLOAD_DATA_SIZE,
-                )));
+    fn get_range(&self, chunk_root: H256, offset: u32, len: u32) -> Result<Option<DataRange>> {
+        if len > MAX_DOWNLOAD_DATA_SIZE {
+            return Err(Error::DataTooLarge(InvalidCount::new(
+                len,
+                MAX_DOWNLOAD_DATA_SIZE,
+            )));
+        }
+
+        let key = chunk_root.encode();
+
+        let storage = self.storage.read();
+
+        let data_meta = match storage.data_meta(&key) {
+            Some(data_meta) => data_meta,
+            None => return Ok(None),
+        };
+
+        if len == 0 || offset >= data_meta.data_size {
+            return Err(Error::RangeOutOfBounds(data_meta.data_size));
+        }
+
+        let len = len.min(data_meta.data_size - offset);
+
+        let first_chunk_index = chunk_tree::chunk_index(offset);
+        let last_chunk_index = chunk_tree::chunk_index(offset + len - 1);
+
+        let chunks = (first_chunk_index..=last_chunk_index)
+            .map(|chunk_index| {
+                chunk_with_proof(&*storage, &key, &data_meta.chunk_hashes, chunk_index)
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .collect::<Option<Vec<_>>>();
+
+        Ok(chunks.map(|chunks| DataRange {
+            data_size: data_meta.data_size,
+            offset,
+            len,
+            chunks,
+        }))
+    }
+
+    fn submit_bundle(&self, items: Vec<Bytes>) -> Result<SubmittedBundle> {
+        self.deny_unsafe.check_if_safe()?;
+
+        let items = items.into_iter().map(|item| item.0).collect::<Vec<_>>();
+        let bundle = bundle::encode(&items).map_err(invalid_bundle)?;
+
+        let data_size = bundle.len() as u32;
+        let item_ids = items.iter().map(|item| bundle::item_id(item)).collect();
+
+        let bundle_root = self.store_data(bundle.into())?;
+
+        log::debug!(
+            target: "rpc::permastore",
+            "Submitted bundle of {} items, bundle_root: {:?}",
+            items.len(), bundle_root,
+        );
+
+        Ok(SubmittedBundle {
+            bundle_root,
+            data_size,
+            item_ids,
+        })
+    }
+
+    fn get_bundle_item(&self, bundle_root: H256, item_id: H256) -> Result<Option<BundleItem>> {
+        let key = bundle_root.encode();
+
+        let storage = self.storage.read();
+
+        let data_meta = match storage.data_meta(&key) {
+            Some(data_meta) => data_meta,
+            None => return Ok(None),
+        };
+
+        let item_count = match read_range(&*storage, &key, 0, bundle::ITEM_COUNT_SIZE) {
+            Some(encoded) => bundle::decode_item_count(&encoded).map_err(invalid_bundle)?,
+            None if data_meta.data_size < bundle::ITEM_COUNT_SIZE => {
+                return Err(invalid_bundle(BundleError::Truncated))
             }
-            Ok(Some(value.into()))
-        } else {
-            Ok(None)
+            None => return Ok(None),
+        };
+
+        let header_size = bundle::header_size(item_count)
+            .filter(|header_size| *header_size <= data_meta.data_size)
+            .ok_or_else(|| invalid_bundle(BundleError::Truncated))?;
+        if header_size > MAX_DOWNLOAD_DATA_SIZE {
+            return Err(Error::DataTooLarge(InvalidCount::new(
+                header_size,
+                MAX_DOWNLOAD_DATA_SIZE,
+            )));
+        }
+
+        let header = match read_range(&*storage, &key, 0, header_size) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let entries =
+            bundle::decode_header(&header, data_meta.data_size).map_err(invalid_bundle)?;
+
+        let (index, entry) = match entries
+            .into_iter()
+            .enumerate()
+            .find(|(_, entry)| entry.id == item_id)
+        {
+            Some((index, entry)) => (index as u32, entry),
+            None => return Ok(None),
+        };
+
+        if entry.size > MAX_DOWNLOAD_DATA_SIZE {
+            return Err(Error::DataTooLarge(InvalidCount::new(
+                entry.size,
+                MAX_DOWNLOAD_DATA_SIZE,
+            )));
         }
+
+        let item = match read_range(&*storage, &key, entry.offset, entry.size) {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+
+        // The data may be something else that happens to look like a bundle.
+        if bundle::item_id(&item) != item_id {
+            return Err(invalid_bundle(BundleError::InvalidEntry(index)));
+        }
+
+        let chunks = bundle::proof_chunk_indices(index, &entry)
+            .map_err(invalid_bundle)?
+            .into_iter()
+            .map(|chunk_index| {
+                chunk_with_proof(&*storage, &key, &data_meta.chunk_hashes, chunk_index)
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .collect::<Option<Vec<_>>>();
+
+        Ok(chunks.map(|chunks| BundleItem {
+            item: item.into(),
+            index,
+            chunks,
+        }))
     }
 }
\ No newline at end of file
@@ -26,17 +26,31 @@ use futures::{executor, StreamExt};
 use jsonrpc_pubsub::{manager::SubscriptionManager, SubscriptionId};
 use parking_lot::RwLock;
 
+use sc_client_db::offchain::LocalStorage;
 use sc_rpc::author::Author;
 use sc_rpc_api::{author::hash::ExtrinsicOrHash, DenyUnsafe};
 use sc_transaction_pool::{BasicPool, FullChainApi};
+use sp_api::{ApiRef, ProvideRuntimeApi};
+use sp_blockchain::{BlockStatus, HeaderBackend, Info};
 use sp_core::{blake2_256, hexdisplay::HexDisplay, H256};
 use sp_keystore::testing::KeyStore;
+use sp_runtime::generic::BlockId;
 use substrate_test_runtime_client::{
     self,
-    runtime::{Block, Extrinsic, Transfer},
+    runtime::{Block, Extrinsic, Header, Transfer},
     AccountKeyring, Backend, Client, DefaultTestClientBuilderExt, TestClientBuilderExt,
 };
 
+use cc_datastore::{PendingData, PendingEntry, WeaveBlock, WeaveIndex};
+use cc_indexer::{IndexedTransaction, TransactionIndex};
+use cc_rpc_api::permastore::{
+    indexer::{self as indexer_api, IndexerApi, TransactionInfo},
+    transaction_data::{OrderMetadata, TransactionDataApi},
+};
+use cp_permastore::{bundle, StoredTransaction, Tag, CHUNK_SIZE};
+
+use self::{indexer::Indexer, transaction_data::TransactionData};
+
 fn uxt(sender: AccountKeyring, nonce: u64) -> Extrinsic {
     let tx = Transfer {
         amount: Default::default(),
@@ -47,10 +61,53 @@ fn uxt(sender: AccountKeyring, nonce: u64) -> Extrinsic {
     tx.into_signed_tx()
 }
 
+/// Treats every extrinsic as a `store` call with the given declaration.
+struct MockStoreCallExtractor {
+    declaration: Option<(u32, H256)>,
+    signer: Option<Vec<u8>>,
+    check_result: std::result::Result<(), String>,
+}
+
+impl MockStoreCallExtractor {
+    fn new(data: &[u8]) -> Self {
+        Self::declare(Some((data.len() as u32, chunk_root(data))))
+    }
+
+    fn declare(declaration: Option<(u32, H256)>) -> Self {
+        Self {
+            declaration,
+            signer: Some(b"signer".to_vec()),
+            check_result: Ok(()),
+        }
+    }
+}
+
+impl StoreCallExtractor for MockStoreCallExtractor {
+    fn extract_store_call(&self, _ext: &[u8]) -> Option<StoreCall> {
+        self.declaration.map(|(data_size, chunk_root)| StoreCall {
+            signer: self.signer.clone(),
+            data_size,
+            chunk_root,
+        })
+    }
+
+    fn check_store_call(&self, _ext: &[u8]) -> std::result::Result<(), String> {
+        self.check_result.clone()
+    }
+}
+
 type FullTransactionPool = BasicPool<FullChainApi<Client<Backend>, Block>, Block>;
 
 type TestAuthor = Author<FullTransactionPool, Client<Backend>>;
 
+type TestPermastore = Permastore<
+    cc_datastore::PermanentStorage<Client<Backend>>,
+    FullTransactionPool,
+    TestAuthor,
+    MockStoreCallExtractor,
+    Block,
+>;
+
 struct TestSetup {
     pub client: Arc<Client<Backend>>,
     pub keystore: Arc<KeyStore>,
@@ -92,20 +149,20 @@ impl TestSetup {
         )
     }
 
-    fn permastore(
-        &self,
-    ) -> Permastore<
-        cc_datastore::PermanentStorage<Client<Backend>>,
-        FullTransactionPool,
-        TestAuthor,
-        Block,
-    > {
+    fn permastore(&self) -> TestPermastore {
+        self.permastore_with(MockStoreCallExtractor::new(b"mocked data"))
+    }
+
+    fn permastore_with(&self, store_call_extractor: MockStoreCallExtractor) -> TestPermastore {
         Permastore {
             storage: Arc::new(RwLock::new(cc_datastore::PermanentStorage::new_test(
                 self.client.clone(),
             ))),
             pool: self.pool.clone(),
             author: self.author(),
+            store_call_extractor,
+            pending_data: PendingData::new(LocalStorage::new_test()),
+            admission: AdmissionControl::new(Default::default()),
             deny_unsafe: DenyUnsafe::No,
             phatom: PhantomData::<Block>,
         }
@@ -264,3 +321,1178 @@ fn should_remove_extrinsics() {
 
     assert_eq!(removed.len(), 3);
 }
+
+#[test]
+fn submit_extrinsic_should_track_new_data() {
+    let p = TestSetup::default().permastore();
+
+    let data: Bytes = b"mocked data".to_vec().into();
+    let key = chunk_root(&data).encode();
+
+    let ex1 = uxt(AccountKeyring::Alice, 0);
+    let hash1 = executor::block_on(p.submit_extrinsic(ex1.encode().into(), data.clone())).unwrap();
+    assert_eq!(
+        p.pending_data.entries(),
+        vec![PendingEntry {
+            chunk_root: chunk_root(&data),
+            extrinsic_hash: Some(hash1),
+            data_size: 11,
+            first_seen: None,
+            ready: false,
+            left_pool_at: None,
+        }]
+    );
+
+    // The data already exists, which is not tracked again.
+    let ex2 = uxt(AccountKeyring::Bob, 0);
+    executor::block_on(p.submit_extrinsic(ex2.encode().into(), data)).unwrap();
+    assert_eq!(p.pending_data.entries().len(), 1);
+    assert!(p.storage.read().exists(&key));
+}
+
+#[test]
+fn rejected_extrinsic_should_remove_new_data() {
+    let p = TestSetup::default().permastore();
+
+    let data: Bytes = b"mocked data".to_vec().into();
+    let key = chunk_root(&data).encode();
+
+    // Undecodable extrinsic is rejected by the pool.
+    assert!(executor::block_on(p.submit_extrinsic(vec![1, 2, 3].into(), data)).is_err());
+    assert!(!p.storage.read().exists(&key));
+    assert!(p.pending_data.entries().is_empty());
+}
+
+#[test]
+fn remove_extrinsic_should_remove_pending_data() {
+    let setup = TestSetup::default();
+    let p = setup.permastore();
+
+    let data: Bytes = b"mocked data".to_vec().into();
+    let key = chunk_root(&data).encode();
+
+    let ex = uxt(AccountKeyring::Alice, 0);
+    let hash = executor::block_on(p.submit_extrinsic(ex.encode().into(), data)).unwrap();
+    assert!(p.storage.read().exists(&key));
+
+    let removed = p
+        .remove_extrinsic(vec![ExtrinsicOrHash::Hash(hash)])
+        .unwrap();
+    assert_eq!(removed, vec![hash]);
+    assert_eq!(setup.pool.status().ready, 0);
+    assert!(!p.storage.read().exists(&key));
+    assert!(p.pending_data.entries().is_empty());
+}
+
+#[test]
+fn submit_and_retrieve_should_work() {
+    let p = TestSetup::default().permastore();
+
+    let data = vec![7u8; CHUNK_SIZE as usize + 1];
+    let chunk_root = PermastoreApi::submit(&p, data.clone().into()).unwrap();
+
+    assert_eq!(
+        PermastoreApi::retrieve(&p, chunk_root.encode().into()).unwrap(),
+        Some(data.into())
+    );
+    assert_eq!(
+        PermastoreApi::retrieve(&p, H256::zero().encode().into()).unwrap(),
+        None
+    );
+}
+
+fn submit_extrinsic_error(p: &TestPermastore, data: &[u8]) -> Error {
+    let xt = uxt(AccountKeyring::Alice, 0).encode();
+    match executor::block_on(PermastoreApi::submit_extrinsic(
+        p,
+        xt.into(),
+        data.to_vec().into(),
+    )) {
+        Err(sc_rpc_api::author::error::Error::Client(e)) => *e
+            .downcast::<Error>()
+            .expect("Client error must be a permastore error"),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn submit_extrinsic_with_mismatched_chunk_root_should_fail() {
+    let setup = TestSetup::default();
+    let p = setup.permastore_with(MockStoreCallExtractor::declare(Some((11, H256::zero()))));
+
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::InvalidProof
+    );
+    assert_eq!(setup.pool.status().ready, 0);
+    assert_eq!(
+        PermastoreApi::retrieve(&p, chunk_root(b"mocked data").encode().into()).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn submit_extrinsic_with_mismatched_data_size_should_fail() {
+    let setup = TestSetup::default();
+    let p = setup.permastore_with(MockStoreCallExtractor::new(b"mocked"));
+
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::DataTooLarge(InvalidCount {
+            provided: 11,
+            max: 6
+        })
+    );
+
+    let p = setup.permastore_with(MockStoreCallExtractor::new(b"mocked data and more"));
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::DataSizeTooLarge
+    );
+
+    assert_eq!(setup.pool.status().ready, 0);
+}
+
+#[test]
+fn submit_extrinsic_without_store_call_should_fail() {
+    let setup = TestSetup::default();
+    let p = setup.permastore_with(MockStoreCallExtractor::declare(None));
+
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::NotStoreCall
+    );
+    assert_eq!(setup.pool.status().ready, 0);
+}
+
+#[test]
+fn submit_extrinsic_with_unsigned_store_call_should_fail() {
+    let setup = TestSetup::default();
+    let p = setup.permastore_with(MockStoreCallExtractor {
+        signer: None,
+        ..MockStoreCallExtractor::new(b"mocked data")
+    });
+
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::UnsignedStoreCall
+    );
+    assert_eq!(setup.pool.status().ready, 0);
+    assert!(!p
+        .storage
+        .read()
+        .exists(&chunk_root(b"mocked data").encode()));
+}
+
+#[test]
+fn submit_extrinsic_with_invalid_store_call_should_fail() {
+    let setup = TestSetup::default();
+    let p = setup.permastore_with(MockStoreCallExtractor {
+        check_result: Err("Payment".into()),
+        ..MockStoreCallExtractor::new(b"mocked data")
+    });
+
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::InvalidStoreCall(e) if e == "Payment"
+    );
+    assert_eq!(setup.pool.status().ready, 0);
+    assert!(!p
+        .storage
+        .read()
+        .exists(&chunk_root(b"mocked data").encode()));
+}
+
+#[test]
+fn submit_extrinsic_exceeding_pending_data_quota_should_fail() {
+    let setup = TestSetup::default();
+    let p = Permastore {
+        admission: AdmissionControl::new(AdmissionConfig {
+            max_pending_bytes: 20,
+            ..Default::default()
+        }),
+        ..setup.permastore()
+    };
+
+    p.pending_data
+        .track(H256::repeat_byte(1), H256::repeat_byte(2), 10);
+    assert_matches!(
+        submit_extrinsic_error(&p, b"mocked data"),
+        Error::PendingDataQuotaExceeded(20)
+    );
+    assert_eq!(setup.pool.status().ready, 0);
+    assert!(!p
+        .storage
+        .read()
+        .exists(&chunk_root(b"mocked data").encode()));
+
+    // The quota is released once the pending data is confirmed.
+    p.pending_data.untrack(&[H256::repeat_byte(2)]);
+    let xt = uxt(AccountKeyring::Alice, 0).encode();
+    assert!(
+        executor::block_on(p.submit_extrinsic(xt.into(), b"mocked data".to_vec().into())).is_ok()
+    );
+    assert_eq!(p.pending_data.pending_bytes(), 11);
+}
+
+#[test]
+fn begin_upload_exceeding_pending_data_quota_should_fail() {
+    let data_size = (CHUNK_SIZE + 5) as u64;
+    let p = Permastore {
+        admission: AdmissionControl::new(AdmissionConfig {
+            max_pending_bytes: data_size * 2,
+            ..Default::default()
+        }),
+        ..TestSetup::default().permastore()
+    };
+
+    let data1 = upload_data(CHUNK_SIZE + 5);
+    let chunk_root1 = cp_permastore::chunk_tree::chunk_root(&data1);
+    p.begin_upload(chunk_root1, data_size as u32).unwrap();
+    assert_eq!(p.pending_data.pending_bytes(), data_size);
+
+    // The open session counts towards the pending data.
+    let chunk_root2 = H256::repeat_byte(1);
+    assert_matches!(
+        p.begin_upload(chunk_root2, data_size as u32 + 1),
+        Err(Error::PendingDataQuotaExceeded(max)) if max == data_size * 2
+    );
+    assert!(p
+        .storage
+        .read()
+        .upload_session(&chunk_root2.encode())
+        .is_none());
+
+    // Resuming an admitted session is not charged again.
+    assert_eq!(
+        p.begin_upload(chunk_root1, data_size as u32).unwrap(),
+        vec![0, 1]
+    );
+    assert!(p.begin_upload(chunk_root2, data_size as u32).is_ok());
+}
+
+#[test]
+fn chunked_uploads_should_not_be_rate_limited() {
+    // The default config allows far less bytes per origin than a chunked upload.
+    let p = TestSetup::default().permastore();
+    let max_pending_bytes = AdmissionConfig::default().max_pending_bytes;
+    assert!(AdmissionConfig::default().max_origin_bytes < MAX_CHUNKED_UPLOAD_DATA_SIZE as u64);
+
+    assert!(p
+        .begin_upload(H256::repeat_byte(1), MAX_CHUNKED_UPLOAD_DATA_SIZE / 2)
+        .is_ok());
+    assert!(p
+        .begin_upload(H256::repeat_byte(2), MAX_CHUNKED_UPLOAD_DATA_SIZE / 2)
+        .is_ok());
+    assert_eq!(
+        p.pending_data.pending_bytes(),
+        MAX_CHUNKED_UPLOAD_DATA_SIZE as u64
+    );
+
+    // Only the pending data is bounded.
+    assert_matches!(
+        p.begin_upload(H256::repeat_byte(3), 1),
+        Err(Error::PendingDataQuotaExceeded(max)) if max == max_pending_bytes
+    );
+}
+
+#[test]
+fn admission_should_limit_submission_rate_per_origin() {
+    let admission = AdmissionControl::new(AdmissionConfig {
+        max_pending_bytes: 1000,
+        max_origin_bytes: 100,
+    });
+    let now = std::time::Instant::now();
+
+    assert!(admission.admit_at(b"alice", 60, 0, now).is_ok());
+    assert_matches!(
+        admission.admit_at(b"alice", 50, 0, now),
+        Err(Error::RateLimitExceeded(100, 60))
+    );
+    assert!(admission.admit_at(b"alice", 40, 0, now).is_ok());
+    assert!(admission.admit_at(b"bob", 100, 0, now).is_ok());
+
+    // Quota of the pending data is checked first.
+    assert_matches!(
+        admission.admit_at(b"charlie", 1, 1000, now),
+        Err(Error::PendingDataQuotaExceeded(1000))
+    );
+
+    // Rate limit is reset in the next period.
+    let later = now + RATE_LIMIT_PERIOD;
+    assert!(admission.admit_at(b"alice", 100, 0, later).is_ok());
+}
+
+#[test]
+fn submit_data_without_extrinsic_should_be_unsafe() {
+    let p = Permastore {
+        deny_unsafe: DenyUnsafe::Yes,
+        ..TestSetup::default().permastore()
+    };
+
+    assert_matches!(
+        PermastoreApi::submit(&p, b"mocked data".to_vec().into()),
+        Err(Error::UnsafeRpcCalled(_))
+    );
+    assert_matches!(
+        p.submit_bundle(vec![b"item".to_vec().into()]),
+        Err(Error::UnsafeRpcCalled(_))
+    );
+    assert!(!p
+        .storage
+        .read()
+        .exists(&chunk_root(b"mocked data").encode()));
+}
+
+#[test]
+fn submit_should_return_shared_chunk_root_for_edge_sizes() {
+    let p = TestSetup::default().permastore();
+
+    for data_size in [
+        0,
+        1,
+        CHUNK_SIZE - 1,
+        CHUNK_SIZE,
+        CHUNK_SIZE + 1,
+        CHUNK_SIZE * 3 + 7,
+    ]
+    .iter()
+    {
+        let data = vec![7u8; *data_size as usize];
+        let chunk_root = PermastoreApi::submit(&p, data.clone().into()).unwrap();
+
+        assert_eq!(
+            chunk_root,
+            cp_permastore::chunk_tree::chunk_root(&data),
+            "data size: {}",
+            data_size
+        );
+        assert_eq!(
+            PermastoreApi::retrieve(&p, chunk_root.encode().into()).unwrap(),
+            Some(data.into())
+        );
+    }
+}
+
+fn upload_data(data_size: u32) -> Vec<u8> {
+    (0..data_size).map(|i| (i % 251) as u8).collect()
+}
+
+fn chunk_and_proof(data: &[u8], chunk_index: u32) -> (Bytes, Vec<Bytes>) {
+    let chunk_proof =
+        cc_consensus_poa::ChunkProofBuilder::new(data.to_vec(), chunk_index * CHUNK_SIZE)
+            .build()
+            .unwrap();
+    (
+        chunk_proof.chunk.into(),
+        chunk_proof.proof.into_iter().map(Into::into).collect(),
+    )
+}
+
+#[test]
+fn chunked_upload_should_work() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE * 2 + 5);
+    let chunk_root = cp_permastore::chunk_tree::chunk_root(&data);
+
+    assert_eq!(
+        p.begin_upload(chunk_root, data.len() as u32).unwrap(),
+        vec![0, 1, 2]
+    );
+
+    for (chunk_index, remaining) in [(2, 2), (0, 1), (1, 0)].iter() {
+        let (chunk, proof) = chunk_and_proof(&data, *chunk_index);
+        assert_eq!(
+            p.upload_chunk(chunk_root, *chunk_index, chunk, proof)
+                .unwrap(),
+            *remaining
+        );
+    }
+
+    assert!(p.finish_upload(chunk_root).unwrap());
+    assert_eq!(
+        PermastoreApi::retrieve(&p, chunk_root.encode().into()).unwrap(),
+        Some(data.clone().into())
+    );
+    assert_matches!(p.finish_upload(chunk_root), Err(Error::UploadNotFound));
+
+    // The uploaded data is pending until stored on chain.
+    assert_eq!(
+        p.pending_data.entries(),
+        vec![PendingEntry {
+            chunk_root,
+            extrinsic_hash: None,
+            data_size: data.len() as u32,
+            first_seen: None,
+            ready: false,
+            left_pool_at: None,
+        }]
+    );
+}
+
+#[test]
+fn chunked_upload_should_be_resumable() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE * 2 + 5);
+    let data_size = data.len() as u32;
+    let chunk_root = cp_permastore::chunk_tree::chunk_root(&data);
+
+    p.begin_upload(chunk_root, data_size).unwrap();
+    let (chunk, proof) = chunk_and_proof(&data, 1);
+    p.upload_chunk(chunk_root, 1, chunk, proof).unwrap();
+
+    assert_matches!(p.finish_upload(chunk_root), Err(Error::UploadIncomplete(2)));
+    assert_eq!(p.begin_upload(chunk_root, data_size).unwrap(), vec![0, 2]);
+    assert_matches!(
+        p.begin_upload(chunk_root, data_size + 1),
+        Err(Error::UploadDataSizeMismatch(size)) if size == data_size
+    );
+
+    for chunk_index in [0, 2].iter() {
+        let (chunk, proof) = chunk_and_proof(&data, *chunk_index);
+        p.upload_chunk(chunk_root, *chunk_index, chunk, proof)
+            .unwrap();
+    }
+    assert!(p.finish_upload(chunk_root).unwrap());
+
+    assert_matches!(
+        p.begin_upload(chunk_root, data_size),
+        Err(Error::DataExists)
+    );
+}
+
+#[test]
+fn upload_invalid_chunk_should_fail() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE + 5);
+    let chunk_root = cp_permastore::chunk_tree::chunk_root(&data);
+    let (chunk, proof) = chunk_and_proof(&data, 1);
+
+    assert_matches!(
+        p.upload_chunk(chunk_root, 1, chunk.clone(), proof.clone()),
+        Err(Error::UploadNotFound)
+    );
+
+    p.begin_upload(chunk_root, data.len() as u32).unwrap();
+
+    // Chunk out of range.
+    assert_matches!(
+        p.upload_chunk(chunk_root, 2, chunk.clone(), proof.clone()),
+        Err(Error::InvalidProof)
+    );
+
+    // The last chunk is larger than the declared data size.
+    let (full_chunk, full_chunk_proof) = chunk_and_proof(&data, 0);
+    assert_matches!(
+        p.upload_chunk(chunk_root, 1, full_chunk, full_chunk_proof),
+        Err(Error::ChunkTooLarge)
+    );
+
+    // Tampered chunk.
+    let mut tampered = chunk.clone();
+    tampered.0[0] = tampered.0[0].wrapping_add(1);
+    assert_matches!(
+        p.upload_chunk(chunk_root, 1, tampered, proof.clone()),
+        Err(Error::InvalidProof)
+    );
+
+    // Oversized proof.
+    assert_matches!(
+        p.upload_chunk(
+            chunk_root,
+            1,
+            chunk.clone(),
+            vec![vec![0u8; 256 * 1024 + 1].into()]
+        ),
+        Err(Error::DataPathTooLarge)
+    );
+
+    assert_eq!(
+        p.upload_chunk(chunk_root, 1, chunk.clone(), proof.clone())
+            .unwrap(),
+        1
+    );
+    assert_matches!(
+        p.upload_chunk(chunk_root, 1, chunk, proof),
+        Err(Error::ChunkExists)
+    );
+}
+
+#[test]
+fn finish_upload_with_too_small_data_size_should_fail() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE * 2);
+    let chunk_root = cp_permastore::chunk_tree::chunk_root(&data);
+
+    // Only the first chunk is declared.
+    p.begin_upload(chunk_root, CHUNK_SIZE).unwrap();
+    let (chunk, proof) = chunk_and_proof(&data, 0);
+    assert_eq!(p.upload_chunk(chunk_root, 0, chunk, proof).unwrap(), 0);
+
+    assert_matches!(p.finish_upload(chunk_root), Err(Error::InvalidProof));
+    assert_eq!(
+        PermastoreApi::retrieve(&p, chunk_root.encode().into()).unwrap(),
+        None
+    );
+    assert_matches!(p.finish_upload(chunk_root), Err(Error::UploadNotFound));
+}
+
+#[test]
+fn chunked_upload_should_be_unsafe() {
+    let p = Permastore {
+        deny_unsafe: DenyUnsafe::Yes,
+        ..TestSetup::default().permastore()
+    };
+
+    let data = upload_data(CHUNK_SIZE + 5);
+    let chunk_root = cp_permastore::chunk_tree::chunk_root(&data);
+    let (chunk, proof) = chunk_and_proof(&data, 0);
+
+    assert_matches!(
+        p.begin_upload(chunk_root, data.len() as u32),
+        Err(Error::UnsafeRpcCalled(_))
+    );
+    assert_matches!(
+        p.upload_chunk(chunk_root, 0, chunk, proof),
+        Err(Error::UnsafeRpcCalled(_))
+    );
+    assert_matches!(p.finish_upload(chunk_root), Err(Error::UnsafeRpcCalled(_)));
+    assert!(p
+        .storage
+        .read()
+        .upload_session(&chunk_root.encode())
+        .is_none());
+}
+
+#[test]
+fn upload_session_should_be_tracked_until_expiry() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE + 5);
+    let data_size = data.len() as u32;
+    let chunk_root = cp_permastore::chunk_tree::chunk_root(&data);
+    let key = chunk_root.encode();
+
+    p.begin_upload(chunk_root, data_size).unwrap();
+    assert_eq!(p.pending_data.pending_bytes(), data_size as u64);
+
+    // The expiry restarts once a chunk is uploaded.
+    assert!(p
+        .pending_data
+        .prune(10, 20, |_| false, |_| false, |_| false)
+        .is_empty());
+    let (chunk, proof) = chunk_and_proof(&data, 0);
+    p.upload_chunk(chunk_root, 0, chunk, proof).unwrap();
+    assert!(p
+        .pending_data
+        .prune(25, 20, |_| false, |_| false, |_| false)
+        .is_empty());
+
+    // The abandoned session is removed along with the uploaded chunks.
+    assert_eq!(
+        p.pending_data
+            .prune(45, 20, |_| false, |_| false, |_| false),
+        vec![chunk_root]
+    );
+    p.storage.write().remove(&key);
+    assert!(p.storage.read().upload_session(&key).is_none());
+    assert_eq!(p.storage.read().retrieve_chunk(&key, 0), None);
+    assert_eq!(p.pending_data.pending_bytes(), 0);
+}
+
+#[test]
+fn begin_upload_too_large_data_should_fail() {
+    let p = TestSetup::default().permastore();
+
+    assert_matches!(
+        p.begin_upload(H256::zero(), MAX_CHUNKED_UPLOAD_DATA_SIZE + 1),
+        Err(Error::DataTooLarge(_))
+    );
+}
+
+#[test]
+fn get_chunk_should_return_verifiable_chunk() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE * 2 + 5);
+    let chunk_root = PermastoreApi::submit(&p, data.clone().into()).unwrap();
+
+    for chunk_index in 0..3 {
+        let ChunkWithProof {
+            chunk_index: index,
+            chunk,
+            proof,
+        } = p.get_chunk(chunk_root, chunk_index).unwrap().unwrap();
+
+        assert_eq!(index, chunk_index);
+        assert_eq!(
+            chunk.0,
+            cp_permastore::chunk_tree::chunks(&data)
+                .nth(chunk_index as usize)
+                .unwrap()
+        );
+        let proof = proof.into_iter().map(|p| p.0).collect::<Vec<_>>();
+        assert!(
+            cp_permastore::chunk_tree::verify_chunk(&chunk_root, chunk_index, &chunk, &proof)
+                .is_ok()
+        );
+    }
+
+    assert_eq!(p.get_chunk(chunk_root, 3).unwrap(), None);
+    assert_eq!(p.get_chunk(H256::zero(), 0).unwrap(), None);
+}
+
+#[test]
+fn get_range_should_return_covering_chunks() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE * 3 + 5);
+    let chunk_root = PermastoreApi::submit(&p, data.clone().into()).unwrap();
+
+    let offset = CHUNK_SIZE - 10;
+    let range = p
+        .get_range(chunk_root, offset, CHUNK_SIZE + 20)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(range.data_size, data.len() as u32);
+    assert_eq!(range.len, CHUNK_SIZE + 20);
+    assert_eq!(
+        range
+            .chunks
+            .iter()
+            .map(|c| c.chunk_index)
+            .collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+
+    let mut covered = Vec::new();
+    for ChunkWithProof {
+        chunk_index,
+        chunk,
+        proof,
+    } in range.chunks
+    {
+        let proof = proof.into_iter().map(|p| p.0).collect::<Vec<_>>();
+        assert!(
+            cp_permastore::chunk_tree::verify_chunk(&chunk_root, chunk_index, &chunk, &proof)
+                .is_ok()
+        );
+        covered.extend(chunk.0);
+    }
+
+    let start = (offset % CHUNK_SIZE) as usize;
+    assert_eq!(
+        &covered[start..start + range.len as usize],
+        &data[offset as usize..(offset + range.len) as usize]
+    );
+
+    // Truncated at the end of data.
+    let range = p
+        .get_range(chunk_root, CHUNK_SIZE * 3, CHUNK_SIZE)
+        .unwrap()
+        .unwrap();
+    assert_eq!(range.len, 5);
+    assert_eq!(range.chunks.len(), 1);
+    assert_eq!(range.chunks[0].chunk_index, 3);
+}
+
+#[test]
+fn get_invalid_range_should_fail() {
+    let p = TestSetup::default().permastore();
+
+    let data = upload_data(CHUNK_SIZE + 5);
+    let data_size = data.len() as u32;
+    let chunk_root = PermastoreApi::submit(&p, data.into()).unwrap();
+
+    assert_matches!(
+        p.get_range(chunk_root, data_size, 1),
+        Err(Error::RangeOutOfBounds(size)) if size == data_size
+    );
+    assert_matches!(
+        p.get_range(chunk_root, 0, 0),
+        Err(Error::RangeOutOfBounds(_))
+    );
+    assert_matches!(
+        p.get_range(chunk_root, 0, MAX_DOWNLOAD_DATA_SIZE + 1),
+        Err(Error::DataTooLarge(_))
+    );
+    assert_eq!(p.get_range(H256::zero(), 0, 1).unwrap(), None);
+}
+
+fn verify_bundle_item(bundle_root: &H256, item_id: &H256, bundle_item: BundleItem) -> bool {
+    let chunks = bundle_item
+        .chunks
+        .into_iter()
+        .map(|c| bundle::ProvenChunk {
+            chunk_index: c.chunk_index,
+            chunk: c.chunk.0,
+            proof: c.proof.into_iter().map(|p| p.0).collect(),
+        })
+        .collect::<Vec<_>>();
+    bundle::verify_item(
+        bundle_root,
+        item_id,
+        &bundle_item.item,
+        bundle_item.index,
+        &chunks,
+    )
+    .is_ok()
+}
+
+#[test]
+fn submit_bundle_and_get_item_should_work() {
+    let p = TestSetup::default().permastore();
+
+    let items = vec![b"{\"a\":1}".to_vec(), b"{\"b\":2}".to_vec()];
+    let SubmittedBundle {
+        bundle_root,
+        data_size,
+        item_ids,
+    } = p
+        .submit_bundle(items.iter().cloned().map(Into::into).collect())
+        .unwrap();
+
+    let bundle = bundle::encode(&items).unwrap();
+    assert_eq!(bundle_root, chunk_root(&bundle));
+    assert_eq!(data_size, bundle.len() as u32);
+    assert_eq!(
+        item_ids,
+        items
+            .iter()
+            .map(|item| bundle::item_id(item))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        PermastoreApi::retrieve(&p, bundle_root.encode().into()).unwrap(),
+        Some(bundle.into())
+    );
+
+    for (index, (item, item_id)) in items.iter().zip(item_ids.iter()).enumerate() {
+        let bundle_item = p.get_bundle_item(bundle_root, *item_id).unwrap().unwrap();
+        assert_eq!(bundle_item.item.0, *item);
+        assert_eq!(bundle_item.index, index as u32);
+        assert!(verify_bundle_item(&bundle_root, item_id, bundle_item));
+    }
+
+    // The proof does not hold for any other item.
+    let bundle_item = p
+        .get_bundle_item(bundle_root, item_ids[0])
+        .unwrap()
+        .unwrap();
+    assert!(!verify_bundle_item(&bundle_root, &item_ids[1], bundle_item));
+
+    assert_eq!(
+        p.get_bundle_item(bundle_root, H256::repeat_byte(1))
+            .unwrap(),
+        None
+    );
+    assert_eq!(p.get_bundle_item(H256::zero(), item_ids[0]).unwrap(), None);
+}
+
+#[test]
+fn get_item_of_bundle_with_large_header_should_work() {
+    let p = TestSetup::default().permastore();
+
+    // The header spans two chunks.
+    let items = (0..7000u32)
+        .map(|i| i.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let submitted = p
+        .submit_bundle(items.iter().cloned().map(Into::into).collect())
+        .unwrap();
+    assert!(submitted.data_size > CHUNK_SIZE);
+
+    for index in [0, 6553, 6999] {
+        let item_id = submitted.item_ids[index];
+        let bundle_item = p
+            .get_bundle_item(submitted.bundle_root, item_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bundle_item.item.0, items[index]);
+        assert_eq!(
+            bundle_item
+                .chunks
+                .iter()
+                .map(|c| c.chunk_index)
+                .collect::<Vec<_>>(),
+            bundle::proof_chunk_indices(
+                index as u32,
+                &bundle::BundleEntry {
+                    id: item_id,
+                    offset: bundle::header_size(7000).unwrap() + index as u32 * 4,
+                    size: 4,
+                }
+            )
+            .unwrap()
+        );
+        assert!(verify_bundle_item(
+            &submitted.bundle_root,
+            &item_id,
+            bundle_item
+        ));
+    }
+}
+
+#[test]
+fn bundle_item_not_contained_should_not_be_verified() {
+    let real = b"{\"a\":1}".to_vec();
+    let fake = b"{\"a\":2}".to_vec();
+
+    // The entry claims the fake item while the bundle contains the real one.
+    let mut forged = bundle::encode(&[&real]).unwrap();
+    forged[bundle::ITEM_COUNT_SIZE as usize..][..32]
+        .copy_from_slice(bundle::item_id(&fake).as_bytes());
+
+    let entry = bundle::BundleEntry {
+        id: bundle::item_id(&fake),
+        offset: bundle::header_size(1).unwrap(),
+        size: fake.len() as u32,
+    };
+    let chunks = bundle::proof_chunk_indices(0, &entry)
+        .unwrap()
+        .into_iter()
+        .map(|chunk_index| {
+            let chunk_proof = ChunkProofBuilder::new(forged.clone(), chunk_index * CHUNK_SIZE)
+                .build()
+                .unwrap();
+            bundle::ProvenChunk {
+                chunk_index,
+                chunk: chunk_proof.chunk,
+                proof: chunk_proof.proof,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        bundle::verify_item(&chunk_root(&forged), &entry.id, &fake, 0, &chunks),
+        Err(bundle::BundleError::ItemMismatch)
+    );
+}
+
+#[test]
+fn invalid_bundle_should_fail() {
+    let p = TestSetup::default().permastore();
+
+    assert_matches!(p.submit_bundle(vec![]), Err(Error::InvalidBundle(_)));
+
+    let chunk_root = PermastoreApi::submit(&p, b"mocked data".to_vec().into()).unwrap();
+    assert_matches!(
+        p.get_bundle_item(chunk_root, H256::repeat_byte(1)),
+        Err(Error::InvalidBundle(_))
+    );
+
+    let chunk_root = PermastoreApi::submit(&p, b"abc".to_vec().into()).unwrap();
+    assert_matches!(
+        p.get_bundle_item(chunk_root, H256::repeat_byte(1)),
+        Err(Error::InvalidBundle(_))
+    );
+}
+
+/// Runtime API returning the given order state.
+#[derive(Clone)]
+struct MockRuntimeApi {
+    order: Option<(u64, u64)>,
+}
+
+sp_api::mock_impl_runtime_apis! {
+    impl cp_permastore::PermastoreApi<Block, u64, u32, H256> for MockRuntimeApi {
+        fn find_recall_block(_recall_byte: u64) -> Option<u64> {
+            None
+        }
+
+        fn require_proof_of_access() -> bool {
+            false
+        }
+
+        fn block_size() -> u64 {
+            0
+        }
+
+        fn weave_size() -> u64 {
+            0
+        }
+
+        fn is_forgotten(&self, _block_number: u64, _extrinsic_index: u32) -> bool {
+            self.order.is_none()
+        }
+
+        fn chunk_root(_block_number: u64, _extrinsic_index: u32) -> Option<H256> {
+            None
+        }
+
+        fn data_size(_block_number: u64, _extrinsic_index: u32) -> u32 {
+            0
+        }
+    }
+
+    impl cp_permastore::OrderApi<Block, u64, u64, u32, u64> for MockRuntimeApi {
+        fn order(&self, _block_number: u64, _extrinsic_index: u32) -> Option<(u64, u64)> {
+            self.order
+        }
+    }
+}
+
+/// Test client with the runtime api mocked.
+struct TestClient {
+    client: Arc<Client<Backend>>,
+    runtime_api: MockRuntimeApi,
+}
+
+impl HeaderBackend<Block> for TestClient {
+    fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+        HeaderBackend::header(&*self.client, id)
+    }
+
+    fn info(&self) -> Info<Block> {
+        self.client.info()
+    }
+
+    fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+        self.client.status(id)
+    }
+
+    fn number(&self, hash: H256) -> sp_blockchain::Result<Option<u64>> {
+        self.client.number(hash)
+    }
+
+    fn hash(&self, number: u64) -> sp_blockchain::Result<Option<H256>> {
+        self.client.hash(number)
+    }
+}
+
+impl ProvideRuntimeApi<Block> for TestClient {
+    type Api = MockRuntimeApi;
+
+    fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+        self.runtime_api.clone().into()
+    }
+}
+
+type TestTransactionData =
+    TransactionData<TestClient, cc_datastore::PermanentStorage<Client<Backend>>, Block>;
+
+/// Stores `data` by the extrinsic 1 of genesis block whose hash is `extrinsic_hash`.
+fn transaction_data_with(
+    data: &[u8],
+    extrinsic_hash: H256,
+    order: Option<(u64, u64)>,
+) -> (TestTransactionData, H256) {
+    let client = Arc::new(substrate_test_runtime_client::new());
+    let genesis_hash = client.info().genesis_hash;
+
+    let offchain_storage = LocalStorage::new_test();
+    let mut storage = cc_datastore::PermanentStorage::new(offchain_storage.clone(), client.clone());
+
+    let chunk_root = chunk_root(data);
+    storage.submit(&chunk_root.encode(), data);
+
+    WeaveIndex::<Block>::new(offchain_storage).push_for_test(
+        WeaveBlock {
+            block_number: 0,
+            block_hash: genesis_hash,
+            weave_base: 0,
+            transactions: vec![StoredTransaction {
+                extrinsic_index: 1,
+                chunk_root,
+                data_size: data.len() as u32,
+            }],
+        },
+        &[(1, extrinsic_hash)],
+    );
+
+    let test_client = TestClient {
+        client,
+        runtime_api: MockRuntimeApi { order },
+    };
+
+    (
+        TransactionData::new(Arc::new(test_client), storage),
+        genesis_hash,
+    )
+}
+
+#[test]
+fn retrieve_by_index_should_work() {
+    let data = upload_data(CHUNK_SIZE + 5);
+    let (t, genesis_hash) = transaction_data_with(&data, H256::repeat_byte(1), None);
+
+    assert_eq!(
+        t.retrieve_by_index(genesis_hash, 1).unwrap(),
+        Some(data.into())
+    );
+    assert_eq!(t.retrieve_by_index(genesis_hash, 2).unwrap(), None);
+    // Unknown block.
+    assert_eq!(t.retrieve_by_index(H256::repeat_byte(9), 1).unwrap(), None);
+}
+
+#[test]
+fn retrieve_by_hash_should_work() {
+    let data = b"mocked data".to_vec();
+    let (t, _) = transaction_data_with(&data, H256::repeat_byte(1), None);
+
+    assert_eq!(
+        t.retrieve_by_hash(H256::repeat_byte(1)).unwrap(),
+        Some(data.into())
+    );
+    assert_eq!(t.retrieve_by_hash(H256::repeat_byte(2)).unwrap(), None);
+}
+
+#[test]
+fn retrieve_too_large_data_by_index_should_fail() {
+    let data = vec![0u8; MAX_DOWNLOAD_DATA_SIZE as usize + 1];
+    let (t, genesis_hash) = transaction_data_with(&data, H256::repeat_byte(1), None);
+
+    assert_matches!(
+        t.retrieve_by_index(genesis_hash, 1),
+        Err(Error::DataTooLarge(_))
+    );
+}
+
+#[test]
+fn order_should_work() {
+    let data = b"mocked data".to_vec();
+    let (t, genesis_hash) = transaction_data_with(&data, H256::repeat_byte(1), Some((7, 100)));
+
+    assert_eq!(
+        TransactionDataApi::order(&t, genesis_hash, 1).unwrap(),
+        Some(OrderMetadata {
+            owner: Some(7),
+            data_size: data.len() as u32,
+            chunk_root: chunk_root(&data),
+            fee: Some(100),
+            forgotten: false,
+        })
+    );
+    assert_eq!(
+        TransactionDataApi::order(&t, genesis_hash, 2).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn order_of_forgotten_data_should_work() {
+    let data = b"mocked data".to_vec();
+    let (t, genesis_hash) = transaction_data_with(&data, H256::repeat_byte(1), None);
+
+    assert_eq!(
+        TransactionDataApi::order(&t, genesis_hash, 1).unwrap(),
+        Some(OrderMetadata {
+            owner: None,
+            data_size: data.len() as u32,
+            chunk_root: chunk_root(&data),
+            fee: None,
+            forgotten: true,
+        })
+    );
+}
+
+#[test]
+fn chunk_roots_by_tag_should_work() {
+    let mut transaction_index = TransactionIndex::<Block, u64>::new(LocalStorage::new_test());
+    transaction_index.push_for_test(
+        1,
+        H256::repeat_byte(1),
+        vec![IndexedTransaction {
+            block_number: 1,
+            block_hash: H256::repeat_byte(1),
+            extrinsic_index: 1,
+            chunk_root: chunk_root(b"mocked data"),
+            data_size: 11,
+            owner: None,
+            tags: vec![Tag::new("Content-Type", "text/plain")],
+        }],
+    );
+
+    let indexer = Indexer::new(transaction_index);
+
+    assert_eq!(
+        indexer
+            .chunk_roots_by_tag(
+                b"Content-Type".to_vec().into(),
+                b"text/plain".to_vec().into()
+            )
+            .unwrap(),
+        vec![chunk_root(b"mocked data")]
+    );
+    assert!(indexer
+        .chunk_roots_by_tag(
+            b"Content-Type".to_vec().into(),
+            b"text/html".to_vec().into()
+        )
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn query_transactions_should_work() {
+    let mut transaction_index = TransactionIndex::<Block, u64>::new(LocalStorage::new_test());
+    transaction_index.push_for_test(
+        1,
+        H256::repeat_byte(1),
+        (0..3)
+            .map(|extrinsic_index| IndexedTransaction {
+                block_number: 1,
+                block_hash: H256::repeat_byte(1),
+                extrinsic_index,
+                chunk_root: H256::repeat_byte(100 + extrinsic_index as u8),
+                data_size: 10,
+                owner: Some(extrinsic_index as u64 % 2),
+                tags: vec![Tag::new("Content-Type", "text/plain")],
+            })
+            .collect(),
+    );
+
+    let indexer = Indexer::new(transaction_index);
+
+    let query = indexer_api::TransactionQuery {
+        owner: Some(0),
+        tag: Some(indexer_api::Tag {
+            name: b"Content-Type".to_vec().into(),
+            value: b"text/plain".to_vec().into(),
+        }),
+        from_block: None,
+        to_block: None,
+    };
+
+    let page = indexer
+        .query_transactions(query.clone(), None, Some(1))
+        .unwrap();
+    assert_eq!(
+        page.transactions,
+        vec![TransactionInfo {
+            block_number: 1,
+            block_hash: H256::repeat_byte(1),
+            extrinsic_index: 0,
+            chunk_root: H256::repeat_byte(100),
+            data_size: 10,
+            owner: Some(0),
+            tags: vec![indexer_api::Tag {
+                name: b"Content-Type".to_vec().into(),
+                value: b"text/plain".to_vec().into(),
+            }],
+        }]
+    );
+    assert_eq!(page.next_cursor, Some(1));
+
+    let page = indexer
+        .query_transactions(query.clone(), page.next_cursor, None)
+        .unwrap();
+    assert_eq!(
+        page.transactions
+            .iter()
+            .map(|tx| tx.extrinsic_index)
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(page.next_cursor, None);
+
+    assert_matches!(
+        indexer.query_transactions(query, None, Some(101)),
+        Err(Error::PageTooLarge(_))
+    );
+}
@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Retrieving the transaction data by the position or hash of the extrinsic storing it.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use codec::Codec;
+use serde::{de::DeserializeOwned, Serialize};
+
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{Bytes, Encode};
+use sp_runtime::{
+    generic::BlockId,
+    traits::{Block as BlockT, NumberFor},
+};
+
+use cc_datastore::ChunkRootBackend;
+use cc_rpc_api::permastore::{
+    error::{Error, InvalidCount, Result},
+    transaction_data::{OrderMetadata, TransactionDataApi},
+};
+use cp_permastore::{OrderApi, PermaStorage, PermastoreApi};
+
+use super::MAX_DOWNLOAD_DATA_SIZE;
+
+/// Transaction data API for the full node.
+pub struct TransactionData<C, T, B> {
+    /// Client for accessing the blockchain and runtime.
+    client: Arc<C>,
+    /// Permanent data storage.
+    storage: T,
+    /// Block.
+    phantom: PhantomData<B>,
+}
+
+impl<C, T, B> TransactionData<C, T, B> {
+    /// Creates a new instance of [`TransactionData`].
+    pub fn new(client: Arc<C>, storage: T) -> Self {
+        Self {
+            client,
+            storage,
+            phantom: PhantomData::<B>,
+        }
+    }
+}
+
+fn client_error(e: impl std::error::Error + Send + 'static) -> Error {
+    Error::Client(Box::new(e))
+}
+
+impl<C, T, B> TransactionData<C, T, B>
+where
+    B: BlockT,
+    C: HeaderBackend<B>,
+    T: PermaStorage + ChunkRootBackend<B>,
+{
+    /// Returns the number of block `block_hash` if it's on the best chain.
+    ///
+    /// The datastore indexes the transaction data of the best chain only.
+    fn canonical_number(&self, block_hash: B::Hash) -> Result<Option<NumberFor<B>>> {
+        let number = match self.client.number(block_hash).map_err(client_error)? {
+            Some(number) => number,
+            None => return Ok(None),
+        };
+
+        let canonical_hash = self.client.hash(number).map_err(client_error)?;
+
+        Ok(if canonical_hash == Some(block_hash) {
+            Some(number)
+        } else {
+            None
+        })
+    }
+
+    fn retrieve_at(
+        &self,
+        block_number: NumberFor<B>,
+        extrinsic_index: u32,
+    ) -> Result<Option<Bytes>> {
+        let key = match self.storage.chunk_root(block_number, extrinsic_index) {
+            Some(chunk_root) => chunk_root.encode(),
+            None => return Ok(None),
+        };
+
+        match self.storage.data_meta(&key) {
+            Some(data_meta) => {
+                if data_meta.data_size > MAX_DOWNLOAD_DATA_SIZE {
+                    return Err(Error::DataTooLarge(InvalidCount::new(
+                        data_meta.data_size,
+                        MAX_DOWNLOAD_DATA_SIZE,
+                    )));
+                }
+                Ok(self.storage.retrieve(&key).map(Into::into))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<C, T, B, AccountId, Balance> TransactionDataApi<B::Hash, AccountId, Balance>
+    for TransactionData<C, T, B>
+where
+    B: BlockT,
+    C: HeaderBackend<B> + ProvideRuntimeApi<B> + Send + Sync + 'static,
+    C::Api: PermastoreApi<B, NumberFor<B>, u32, B::Hash>
+        + OrderApi<B, AccountId, NumberFor<B>, u32, Balance>,
+    T: PermaStorage + ChunkRootBackend<B> + 'static,
+    AccountId: Codec + Serialize + DeserializeOwned + Send + Sync + 'static,
+    Balance: Codec + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn retrieve_by_index(
+        &self,
+        block_hash: B::Hash,
+        extrinsic_index: u32,
+    ) -> Result<Option<Bytes>> {
+        match self.canonical_number(block_hash)? {
+            Some(block_number) => self.retrieve_at(block_number, extrinsic_index),
+            None => Ok(None),
+        }
+    }
+
+    fn retrieve_by_hash(&self, extrinsic_hash: B::Hash) -> Result<Option<Bytes>> {
+        match self.storage.extrinsic_position(extrinsic_hash) {
+            Some((block_number, extrinsic_index)) => {
+                self.retrieve_at(block_number, extrinsic_index)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn order(
+        &self,
+        block_hash: B::Hash,
+        extrinsic_index: u32,
+    ) -> Result<Option<OrderMetadata<AccountId, Balance, B::Hash>>> {
+        let block_number = match self.canonical_number(block_hash)? {
+            Some(block_number) => block_number,
+            None => return Ok(None),
+        };
+
+        let (chunk_root, data_size) = match (
+            self.storage.chunk_root(block_number, extrinsic_index),
+            self.storage.data_size(block_number, extrinsic_index),
+        ) {
+            (Some(chunk_root), Some(data_size)) => (chunk_root, data_size),
+            _ => return Ok(None),
+        };
+
+        // The order may be forgotten after its inclusion, always query the latest state.
+        let at = BlockId::Hash(self.client.info().best_hash);
+        let api = self.client.runtime_api();
+
+        let forgotten = api
+            .is_forgotten(&at, block_number, extrinsic_index)
+            .map_err(client_error)?;
+        let (owner, fee) = match api
+            .order(&at, block_number, extrinsic_index)
+            .map_err(client_error)?
+        {
+            Some((owner, fee)) => (Some(owner), Some(fee)),
+            None => (None, None),
+        };
+
+        Ok(Some(OrderMetadata {
+            owner,
+            data_size,
+            chunk_root,
+            fee,
+            forgotten,
+        }))
+    }
+}
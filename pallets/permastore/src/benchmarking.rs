@@ -2,23 +2,31 @@
 use codec::Encode;
 
 use frame_benchmarking::{benchmarks, impl_benchmark_test_suite, whitelisted_caller};
-use frame_support::traits::Currency;
+use frame_support::traits::{Currency, Get};
 use frame_system::RawOrigin;
 use sp_runtime::traits::Hash;
+use sp_std::prelude::*;
 
-use crate::{Call, Config, Pallet};
+use cp_permastore::Tag;
+
+use crate::{Call, Config, Pallet, RecallIndex, RecallIndexLen};
 
 benchmarks! {
     store {
+        let t in 0 .. T::MaxTags::get();
         let caller = whitelisted_caller();
         let balance = 10_000u32;
         let data = b"transaction data";
         let min = T::Currency::minimum_balance().max(1u32.into());
         T::Currency::make_free_balance_be(&caller, min * balance.into());
         let chunk_root = T::Hashing::hash(&data.encode()[..]);
-    }: store (RawOrigin::Signed(caller), data.len() as u32, chunk_root)
+        let half_tag_size = T::MaxTagSize::get() as usize / 2;
+        let tag = Tag::new(sp_std::vec![b'n'; half_tag_size], sp_std::vec![b'v'; half_tag_size]);
+        let tags = sp_std::vec![tag; t as usize];
+    }: store (RawOrigin::Signed(caller), data.len() as u32, chunk_root, tags)
     verify {
-        // TODO
+        let block_tags = Pallet::<T>::block_tags();
+        assert_eq!(block_tags.iter().map(|(_, tags)| tags.len() as u32).sum::<u32>(), t);
     }
 
     forget {
@@ -30,11 +38,31 @@ benchmarks! {
         frame_system::Pallet::<T>::set_block_number(block_number);
         let data = b"transaction data";
         let chunk_root = T::Hashing::hash(&data.encode()[..]);
-        Pallet::<T>::store(RawOrigin::Signed(caller.clone()).into(), data.len() as u32, chunk_root)?;
+        let origin = RawOrigin::Signed(caller.clone()).into();
+        Pallet::<T>::store(origin, data.len() as u32, chunk_root, Vec::new())?;
         let extrinsic_index = 0u32;
-    }: forget (RawOrigin::Signed(caller), block_number, extrinsic_index)
+        let balance_before_forget = T::Currency::free_balance(&caller);
+    }: forget (RawOrigin::Signed(caller.clone()), block_number, extrinsic_index)
+    verify {
+        assert!(Pallet::<T>::is_forgotten(block_number, extrinsic_index));
+        // The remaining endowment is refunded in the worst case.
+        assert!(T::Currency::free_balance(&caller) > balance_before_forget);
+    }
+
+    // The cost of locating the recall block should stay flat as the weave grows.
+    find_recall_block {
+        let n in 1 .. 100_000;
+        for i in 0..n {
+            let weave_size = (i as u64 + 1) * 1024;
+            RecallIndex::<T>::insert(i, (weave_size, T::BlockNumber::from(i + 1)));
+        }
+        RecallIndexLen::<T>::put(n);
+        let recall_byte = n as u64 * 1024 / 3;
+    }: {
+        let _ = Pallet::<T>::find_recall_block(recall_byte);
+    }
     verify {
-        // TODO
+        assert!(Pallet::<T>::find_recall_block(recall_byte).is_some());
     }
 }
 
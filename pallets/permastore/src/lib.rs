@@ -23,12 +23,24 @@
 //! onto the network. It also records some information necessary for
 //! the PoA consensus on chain.
 //!
+//! The chunk root and size of the transaction data stored in a block are
+//! not kept in the state, they are deposited as a [`PERMASTORE_ENGINE_ID`]
+//! digest at the end of block and indexed by the node locally.
+//!
 //! ## Interface
 //!
 //! ### Dispatchable Functions
 //!
-//! * `store`: Make an order of storing data.
-//! * `forget`: Unimplemented.
+//! * `store`: Make an order of storing data, optionally described by a bounded
+//!   list of [`Tag`]s, e.g., `Content-Type`, which are indexed by the node.
+//! * `forget`: Forget the data of a storage order and refund the remaining
+//!   storage fee. The forgotten data is excluded from the PoA consensus.
+//!
+//! ### Storage Endowment
+//!
+//! All the storage fees go to an endowment pool, which pays out the
+//! block authors providing a valid proof of access gradually, see
+//! [`Pallet::payout_endowment`].
 //!
 //! ### Public Functions
 //!
@@ -48,8 +60,16 @@ use codec::{Decode, Encode};
 use scale_info::TypeInfo;
 
 use sp_runtime::{
-    traits::{AccountIdConversion, DispatchInfoOf, SaturatedConversion, SignedExtension},
-    transaction_validity::{InvalidTransaction, TransactionValidity, TransactionValidityError},
+    generic::DigestItem,
+    traits::{
+        AccountIdConversion, Bounded, DispatchInfoOf, SaturatedConversion, Saturating,
+        SignedExtension, Zero,
+    },
+    transaction_validity::{
+        InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError,
+        ValidTransaction,
+    },
+    PerThing, Perbill, Permill,
 };
 use sp_std::{marker::PhantomData, prelude::*};
 
@@ -60,8 +80,11 @@ use frame_support::{
 };
 use frame_system::ensure_signed;
 
+use cp_permastore::{chunk_tree, StoredTransaction, Tag, PERMASTORE_ENGINE_ID};
+
 #[cfg(any(feature = "runtime-benchmarks", test))]
 mod benchmarking;
+pub mod migrations;
 #[cfg(all(feature = "std", test))]
 mod mock;
 #[cfg(all(feature = "std", test))]
@@ -78,6 +101,17 @@ pub type BalanceOf<T> =
 
 type ExtrinsicIndex = u32;
 
+/// Custom validity error code of the `store` call whose data is not stored locally.
+pub const DATA_NOT_STORED: u8 = 100;
+
+/// Custom validity error code of the `store` call whose tags exceed the limits.
+pub const INVALID_TAGS: u8 = 101;
+
+/// Maximum priority of the `store` transaction given by [`CheckStore`].
+///
+/// It's kept small so that the data size does not outweigh the tip.
+pub const MAX_STORE_PRIORITY: TransactionPriority = 256;
+
 #[frame_support::pallet]
 pub mod pallet {
     use super::*;
@@ -96,34 +130,95 @@ pub mod pallet {
         /// The native currency.
         type Currency: Currency<Self::AccountId>;
 
-        /// The treasury pallet id.
-        type TreasuryPalletId: Get<PalletId>;
+        /// The endowment pool pallet id, whose account receives all the storage fees.
+        #[pallet::constant]
+        type EndowmentPalletId: Get<PalletId>;
+
+        /// Maximum proportion of the endowment pool paid out to the block author per block.
+        #[pallet::constant]
+        type EndowmentPayoutRate: Get<Perbill>;
 
         /// Maximum of a transaction data in bytes.
         type MaxDataSize: Get<u32>;
 
+        /// Price of storing one byte of data for one period.
+        #[pallet::constant]
+        type StoragePricePerBytePerPeriod: Get<BalanceOf<Self>>;
+
+        /// Rate at which the storage cost is assumed to decline per period.
+        #[pallet::constant]
+        type StorageCostDeclineRate: Get<Perbill>;
+
+        /// Number of replicas of the data the storage fee pays for.
+        #[pallet::constant]
+        type StorageReplicas: Get<u32>;
+
+        /// Minimum storage fee of a single `store` order.
+        #[pallet::constant]
+        type MinStorageFee: Get<BalanceOf<Self>>;
+
+        /// Maximum number of tags of a single `store` order.
+        #[pallet::constant]
+        type MaxTags: Get<u32>;
+
+        /// Maximum byte size of a tag, i.e., the total size of its name and value.
+        #[pallet::constant]
+        type MaxTagSize: Get<u32>;
+
         /// Weight information for extrinsics in this pallet.
         type WeightInfo: WeightInfo;
     }
 
+    /// The current storage version.
+    const STORAGE_VERSION: StorageVersion = StorageVersion::new(3);
+
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
     #[pallet::hooks]
     impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
         fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
-            // Clear the block size of last block.
+            // Clear the block size and tags of last block.
             <BlockDataSize<T>>::kill();
+            <BlockTags<T>>::kill();
             1
         }
 
         fn on_finalize(n: BlockNumberFor<T>) {
             if <BlockDataSize<T>>::get() > 0 {
                 let latest_weave_size = <WeaveSize<T>>::get();
-                <GlobalWeaveSizeIndex<T>>::append(latest_weave_size);
-                <GlobalBlockNumberIndex<T>>::append(n);
+                let index = <RecallIndexLen<T>>::get();
+                <RecallIndex<T>>::insert(index, (latest_weave_size, n));
+                <RecallIndexLen<T>>::put(index + 1);
             }
+
+            // The transaction data info is indexed by the node, no need to keep them in state.
+            let block_transactions = <BlockTransactions<T>>::take();
+            if !block_transactions.is_empty() {
+                <frame_system::Pallet<T>>::deposit_log(DigestItem::Consensus(
+                    PERMASTORE_ENGINE_ID,
+                    block_transactions.encode(),
+                ));
+            }
+        }
+
+        fn on_runtime_upgrade() -> Weight {
+            migrations::v1::migrate::<T>()
+                .saturating_add(migrations::v2::migrate::<T>())
+                .saturating_add(migrations::v3::migrate::<T>())
+                .saturating_add(Pallet::<T>::ensure_endowment_account())
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn pre_upgrade() -> Result<(), &'static str> {
+            migrations::v3::pre_migrate::<T>()
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn post_upgrade() -> Result<(), &'static str> {
+            migrations::v3::post_migrate::<T>()
         }
     }
 
@@ -134,14 +229,22 @@ pub mod pallet {
         /// The minimum data size is 1 bytes, the maximum is `MAX_DATA_SIZE`.
         /// The digest of data will be recorded on chain, the actual data has
         /// to be stored off-chain before executing this extrinsic.
-        #[pallet::weight(T::WeightInfo::store())]
-        pub fn store(origin: OriginFor<T>, data_size: u32, chunk_root: T::Hash) -> DispatchResult {
+        ///
+        /// At most `MaxTags` tags of `MaxTagSize` bytes each can be attached.
+        #[pallet::weight(T::WeightInfo::store(tags.len() as u32))]
+        pub fn store(
+            origin: OriginFor<T>,
+            data_size: u32,
+            chunk_root: T::Hash,
+            tags: Vec<Tag>,
+        ) -> DispatchResult {
             let sender = ensure_signed(origin)?;
 
             ensure!(
                 data_size > 0 && data_size < T::MaxDataSize::get(),
                 Error::<T>::InvalidDataSize
             );
+            Self::ensure_valid_tags(&tags)?;
             ensure!(Self::stored_locally(&chunk_root), Error::<T>::NotStored);
 
             // TODO: ensure the validity of stored data in the local DB?
@@ -152,10 +255,17 @@ pub mod pallet {
             let extrinsic_index = frame_system::Pallet::<T>::extrinsic_index().unwrap_or_default();
 
             Orders::<T>::insert(&sender, (block_number, extrinsic_index), storage_fee);
+            OrderOwners::<T>::insert((block_number, extrinsic_index), &sender);
+
+            <BlockTransactions<T>>::append(StoredTransaction {
+                extrinsic_index,
+                chunk_root,
+                data_size,
+            });
 
-            // FIXME: store these info in db directly.
-            ChunkRootIndex::<T>::insert((block_number, extrinsic_index), chunk_root);
-            TransactionDataSize::<T>::insert((block_number, extrinsic_index), data_size);
+            if !tags.is_empty() {
+                <BlockTags<T>>::append((extrinsic_index, tags));
+            }
 
             <BlockDataSize<T>>::mutate(|s| *s += data_size as u64);
             <WeaveSize<T>>::mutate(|s| *s += data_size as u64);
@@ -180,11 +290,14 @@ pub mod pallet {
             let sender = ensure_signed(origin)?;
 
             // Remove the order.
-            let _fee = Orders::<T>::take(&sender, (block_number, extrinsic_index))
+            let fee = Orders::<T>::take(&sender, (block_number, extrinsic_index))
                 .ok_or(Error::<T>::OrderDoesNotExist)?;
+            OrderOwners::<T>::remove((block_number, extrinsic_index));
+
+            Forgotten::<T>::insert((block_number, extrinsic_index), ());
 
             // refund the remaining fee.
-            Self::refund_storage_fee(&sender, block_number);
+            Self::refund_storage_fee(&sender, block_number, fee);
 
             Self::deposit_event(Event::Forgot(block_number, extrinsic_index));
 
@@ -192,6 +305,23 @@ pub mod pallet {
         }
     }
 
+    #[pallet::genesis_config]
+    pub struct GenesisConfig;
+
+    #[cfg(feature = "std")]
+    impl Default for GenesisConfig {
+        fn default() -> Self {
+            Self
+        }
+    }
+
+    #[pallet::genesis_build]
+    impl<T: Config> GenesisBuild<T> for GenesisConfig {
+        fn build(&self) {
+            Pallet::<T>::ensure_endowment_account();
+        }
+    }
+
     /// Event for the Permastore pallet.
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
@@ -200,6 +330,8 @@ pub mod pallet {
         Stored(T::AccountId, T::Hash),
         /// The data has been forgotten. [block_number, extrinsic_index]
         Forgot(T::BlockNumber, ExtrinsicIndex),
+        /// The block author has been paid from the endowment pool. [author, amount]
+        EndowmentPaid(T::AccountId, BalanceOf<T>),
     }
 
     /// Error for the Permastore pallet.
@@ -211,6 +343,10 @@ pub mod pallet {
         NotStored,
         /// The storage order does not exist.
         OrderDoesNotExist,
+        /// The number of tags exceeds `MaxTags`.
+        TooManyTags,
+        /// The size of a tag exceeds `MaxTagSize`.
+        TagTooLarge,
     }
 
     /// Map of all the storage orders.
@@ -225,6 +361,13 @@ pub mod pallet {
         BalanceOf<T>,
     >;
 
+    /// Owners of the storage orders, (block_number, extrinsic_index) => owner
+    ///
+    /// The reverse index of [`Orders`] for looking up an order by its position only.
+    #[pallet::storage]
+    pub(super) type OrderOwners<T: Config> =
+        StorageMap<_, Twox64Concat, (T::BlockNumber, ExtrinsicIndex), T::AccountId>;
+
     /// Total byte size of data stored onto the network so far.
     ///
     /// In another word, it equals to the sum of [`BlockDataSize`]
@@ -237,64 +380,127 @@ pub mod pallet {
     #[pallet::getter(fn block_data_size)]
     pub(super) type BlockDataSize<T: Config> = StorageValue<_, u64, ValueQuery>;
 
-    /// (block_number, extrinsic_index) => Option<chunk_root>
+    /// Transaction data stored during current block.
+    ///
+    /// It's deposited as a [`PERMASTORE_ENGINE_ID`] digest and cleared at the end of block.
+    #[pallet::storage]
+    pub(super) type BlockTransactions<T: Config> =
+        StorageValue<_, Vec<StoredTransaction<T::Hash>>, ValueQuery>;
+
+    /// Tags of the transaction data stored during current block, extrinsic_index => tags
+    ///
+    /// Unlike [`BlockTransactions`], it's cleared at the beginning of next block, so
+    /// that the node can read the tags of a block from its state.
+    #[pallet::storage]
+    pub(super) type BlockTags<T: Config> =
+        StorageValue<_, Vec<(ExtrinsicIndex, Vec<Tag>)>, ValueQuery>;
+
+    /// Number of the entries in [`RecallIndex`].
+    #[pallet::storage]
+    #[pallet::getter(fn recall_index_len)]
+    pub(super) type RecallIndexLen<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Index for locating the recall block, index => (weave_size, block_number).
+    ///
+    /// One entry is appended at the end of each block containing data, the weave
+    /// sizes are therefore in ascending order, which enables finding the recall
+    /// block with a binary search in `O(log n)` storage reads.
+    #[pallet::storage]
+    #[pallet::getter(fn recall_index)]
+    pub(super) type RecallIndex<T: Config> =
+        StorageMap<_, Twox64Concat, u32, (u64, T::BlockNumber)>;
+
+    /// Legacy chunk root of transaction data, (block_number, extrinsic_index) => chunk_root
+    ///
+    /// No longer written since storage version 2, see [`BlockTransactions`].
     #[pallet::storage]
-    #[pallet::getter(fn chunk_root_index)]
+    #[pallet::getter(fn legacy_chunk_root)]
     pub(super) type ChunkRootIndex<T: Config> =
         StorageMap<_, Twox64Concat, (T::BlockNumber, ExtrinsicIndex), T::Hash>;
 
-    /// (block_number, extrinsic_index) => transaction_data_size
+    /// Legacy size of transaction data, (block_number, extrinsic_index) => data_size
+    ///
+    /// No longer written since storage version 2, see [`BlockTransactions`].
     #[pallet::storage]
-    #[pallet::getter(fn transaction_data_size)]
+    #[pallet::getter(fn legacy_data_size)]
     pub(super) type TransactionDataSize<T: Config> =
-        StorageMap<_, Twox64Concat, (T::BlockNumber, ExtrinsicIndex), u32, ValueQuery>;
+        StorageMap<_, Twox64Concat, (T::BlockNumber, ExtrinsicIndex), u32>;
 
-    /// FIXME: find a proper way to store these info.
+    /// Set of the forgotten transaction data, (block_number, extrinsic_index) => ()
     ///
-    /// Temp solution for locating the recall block. An ever increasing array of global weave size.
-    #[pallet::storage]
-    #[pallet::getter(fn global_block_size_index)]
-    pub(super) type GlobalWeaveSizeIndex<T: Config> = StorageValue<_, Vec<u64>, ValueQuery>;
-
-    /// Temp solution for locating the recall block.
+    /// The forgotten data can not be used as the recall data in PoA consensus.
     #[pallet::storage]
-    #[pallet::getter(fn global_block_number_index)]
-    pub(super) type GlobalBlockNumberIndex<T: Config> =
-        StorageValue<_, Vec<T::BlockNumber>, ValueQuery>;
+    pub(super) type Forgotten<T: Config> =
+        StorageMap<_, Twox64Concat, (T::BlockNumber, ExtrinsicIndex), ()>;
 }
 
 impl<T: Config> Pallet<T> {
-    /// Returns the chunk root given `block_number` and `extrinsic_index`.
-    pub fn chunk_root(block_number: T::BlockNumber, extrinsic_index: u32) -> Option<T::Hash> {
-        <ChunkRootIndex<T>>::get((block_number, extrinsic_index))
-    }
-
     /// Returns the block number in which the recall byte is included.
+    ///
+    /// The recall block is the first block whose weave size is not less than
+    /// `recall_byte` in [`RecallIndex`].
     pub fn find_recall_block(recall_byte: u64) -> Option<T::BlockNumber> {
+        let (mut low, mut high) = (0u32, <RecallIndexLen<T>>::get());
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (weave_size, _) = <RecallIndex<T>>::get(mid)?;
+            if weave_size < recall_byte {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let recall_block = <RecallIndex<T>>::get(low).map(|(_, block_number)| block_number);
+
         frame_support::log::debug!(
             target: "runtime::permastore",
-            "Global weave size list: {:?}",
-            <GlobalBlockNumberIndex<T>>::get()
-                .iter()
-                .zip(<GlobalWeaveSizeIndex<T>>::get().iter())
-                .collect::<Vec<_>>()
+            "Recall block {:?} was found at index {} given the recall byte {}",
+            recall_block,
+            low,
+            recall_byte,
         );
-        let weave_size_list = <GlobalWeaveSizeIndex<T>>::get();
 
-        let recall_block_number_index =
-            match weave_size_list.binary_search_by_key(&recall_byte, |&weave_size| weave_size) {
-                Ok(i) => i,
-                Err(i) => i,
-            };
+        recall_block
+    }
+
+    /// Returns `true` if the transaction data given `block_number` and `extrinsic_index`
+    /// has been forgotten.
+    pub fn is_forgotten(block_number: T::BlockNumber, extrinsic_index: u32) -> bool {
+        <Forgotten<T>>::contains_key((block_number, extrinsic_index))
+    }
+
+    /// Returns the owner and storage fee of the order given `block_number` and
+    /// `extrinsic_index`.
+    ///
+    /// Returns `None` if the order does not exist or has been forgotten.
+    pub fn order(
+        block_number: T::BlockNumber,
+        extrinsic_index: ExtrinsicIndex,
+    ) -> Option<(T::AccountId, BalanceOf<T>)> {
+        let owner = <OrderOwners<T>>::get((block_number, extrinsic_index))?;
+        let fee = <Orders<T>>::get(&owner, (block_number, extrinsic_index))?;
+        Some((owner, fee))
+    }
 
-        <GlobalBlockNumberIndex<T>>::get()
-            .get(recall_block_number_index)
-            .copied()
+    /// Returns the tags of the transaction data stored in current block.
+    pub fn block_tags() -> Vec<(ExtrinsicIndex, Vec<Tag>)> {
+        <BlockTags<T>>::get()
     }
 
-    /// Returns the data size of transaction given `block_number` and `extrinsic_index`.
-    pub fn data_size(block_number: T::BlockNumber, extrinsic_index: u32) -> u32 {
-        <TransactionDataSize<T>>::get((block_number, extrinsic_index))
+    /// Ensures `tags` are within the limits of [`Config::MaxTags`] and [`Config::MaxTagSize`].
+    fn ensure_valid_tags(tags: &[Tag]) -> Result<(), Error<T>> {
+        ensure!(
+            tags.len() <= T::MaxTags::get() as usize,
+            Error::<T>::TooManyTags
+        );
+        ensure!(
+            tags.iter()
+                .all(|tag| tag.size() <= T::MaxTagSize::get() as usize),
+            Error::<T>::TagTooLarge
+        );
+        Ok(())
     }
 
     /// Returns true if poa proof should be included and verified.
@@ -312,40 +518,183 @@ impl<T: Config> Pallet<T> {
         <WeaveSize<T>>::get()
     }
 
-    // TODO: ensure the transaction data has been indeed stored in the local DB.
-    fn stored_locally(_chunk_root: &T::Hash) -> bool {
-        true
+    /// Returns `true` if the transaction data of `chunk_root` exists in the local DB.
+    ///
+    /// The check is only effective during the transaction validation in the pool,
+    /// see [`cp_permastore::permastore::data_exists`] for more details.
+    fn stored_locally(chunk_root: &T::Hash) -> bool {
+        cp_permastore::permastore::data_exists(&chunk_root.encode())
+    }
+
+    /// Returns the perpetual storage fee of `data_size` bytes.
+    ///
+    /// The fee is an endowment for storing [`Config::StorageReplicas`] replicas
+    /// of the data forever. Given the storage cost declines by
+    /// [`Config::StorageCostDeclineRate`] each period, the cost of all periods
+    /// sums up to:
+    ///
+    /// `data_size * replicas * price_per_byte_per_period / decline_rate`
+    ///
+    /// The fee is never lower than [`Config::MinStorageFee`].
+    pub fn storage_fee(data_size: u32) -> BalanceOf<T> {
+        let decline_rate = T::StorageCostDeclineRate::get();
+
+        let fee = if decline_rate.is_zero() {
+            // The storage cost never declines, no finite endowment is enough.
+            BalanceOf::<T>::max_value()
+        } else {
+            let cost_per_period = T::StoragePricePerBytePerPeriod::get()
+                .saturating_mul(data_size.into())
+                .saturating_mul(T::StorageReplicas::get().into());
+            decline_rate.saturating_reciprocal_mul(cost_per_period)
+        };
+
+        fee.max(T::MinStorageFee::get())
+    }
+
+    /// Returns the account of endowment pool.
+    pub fn endowment_account_id() -> T::AccountId {
+        T::EndowmentPalletId::get().into_account()
     }
 
-    // TODO: calculate the perpetual storage cost based on the data size.
-    fn calculate_storage_fee(data_size: u32) -> BalanceOf<T> {
-        data_size.saturated_into()
+    /// Creates the endowment pool account with the existential deposit if it does not exist.
+    ///
+    /// The storage fee lower than the existential deposit and the payout can not be
+    /// accepted otherwise. This is done in genesis and on runtime upgrade for the
+    /// existing chains.
+    pub(crate) fn ensure_endowment_account() -> Weight {
+        let account_id = Self::endowment_account_id();
+        let min = T::Currency::minimum_balance();
+        if T::Currency::free_balance(&account_id) < min {
+            let _ = T::Currency::make_free_balance_be(&account_id, min);
+            // The account and the total issuance.
+            T::DbWeight::get().reads_writes(1, 2)
+        } else {
+            T::DbWeight::get().reads(1)
+        }
     }
 
-    /// Charges the perpetual storage fee.
+    /// Pays the block author out of the endowment pool and returns the actual payout.
     ///
-    /// TODO: Currently all the fee is simply transfered to the treasury,
-    /// we might want a new destination for that.
+    /// At most [`Config::EndowmentPayoutRate`] of the pool is released per block,
+    /// which is further scaled by the estimated `storage_capacity` of block author,
+    /// i.e., the author storing more data is paid more.
+    pub fn payout_endowment(author: &T::AccountId, storage_capacity: Permill) -> BalanceOf<T> {
+        let endowment_account = Self::endowment_account_id();
+        let releasable = T::Currency::free_balance(&endowment_account)
+            .saturating_sub(T::Currency::minimum_balance());
+        let payout = storage_capacity * (T::EndowmentPayoutRate::get() * releasable);
+
+        if payout.is_zero() {
+            return payout;
+        }
+
+        match T::Currency::transfer(
+            &endowment_account,
+            author,
+            payout,
+            ExistenceRequirement::KeepAlive,
+        ) {
+            Ok(()) => {
+                Self::deposit_event(Event::EndowmentPaid(author.clone(), payout));
+                payout
+            }
+            Err(e) => {
+                frame_support::log::debug!(
+                    target: "runtime::permastore",
+                    "Failed to pay {:?} to the block author: {:?}",
+                    payout,
+                    e,
+                );
+                Zero::zero()
+            }
+        }
+    }
+
+    /// Charges the perpetual storage fee, which goes to the endowment pool.
     fn charge_storage_fee(
         who: &T::AccountId,
         data_size: u32,
     ) -> Result<BalanceOf<T>, sp_runtime::DispatchError> {
-        let fee = Self::calculate_storage_fee(data_size);
-        let treasury_account: T::AccountId = T::TreasuryPalletId::get().into_account();
-        T::Currency::transfer(who, &treasury_account, fee, ExistenceRequirement::KeepAlive)?;
+        let fee = Self::storage_fee(data_size);
+        T::Currency::transfer(
+            who,
+            &Self::endowment_account_id(),
+            fee,
+            ExistenceRequirement::KeepAlive,
+        )?;
         Ok(fee)
     }
 
-    fn refund_storage_fee(_who: &T::AccountId, _created_at: T::BlockNumber) {}
+    /// Refunds the remaining endowment of an order and returns the actual refund.
+    ///
+    /// The endowment pool releases at most [`Config::EndowmentPayoutRate`] of the
+    /// pool per block, the remaining endowment of an order created at `created_at`
+    /// is therefore estimated as `fee * (1 - payout_rate) ^ elapsed_blocks`.
+    fn refund_storage_fee(
+        who: &T::AccountId,
+        created_at: T::BlockNumber,
+        fee: BalanceOf<T>,
+    ) -> BalanceOf<T> {
+        let elapsed_blocks = frame_system::Pallet::<T>::block_number()
+            .saturating_sub(created_at)
+            .saturated_into::<usize>();
+        let remaining = Perbill::one()
+            .saturating_sub(T::EndowmentPayoutRate::get())
+            .saturating_pow(elapsed_blocks);
+
+        let endowment_account = Self::endowment_account_id();
+        let releasable = T::Currency::free_balance(&endowment_account)
+            .saturating_sub(T::Currency::minimum_balance());
+        let refund = (remaining * fee).min(releasable);
+
+        if refund.is_zero() {
+            return refund;
+        }
+
+        match T::Currency::transfer(
+            &endowment_account,
+            who,
+            refund,
+            ExistenceRequirement::KeepAlive,
+        ) {
+            Ok(()) => refund,
+            Err(e) => {
+                frame_support::log::debug!(
+                    target: "runtime::permastore",
+                    "Failed to refund {:?} to {:?}: {:?}",
+                    refund,
+                    who,
+                    e,
+                );
+                Zero::zero()
+            }
+        }
+    }
 }
 
 /// A signed extension that checks for the `store` call.
 ///
-/// It ensures the transaction data has been stored locally.
+/// It ensures the transaction data has been stored locally, the tags are within
+/// the limits and the sender is able to pay the storage fee. The priority of `store` transaction
+/// is the number of chunks of data, up to [`MAX_STORE_PRIORITY`].
 #[derive(Encode, Decode, Clone, Eq, PartialEq, TypeInfo)]
 #[scale_info(skip_type_params(T))]
 pub struct CheckStore<T: Config + Send + Sync>(PhantomData<T>);
 
+impl<T: Config + Send + Sync> CheckStore<T> {
+    /// Creates new `SignedExtension` to check the `store` call.
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Config + Send + Sync> Default for CheckStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Config + Send + Sync> sp_std::fmt::Debug for CheckStore<T> {
     fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
         write!(f, "CheckStore")
@@ -359,7 +708,8 @@ where
     const IDENTIFIER: &'static str = "CheckStore";
     type AccountId = T::AccountId;
     type Call = <T as frame_system::Config>::Call;
-    // TODO: Sign the chunk root
+    // The chunk root is a parameter of `store` call, which is already
+    // a part of the signed payload, no need to sign it again here.
     type AdditionalSigned = ();
     type Pre = ();
 
@@ -377,27 +727,35 @@ where
         if let Some(Call::store {
             data_size,
             chunk_root,
-            ..
+            tags,
         }) = call.is_sub_type()
         {
-            // TODO:
-            //
-            // 1. Check the balance is enough to pay the storage fee according to the data size.
-            //
-            // 2. Check if the data has been stored locally.
-            //
-            // 3. Adjust the transaction priority according to the data size.
+            ensure!(
+                Pallet::<T>::ensure_valid_tags(tags).is_ok(),
+                InvalidTransaction::Custom(INVALID_TAGS)
+            );
 
+            // The storage fee is charged with `KeepAlive`, the existential deposit
+            // has to be retained after paying the fee.
+            let usable_balance =
+                T::Currency::free_balance(who).saturating_sub(T::Currency::minimum_balance());
             ensure!(
-                T::Currency::free_balance(who) >= Pallet::<T>::calculate_storage_fee(*data_size),
+                usable_balance >= Pallet::<T>::storage_fee(*data_size),
                 InvalidTransaction::Payment
             );
 
-            const DATA_NOT_STORED: u8 = 100;
             ensure!(
                 Pallet::<T>::stored_locally(chunk_root),
                 InvalidTransaction::Custom(DATA_NOT_STORED)
             );
+
+            // The larger data pays more storage fee, thus being prioritized.
+            let priority = (chunk_tree::chunk_count(*data_size) as TransactionPriority)
+                .min(MAX_STORE_PRIORITY);
+            return Ok(ValidTransaction {
+                priority,
+                ..Default::default()
+            });
         }
 
         Ok(Default::default())
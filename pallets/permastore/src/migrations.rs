@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+//! Storage migrations for the Permastore pallet.
+
+use super::*;
+
+use frame_support::{
+    storage::migration,
+    traits::{GetStorageVersion, PalletInfoAccess, StorageVersion},
+};
+
+/// Migrates the recall index from the vectors to a map.
+pub mod v1 {
+    use super::*;
+
+    /// Moves the entries of deprecated `GlobalWeaveSizeIndex` and `GlobalBlockNumberIndex`
+    /// into [`RecallIndex`].
+    pub fn migrate<T: Config>() -> Weight {
+        let on_chain_version = Pallet::<T>::on_chain_storage_version();
+
+        if on_chain_version >= 1 {
+            frame_support::log::info!(
+                target: "runtime::permastore",
+                "Skipping the migration v1 as the on-chain storage version is {:?}",
+                on_chain_version,
+            );
+            return T::DbWeight::get().reads(1);
+        }
+
+        let pallet_name = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+
+        let weave_sizes: Vec<u64> =
+            migration::take_storage_value(pallet_name, b"GlobalWeaveSizeIndex", &[])
+                .unwrap_or_default();
+        let block_numbers: Vec<T::BlockNumber> =
+            migration::take_storage_value(pallet_name, b"GlobalBlockNumberIndex", &[])
+                .unwrap_or_default();
+
+        let mut len = 0u32;
+        for (weave_size, block_number) in weave_sizes.into_iter().zip(block_numbers) {
+            RecallIndex::<T>::insert(len, (weave_size, block_number));
+            len += 1;
+        }
+        RecallIndexLen::<T>::put(len);
+
+        StorageVersion::new(1).put::<Pallet<T>>();
+
+        frame_support::log::info!(
+            target: "runtime::permastore",
+            "Migrated {} entries to the recall index",
+            len,
+        );
+
+        T::DbWeight::get().reads_writes(3, len as Weight + 4)
+    }
+}
+
+/// Stops writing the chunk root and data size of transactions to the state.
+pub mod v2 {
+    use super::*;
+
+    /// Marks the deprecated [`ChunkRootIndex`] and [`TransactionDataSize`] as legacy.
+    ///
+    /// Since then, these info are deposited as a [`PERMASTORE_ENGINE_ID`] digest
+    /// and indexed by the node. The existing entries are kept as is, they're the
+    /// only source of the transaction data stored before the digest was introduced,
+    /// from which the node backfills its index via `PermastoreApi::chunk_root` and
+    /// `PermastoreApi::data_size`.
+    pub fn migrate<T: Config>() -> Weight {
+        let on_chain_version = Pallet::<T>::on_chain_storage_version();
+
+        if on_chain_version >= 2 {
+            frame_support::log::info!(
+                target: "runtime::permastore",
+                "Skipping the migration v2 as the on-chain storage version is {:?}",
+                on_chain_version,
+            );
+            return T::DbWeight::get().reads(1);
+        }
+
+        StorageVersion::new(2).put::<Pallet<T>>();
+
+        frame_support::log::info!(
+            target: "runtime::permastore",
+            "Kept ChunkRootIndex and TransactionDataSize as legacy storage",
+        );
+
+        T::DbWeight::get().reads_writes(1, 1)
+    }
+}
+
+/// Adds the reverse index of storage orders.
+pub mod v3 {
+    use super::*;
+
+    /// Populates [`OrderOwners`] from the existing [`Orders`].
+    ///
+    /// All the orders are indexed in one block, whose number is logged by
+    /// [`pre_migrate`] ahead of the upgrade with `try-runtime`.
+    pub fn migrate<T: Config>() -> Weight {
+        let on_chain_version = Pallet::<T>::on_chain_storage_version();
+
+        if on_chain_version >= 3 {
+            frame_support::log::info!(
+                target: "runtime::permastore",
+                "Skipping the migration v3 as the on-chain storage version is {:?}",
+                on_chain_version,
+            );
+            return T::DbWeight::get().reads(1);
+        }
+
+        let mut count = 0u64;
+        for (owner, position, _fee) in Orders::<T>::iter() {
+            OrderOwners::<T>::insert(position, owner);
+            count += 1;
+        }
+
+        StorageVersion::new(3).put::<Pallet<T>>();
+
+        frame_support::log::info!(
+            target: "runtime::permastore",
+            "Migrated {} entries to the order owners",
+            count,
+        );
+
+        T::DbWeight::get().reads_writes(count + 1, count + 1)
+    }
+
+    /// Checks the state before [`migrate`], logging the number of orders to be indexed.
+    pub fn pre_migrate<T: Config>() -> Result<(), &'static str> {
+        if Pallet::<T>::on_chain_storage_version() >= 3 {
+            return Ok(());
+        }
+
+        if OrderOwners::<T>::iter().next().is_some() {
+            return Err("OrderOwners must be empty before the migration v3");
+        }
+
+        frame_support::log::info!(
+            target: "runtime::permastore",
+            "{} orders to be indexed by the migration v3",
+            Orders::<T>::iter().count(),
+        );
+
+        Ok(())
+    }
+
+    /// Checks every order is indexed in [`OrderOwners`] after [`migrate`].
+    pub fn post_migrate<T: Config>() -> Result<(), &'static str> {
+        if Pallet::<T>::on_chain_storage_version() < 3 {
+            return Err("Permastore storage version must be 3 after the migration v3");
+        }
+
+        let mut count = 0usize;
+        for (owner, position, _fee) in Orders::<T>::iter() {
+            if OrderOwners::<T>::get(position) != Some(owner) {
+                return Err("Order is not indexed in OrderOwners after the migration v3");
+            }
+            count += 1;
+        }
+
+        if OrderOwners::<T>::iter().count() != count {
+            return Err("OrderOwners has entries without order after the migration v3");
+        }
+
+        Ok(())
+    }
+}
@@ -18,6 +18,8 @@
 
 use super::*;
 
+use std::collections::HashMap;
+
 use frame_support::{parameter_types, traits::Everything, PalletId};
 use sp_core::H256;
 // The testing primitives are very useful for avoiding having to work with signatures
@@ -30,6 +32,8 @@ use sp_runtime::{
 // Reexport crate as its pallet name for construct_runtime.
 use crate as pallet_permastore;
 
+use cp_permastore::{chunk_tree, DataMeta, PermaStorage, PermaStorageExt, UploadSession};
+
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 type Block = frame_system::mocking::MockBlock<Test>;
 
@@ -42,7 +46,7 @@ frame_support::construct_runtime!(
     {
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
-        Permastore: pallet_permastore::{Pallet, Call, Storage, Event<T>},
+        Permastore: pallet_permastore::{Pallet, Call, Config, Storage, Event<T>},
     }
 );
 
@@ -93,17 +97,112 @@ impl pallet_balances::Config for Test {
     type WeightInfo = ();
 }
 parameter_types! {
-    pub const TreasuryPalletId: PalletId = PalletId(*b"py/trsry");
+    pub const EndowmentPalletId: PalletId = PalletId(*b"py/endow");
+    pub const EndowmentPayoutRate: Perbill = Perbill::from_percent(10);
     pub const MaxDataSize: u32 = 1024 * 1024 * 1024;
+    // The storage fee equals to the data size by default.
+    pub static StoragePricePerBytePerPeriod: u64 = 1;
+    pub static StorageCostDeclineRate: Perbill = Perbill::one();
+    pub static StorageReplicas: u32 = 1;
+    pub static MinStorageFee: u64 = 1;
+    pub const MaxTags: u32 = 2;
+    pub const MaxTagSize: u32 = 16;
 }
 impl Config for Test {
     type Event = Event;
     type Currency = Balances;
-    type TreasuryPalletId = TreasuryPalletId;
+    type EndowmentPalletId = EndowmentPalletId;
+    type EndowmentPayoutRate = EndowmentPayoutRate;
     type MaxDataSize = MaxDataSize;
+    type StoragePricePerBytePerPeriod = StoragePricePerBytePerPeriod;
+    type StorageCostDeclineRate = StorageCostDeclineRate;
+    type StorageReplicas = StorageReplicas;
+    type MinStorageFee = MinStorageFee;
+    type MaxTags = MaxTags;
+    type MaxTagSize = MaxTagSize;
     type WeightInfo = ();
 }
 
+/// A stand-in for the local permanent storage of node.
+#[derive(Default)]
+pub struct MockPermaStorage {
+    data: HashMap<Vec<u8>, Vec<u8>>,
+    chunks: HashMap<(Vec<u8>, u32), Vec<u8>>,
+    uploads: HashMap<Vec<u8>, UploadSession>,
+}
+
+impl MockPermaStorage {
+    /// Creates a new storage in which the data of `chunk_roots` exist.
+    pub fn with_chunk_roots(chunk_roots: &[H256]) -> Self {
+        let mut storage = Self::default();
+        for chunk_root in chunk_roots {
+            storage.submit(&chunk_root.encode(), b"mocked data");
+        }
+        storage
+    }
+}
+
+impl PermaStorage for MockPermaStorage {
+    fn submit(&mut self, key: &[u8], value: &[u8]) {
+        for (chunk_index, chunk) in chunk_tree::chunks(value).enumerate() {
+            self.submit_chunk(key, chunk_index as u32, chunk);
+        }
+        self.data.insert(key.to_vec(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.data.remove(key);
+        self.uploads.remove(key);
+        self.chunks.retain(|(chunk_key, _), _| chunk_key != key);
+    }
+
+    fn retrieve(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.data.get(key).cloned()
+    }
+
+    fn data_meta(&self, key: &[u8]) -> Option<DataMeta> {
+        self.data.get(key).map(|value| DataMeta {
+            data_size: value.len() as u32,
+            chunk_hashes: chunk_tree::chunk_hashes(value),
+        })
+    }
+
+    fn submit_chunk(&mut self, key: &[u8], chunk_index: u32, chunk: &[u8]) {
+        self.chunks
+            .insert((key.to_vec(), chunk_index), chunk.to_vec());
+    }
+
+    fn retrieve_chunk(&self, key: &[u8], chunk_index: u32) -> Option<Vec<u8>> {
+        self.chunks.get(&(key.to_vec(), chunk_index)).cloned()
+    }
+
+    fn upload_session(&self, key: &[u8]) -> Option<UploadSession> {
+        self.uploads.get(key).cloned()
+    }
+
+    fn set_upload_session(&mut self, key: &[u8], upload_session: &UploadSession) {
+        self.uploads.insert(key.to_vec(), upload_session.clone());
+    }
+
+    fn finish_upload(&mut self, key: &[u8]) -> Option<DataMeta> {
+        let upload_session = self.upload_session(key)?;
+
+        if !upload_session.is_complete() {
+            return None;
+        }
+
+        let value = (0..upload_session.chunk_count())
+            .map(|chunk_index| self.retrieve_chunk(key, chunk_index))
+            .collect::<Option<Vec<_>>>()?
+            .concat();
+
+        self.data.insert(key.to_vec(), value);
+        self.uploads.remove(key);
+
+        self.data_meta(key)
+    }
+}
+
 // This function basically just builds a genesis storage key/value store according to
 // our desired mockup.
 pub fn new_test_ext() -> sp_io::TestExternalities {
@@ -111,8 +210,19 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
         // We use default for brevity, but you can configure as desired if needed.
         system: Default::default(),
         balances: Default::default(),
+        permastore: Default::default(),
     }
     .build_storage()
     .unwrap();
     t.into()
 }
+
+/// Builds the test externalities with [`PermaStorageExt`] registered as if
+/// the transaction validation is performed in the pool.
+pub fn new_test_ext_with_perma_storage(
+    perma_storage: MockPermaStorage,
+) -> sp_io::TestExternalities {
+    let mut ext = new_test_ext();
+    ext.register_extension(PermaStorageExt::new(perma_storage));
+    ext
+}
@@ -16,10 +16,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
-use frame_support::traits::OnFinalize;
+use frame_support::{
+    assert_noop, assert_ok,
+    traits::{OnFinalize, OnInitialize},
+    weights::DispatchInfo,
+};
+use sp_core::H256;
+
+use cp_permastore::CHUNK_SIZE;
 
 use crate::{
-    mock::{new_test_ext, Permastore, Test},
+    mock::{
+        new_test_ext, new_test_ext_with_perma_storage, Balances, Call, MinStorageFee,
+        MockPermaStorage, Origin, Permastore, StorageCostDeclineRate, StoragePricePerBytePerPeriod,
+        StorageReplicas, System, Test,
+    },
     *,
 };
 
@@ -51,5 +62,622 @@ fn find_recall_block_should_work() {
         assert_eq!(Pallet::<Test>::find_recall_block(12), Some(4));
         assert_eq!(Pallet::<Test>::find_recall_block(13), Some(10));
         assert_eq!(Pallet::<Test>::find_recall_block(15), Some(10));
+        assert_eq!(Pallet::<Test>::find_recall_block(22), Some(10));
+        assert_eq!(Pallet::<Test>::find_recall_block(23), None);
+    });
+}
+
+#[test]
+fn migrate_recall_index_to_v1_should_work() {
+    use frame_support::{
+        storage::migration,
+        traits::{GetStorageVersion, StorageVersion},
+    };
+
+    new_test_ext().execute_with(|| {
+        StorageVersion::new(0).put::<Permastore>();
+
+        migration::put_storage_value(
+            b"Permastore",
+            b"GlobalWeaveSizeIndex",
+            &[],
+            vec![5u64, 12, 22],
+        );
+        migration::put_storage_value(
+            b"Permastore",
+            b"GlobalBlockNumberIndex",
+            &[],
+            vec![1u64, 4, 10],
+        );
+
+        crate::migrations::v1::migrate::<Test>();
+
+        assert_eq!(Permastore::on_chain_storage_version(), 1);
+        assert_eq!(Permastore::recall_index_len(), 3);
+        assert_eq!(Permastore::recall_index(0), Some((5, 1)));
+        assert_eq!(Permastore::recall_index(2), Some((22, 10)));
+
+        assert_eq!(Pallet::<Test>::find_recall_block(3), Some(1));
+        assert_eq!(Pallet::<Test>::find_recall_block(12), Some(4));
+        assert_eq!(Pallet::<Test>::find_recall_block(13), Some(10));
+
+        assert!(migration::get_storage_value::<Vec<u64>>(
+            b"Permastore",
+            b"GlobalWeaveSizeIndex",
+            &[]
+        )
+        .is_none());
+        assert!(migration::get_storage_value::<Vec<u64>>(
+            b"Permastore",
+            b"GlobalBlockNumberIndex",
+            &[]
+        )
+        .is_none());
+    });
+}
+
+#[test]
+fn migrate_to_v2_should_keep_chunk_root_and_data_size() {
+    use frame_support::{
+        storage::migration,
+        traits::{GetStorageVersion, StorageVersion},
+        StorageHasher, Twox64Concat,
+    };
+
+    new_test_ext().execute_with(|| {
+        StorageVersion::new(1).put::<Permastore>();
+
+        let key = (1u64, 0u32);
+        migration::put_storage_value(
+            b"Permastore",
+            b"ChunkRootIndex",
+            &Twox64Concat::hash(&key.encode()),
+            H256::repeat_byte(1),
+        );
+        migration::put_storage_value(
+            b"Permastore",
+            b"TransactionDataSize",
+            &Twox64Concat::hash(&key.encode()),
+            10u32,
+        );
+
+        crate::migrations::v2::migrate::<Test>();
+
+        assert_eq!(Permastore::on_chain_storage_version(), 2);
+        assert_eq!(
+            Permastore::legacy_chunk_root(key),
+            Some(H256::repeat_byte(1))
+        );
+        assert_eq!(Permastore::legacy_data_size(key), Some(10));
+        assert_eq!(Permastore::legacy_chunk_root((1, 1)), None);
+    });
+}
+
+#[test]
+fn migrate_to_v3_should_index_order_owners() {
+    use frame_support::traits::{GetStorageVersion, StorageVersion};
+
+    new_test_ext().execute_with(|| {
+        StorageVersion::new(2).put::<Permastore>();
+
+        Orders::<Test>::insert(1, (1, 0), 10);
+        Orders::<Test>::insert(2, (1, 1), 20);
+        Orders::<Test>::insert(1, (3, 2), 30);
+
+        crate::migrations::v3::migrate::<Test>();
+
+        assert_eq!(Permastore::on_chain_storage_version(), 3);
+        assert_eq!(Permastore::order(1, 0), Some((1, 10)));
+        assert_eq!(Permastore::order(1, 1), Some((2, 20)));
+        assert_eq!(Permastore::order(3, 2), Some((1, 30)));
+        assert_eq!(Permastore::order(3, 3), None);
+    });
+}
+
+#[test]
+fn migrations_should_chain_from_populated_v0() {
+    use frame_support::{
+        storage::migration,
+        traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion},
+        StorageHasher, Twox64Concat,
+    };
+
+    new_test_ext().execute_with(|| {
+        StorageVersion::new(0).put::<Permastore>();
+
+        migration::put_storage_value(b"Permastore", b"GlobalWeaveSizeIndex", &[], vec![5u64, 12]);
+        migration::put_storage_value(b"Permastore", b"GlobalBlockNumberIndex", &[], vec![1u64, 4]);
+        for (key, chunk_root, data_size) in [((1u64, 0u32), 1u8, 5u32), ((4, 1), 4, 7)] {
+            migration::put_storage_value(
+                b"Permastore",
+                b"ChunkRootIndex",
+                &Twox64Concat::hash(&key.encode()),
+                H256::repeat_byte(chunk_root),
+            );
+            migration::put_storage_value(
+                b"Permastore",
+                b"TransactionDataSize",
+                &Twox64Concat::hash(&key.encode()),
+                data_size,
+            );
+        }
+        Orders::<Test>::insert(1, (1, 0), 10);
+        Orders::<Test>::insert(2, (4, 1), 20);
+
+        assert_ok!(crate::migrations::v3::pre_migrate::<Test>());
+        assert!(crate::migrations::v3::post_migrate::<Test>().is_err());
+
+        Permastore::on_runtime_upgrade();
+
+        assert_eq!(Permastore::on_chain_storage_version(), 3);
+        assert_ok!(crate::migrations::v3::post_migrate::<Test>());
+
+        // v1
+        assert_eq!(Permastore::recall_index_len(), 2);
+        assert_eq!(Pallet::<Test>::find_recall_block(6), Some(4));
+        // v2
+        assert_eq!(
+            Permastore::legacy_chunk_root((4, 1)),
+            Some(H256::repeat_byte(4))
+        );
+        assert_eq!(Permastore::legacy_data_size((1, 0)), Some(5));
+        // v3
+        assert_eq!(Permastore::order(1, 0), Some((1, 10)));
+        assert_eq!(Permastore::order(4, 1), Some((2, 20)));
+    });
+}
+
+#[test]
+fn find_recall_block_should_work_with_empty_index() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(Pallet::<Test>::find_recall_block(0), None);
+        assert_eq!(Pallet::<Test>::find_recall_block(100), None);
+    });
+}
+
+#[test]
+fn store_should_fail_if_data_is_not_stored_locally() {
+    let chunk_root = H256::repeat_byte(1);
+    new_test_ext_with_perma_storage(MockPermaStorage::default()).execute_with(|| {
+        assert_noop!(
+            Permastore::store(Origin::signed(1), 10, chunk_root, vec![]),
+            Error::<Test>::NotStored
+        );
+    });
+}
+
+#[test]
+fn store_should_work_if_data_is_stored_locally() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, 1_000);
+        assert_ok!(Permastore::store(Origin::signed(1), 10, chunk_root, vec![]));
+        assert_eq!(
+            BlockTransactions::<Test>::get(),
+            vec![StoredTransaction {
+                extrinsic_index: 0,
+                chunk_root,
+                data_size: 10,
+            }]
+        );
+    });
+}
+
+#[test]
+fn stored_transactions_should_be_deposited_as_digest() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, 1_000);
+        assert_ok!(Permastore::store(Origin::signed(1), 10, chunk_root, vec![]));
+        assert_ok!(Permastore::store(Origin::signed(1), 20, chunk_root, vec![]));
+
+        <Permastore as OnFinalize<u64>>::on_finalize(0);
+
+        let expected = vec![
+            StoredTransaction {
+                extrinsic_index: 0,
+                chunk_root,
+                data_size: 10,
+            },
+            StoredTransaction {
+                extrinsic_index: 0,
+                chunk_root,
+                data_size: 20,
+            },
+        ];
+        assert!(System::digest().logs.contains(&DigestItem::Consensus(
+            PERMASTORE_ENGINE_ID,
+            expected.encode()
+        )));
+
+        // Nothing is left in the state.
+        assert!(BlockTransactions::<Test>::get().is_empty());
+    });
+}
+
+#[test]
+fn no_digest_should_be_deposited_without_stored_transactions() {
+    new_test_ext().execute_with(|| {
+        <Permastore as OnFinalize<u64>>::on_finalize(0);
+        assert!(System::digest().logs.is_empty());
+    });
+}
+
+#[test]
+fn data_is_assumed_to_be_stored_without_perma_storage_ext() {
+    // The local storage is not checked on block import.
+    new_test_ext().execute_with(|| {
+        assert!(Pallet::<Test>::stored_locally(&H256::repeat_byte(1)));
+    });
+}
+
+#[test]
+fn check_store_should_reject_data_not_stored_locally() {
+    let stored = H256::repeat_byte(1);
+    let not_stored = H256::repeat_byte(2);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[stored]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, 1_000);
+
+        let info = DispatchInfo::default();
+        let store_call = |chunk_root| {
+            Call::Permastore(crate::Call::store {
+                data_size: 10,
+                chunk_root,
+                tags: vec![],
+            })
+        };
+
+        assert!(CheckStore::<Test>::new()
+            .validate(&1, &store_call(stored), &info, 0)
+            .is_ok());
+        assert_eq!(
+            CheckStore::<Test>::new().validate(&1, &store_call(not_stored), &info, 0),
+            Err(InvalidTransaction::Custom(DATA_NOT_STORED).into())
+        );
+    });
+}
+
+#[test]
+fn check_store_should_reject_insufficient_balance() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        // Existential deposit is 1.
+        let _ = Balances::deposit_creating(&1, 10);
+
+        let info = DispatchInfo::default();
+        let store_call = |data_size| {
+            Call::Permastore(crate::Call::store {
+                data_size,
+                chunk_root,
+                tags: vec![],
+            })
+        };
+
+        assert!(CheckStore::<Test>::new()
+            .validate(&1, &store_call(9), &info, 0)
+            .is_ok());
+        assert_eq!(
+            CheckStore::<Test>::new().validate(&1, &store_call(10), &info, 0),
+            Err(InvalidTransaction::Payment.into())
+        );
+    });
+}
+
+#[test]
+fn check_store_priority_should_be_bounded_by_chunk_count() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, u64::MAX / 2);
+
+        let info = DispatchInfo::default();
+        let priority_of = |data_size| {
+            let call = Call::Permastore(crate::Call::store {
+                data_size,
+                chunk_root,
+                tags: vec![],
+            });
+            CheckStore::<Test>::new()
+                .validate(&1, &call, &info, 0)
+                .unwrap()
+                .priority
+        };
+
+        assert_eq!(priority_of(10), 1);
+        assert_eq!(priority_of(CHUNK_SIZE), 1);
+        assert_eq!(priority_of(CHUNK_SIZE + 1), 2);
+        assert_eq!(
+            priority_of(<Test as Config>::MaxDataSize::get()),
+            MAX_STORE_PRIORITY
+        );
+    });
+}
+
+#[test]
+fn store_with_tags_should_work() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, 1_000);
+
+        let tags = vec![
+            Tag::new("Content-Type", "text"),
+            Tag::new("App-Name", "canyon"),
+        ];
+        assert_ok!(Permastore::store(
+            Origin::signed(1),
+            10,
+            chunk_root,
+            tags.clone()
+        ));
+        // No tags are recorded for the order without tags.
+        assert_ok!(Permastore::store(Origin::signed(1), 20, chunk_root, vec![]));
+
+        assert_eq!(Permastore::block_tags(), vec![(0, tags)]);
+
+        // The tags of last block are kept until the next block begins.
+        <Permastore as OnFinalize<u64>>::on_finalize(1);
+        assert_eq!(Permastore::block_tags().len(), 1);
+
+        <Permastore as OnInitialize<u64>>::on_initialize(2);
+        assert!(Permastore::block_tags().is_empty());
+    });
+}
+
+#[test]
+fn store_with_invalid_tags_should_fail() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, 1_000);
+
+        // MaxTags is 2.
+        assert_noop!(
+            Permastore::store(
+                Origin::signed(1),
+                10,
+                chunk_root,
+                vec![Tag::new("a", "1"), Tag::new("b", "2"), Tag::new("c", "3")]
+            ),
+            Error::<Test>::TooManyTags
+        );
+
+        // MaxTagSize is 16.
+        assert_noop!(
+            Permastore::store(
+                Origin::signed(1),
+                10,
+                chunk_root,
+                vec![Tag::new("Content-Type", "text/html")]
+            ),
+            Error::<Test>::TagTooLarge
+        );
+        assert_ok!(Permastore::store(
+            Origin::signed(1),
+            10,
+            chunk_root,
+            vec![Tag::new("Content-Type", "text")]
+        ));
+    });
+}
+
+#[test]
+fn check_store_should_reject_invalid_tags() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let _ = Balances::deposit_creating(&1, 1_000);
+
+        let info = DispatchInfo::default();
+        let store_call = |tags| {
+            Call::Permastore(crate::Call::store {
+                data_size: 10,
+                chunk_root,
+                tags,
+            })
+        };
+
+        assert!(CheckStore::<Test>::new()
+            .validate(
+                &1,
+                &store_call(vec![Tag::new("App-Name", "canyon")]),
+                &info,
+                0
+            )
+            .is_ok());
+        assert_eq!(
+            CheckStore::<Test>::new().validate(
+                &1,
+                &store_call(vec![Tag::new("App-Name", "canyon network")]),
+                &info,
+                0
+            ),
+            Err(InvalidTransaction::Custom(INVALID_TAGS).into())
+        );
+    });
+}
+
+#[test]
+fn storage_fee_should_follow_the_endowment_model() {
+    new_test_ext().execute_with(|| {
+        StoragePricePerBytePerPeriod::set(&2);
+        StorageReplicas::set(&3);
+        StorageCostDeclineRate::set(&Perbill::from_percent(10));
+        MinStorageFee::set(&1);
+
+        // 100 * 2 * 3 / 10%
+        assert_eq!(Permastore::storage_fee(100), 6_000);
+
+        // The storage cost never declines.
+        StorageCostDeclineRate::set(&Perbill::zero());
+        assert_eq!(Permastore::storage_fee(100), u64::MAX);
+    });
+}
+
+#[test]
+fn storage_fee_should_be_monotonic_in_data_size() {
+    new_test_ext().execute_with(|| {
+        StoragePricePerBytePerPeriod::set(&3);
+        StorageReplicas::set(&7);
+        StorageCostDeclineRate::set(&Perbill::from_rational(3u32, 101u32));
+        MinStorageFee::set(&1_000);
+
+        let max_data_size = <Test as Config>::MaxDataSize::get();
+        let sizes = (0..10_000)
+            .chain((10_000..max_data_size).step_by(1_000_003))
+            .chain(sp_std::iter::once(max_data_size));
+
+        let mut last_fee = 0;
+        for data_size in sizes {
+            let fee = Permastore::storage_fee(data_size);
+            assert!(fee >= last_fee, "storage fee must be monotonic");
+            last_fee = fee;
+        }
+    });
+}
+
+#[test]
+fn storage_fee_should_respect_bounds() {
+    new_test_ext().execute_with(|| {
+        MinStorageFee::set(&100);
+        assert_eq!(Permastore::storage_fee(0), 100);
+        assert_eq!(Permastore::storage_fee(1), 100);
+        assert_eq!(Permastore::storage_fee(101), 101);
+
+        // Saturates instead of overflowing.
+        StoragePricePerBytePerPeriod::set(&u64::MAX);
+        StorageCostDeclineRate::set(&Perbill::from_percent(1));
+        assert_eq!(
+            Permastore::storage_fee(<Test as Config>::MaxDataSize::get()),
+            u64::MAX
+        );
+    });
+}
+
+#[test]
+fn storage_fee_should_go_to_endowment_pool() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let endowment_account = Permastore::endowment_account_id();
+        // The endowment pool is created in genesis.
+        assert_eq!(Balances::free_balance(&endowment_account), 1);
+
+        let _ = Balances::deposit_creating(&1, 1_000);
+        assert_ok!(Permastore::store(
+            Origin::signed(1),
+            100,
+            chunk_root,
+            vec![]
+        ));
+
+        assert_eq!(Balances::free_balance(&1), 900);
+        assert_eq!(Balances::free_balance(&endowment_account), 101);
+    });
+}
+
+#[test]
+fn endowment_account_should_be_created_on_runtime_upgrade() {
+    use frame_support::traits::OnRuntimeUpgrade;
+
+    new_test_ext().execute_with(|| {
+        let endowment_account = Permastore::endowment_account_id();
+
+        // An existing chain on which the endowment pool was never created.
+        let _ = Balances::make_free_balance_be(&endowment_account, 0);
+        assert!(!System::account_exists(&endowment_account));
+
+        Permastore::on_runtime_upgrade();
+        assert_eq!(Balances::free_balance(&endowment_account), 1);
+
+        // Nothing changes once created.
+        Permastore::on_runtime_upgrade();
+        assert_eq!(Balances::free_balance(&endowment_account), 1);
+    });
+}
+
+#[test]
+fn payout_endowment_should_be_scaled_by_storage_capacity() {
+    new_test_ext().execute_with(|| {
+        let endowment_account = Permastore::endowment_account_id();
+        let _ = Balances::deposit_creating(&endowment_account, 10_000);
+        let _ = Balances::deposit_creating(&2, 1);
+        let _ = Balances::deposit_creating(&3, 1);
+
+        // 10% of the releasable balance 10_000 is paid to the author storing all data.
+        assert_eq!(Permastore::payout_endowment(&2, Permill::one()), 1_000);
+        assert_eq!(Balances::free_balance(&2), 1_001);
+        assert_eq!(Balances::free_balance(&endowment_account), 9_001);
+
+        // Only half of that is paid if the author stores half of the data.
+        assert_eq!(
+            Permastore::payout_endowment(&3, Permill::from_percent(50)),
+            450
+        );
+        assert_eq!(Balances::free_balance(&3), 451);
+        assert_eq!(Balances::free_balance(&endowment_account), 8_551);
+
+        // Nothing is paid if the author stores nothing.
+        assert_eq!(Permastore::payout_endowment(&3, Permill::zero()), 0);
+        assert_eq!(Balances::free_balance(&endowment_account), 8_551);
+    });
+}
+
+#[test]
+fn forget_should_work() {
+    let chunk_root = H256::repeat_byte(1);
+    let perma_storage = MockPermaStorage::with_chunk_roots(&[chunk_root]);
+    new_test_ext_with_perma_storage(perma_storage).execute_with(|| {
+        let endowment_account = Permastore::endowment_account_id();
+        let _ = Balances::deposit_creating(&1, 1_000);
+
+        System::set_block_number(1);
+        assert_ok!(Permastore::store(
+            Origin::signed(1),
+            100,
+            chunk_root,
+            vec![]
+        ));
+        assert_eq!(Balances::free_balance(&endowment_account), 101);
+        assert!(!Permastore::is_forgotten(1, 0));
+        assert_eq!(Permastore::order(1, 0), Some((1, 100)));
+
+        // Only the owner of order can forget the data.
+        assert_noop!(
+            Permastore::forget(Origin::signed(2), 1, 0),
+            Error::<Test>::OrderDoesNotExist
+        );
+
+        System::set_block_number(3);
+        assert_ok!(Permastore::forget(Origin::signed(1), 1, 0));
+        assert!(Permastore::is_forgotten(1, 0));
+        assert!(Permastore::orders(1, (1, 0)).is_none());
+        assert_eq!(Permastore::order(1, 0), None);
+
+        // 100 * (1 - 10%)^2
+        assert_eq!(Balances::free_balance(&1), 900 + 81);
+        assert_eq!(Balances::free_balance(&endowment_account), 101 - 81);
+
+        assert_noop!(
+            Permastore::forget(Origin::signed(1), 1, 0),
+            Error::<Test>::OrderDoesNotExist
+        );
+    });
+}
+
+#[test]
+fn refund_should_not_exceed_releasable_endowment() {
+    new_test_ext().execute_with(|| {
+        let endowment_account = Permastore::endowment_account_id();
+        let _ = Balances::deposit_creating(&endowment_account, 50);
+        let _ = Balances::deposit_creating(&1, 1);
+
+        assert_eq!(Pallet::<Test>::refund_storage_fee(&1, 0, 100), 50);
+        assert_eq!(Balances::free_balance(&1), 51);
+        assert_eq!(Balances::free_balance(&endowment_account), 1);
     });
 }
@@ -52,49 +52,59 @@ use sp_std::marker::PhantomData;
 
 /// Weight functions needed for pallet_permastore.
 pub trait WeightInfo {
-    fn store() -> Weight;
+    fn store(t: u32) -> Weight;
     fn forget() -> Weight;
 }
 
 /// Weights for pallet_permastore using the Substrate node and recommended hardware.
 pub struct SubstrateWeight<T>(PhantomData<T>);
 impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
-    // Storage: Permastore ChunkRootIndex (r:0 w:1)
     // Storage: Permastore Orders (r:0 w:1)
+    // Storage: Permastore OrderOwners (r:0 w:1)
     // Storage: unknown [0x3a65787472696e7369635f696e646578] (r:1 w:0)
     // Storage: Permastore BlockDataSize (r:1 w:1)
     // Storage: Permastore WeaveSize (r:1 w:1)
-    // Storage: Permastore TransactionDataSize (r:0 w:1)
-    fn store() -> Weight {
+    // Storage: Permastore BlockTransactions (r:0 w:1)
+    // Storage: Permastore BlockTags (r:0 w:1)
+    fn store(t: u32) -> Weight {
         (64_430_000 as Weight)
+            .saturating_add((1_152_000 as Weight).saturating_mul(t as Weight))
             .saturating_add(T::DbWeight::get().reads(3 as Weight))
-            .saturating_add(T::DbWeight::get().writes(5 as Weight))
+            .saturating_add(T::DbWeight::get().writes(6 as Weight))
     }
     // Storage: Permastore Orders (r:1 w:1)
+    // Storage: Permastore OrderOwners (r:0 w:1)
+    // Storage: Permastore Forgotten (r:0 w:1)
+    // Storage: System Account (r:2 w:2)
     fn forget() -> Weight {
-        (24_665_000 as Weight)
-            .saturating_add(T::DbWeight::get().reads(1 as Weight))
-            .saturating_add(T::DbWeight::get().writes(1 as Weight))
+        (68_912_000 as Weight)
+            .saturating_add(T::DbWeight::get().reads(3 as Weight))
+            .saturating_add(T::DbWeight::get().writes(5 as Weight))
     }
 }
 
 // For backwards compatibility and tests
 impl WeightInfo for () {
-    // Storage: Permastore ChunkRootIndex (r:0 w:1)
     // Storage: Permastore Orders (r:0 w:1)
+    // Storage: Permastore OrderOwners (r:0 w:1)
     // Storage: unknown [0x3a65787472696e7369635f696e646578] (r:1 w:0)
     // Storage: Permastore BlockDataSize (r:1 w:1)
     // Storage: Permastore WeaveSize (r:1 w:1)
-    // Storage: Permastore TransactionDataSize (r:0 w:1)
-    fn store() -> Weight {
+    // Storage: Permastore BlockTransactions (r:0 w:1)
+    // Storage: Permastore BlockTags (r:0 w:1)
+    fn store(t: u32) -> Weight {
         (64_430_000 as Weight)
+            .saturating_add((1_152_000 as Weight).saturating_mul(t as Weight))
             .saturating_add(RocksDbWeight::get().reads(3 as Weight))
-            .saturating_add(RocksDbWeight::get().writes(5 as Weight))
+            .saturating_add(RocksDbWeight::get().writes(6 as Weight))
     }
     // Storage: Permastore Orders (r:1 w:1)
+    // Storage: Permastore OrderOwners (r:0 w:1)
+    // Storage: Permastore Forgotten (r:0 w:1)
+    // Storage: System Account (r:2 w:2)
     fn forget() -> Weight {
-        (24_665_000 as Weight)
-            .saturating_add(RocksDbWeight::get().reads(1 as Weight))
-            .saturating_add(RocksDbWeight::get().writes(1 as Weight))
+        (68_912_000 as Weight)
+            .saturating_add(RocksDbWeight::get().reads(3 as Weight))
+            .saturating_add(RocksDbWeight::get().writes(5 as Weight))
     }
 }
@@ -17,7 +17,7 @@
 // along with Canyon. If not, see <http://www.gnu.org/licenses/>.
 
 use super::*;
-use cp_consensus_poa::{ChunkProof, PoaConfiguration, ProofOfAccess};
+use cp_consensus_poa::{ChunkProof, PoaConfiguration, PoaMode, ProofOfAccess};
 use frame_benchmarking::{benchmarks, impl_benchmark_test_suite};
 use frame_system::RawOrigin;
 use sp_std::vec;
@@ -46,16 +46,20 @@ benchmarks! {
 
         let poa = ProofOfAccess::new(1, tx_proof, chunk_proof);
         let poa_outcome = PoaOutcome::Justification(poa);
+
+        // The block author is paid in the worst case.
+        T::Reward::prepare_reward();
     }: deposit (RawOrigin::None, poa_outcome)
     verify {
-        // TODO: verify deposit
+        assert!(HistoryDepth::<T>::get(&T::BlockAuthor::author()).is_some());
     }
 
     set_config {
         let new = PoaConfiguration {
             max_depth: 1u32,
             max_tx_path: 100u32,
-            max_chunk_path: 100u32
+            max_chunk_path: 100u32,
+            mode: PoaMode::Classic,
         };
     }: set_config (RawOrigin::Root, new.clone())
     verify {
@@ -35,7 +35,9 @@
 //!
 //! The Poa pallet creates the inherent extrinsic [`Call::deposit`]
 //! when the inherent data contains a valid [`POA_INHERENT_IDENTIFIER`],
-//! in which a new digest item will probably be deposited.
+//! in which a new digest item will probably be deposited. The block
+//! author providing a valid proof of access is rewarded via
+//! [`Config::Reward`] according to the estimated storage capacity.
 
 // Ensure we're `no_std` when compiling for Wasm.
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -62,6 +64,7 @@ use cp_consensus_poa::{PoaConfiguration, PoaOutcome, POA_ENGINE_ID, POA_INHERENT
 
 #[cfg(any(feature = "runtime-benchmarks", test))]
 mod benchmarking;
+pub mod migrations;
 #[cfg(all(feature = "std", test))]
 mod mock;
 #[cfg(all(feature = "std", test))]
@@ -124,6 +127,20 @@ pub trait BlockAuthor<AccountId> {
     fn author() -> AccountId;
 }
 
+/// Trait for rewarding the block author who provided a valid proof of access.
+pub trait RewardProofOfAccess<AccountId> {
+    /// Rewards `author` whose estimated storage capacity is `storage_capacity`.
+    fn reward(author: &AccountId, storage_capacity: Permill);
+
+    /// Sets up the state in which [`Self::reward`] actually pays out, for benchmarking.
+    #[cfg(feature = "runtime-benchmarks")]
+    fn prepare_reward() {}
+}
+
+impl<AccountId> RewardProofOfAccess<AccountId> for () {
+    fn reward(_author: &AccountId, _storage_capacity: Permill) {}
+}
+
 /// Error type for the poa inherent.
 #[derive(RuntimeDebug, Clone, Encode, Decode, TypeInfo)]
 pub enum InherentError {
@@ -152,18 +169,29 @@ pub mod pallet {
         /// Find the author of current block.
         type BlockAuthor: BlockAuthor<Self::AccountId>;
 
+        /// Reward the block author providing a valid proof of access.
+        type Reward: RewardProofOfAccess<Self::AccountId>;
+
         /// Weight information for extrinsics in this pallet.
         type WeightInfo: WeightInfo;
     }
 
+    /// The current storage version.
+    const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
     #[pallet::generate_storage_info]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
     #[pallet::hooks]
     impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
         fn on_finalize(_n: BlockNumberFor<T>) {}
+
+        fn on_runtime_upgrade() -> Weight {
+            migrations::v1::migrate::<T>()
+        }
     }
 
     #[pallet::call]
@@ -186,7 +214,9 @@ pub mod pallet {
                         Error::<T>::InvalidProofOfAccess
                     })?;
 
-                    Self::note_depth(poa.depth);
+                    let depth_info =
+                        Self::note_depth(Self::poa_config().mode.normalize_depth(poa.depth));
+                    T::Reward::reward(&T::BlockAuthor::author(), depth_info.as_storage_capacity());
                     <frame_system::Pallet<T>>::deposit_log(DigestItem::Seal(
                         POA_ENGINE_ID,
                         poa.encode(),
@@ -315,22 +345,23 @@ pub mod pallet {
     pub(super) type TestAuthor<T: Config> = StorageValue<_, T::AccountId, ValueQuery>;
 
     impl<T: Config> Pallet<T> {
-        /// Updates the historical depth info of block author.
-        pub(crate) fn note_depth(depth: Depth) {
+        /// Updates the historical depth info of block author and returns the updated one.
+        pub(crate) fn note_depth(depth: Depth) -> DepthInfo<T::BlockNumber> {
             let block_author = T::BlockAuthor::author();
 
-            if let Some(mut old) = HistoryDepth::<T>::get(&block_author) {
+            let depth_info = if let Some(mut old) = HistoryDepth::<T>::get(&block_author) {
                 old.add_depth(depth);
-                HistoryDepth::<T>::insert(&block_author, old);
+                old
             } else {
-                HistoryDepth::<T>::insert(
-                    &block_author,
-                    DepthInfo {
-                        blocks: 1u32.into(),
-                        total_depth: depth,
-                    },
-                );
-            }
+                DepthInfo {
+                    blocks: 1u32.into(),
+                    total_depth: depth,
+                }
+            };
+
+            HistoryDepth::<T>::insert(&block_author, &depth_info);
+
+            depth_info
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+//! Storage migrations for the Poa pallet.
+
+use super::*;
+
+use frame_support::{
+    traits::{Get, GetStorageVersion, StorageVersion},
+    weights::Weight,
+};
+
+use cp_consensus_poa::OldPoaConfiguration;
+
+/// Adds the mode to [`PoaConfiguration`].
+pub mod v1 {
+    use super::*;
+
+    /// Translates the stored [`PoaConfig`] to the new format using [`cp_consensus_poa::PoaMode::Classic`].
+    pub fn migrate<T: Config>() -> Weight {
+        let on_chain_version = Pallet::<T>::on_chain_storage_version();
+
+        if on_chain_version >= 1 {
+            frame_support::log::info!(
+                target: "runtime::poa",
+                "Skipping the migration v1 as the on-chain storage version is {:?}",
+                on_chain_version,
+            );
+            return T::DbWeight::get().reads(1);
+        }
+
+        let translated = PoaConfig::<T>::translate::<OldPoaConfiguration, _>(|maybe_old| {
+            maybe_old.map(Into::into)
+        });
+
+        if translated.is_err() {
+            frame_support::log::error!(
+                target: "runtime::poa",
+                "Failed to decode the old PoaConfiguration, the default one will be used",
+            );
+            PoaConfig::<T>::kill();
+        }
+
+        StorageVersion::new(1).put::<Pallet<T>>();
+
+        T::DbWeight::get().reads_writes(2, 2)
+    }
+}
@@ -18,14 +18,17 @@
 
 use super::*;
 
-use frame_support::{parameter_types, traits::Everything};
+use frame_support::{
+    parameter_types,
+    traits::{Currency, Everything, ExistenceRequirement},
+};
 use sp_core::H256;
 // The testing primitives are very useful for avoiding having to work with signatures
 // or public keys. `u64` is used as the `AccountId` and no `Signature`s are required.
 use sp_runtime::{
     testing::Header,
     traits::{BlakeTwo256, IdentityLookup},
-    BuildStorage,
+    BuildStorage, Permill,
 };
 // Reexport crate as its pallet name for construct_runtime.
 use crate as pallet_poa;
@@ -101,9 +104,31 @@ impl BlockAuthor<u64> for Test {
     }
 }
 
+/// Account of the pool paying the PoA rewards.
+pub const ENDOWMENT: u64 = 100;
+
+/// Pays the block author at most 10% of the endowment per block, scaled by
+/// its storage capacity.
+pub struct EndowmentReward;
+
+impl RewardProofOfAccess<u64> for EndowmentReward {
+    fn reward(author: &u64, storage_capacity: Permill) {
+        let releasable =
+            Balances::free_balance(&ENDOWMENT).saturating_sub(ExistentialDeposit::get());
+        let payout = storage_capacity * (Permill::from_percent(10) * releasable);
+        let _ = <Balances as Currency<u64>>::transfer(
+            &ENDOWMENT,
+            author,
+            payout,
+            ExistenceRequirement::KeepAlive,
+        );
+    }
+}
+
 impl Config for Test {
     type Event = Event;
     type BlockAuthor = Self;
+    type Reward = EndowmentReward;
     type WeightInfo = ();
 }
 
@@ -27,13 +27,12 @@ use substrate_test_runtime_client::{
 };
 
 use cc_consensus_poa::{build_extrinsic_proof, ChunkProof, ChunkProofBuilder};
-use cp_permastore::CHUNK_SIZE;
 
 use crate::mock::{new_test_ext, Poa, Test};
 use crate::{DepthInfo, HistoryDepth, TestAuthor};
 
 fn generate_chunk_proof(data: Vec<u8>, offset: u32) -> ChunkProof {
-    ChunkProofBuilder::new(data, CHUNK_SIZE, offset)
+    ChunkProofBuilder::new(data, offset)
         .build()
         .expect("Couldn't build chunk proof")
 }
@@ -127,4 +126,90 @@ fn note_depth_should_work() {
             }
         );
     });
+}
+#[test]
+fn set_config_should_reject_zero_spora_difficulty() {
+    use crate::mock::Origin;
+    use cp_consensus_poa::{PoaConfiguration, PoaMode};
+    use frame_support::{assert_noop, assert_ok};
+
+    new_test_ext().execute_with(|| {
+        let mut config = PoaConfiguration {
+            mode: PoaMode::Spora { difficulty: 0 },
+            ..Default::default()
+        };
+        assert_noop!(
+            Poa::set_config(Origin::root(), config.clone()),
+            crate::Error::<Test>::InvalidPoaConfiguration
+        );
+
+        config.mode = PoaMode::Spora { difficulty: 10 };
+        assert_ok!(Poa::set_config(Origin::root(), config.clone()));
+        assert_eq!(Poa::poa_config(), config);
+    });
+}
+
+#[test]
+fn spora_depth_should_be_normalized() {
+    use cp_consensus_poa::PoaMode;
+
+    assert_eq!(PoaMode::Classic.normalize_depth(7), 7);
+
+    let spora = PoaMode::Spora { difficulty: 10 };
+    assert_eq!(spora.normalize_depth(1), 1);
+    assert_eq!(spora.normalize_depth(10), 1);
+    assert_eq!(spora.normalize_depth(11), 2);
+    assert_eq!(spora.normalize_depth(u32::MAX), u32::MAX / 10 + 1);
+}
+
+#[test]
+fn migrate_poa_config_to_v1_should_work() {
+    use codec::Encode;
+    use cp_consensus_poa::{PoaConfiguration, PoaMode};
+    use frame_support::{
+        storage::unhashed,
+        traits::{GetStorageVersion, StorageVersion},
+    };
+
+    new_test_ext().execute_with(|| {
+        StorageVersion::new(0).put::<Poa>();
+
+        // The old PoaConfiguration of three u32 fields.
+        unhashed::put_raw(
+            &crate::PoaConfig::<Test>::hashed_key(),
+            &(10u32, 20u32, 30u32).encode(),
+        );
+
+        crate::migrations::v1::migrate::<Test>();
+
+        assert_eq!(Poa::on_chain_storage_version(), 1);
+        assert_eq!(
+            Poa::poa_config(),
+            PoaConfiguration {
+                max_depth: 10,
+                max_tx_path: 20,
+                max_chunk_path: 30,
+                mode: PoaMode::Classic,
+            }
+        );
+    });
+}
+
+#[test]
+fn deposit_should_pay_block_author() {
+    use crate::mock::{Balances, Origin, ENDOWMENT};
+    use cp_consensus_poa::{PoaOutcome, ProofOfAccess};
+    use frame_support::{assert_ok, traits::Currency};
+
+    new_test_ext().execute_with(|| {
+        Balances::make_free_balance_be(&ENDOWMENT, 1_001);
+        TestAuthor::<Test>::put(6);
+
+        // Storage capacity of depth 2 is 50%, 10% of the releasable 1_000 is scaled by it.
+        let poa = ProofOfAccess::new(2, vec![], ChunkProof::new(vec![], vec![], 0));
+        assert_ok!(Poa::deposit(Origin::none(), PoaOutcome::Justification(poa)));
+
+        assert_eq!(Balances::free_balance(&6), 50);
+        assert_eq!(Balances::free_balance(&ENDOWMENT), 951);
+    });
 }
\ No newline at end of file
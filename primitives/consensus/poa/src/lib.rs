@@ -23,7 +23,7 @@ use codec::{Decode, Encode, MaxEncodedLen};
 use scale_info::TypeInfo;
 
 use sp_inherents::InherentIdentifier;
-use sp_runtime::ConsensusEngineId;
+use sp_runtime::{ConsensusEngineId, RuntimeDebug};
 use sp_std::vec::Vec;
 
 /// The identifier for the inherent of poa pallet.
@@ -69,11 +69,7 @@ impl sp_std::fmt::Debug for ChunkProof {
 }
 
 /// An utility function to enocde chunk/extrinsic index as trie key.
-// The final proof can be more compact.
-// See https://github.com/paritytech/substrate/pull/8624#discussion_r616075183
-pub fn encode_index(input: u32) -> Vec<u8> {
-    codec::Encode::encode(&codec::Compact(input))
-}
+pub use cp_permastore::chunk_tree::encode_index;
 
 impl ChunkProof {
     /// Creates a new instance of [`ChunkProof`].
@@ -157,6 +153,7 @@ impl ProofOfAccess {
             max_depth,
             max_tx_path,
             max_chunk_path,
+            ..
         } = poa_config;
 
         if self.depth == 0 {
@@ -217,6 +214,45 @@ const MAX_TX_PATH: u32 = 256 * 1024;
 /// Maximu byte size of chunk path 256 KiB.
 const MAX_CHUNK_PATH: u32 = 256 * 1024;
 
+/// Mode of generating and verifying the [`ProofOfAccess`].
+#[derive(RuntimeDebug, Clone, Copy, Eq, PartialEq, Encode, Decode, MaxEncodedLen, TypeInfo)]
+pub enum PoaMode {
+    /// The recall byte of each depth is derived by hashing the parent hash for `depth`
+    /// times, the first recall chunk available locally makes a valid proof.
+    Classic,
+    /// Succinct Proofs of Random Access.
+    ///
+    /// The recall byte of each attempt is derived from the parent hash and a nonce,
+    /// the proof is valid only if the hash of the nonce and recall chunk meets the
+    /// `difficulty`, which rewards reading the chunks from local storage quickly.
+    /// The `depth` of [`ProofOfAccess`] is the nonce in this mode.
+    Spora {
+        /// Expected number of attempts for finding a valid solution.
+        difficulty: u64,
+    },
+}
+
+impl Default for PoaMode {
+    fn default() -> Self {
+        Self::Classic
+    }
+}
+
+impl PoaMode {
+    /// Returns the equivalent depth of [`PoaMode::Classic`] given the `depth` of a proof.
+    ///
+    /// In SPoRA mode, the expected number of attempts is `difficulty` times the classic
+    /// depth, which is normalized here for estimating the storage capacity consistently.
+    pub fn normalize_depth(&self, depth: u32) -> u32 {
+        match self {
+            Self::Classic => depth,
+            Self::Spora { difficulty } => {
+                ((depth as u64 + difficulty - 1) / difficulty).min(u32::MAX as u64) as u32
+            }
+        }
+    }
+}
+
 /// Configuration of the PoA consensus engine.
 #[derive(Clone, Eq, PartialEq, Encode, Decode, MaxEncodedLen, TypeInfo)]
 pub struct PoaConfiguration {
@@ -226,6 +262,8 @@ pub struct PoaConfiguration {
     pub max_tx_path: u32,
     /// Maximum byte size of chunk merkle path.
     pub max_chunk_path: u32,
+    /// Mode of the PoA consensus.
+    pub mode: PoaMode,
 }
 
 impl Default for PoaConfiguration {
@@ -234,6 +272,7 @@ impl Default for PoaConfiguration {
             max_depth: MAX_DEPTH,
             max_tx_path: MAX_TX_PATH,
             max_chunk_path: MAX_CHUNK_PATH,
+            mode: PoaMode::Classic,
         }
     }
 }
@@ -244,7 +283,33 @@ impl PoaConfiguration {
         // TODO:
         // 1. upper limit check?
         // 2. more accurate check for the proof since the size of merkle proof has a lower bound?
-        self.max_depth > 0 && self.max_tx_path > 0 && self.max_chunk_path > 0
+        let valid_mode = match self.mode {
+            PoaMode::Classic => true,
+            PoaMode::Spora { difficulty } => difficulty > 0,
+        };
+        self.max_depth > 0 && self.max_tx_path > 0 && self.max_chunk_path > 0 && valid_mode
+    }
+}
+
+/// [`PoaConfiguration`] before the mode was introduced.
+#[derive(RuntimeDebug, Clone, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct OldPoaConfiguration {
+    /// The maximum depth of attempting to generate a valid [`ProofOfAccess`].
+    pub max_depth: u32,
+    /// Maximum byte size of tx merkle path.
+    pub max_tx_path: u32,
+    /// Maximum byte size of chunk merkle path.
+    pub max_chunk_path: u32,
+}
+
+impl From<OldPoaConfiguration> for PoaConfiguration {
+    fn from(old: OldPoaConfiguration) -> Self {
+        Self {
+            max_depth: old.max_depth,
+            max_tx_path: old.max_tx_path,
+            max_chunk_path: old.max_chunk_path,
+            mode: PoaMode::Classic,
+        }
     }
 }
 
@@ -255,6 +320,7 @@ impl sp_std::fmt::Debug for PoaConfiguration {
             .field("max_depth", &self.max_depth)
             .field("max_tx_path", &self.max_tx_path)
             .field("max_chunk_path", &self.max_chunk_path)
+            .field("mode", &self.mode)
             .finish()
     }
 
@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Bundle of many small data items stored by a single `store` order.
+//!
+//! A bundle is an ordinary transaction data in the format below, all the integers
+//! are little-endian:
+//!
+//! ```text
+//! item_count: u32 | entry_0 .. entry_{item_count - 1} | item_0 .. item_{item_count - 1}
+//! entry: id: [u8; 32] | offset: u32 | size: u32
+//! ```
+//!
+//! The header consisting of the item count and entries has a fixed size given the
+//! item count, the `offset` of an entry is the absolute position of the item in the
+//! bundle, the items are concatenated right after the header in the order of entries.
+//! The id of an item is the blake2-256 hash of the item.
+//!
+//! The root of a bundle is its chunk root, an item is proven to be included in the
+//! bundle by the chunks covering the item count, the entry of the item and the item
+//! itself, see [`verify_item`].
+
+use codec::{Decode, Encode};
+use sp_core::{RuntimeDebug, H256};
+use sp_std::{convert::TryFrom, vec::Vec};
+
+use crate::{chunk_tree, Hasher, CHUNK_SIZE};
+
+/// Id of an item in the bundle.
+pub type ItemId = H256;
+
+/// Byte size of the item count in the header.
+pub const ITEM_COUNT_SIZE: u32 = 4;
+
+/// Byte size of an entry in the header.
+pub const ENTRY_SIZE: u32 = 40;
+
+/// Error type of bundle.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum BundleError {
+    /// The bundle does not contain any item.
+    Empty,
+    /// The bundle is too large.
+    TooLarge,
+    /// The bundle is shorter than the header claims.
+    Truncated,
+    /// The entry at the given index does not match the items.
+    InvalidEntry(u32),
+    /// The chunks of proof are invalid or do not cover the item.
+    InvalidProof,
+    /// The item does not match the entry in the proof.
+    ItemMismatch,
+}
+
+/// An entry of the bundle header.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct BundleEntry {
+    /// Id of the item.
+    pub id: ItemId,
+    /// Absolute position of the item in the bundle.
+    pub offset: u32,
+    /// Byte size of the item.
+    pub size: u32,
+}
+
+/// A chunk of the bundle along with its chunk proof.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct ProvenChunk {
+    /// Index of the chunk.
+    pub chunk_index: u32,
+    /// Raw bytes of the chunk.
+    pub chunk: Vec<u8>,
+    /// Merkle proof of the chunk against the bundle root.
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Returns the id of `item`.
+pub fn item_id(item: &[u8]) -> ItemId {
+    <Hasher as sp_core::Hasher>::hash(item)
+}
+
+/// Returns the byte size of the header given `item_count`.
+///
+/// Returns `None` if the header does not fit in `u32`.
+pub fn header_size(item_count: u32) -> Option<u32> {
+    item_count
+        .checked_mul(ENTRY_SIZE)
+        .and_then(|entries_size| entries_size.checked_add(ITEM_COUNT_SIZE))
+}
+
+/// Returns the position of the entry at `index` in the bundle.
+pub fn entry_offset(index: u32) -> Option<u32> {
+    header_size(index)
+}
+
+/// Encodes `items` into a bundle.
+pub fn encode<I: AsRef<[u8]>>(items: &[I]) -> Result<Vec<u8>, BundleError> {
+    if items.is_empty() {
+        return Err(BundleError::Empty);
+    }
+
+    let item_count = u32::try_from(items.len()).map_err(|_| BundleError::TooLarge)?;
+    let mut offset = header_size(item_count).ok_or(BundleError::TooLarge)?;
+
+    let mut header = Vec::with_capacity(offset as usize);
+    item_count.encode_to(&mut header);
+    for item in items {
+        let item = item.as_ref();
+        let size = u32::try_from(item.len()).map_err(|_| BundleError::TooLarge)?;
+        BundleEntry {
+            id: item_id(item),
+            offset,
+            size,
+        }
+        .encode_to(&mut header);
+        offset = offset.checked_add(size).ok_or(BundleError::TooLarge)?;
+    }
+
+    let mut bundle = header;
+    bundle.reserve(offset as usize - bundle.len());
+    for item in items {
+        bundle.extend_from_slice(item.as_ref());
+    }
+
+    Ok(bundle)
+}
+
+/// Decodes the item count from the beginning of a bundle.
+pub fn decode_item_count(mut header: &[u8]) -> Result<u32, BundleError> {
+    u32::decode(&mut header).map_err(|_| BundleError::Truncated)
+}
+
+/// Decodes the entries from the `header` of a bundle of `data_size` bytes.
+///
+/// `header` may be followed by the items, the entries are ensured to cover
+/// the rest of bundle contiguously.
+pub fn decode_header(header: &[u8], data_size: u32) -> Result<Vec<BundleEntry>, BundleError> {
+    let item_count = decode_item_count(header)?;
+    if item_count == 0 {
+        return Err(BundleError::Empty);
+    }
+
+    let header_size = header_size(item_count).ok_or(BundleError::Truncated)?;
+    if header_size > data_size || header_size as usize > header.len() {
+        return Err(BundleError::Truncated);
+    }
+
+    let mut input = &header[ITEM_COUNT_SIZE as usize..header_size as usize];
+    let mut expected_offset = header_size;
+    let mut entries = Vec::with_capacity(item_count as usize);
+    for index in 0..item_count {
+        let entry = BundleEntry::decode(&mut input).map_err(|_| BundleError::Truncated)?;
+        if entry.offset != expected_offset {
+            return Err(BundleError::InvalidEntry(index));
+        }
+        expected_offset = entry
+            .offset
+            .checked_add(entry.size)
+            .ok_or(BundleError::InvalidEntry(index))?;
+        entries.push(entry);
+    }
+
+    if expected_offset != data_size {
+        return Err(BundleError::Truncated);
+    }
+
+    Ok(entries)
+}
+
+/// Decodes the items of `bundle`, the id of each item is checked against its entry.
+pub fn decode(bundle: &[u8]) -> Result<Vec<(ItemId, &[u8])>, BundleError> {
+    let data_size = u32::try_from(bundle.len()).map_err(|_| BundleError::TooLarge)?;
+
+    decode_header(bundle, data_size)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let item = &bundle[entry.offset as usize..(entry.offset + entry.size) as usize];
+            if item_id(item) == entry.id {
+                Ok((entry.id, item))
+            } else {
+                Err(BundleError::InvalidEntry(index as u32))
+            }
+        })
+        .collect()
+}
+
+/// Returns the indices of the chunks required for proving the item at `index` of
+/// `entry`, i.e., the chunks covering the item count, the entry and the item.
+pub fn proof_chunk_indices(index: u32, entry: &BundleEntry) -> Result<Vec<u32>, BundleError> {
+    let entry_offset = entry_offset(index).ok_or(BundleError::InvalidProof)?;
+
+    let mut chunk_indices = Vec::new();
+    for (offset, size) in [
+        (0, ITEM_COUNT_SIZE),
+        (entry_offset, ENTRY_SIZE),
+        (entry.offset, entry.size),
+    ] {
+        // An empty item is covered by no chunk.
+        if size == 0 {
+            continue;
+        }
+        let end = offset
+            .checked_add(size - 1)
+            .ok_or(BundleError::InvalidProof)?;
+        let first = chunk_tree::chunk_index(offset);
+        let last = chunk_tree::chunk_index(end);
+        for chunk_index in first..=last {
+            if !chunk_indices.contains(&chunk_index) {
+                chunk_indices.push(chunk_index);
+            }
+        }
+    }
+
+    Ok(chunk_indices)
+}
+
+/// Reads `len` bytes at `offset` of the bundle from the proven `chunks`.
+fn read_proven(chunks: &[ProvenChunk], offset: u32, len: u32) -> Result<Vec<u8>, BundleError> {
+    let mut bytes = Vec::with_capacity(len as usize);
+    for pos in offset..offset.checked_add(len).ok_or(BundleError::InvalidProof)? {
+        let chunk_index = chunk_tree::chunk_index(pos);
+        let byte = chunks
+            .iter()
+            .find(|c| c.chunk_index == chunk_index)
+            .and_then(|c| c.chunk.get((pos % CHUNK_SIZE) as usize))
+            .ok_or(BundleError::InvalidProof)?;
+        bytes.push(*byte);
+    }
+    Ok(bytes)
+}
+
+/// Verifies `item` of `id` is the item at `index` of the bundle of `bundle_root`.
+///
+/// `chunks` must contain the chunks at [`proof_chunk_indices`] of the item, from
+/// which the entry and the bytes of the item are read and checked.
+pub fn verify_item(
+    bundle_root: &H256,
+    id: &ItemId,
+    item: &[u8],
+    index: u32,
+    chunks: &[ProvenChunk],
+) -> Result<(), BundleError> {
+    if item_id(item) != *id {
+        return Err(BundleError::ItemMismatch);
+    }
+
+    for chunk in chunks {
+        // Only the last chunk of data can be shorter than `CHUNK_SIZE`, the offsets
+        // within the other chunks are trustworthy once the chunks are proven.
+        if chunk.chunk.len() > CHUNK_SIZE as usize {
+            return Err(BundleError::InvalidProof);
+        }
+        chunk_tree::verify_chunk(bundle_root, chunk.chunk_index, &chunk.chunk, &chunk.proof)
+            .map_err(|_| BundleError::InvalidProof)?;
+    }
+
+    let item_count = decode_item_count(&read_proven(chunks, 0, ITEM_COUNT_SIZE)?)?;
+    if index >= item_count {
+        return Err(BundleError::InvalidProof);
+    }
+
+    let entry_offset = entry_offset(index).ok_or(BundleError::InvalidProof)?;
+    let entry = BundleEntry::decode(&mut read_proven(chunks, entry_offset, ENTRY_SIZE)?.as_slice())
+        .map_err(|_| BundleError::InvalidProof)?;
+
+    if entry.id != *id || entry.size as usize != item.len() {
+        return Err(BundleError::ItemMismatch);
+    }
+
+    // The entry may claim an item which is not what the bundle contains.
+    if read_proven(chunks, entry.offset, entry.size)? != item {
+        return Err(BundleError::ItemMismatch);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<Vec<u8>> {
+        vec![b"{\"a\":1}".to_vec(), Vec::new(), b"{\"b\":[2,3]}".to_vec()]
+    }
+
+    #[test]
+    fn encode_and_decode_should_work() {
+        let items = items();
+        let bundle = encode(&items).unwrap();
+
+        let header_size = header_size(3).unwrap();
+        assert_eq!(header_size, 124);
+        assert_eq!(
+            bundle.len(),
+            header_size as usize + items.iter().map(|item| item.len()).sum::<usize>()
+        );
+        assert_eq!(&bundle[..4], &3u32.to_le_bytes());
+        assert_eq!(
+            &bundle[header_size as usize..header_size as usize + 7],
+            b"{\"a\":1}"
+        );
+
+        let entries = decode_header(&bundle[..header_size as usize], bundle.len() as u32).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                BundleEntry {
+                    id: item_id(&items[0]),
+                    offset: 124,
+                    size: 7,
+                },
+                BundleEntry {
+                    id: item_id(&items[1]),
+                    offset: 131,
+                    size: 0,
+                },
+                BundleEntry {
+                    id: item_id(&items[2]),
+                    offset: 131,
+                    size: 11,
+                },
+            ]
+        );
+
+        assert_eq!(
+            decode(&bundle).unwrap(),
+            items
+                .iter()
+                .map(|item| (item_id(item), item.as_slice()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_bundle_should_be_rejected() {
+        assert_eq!(encode::<Vec<u8>>(&[]), Err(BundleError::Empty));
+        assert_eq!(decode(&0u32.encode()), Err(BundleError::Empty));
+    }
+
+    #[test]
+    fn invalid_bundle_should_be_rejected() {
+        let bundle = encode(&items()).unwrap();
+
+        // Truncated header or items.
+        assert_eq!(decode(&bundle[..3]), Err(BundleError::Truncated));
+        assert_eq!(decode(&bundle[..100]), Err(BundleError::Truncated));
+        assert_eq!(
+            decode(&bundle[..bundle.len() - 1]),
+            Err(BundleError::Truncated)
+        );
+
+        // Trailing bytes.
+        let mut extended = bundle.clone();
+        extended.push(0);
+        assert_eq!(decode(&extended), Err(BundleError::Truncated));
+
+        // Item count overflowing the data.
+        let mut overflowed = bundle.clone();
+        overflowed[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&overflowed), Err(BundleError::Truncated));
+
+        // Non-contiguous offset.
+        let mut shifted = bundle.clone();
+        let offset_pos = (entry_offset(1).unwrap() + 32) as usize;
+        shifted[offset_pos] += 1;
+        assert_eq!(decode(&shifted), Err(BundleError::InvalidEntry(1)));
+
+        // Altered item.
+        let mut altered = bundle;
+        let last = altered.len() - 1;
+        altered[last] = b'!';
+        assert_eq!(decode(&altered), Err(BundleError::InvalidEntry(2)));
+    }
+
+    #[test]
+    fn proof_chunk_indices_should_cover_item_count_entry_and_item() {
+        let entry = |offset, size| BundleEntry {
+            id: H256::zero(),
+            offset,
+            size,
+        };
+
+        assert_eq!(proof_chunk_indices(0, &entry(44, 7)), Ok(vec![0]));
+
+        // An empty item.
+        assert_eq!(
+            proof_chunk_indices(0, &entry(CHUNK_SIZE * 3, 0)),
+            Ok(vec![0])
+        );
+
+        // The entry across the first two chunks.
+        let index = (CHUNK_SIZE - ITEM_COUNT_SIZE) / ENTRY_SIZE;
+        assert!(entry_offset(index).unwrap() < CHUNK_SIZE);
+        assert!(entry_offset(index).unwrap() + ENTRY_SIZE > CHUNK_SIZE);
+        assert_eq!(
+            proof_chunk_indices(index, &entry(CHUNK_SIZE + 10, 10)),
+            Ok(vec![0, 1])
+        );
+
+        // The item across the fourth and fifth chunks.
+        assert_eq!(
+            proof_chunk_indices(index + 1, &entry(CHUNK_SIZE * 4 - 1, 2)),
+            Ok(vec![0, 1, 3, 4])
+        );
+        assert_eq!(
+            proof_chunk_indices(
+                CHUNK_SIZE * 2 / ENTRY_SIZE + 1,
+                &entry(CHUNK_SIZE * 2 + 1, CHUNK_SIZE)
+            ),
+            Ok(vec![0, 2, 3])
+        );
+
+        assert_eq!(
+            proof_chunk_indices(u32::MAX, &entry(0, 1)),
+            Err(BundleError::InvalidProof)
+        );
+        assert_eq!(
+            proof_chunk_indices(0, &entry(u32::MAX, 2)),
+            Err(BundleError::InvalidProof)
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Canyon.
+//
+// Copyright (c) 2021 Canyon Labs.
+//
+// Canyon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// Canyon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Canyon. If not, see <http://www.gnu.org/licenses/>.
+
+//! Chunk tree of the transaction data.
+//!
+//! The transaction data is split into chunks of [`CHUNK_SIZE`] and the chunk root
+//! is the root of a trie whose keys are the compact encoded chunk indices and
+//! values are the blake2-256 hashes of the chunks.
+//!
+//! This module is the only place where the data is chunked and the chunk root is
+//! computed, the RPC, datastore and PoA consensus must all go through it, otherwise
+//! the chunk proof built by the node may not match the `chunk_root` recorded on chain.
+
+use sp_core::H256;
+use sp_std::{slice::Chunks, vec::Vec};
+use sp_trie::TrieConfiguration;
+
+use crate::{Hasher, TrieLayout, VerifyError, CHUNK_SIZE};
+
+/// Encodes the chunk index as the trie key.
+// The final proof can be more compact.
+// See https://github.com/paritytech/substrate/pull/8624#discussion_r616075183
+pub fn encode_index(index: u32) -> Vec<u8> {
+    codec::Encode::encode(&codec::Compact(index))
+}
+
+/// Returns an iterator over the chunks of `data`, the last chunk may be shorter.
+pub fn chunks(data: &[u8]) -> Chunks<'_, u8> {
+    data.chunks(CHUNK_SIZE as usize)
+}
+
+/// Returns the index of the chunk containing the byte at `offset`.
+pub fn chunk_index(offset: u32) -> u32 {
+    offset / CHUNK_SIZE
+}
+
+/// Returns the number of chunks of the data of `data_size` bytes.
+pub fn chunk_count(data_size: u32) -> u32 {
+    data_size / CHUNK_SIZE + (data_size % CHUNK_SIZE != 0) as u32
+}
+
+/// Returns the expected byte size of the chunk at `chunk_index` given `data_size`.
+///
+/// Returns `None` if the chunk index is out of range.
+pub fn chunk_size(data_size: u32, chunk_index: u32) -> Option<u32> {
+    if chunk_index >= chunk_count(data_size) {
+        None
+    } else {
+        Some((data_size - chunk_index * CHUNK_SIZE).min(CHUNK_SIZE))
+    }
+}
+
+/// Returns the hash of a single chunk.
+pub fn chunk_hash(chunk: &[u8]) -> H256 {
+    <Hasher as sp_core::Hasher>::hash(chunk)
+}
+
+/// Returns the hashes of all the chunks of `data` in order.
+pub fn chunk_hashes(data: &[u8]) -> Vec<H256> {
+    chunks(data).map(chunk_hash).collect()
+}
+
+/// Returns the chunk root given the hashes of all the chunks in order.
+pub fn chunk_root_from_hashes(chunk_hashes: &[H256]) -> H256 {
+    TrieLayout::ordered_trie_root(chunk_hashes.iter().map(|chunk_hash| chunk_hash.as_bytes()))
+}
+
+/// Returns the chunk root of `data`.
+pub fn chunk_root(data: &[u8]) -> H256 {
+    chunk_root_from_hashes(&chunk_hashes(data))
+}
+
+/// Verifies `chunk` is the chunk at `chunk_index` of the data of `chunk_root`.
+pub fn verify_chunk(
+    chunk_root: &H256,
+    chunk_index: u32,
+    chunk: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<(), VerifyError> {
+    sp_trie::verify_trie_proof::<TrieLayout, _, _, _>(
+        chunk_root,
+        proof,
+        &[(encode_index(chunk_index), Some(chunk_hash(chunk)))],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use sp_runtime::traits::{BlakeTwo256, Hash};
+
+    const EDGE_SIZES: [usize; 6] = [
+        0,
+        1,
+        CHUNK_SIZE as usize - 1,
+        CHUNK_SIZE as usize,
+        CHUNK_SIZE as usize + 1,
+        CHUNK_SIZE as usize * 3 + 7,
+    ];
+
+    #[test]
+    fn chunks_should_cover_the_entire_data() {
+        let expected_chunk_counts = [0, 1, 1, 1, 2, 4];
+
+        for (size, expected) in EDGE_SIZES.iter().zip(expected_chunk_counts.iter()) {
+            let data = vec![7u8; *size];
+            assert_eq!(chunks(&data).count(), *expected, "data size: {}", size);
+            assert_eq!(chunks(&data).map(|c| c.len()).sum::<usize>(), *size);
+            assert_eq!(chunk_hashes(&data).len(), *expected);
+        }
+    }
+
+    #[test]
+    fn chunk_count_and_size_should_work() {
+        let expected_chunk_counts = [0, 1, 1, 1, 2, 4];
+
+        for (size, expected) in EDGE_SIZES.iter().zip(expected_chunk_counts.iter()) {
+            let data = vec![7u8; *size];
+            assert_eq!(chunk_count(*size as u32), *expected, "data size: {}", size);
+            for (index, chunk) in chunks(&data).enumerate() {
+                assert_eq!(
+                    chunk_size(*size as u32, index as u32),
+                    Some(chunk.len() as u32)
+                );
+            }
+            assert_eq!(chunk_size(*size as u32, *expected), None);
+        }
+
+        assert_eq!(chunk_count(u32::MAX), u32::MAX / CHUNK_SIZE + 1);
+    }
+
+    #[test]
+    fn chunk_index_should_work() {
+        assert_eq!(chunk_index(0), 0);
+        assert_eq!(chunk_index(CHUNK_SIZE - 1), 0);
+        assert_eq!(chunk_index(CHUNK_SIZE), 1);
+        assert_eq!(chunk_index(CHUNK_SIZE * 3 + 6), 3);
+    }
+
+    #[test]
+    fn chunk_root_should_match_the_ordered_trie_root_of_chunk_hashes() {
+        for size in EDGE_SIZES.iter() {
+            let data = vec![7u8; *size];
+
+            let encoded_chunk_hashes = data
+                .chunks(CHUNK_SIZE as usize)
+                .map(|chunk| BlakeTwo256::hash(chunk).encode())
+                .collect();
+
+            assert_eq!(
+                chunk_root(&data),
+                BlakeTwo256::ordered_trie_root(encoded_chunk_hashes),
+                "data size: {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_root_of_empty_data_should_be_empty_trie_root() {
+        assert_eq!(chunk_root(&[]), sp_trie::empty_trie_root::<TrieLayout>());
+    }
+
+    #[test]
+    fn chunk_root_should_depend_on_every_chunk() {
+        let data = vec![7u8; CHUNK_SIZE as usize * 3 + 7];
+
+        for offset in [0, CHUNK_SIZE, CHUNK_SIZE * 3 + 6].iter() {
+            let mut altered = data.clone();
+            altered[*offset as usize] = 8;
+            assert_ne!(chunk_root(&altered), chunk_root(&data));
+        }
+    }
+}
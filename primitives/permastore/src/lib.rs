@@ -19,24 +19,135 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![allow(clippy::too_many_arguments)]
 
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_core::{RuntimeDebug, H256};
+#[cfg(feature = "std")]
+use sp_externalities::ExternalitiesExt;
+use sp_runtime::ConsensusEngineId;
+use sp_runtime_interface::runtime_interface;
 use sp_std::vec::Vec;
 
+pub mod bundle;
+pub mod chunk_tree;
+
 /// 256B per chunk.
 pub const CHUNK_SIZE: u32 = 256 * 1024;
 
+/// The `ConsensusEngineId` of the digest carrying the transaction data stored in a block.
+///
+/// The payload of digest is the encoded `Vec<StoredTransaction>`, which is indexed
+/// by the node instead of being kept in the runtime state.
+pub const PERMASTORE_ENGINE_ID: ConsensusEngineId = *b"PERM";
+
 /// Hasher type for permastore.
-#[cfg(feature = "std")]
-pub type Hasher = sp_core::Blake2Hasher;
+pub type Hasher = sp_runtime::traits::BlakeTwo256;
 
 /// Trie layout used for permastore.
-#[cfg(feature = "std")]
 pub type TrieLayout = sp_trie::Layout<Hasher>;
 
 /// Error type of chunk proof verification.
 pub type VerifyError = sp_trie::VerifyError<sp_core::H256, sp_trie::Error>;
 
+/// Metadata of the transaction data persisted in the chunk-level storage.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct DataMeta {
+    /// Byte size of entire transaction data.
+    pub data_size: u32,
+    /// Hashes of all the chunks in order.
+    ///
+    /// Required for building the chunk proof without loading the entire data.
+    pub chunk_hashes: Vec<H256>,
+}
+
+impl DataMeta {
+    /// Returns the number of chunks of the transaction data.
+    pub fn chunk_count(&self) -> u32 {
+        self.chunk_hashes.len() as u32
+    }
+}
+
+/// State of a chunked upload of the transaction data.
+///
+/// The session is persisted along with the uploaded chunks, which allows resuming
+/// the upload after the node is restarted.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct UploadSession {
+    /// Byte size of entire transaction data declared at the beginning.
+    pub data_size: u32,
+    /// Whether the chunk has been uploaded, indexed by chunk index.
+    pub uploaded: Vec<bool>,
+}
+
+impl UploadSession {
+    /// Creates a new instance of [`UploadSession`] with no chunks uploaded.
+    pub fn new(data_size: u32) -> Self {
+        Self {
+            data_size,
+            uploaded: sp_std::vec![false; chunk_tree::chunk_count(data_size) as usize],
+        }
+    }
+
+    /// Returns the number of chunks of the transaction data.
+    pub fn chunk_count(&self) -> u32 {
+        self.uploaded.len() as u32
+    }
+
+    /// Returns the indices of chunks not yet uploaded.
+    pub fn missing_chunks(&self) -> Vec<u32> {
+        self.uploaded
+            .iter()
+            .enumerate()
+            .filter_map(|(index, uploaded)| if *uploaded { None } else { Some(index as u32) })
+            .collect()
+    }
+
+    /// Returns `true` if all the chunks have been uploaded.
+    pub fn is_complete(&self) -> bool {
+        self.uploaded.iter().all(|uploaded| *uploaded)
+    }
+}
+
+/// Transaction data stored in a block.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo)]
+pub struct StoredTransaction<Hash> {
+    /// Index of the extrinsic in block.
+    pub extrinsic_index: u32,
+    /// Chunk root of the transaction data.
+    pub chunk_root: Hash,
+    /// Size of the transaction data.
+    pub data_size: u32,
+}
+
+/// A name-value pair describing the transaction data, e.g., `Content-Type: text/html`.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo)]
+pub struct Tag {
+    /// Name of the tag.
+    pub name: Vec<u8>,
+    /// Value of the tag.
+    pub value: Vec<u8>,
+}
+
+impl Tag {
+    /// Creates a new instance of [`Tag`].
+    pub fn new(name: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Returns the byte size of the tag, i.e., the total size of name and value.
+    pub fn size(&self) -> usize {
+        self.name.len() + self.value.len()
+    }
+}
+
 /// Low level APIs for manipulating the persistent transaction data storage.
 /// No data validation performed.
+///
+/// The transaction data is persisted as the individual chunks of [`CHUNK_SIZE`]
+/// keyed by `(key, chunk_index)`, along with a [`DataMeta`] record under `key`.
 pub trait PermaStorage: Send + Sync {
     /// Persist a value in storage under given key.
     fn submit(&mut self, key: &[u8], value: &[u8]);
@@ -49,26 +160,76 @@ pub trait PermaStorage: Send + Sync {
 
     /// Checks if the storage exists under given key.
     fn exists(&self, key: &[u8]) -> bool {
-        self.retrieve(key).is_some()
+        self.data_meta(key).is_some()
+    }
+
+    /// Retrieve the metadata of value under given key.
+    fn data_meta(&self, key: &[u8]) -> Option<DataMeta>;
+
+    /// Persist a single chunk of value under given key.
+    fn submit_chunk(&mut self, key: &[u8], chunk_index: u32, chunk: &[u8]);
+
+    /// Retrieve a single chunk of value under given key.
+    fn retrieve_chunk(&self, key: &[u8], chunk_index: u32) -> Option<Vec<u8>>;
+
+    /// Retrieve the ongoing upload session of value under given key.
+    fn upload_session(&self, key: &[u8]) -> Option<UploadSession>;
+
+    /// Persist the upload session of value under given key.
+    fn set_upload_session(&mut self, key: &[u8], upload_session: &UploadSession);
+
+    /// Assemble the uploaded chunks under given key into a value and remove the
+    /// upload session, the value exists afterwards.
+    ///
+    /// Returns `None` if there is no upload session or any chunk is missing.
+    fn finish_upload(&mut self, key: &[u8]) -> Option<DataMeta>;
+}
+
+#[cfg(feature = "std")]
+sp_externalities::decl_extension! {
+    /// The extension for accessing the local [`PermaStorage`] of node from the runtime.
+    pub struct PermaStorageExt(Box<dyn PermaStorage>);
+}
+
+#[cfg(feature = "std")]
+impl PermaStorageExt {
+    /// Creates a new instance of [`PermaStorageExt`].
+    pub fn new<T: PermaStorage + 'static>(perma_storage: T) -> Self {
+        Self(Box::new(perma_storage))
+    }
+}
+
+/// Interfaces for the runtime to interact with the local permanent storage of node.
+#[runtime_interface]
+pub trait Permastore {
+    /// Returns `true` if the transaction data under `key` exists in the local storage.
+    ///
+    /// The [`PermaStorageExt`] is only available in the offchain context, e.g.,
+    /// the transaction validation in the pool. This function always returns `true`
+    /// when the extension is not registered, e.g., on block import, since the
+    /// local storage must not affect the validity of a block.
+    fn data_exists(&mut self, key: &[u8]) -> bool {
+        self.extension::<PermaStorageExt>()
+            .map(|ext| ext.exists(key))
+            .unwrap_or(true)
     }
 }
 
 sp_api::decl_runtime_apis! {
     /// The permastore API.
+    ///
+    /// The chunk root and size of transaction data are deposited in the
+    /// [`PERMASTORE_ENGINE_ID`] digest since version 2, they're only provided for
+    /// the data stored before the digest was introduced, except in version 2.
+    #[api_version(3)]
     pub trait PermastoreApi<BlockNumber, ExtrinsicIndex, Hash> where
         BlockNumber: codec::Codec,
         ExtrinsicIndex: codec::Codec,
         Hash: codec::Codec,
     {
-        /// Returns the chunk root given `block_number` and `extrinsic_index`.
-        fn chunk_root(block_number: BlockNumber, extrinsic_index: ExtrinsicIndex) -> Option<Hash>;
-
         /// Returns the number of block in which the recall byte is included.
         fn find_recall_block(recall_byte: u64) -> Option<BlockNumber>;
 
-        /// Returns the size of transaction data given `block_number` and `extrinsic_index`.
-        fn data_size(block_number: BlockNumber, extrinsic_index: ExtrinsicIndex) -> u32;
-
         /// Returns `true` if the proof of access is required for the block.
         fn require_proof_of_access() -> bool;
 
@@ -77,5 +238,46 @@ sp_api::decl_runtime_apis! {
 
         /// Returns the size of entire weave.
         fn weave_size() -> u64;
+
+        /// Returns `true` if the transaction data given `block_number` and `extrinsic_index`
+        /// has been forgotten.
+        fn is_forgotten(block_number: BlockNumber, extrinsic_index: ExtrinsicIndex) -> bool;
+
+        /// Returns the chunk root given `block_number` and `extrinsic_index` stored
+        /// before the [`PERMASTORE_ENGINE_ID`] digest was introduced.
+        fn chunk_root(block_number: BlockNumber, extrinsic_index: ExtrinsicIndex) -> Option<Hash>;
+
+        /// Returns the size of transaction data given `block_number` and `extrinsic_index`
+        /// stored before the [`PERMASTORE_ENGINE_ID`] digest was introduced.
+        fn data_size(block_number: BlockNumber, extrinsic_index: ExtrinsicIndex) -> u32;
+    }
+
+    /// The API to quote the perpetual storage fee.
+    pub trait StorageFeeApi<Balance> where
+        Balance: codec::Codec,
+    {
+        /// Returns the storage fee of storing `data_size` bytes permanently.
+        fn storage_fee(data_size: u32) -> Balance;
+    }
+
+    /// The API to query the storage orders.
+    pub trait OrderApi<AccountId, BlockNumber, ExtrinsicIndex, Balance> where
+        AccountId: codec::Codec,
+        BlockNumber: codec::Codec,
+        ExtrinsicIndex: codec::Codec,
+        Balance: codec::Codec,
+    {
+        /// Returns the owner and storage fee of the order given `block_number` and
+        /// `extrinsic_index`, `None` if the order does not exist or has been forgotten.
+        fn order(block_number: BlockNumber, extrinsic_index: ExtrinsicIndex) -> Option<(AccountId, Balance)>;
+    }
+
+    /// The API to query the tags of transaction data.
+    pub trait TagApi<ExtrinsicIndex> where
+        ExtrinsicIndex: codec::Codec,
+    {
+        /// Returns the tags of the transaction data stored in the current block,
+        /// in the form of `(extrinsic_index, tags)`.
+        fn block_tags() -> Vec<(ExtrinsicIndex, Vec<Tag>)>;
     }
 }
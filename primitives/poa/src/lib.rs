@@ -20,11 +20,18 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![allow(clippy::too_many_arguments)]
 
-pub use cp_consensus_poa::PoaConfiguration;
+pub use cp_consensus_poa::{OldPoaConfiguration, PoaConfiguration, PoaMode};
 
 sp_api::decl_runtime_apis! {
     /// The poa API.
+    ///
+    /// [`PoaConfiguration`] has the extra field `mode` since version 2.
+    #[api_version(2)]
     pub trait PoaApi {
+        /// Returns the configuration of PoA consensus.
+        #[changed_in(2)]
+        fn poa_config() -> OldPoaConfiguration;
+
         /// Returns the configuration of PoA consensus.
         fn poa_config() -> PoaConfiguration;
     }
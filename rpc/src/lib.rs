@@ -50,6 +50,7 @@ use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
 use sp_consensus::SelectChain;
 use sp_consensus_babe::BabeApi;
 use sp_keystore::SyncCryptoStorePtr;
+use sp_transaction_pool::runtime_api::TaggedTransactionQueue;
 
 use canyon_primitives::{AccountId, Balance, Block, BlockNumber, Hash, Index};
 
@@ -107,6 +108,83 @@ pub struct FullDeps<C, P, SC, B, S> {
     pub grandpa: GrandpaDeps<B>,
     /// permanent storage
     pub perma_storage: S,
+    /// Local index of the transactions storing data.
+    pub transaction_index: cc_indexer::TransactionIndex<Block, AccountId>,
+    /// Data submitted along with the extrinsics not included yet.
+    pub pending_data: cc_datastore::PendingData<Block>,
+    /// Admission control of the data submitted via RPC.
+    pub admission: cc_rpc::permastore::AdmissionControl,
+}
+
+/// Extracts the `store` call from the extrinsic of canyon runtime.
+pub struct StoreCallExtractor<C> {
+    client: Arc<C>,
+}
+
+impl<C> StoreCallExtractor<C> {
+    /// Creates a new instance of [`StoreCallExtractor`].
+    pub fn new(client: Arc<C>) -> Self {
+        Self { client }
+    }
+}
+
+impl<C> cc_rpc::permastore::StoreCallExtractor for StoreCallExtractor<C>
+where
+    C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync,
+    C::Api: TaggedTransactionQueue<Block>,
+{
+    fn extract_store_call(&self, ext: &[u8]) -> Option<cc_rpc::permastore::StoreCall> {
+        use codec::{Decode, Encode};
+
+        let uxt = canyon_runtime::UncheckedExtrinsic::decode(&mut &ext[..]).ok()?;
+        match uxt.function {
+            canyon_runtime::Call::Permastore(pallet_permastore::Call::store {
+                data_size,
+                chunk_root,
+                ..
+            }) => Some(cc_rpc::permastore::StoreCall {
+                signer: uxt.signature.map(|(address, _, _)| address.encode()),
+                data_size,
+                chunk_root,
+            }),
+            _ => None,
+        }
+    }
+
+    fn check_store_call(&self, ext: &[u8]) -> Result<(), String> {
+        use codec::Decode;
+        use sp_runtime::{
+            generic::BlockId,
+            transaction_validity::{
+                InvalidTransaction, TransactionSource, TransactionValidityError,
+            },
+        };
+
+        let uxt = <Block as sp_runtime::traits::Block>::Extrinsic::decode(&mut &ext[..])
+            .map_err(|e| e.to_string())?;
+
+        let best_hash = self.client.info().best_hash;
+        let validity = self
+            .client
+            .runtime_api()
+            .validate_transaction(
+                &BlockId::Hash(best_hash),
+                TransactionSource::External,
+                uxt,
+                best_hash,
+            )
+            .map_err(|e| e.to_string())?;
+
+        match validity {
+            // `CheckStore` is the last signed extension and the data existence is its
+            // last check, every other check has passed if the data is the only missing.
+            Ok(_)
+            | Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(
+                pallet_permastore::DATA_NOT_STORED,
+            ))) => Ok(()),
+            Err(e) => Err(format!("{:?}", e)),
+        }
+    }
 }
 
 /// A IO handler that uses all Full RPC extensions.
@@ -129,12 +207,15 @@ where
     C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
     C::Api: BabeApi<Block>,
     C::Api: BlockBuilder<Block>,
-    P: TransactionPool + 'static,
+    C::Api: TaggedTransactionQueue<Block>,
+    C::Api: cp_permastore::PermastoreApi<Block, BlockNumber, u32, Hash>,
+    C::Api: cp_permastore::OrderApi<Block, AccountId, BlockNumber, u32, Balance>,
+    P: TransactionPool<Hash = Hash> + 'static,
     <P as TransactionPool>::Hash: serde::de::DeserializeOwned,
     SC: SelectChain<Block> + 'static,
     B: sc_client_api::Backend<Block> + Send + Sync + 'static,
     B::State: sc_client_api::backend::StateBackend<sp_runtime::traits::HashFor<Block>>,
-    S: cp_permastore::PermaStorage + 'static,
+    S: cp_permastore::PermaStorage + cc_datastore::ChunkRootBackend<Block> + Clone + 'static,
     A: sc_rpc_api::author::AuthorApi<
         sc_transaction_pool_api::TxHash<P>,
         <Block as sp_runtime::traits::Block>::Hash,
@@ -154,6 +235,9 @@ where
         babe,
         grandpa,
         perma_storage,
+        transaction_index,
+        pending_data,
+        admission,
     } = deps;
 
     let BabeDeps {
@@ -200,18 +284,34 @@ where
     io.extend_with(sc_sync_state_rpc::SyncStateRpcApi::to_delegate(
         sc_sync_state_rpc::SyncStateRpcHandler::new(
             chain_spec,
-            client,
+            client.clone(),
             shared_authority_set,
             shared_epoch_changes,
             deny_unsafe,
         )?,
     ));
 
+    io.extend_with(
+        cc_rpc_api::permastore::transaction_data::TransactionDataApi::to_delegate(
+            cc_rpc::permastore::transaction_data::TransactionData::<_, _, Block>::new(
+                client.clone(),
+                perma_storage.clone(),
+            ),
+        ),
+    );
+
+    io.extend_with(cc_rpc_api::permastore::indexer::IndexerApi::to_delegate(
+        cc_rpc::permastore::indexer::Indexer::new(transaction_index),
+    ));
+
     io.extend_with(cc_rpc_api::permastore::PermastoreApi::to_delegate(
-        cc_rpc::permastore::Permastore::<_, _, _, Block>::new(
+        cc_rpc::permastore::Permastore::<_, _, _, _, Block>::new(
             perma_storage,
             pool,
             author,
+            StoreCallExtractor::new(client),
+            pending_data,
+            admission,
             deny_unsafe,
         ),
     ));
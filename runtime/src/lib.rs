@@ -118,10 +118,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("canyon"),
     impl_name: create_runtime_str!("canyon-node"),
     authoring_version: 0,
-    spec_version: 0,
+    spec_version: 1,
     impl_version: 0,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 0,
+    transaction_version: 1,
 };
 
 /// The BABE epoch configuration at genesis.
@@ -853,13 +853,30 @@ impl pallet_sudo::Config for Runtime {
 parameter_types! {
     /// 1GiB
     pub const MaxDataSize: u32 = 1024 * 1024 * 1024;
+    /// About 1 DOLLARS per GiB per period.
+    pub const StoragePricePerBytePerPeriod: Balance = MILLICENTS / 10_000;
+    pub const StorageCostDeclineRate: Perbill = Perbill::from_percent(5);
+    pub const StorageReplicas: u32 = 10;
+    pub const MinStorageFee: Balance = CENTS;
+    pub const MaxTags: u32 = 16;
+    pub const MaxTagSize: u32 = 256;
+    pub const PermastoreEndowmentPalletId: PalletId = PalletId(*b"py/endow");
+    /// About 5% of the endowment pool per year at most.
+    pub const EndowmentPayoutRate: Perbill = Perbill::from_parts(10);
 }
 
 impl pallet_permastore::Config for Runtime {
     type Event = Event;
     type Currency = Balances;
-    type TreasuryPalletId = TreasuryModuleId;
+    type EndowmentPalletId = PermastoreEndowmentPalletId;
+    type EndowmentPayoutRate = EndowmentPayoutRate;
     type MaxDataSize = MaxDataSize;
+    type StoragePricePerBytePerPeriod = StoragePricePerBytePerPeriod;
+    type StorageCostDeclineRate = StorageCostDeclineRate;
+    type StorageReplicas = StorageReplicas;
+    type MinStorageFee = MinStorageFee;
+    type MaxTags = MaxTags;
+    type MaxTagSize = MaxTagSize;
     type WeightInfo = pallet_permastore::weights::SubstrateWeight<Runtime>;
 }
 
@@ -869,9 +886,22 @@ impl pallet_poa::BlockAuthor<AccountId> for Runtime {
     }
 }
 
+impl pallet_poa::RewardProofOfAccess<AccountId> for Runtime {
+    fn reward(author: &AccountId, storage_capacity: Permill) {
+        Permastore::payout_endowment(author, storage_capacity);
+    }
+
+    #[cfg(feature = "runtime-benchmarks")]
+    fn prepare_reward() {
+        use frame_support::traits::Currency;
+        Balances::make_free_balance_be(&Permastore::endowment_account_id(), 1_000_000 * DOLLARS);
+    }
+}
+
 impl pallet_poa::Config for Runtime {
     type Event = Event;
     type BlockAuthor = Self;
+    type Reward = Self;
     type WeightInfo = pallet_poa::weights::SubstrateWeight<Runtime>;
 }
 
@@ -918,6 +948,7 @@ where
             frame_system::CheckNonce::<Runtime>::from(nonce),
             frame_system::CheckWeight::<Runtime>::new(),
             pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
+            pallet_permastore::CheckStore::<Runtime>::new(),
         );
         let raw_payload = SignedPayload::new(call, extra)
             .map_err(|e| {
@@ -1168,7 +1199,7 @@ construct_runtime!(
         Lottery: pallet_lottery::{Pallet, Call, Storage, Event<T>} = 31,
         Gilt: pallet_gilt::{Pallet, Call, Storage, Event<T>, Config} = 32,
 
-        Permastore: pallet_permastore::{Pallet, Call, Storage, Event<T>} = 33,
+        Permastore: pallet_permastore::{Pallet, Call, Config, Storage, Event<T>} = 33,
         Poa: pallet_poa::{Pallet, Call, Storage, Inherent, Event<T>} = 34,
 
         Sudo: pallet_sudo::{Pallet, Call, Config<T>, Storage, Event<T>} = 35,
@@ -1200,6 +1231,7 @@ pub type SignedExtra = (
     frame_system::CheckNonce<Runtime>,
     frame_system::CheckWeight<Runtime>,
     pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+    pallet_permastore::CheckStore<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -1398,15 +1430,9 @@ impl_runtime_apis! {
     }
 
     impl cp_permastore::PermastoreApi<Block, BlockNumber, u32, Hash> for Runtime {
-        fn chunk_root(block_number: BlockNumber, extrinsic_index: u32) -> Option<Hash> {
-            Permastore::chunk_root(block_number, extrinsic_index)
-        }
         fn find_recall_block(recall_byte: u64) -> Option<BlockNumber> {
             Permastore::find_recall_block(recall_byte)
         }
-        fn data_size(block_number: BlockNumber, extrinsic_index: u32) -> u32 {
-            Permastore::data_size(block_number, extrinsic_index)
-        }
         fn require_proof_of_access() -> bool {
             Permastore::require_proof_of_access()
         }
@@ -1416,6 +1442,33 @@ impl_runtime_apis! {
         fn weave_size() -> u64 {
             Permastore::weave_size()
         }
+        fn is_forgotten(block_number: BlockNumber, extrinsic_index: u32) -> bool {
+            Permastore::is_forgotten(block_number, extrinsic_index)
+        }
+        fn chunk_root(block_number: BlockNumber, extrinsic_index: u32) -> Option<Hash> {
+            Permastore::legacy_chunk_root((block_number, extrinsic_index))
+        }
+        fn data_size(block_number: BlockNumber, extrinsic_index: u32) -> u32 {
+            Permastore::legacy_data_size((block_number, extrinsic_index)).unwrap_or_default()
+        }
+    }
+
+    impl cp_permastore::StorageFeeApi<Block, Balance> for Runtime {
+        fn storage_fee(data_size: u32) -> Balance {
+            Permastore::storage_fee(data_size)
+        }
+    }
+
+    impl cp_permastore::OrderApi<Block, AccountId, BlockNumber, u32, Balance> for Runtime {
+        fn order(block_number: BlockNumber, extrinsic_index: u32) -> Option<(AccountId, Balance)> {
+            Permastore::order(block_number, extrinsic_index)
+        }
+    }
+
+    impl cp_permastore::TagApi<Block, u32> for Runtime {
+        fn block_tags() -> Vec<(u32, Vec<cp_permastore::Tag>)> {
+            Permastore::block_tags()
+        }
     }
 
     impl cp_poa::PoaApi<Block> for Runtime {